@@ -11,8 +11,10 @@
 //! The area_files module contains file handling functionality, to be used by the areas module.
 
 use crate::context;
+use crate::geometry;
 use crate::stats;
 use crate::util;
+use std::collections::HashMap;
 
 #[cfg(not(test))]
 use log::info;
@@ -22,7 +24,7 @@ use std::println as info;
 
 /// OverpassTags contains various tags about one Overpass element.
 #[derive(serde::Deserialize)]
-struct OverpassTags {
+pub struct OverpassTags {
     name: Option<String>,
     highway: Option<String>,
     service: Option<String>,
@@ -56,30 +58,146 @@ struct OverpassTags {
     fixme: Option<String>,
 }
 
+impl OverpassTags {
+    /// Picks the interesting tags from a full key-value map of OSM tags.
+    pub fn from_map(tags: &HashMap<String, String>) -> Self {
+        let get = |key: &str| tags.get(key).cloned();
+        OverpassTags {
+            name: get("name"),
+            highway: get("highway"),
+            service: get("service"),
+            surface: get("surface"),
+            leisure: get("leisure"),
+            street: get("addr:street"),
+            housenumber: get("addr:housenumber"),
+            postcode: get("addr:postcode"),
+            place: get("addr:place"),
+            housename: get("addr:housename"),
+            conscriptionnumber: get("addr:conscriptionnumber"),
+            flats: get("addr:flats"),
+            floor: get("addr:floor"),
+            door: get("addr:door"),
+            unit: get("addr:unit"),
+            city: get("addr:city"),
+            fixme: get("fixme"),
+        }
+    }
+}
+
+/// OverpassCenter is the center of a way or relation from `out center;`, or a node of its geometry
+/// from `out geom;`.
+#[derive(Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct OverpassCenter {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// OverpassBounds is the bounding box of a way or relation, from `out bb;`.
+#[derive(Clone, Copy, serde::Deserialize)]
+pub struct OverpassBounds {
+    pub minlat: f64,
+    pub minlon: f64,
+    pub maxlat: f64,
+    pub maxlon: f64,
+}
+
+/// OverpassMember is a member of a relation, with its geometry from `out geom;`.
+#[derive(serde::Deserialize)]
+pub struct OverpassMember {
+    pub geometry: Option<Vec<OverpassCenter>>,
+}
+
 /// OverpassElement represents one result from Overpass.
 #[derive(serde::Deserialize)]
-struct OverpassElement {
-    id: u64,
+pub struct OverpassElement {
+    pub id: u64,
     #[serde(rename(deserialize = "type"))]
-    osm_type: String,
+    pub osm_type: String,
     pub user: Option<String>,
     pub timestamp: Option<String>,
-    tags: OverpassTags,
+    pub tags: OverpassTags,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub center: Option<OverpassCenter>,
+    pub bounds: Option<OverpassBounds>,
+    pub geometry: Option<Vec<OverpassCenter>>,
+    /// The node ids of a way.
+    pub nodes: Option<Vec<u64>>,
+    pub members: Option<Vec<OverpassMember>>,
+}
+
+impl OverpassElement {
+    /// Gets the lines of a way or relation from `out geom;`: a way is one line, a relation has one
+    /// line for each member way.
+    pub fn get_lines(&self) -> Vec<Vec<OverpassCenter>> {
+        let lines: Vec<Vec<OverpassCenter>> = match self.geometry {
+            Some(ref geometry) => vec![geometry.clone()],
+            None => self
+                .members
+                .iter()
+                .flatten()
+                .filter_map(|member| member.geometry.clone())
+                .collect(),
+        };
+        lines.into_iter().filter(|line| !line.is_empty()).collect()
+    }
+
+    /// Gets a representative point of the object: the location of a node, the center of a way /
+    /// relation or the middle node of its first line, so the point of a curved street is still on
+    /// the street.
+    pub fn get_point(&self) -> Option<OverpassCenter> {
+        if let (Some(lat), Some(lon)) = (self.lat, self.lon) {
+            return Some(OverpassCenter { lat, lon });
+        }
+        if let Some(center) = self.center {
+            return Some(center);
+        }
+        if let Some(line) = self.get_lines().first() {
+            return Some(line[line.len() / 2]);
+        }
+        self.bounds.map(|bounds| OverpassCenter {
+            lat: (bounds.minlat + bounds.maxlat) / 2.0,
+            lon: (bounds.minlon + bounds.maxlon) / 2.0,
+        })
+    }
+
+    /// Gets the bounding box of the object, a node has an empty bounding box.
+    pub fn get_bounds(&self) -> Option<OverpassBounds> {
+        if let Some(bounds) = self.bounds {
+            return Some(bounds);
+        }
+        self.get_point().map(|point| OverpassBounds {
+            minlat: point.lat,
+            minlon: point.lon,
+            maxlat: point.lat,
+            maxlon: point.lon,
+        })
+    }
+}
+
+/// Formats an optional coordinate for the SQL tables, empty when unknown. Rounds to the 7 decimals
+/// OSM stores, so e.g. PBF input doesn't produce noise.
+pub fn format_coord(coord: Option<f64>) -> String {
+    match coord {
+        Some(value) => ((value * 1e7).round() / 1e7).to_string(),
+        None => "".into(),
+    }
 }
 
+/// OverpassTimes contains the timestamps of the data Overpass worked with.
 #[derive(serde::Deserialize)]
-struct OverpassTimes {
+pub struct OverpassTimes {
     #[serde(with = "time::serde::rfc3339")]
-    timestamp_osm_base: time::OffsetDateTime,
+    pub timestamp_osm_base: time::OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
-    timestamp_areas_base: time::OffsetDateTime,
+    pub timestamp_areas_base: time::OffsetDateTime,
 }
 
 /// OverpassResult is the result from Overpass.
 #[derive(serde::Deserialize)]
-struct OverpassResult {
-    osm3s: OverpassTimes,
-    elements: Vec<OverpassElement>,
+pub struct OverpassResult {
+    pub osm3s: OverpassTimes,
+    pub elements: Vec<OverpassElement>,
 }
 
 /// One row in the `osm_streets` SQL table for a relation. Keep this in sync with data/streets-template.overpassql.
@@ -108,11 +226,16 @@ impl OsmStreet {
 #[derive(Clone)]
 pub struct RelationFiles {
     name: String,
+    /// OSM id of the relation boundary.
+    osmrelation: Option<u64>,
 }
 
 impl RelationFiles {
-    pub fn new(name: &str) -> Self {
-        RelationFiles { name: name.into() }
+    pub fn new(name: &str, osmrelation: Option<u64>) -> Self {
+        RelationFiles {
+            name: name.into(),
+            osmrelation,
+        }
     }
 
     /// Opens the OSM street list of a relation for reading.
@@ -139,7 +262,7 @@ impl RelationFiles {
         let mut ret: Vec<util::OsmHouseNumber> = Vec::new();
         let conn = ctx.get_database_connection()?;
         let mut stmt =
-            conn.prepare("select osm_id, housenumber, conscriptionnumber, street, place, osm_type from osm_housenumbers where relation = ?1")?;
+            conn.prepare("select osm_id, housenumber, conscriptionnumber, street, place, osm_type, postcode from osm_housenumbers where relation = ?1")?;
         let mut rows = stmt.query([&self.name])?;
         while let Some(row) = rows.next()? {
             let id: String = row.get(0).unwrap();
@@ -148,14 +271,16 @@ impl RelationFiles {
             let street: String = row.get(3).unwrap();
             let place: String = row.get(4).unwrap();
             let object_type: String = row.get(5).unwrap();
-            ret.push(util::OsmHouseNumber::new(
+            let mut osm_housenumber = util::OsmHouseNumber::new(
                 id.parse()?,
                 &housenumber,
                 &conscriptionnumber,
                 &street,
                 &Some(place),
                 &object_type,
-            ));
+            );
+            osm_housenumber.postcode = row.get(6).unwrap();
+            ret.push(osm_housenumber);
         }
         Ok(ret)
     }
@@ -173,7 +298,15 @@ impl RelationFiles {
                 return Ok(());
             }
         };
+        self.write_osm_streets(ctx, overpass)
+    }
 
+    /// Writes already parsed streets, either from overpass or from a local extract.
+    pub fn write_osm_streets(
+        &self,
+        ctx: &context::Context,
+        overpass: OverpassResult,
+    ) -> anyhow::Result<()> {
         // Insert or update the mtime for the osm streets of this relation.
         stats::set_sql_mtime(ctx, &format!("streets/{}", self.name))?;
 
@@ -183,9 +316,55 @@ impl RelationFiles {
             "delete from osm_streets where relation = ?1",
             [self.name.to_string()],
         )?;
+        tx.execute(
+            "delete from osm_geometries where relation = ?1",
+            [self.name.to_string()],
+        )?;
+        tx.execute(
+            "delete from osm_street_nodes where relation = ?1",
+            [self.name.to_string()],
+        )?;
         for element in overpass.elements {
             let relation = self.name.to_string();
             let osm_id = element.id.to_string();
+            // The boundary relation is only there for the map.
+            let is_boundary =
+                element.osm_type == "relation" && Some(element.id) == self.osmrelation;
+            let lines: Vec<Vec<geometry::Point>> = element
+                .get_lines()
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .map(|i| geometry::Point::new(i.lat, i.lon))
+                        .collect()
+                })
+                .collect();
+            if !lines.is_empty() {
+                let kind = if is_boundary { "boundary" } else { "street" };
+                let geometry = serde_json::to_string(&geometry::lines_to_geojson(&lines))?;
+                tx.execute(
+                    "insert or replace into osm_geometries (relation, kind, osm_type, osm_id, geometry) values (?1, ?2, ?3, ?4, ?5)",
+                    [&relation, kind, &element.osm_type, &osm_id, &geometry],
+                )?;
+            }
+            if is_boundary {
+                continue;
+            }
+            // So replication can detect when a node of a street moves.
+            for node in element.nodes.iter().flatten() {
+                tx.execute(
+                    "insert into osm_street_nodes (relation, osm_id, node_id) values (?1, ?2, ?3)",
+                    rusqlite::params![relation, osm_id, i64::try_from(*node)?],
+                )?;
+            }
+            let point = element.get_point();
+            let lat = format_coord(point.map(|i| i.lat));
+            let lon = format_coord(point.map(|i| i.lon));
+            let bounds = element.get_bounds();
+            let min_lat = format_coord(bounds.map(|i| i.minlat));
+            let min_lon = format_coord(bounds.map(|i| i.minlon));
+            let max_lat = format_coord(bounds.map(|i| i.maxlat));
+            let max_lon = format_coord(bounds.map(|i| i.maxlon));
             let name = element.tags.name.unwrap_or("".into());
             let highway = element.tags.highway.unwrap_or("".into());
             let service = element.tags.service.unwrap_or("".into());
@@ -193,14 +372,26 @@ impl RelationFiles {
             let leisure = element.tags.leisure.unwrap_or("".into());
             let osm_type = element.osm_type.to_string();
             let ret = tx.execute(
-                "insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                [relation, osm_id, name, highway, service, surface, leisure, osm_type],
+                "insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon, min_lat, min_lon, max_lat, max_lon) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                [relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon, min_lat, min_lon, max_lat, max_lon],
             );
             if ret.is_err() {
                 info!("write_osm_json_streets: ignoring duplicated street: relation is '{}', id is '{}'", self.name, element.id);
             }
         }
 
+        tx.execute(
+            "delete from osm_relation_bounds where relation = ?1",
+            [self.name.to_string()],
+        )?;
+        tx.execute(
+            "insert into osm_relation_bounds (relation, min_lat, min_lon, max_lat, max_lon)
+                 select relation, min(cast(min_lat as real)), min(cast(min_lon as real)),
+                     max(cast(max_lat as real)), max(cast(max_lon as real))
+                 from osm_streets where relation = ?1 and min_lat != '' group by relation",
+            [self.name.to_string()],
+        )?;
+
         let osm_page = format!("streets/{}/osm-base", self.name);
         let osm_time = overpass.osm3s.timestamp_osm_base.unix_timestamp_nanos();
         tx.execute(
@@ -234,7 +425,15 @@ impl RelationFiles {
                 return Ok(());
             }
         };
+        self.write_osm_housenumbers(ctx, overpass)
+    }
 
+    /// Writes already parsed house numbers, either from overpass or from a local extract.
+    pub fn write_osm_housenumbers(
+        &self,
+        ctx: &context::Context,
+        overpass: OverpassResult,
+    ) -> anyhow::Result<()> {
         // Insert or update the mtime for the osm housenumbers of this relation.
         stats::set_sql_mtime(ctx, &format!("housenumbers/{}", self.name))?;
 
@@ -247,6 +446,9 @@ impl RelationFiles {
         for element in overpass.elements {
             let relation = self.name.to_string();
             let osm_id = element.id.to_string();
+            let point = element.get_point();
+            let lat = format_coord(point.map(|i| i.lat));
+            let lon = format_coord(point.map(|i| i.lon));
             let street = element.tags.street.unwrap_or("".into());
             let housenumber = element.tags.housenumber.unwrap_or("".into());
             let postcode = element.tags.postcode.unwrap_or("".into());
@@ -260,8 +462,8 @@ impl RelationFiles {
             let name = element.tags.name.unwrap_or("".into());
             let osm_type = element.osm_type.to_string();
             let ret = tx.execute(
-                "insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
-                [relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type],
+                "insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                [relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon],
             );
             if ret.is_err() {
                 info!("write_osm_json_housenumbers: ignoring duplicated housenumber: relation is '{}', id is '{}'", self.name, element.id);
@@ -298,7 +500,15 @@ pub fn write_whole_country(ctx: &context::Context, result: &str) -> anyhow::Resu
             return Ok(());
         }
     };
+    write_whole_country_result(ctx, overpass)
+}
 
+/// Writes already parsed house numbers of the whole country, either from overpass or from a local
+/// extract.
+pub fn write_whole_country_result(
+    ctx: &context::Context,
+    overpass: OverpassResult,
+) -> anyhow::Result<()> {
     let mut conn = ctx.get_database_connection()?;
     let tx = conn.transaction()?;
     tx.execute("delete from whole_country", [])?;
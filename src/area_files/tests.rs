@@ -77,6 +77,248 @@ fn test_write_osm_json_housenumbers_duplicate() {
         .unwrap();
 }
 
+/// Tests RelationFiles::write_osm_json_streets(), when the json has coordinates.
+#[test]
+fn test_write_osm_json_streets_coordinates() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "osmrelation": 2713748,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+    let result = serde_json::json!({
+        "osm3s": {
+            "timestamp_osm_base": "2024-01-01T00:00:00Z",
+            "timestamp_areas_base": "2024-01-01T00:00:00Z",
+        },
+        "elements": [
+            {
+                "type": "way",
+                "id": 1,
+                "bounds": {"minlat": 47.0, "minlon": 19.0, "maxlat": 47.5, "maxlon": 19.5},
+                "nodes": [10, 11],
+                "tags": {"name": "Tűzkő utca", "highway": "residential"},
+            },
+            {
+                "type": "way",
+                "id": 2,
+                "tags": {"name": "Törökugrató utca", "highway": "residential"},
+            },
+        ],
+    });
+
+    relation
+        .get_files()
+        .write_osm_json_streets(&ctx, &result.to_string())
+        .unwrap();
+
+    let conn = ctx.get_database_connection().unwrap();
+    let mut stmt = conn
+        .prepare("select lat, lon, min_lat, min_lon, max_lat, max_lon from osm_streets where relation = 'gazdagret' order by osm_id")
+        .unwrap();
+    let mut rows = stmt.query([]).unwrap();
+    let mut actual: Vec<Vec<String>> = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        actual.push((0..6).map(|i| row.get(i).unwrap()).collect());
+    }
+    assert_eq!(
+        actual,
+        vec![
+            vec!["47.25", "19.25", "47", "19", "47.5", "19.5"],
+            vec!["", "", "", "", "", ""],
+        ]
+    );
+    // The relation bounds and the street nodes, for replication.
+    let bounds: (f64, f64, f64, f64) = conn
+        .query_row(
+            "select min_lat, min_lon, max_lat, max_lon from osm_relation_bounds where relation = 'gazdagret'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(bounds, (47.0, 19.0, 47.5, 19.5));
+    let nodes: Vec<(String, i64)> = conn
+        .prepare("select osm_id, node_id from osm_street_nodes where relation = 'gazdagret' order by node_id")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(nodes, vec![("1".to_string(), 10), ("1".to_string(), 11)]);
+}
+
+/// Tests RelationFiles::write_osm_json_streets(), when the json has geometries.
+#[test]
+fn test_write_osm_json_streets_geometry() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "osmrelation": 2713748,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+    let result = serde_json::json!({
+        "osm3s": {
+            "timestamp_osm_base": "2024-01-01T00:00:00Z",
+            "timestamp_areas_base": "2024-01-01T00:00:00Z",
+        },
+        "elements": [
+            {
+                "type": "way",
+                "id": 1,
+                "bounds": {"minlat": 47.0, "minlon": 19.0, "maxlat": 47.5, "maxlon": 19.5},
+                "geometry": [
+                    {"lat": 47.0, "lon": 19.0},
+                    {"lat": 47.5, "lon": 19.0},
+                    {"lat": 47.5, "lon": 19.5},
+                ],
+                "tags": {"name": "Tűzkő utca", "highway": "residential"},
+            },
+            {
+                "type": "relation",
+                "id": 2713748,
+                "bounds": {"minlat": 47.0, "minlon": 19.0, "maxlat": 47.5, "maxlon": 19.5},
+                "members": [
+                    {
+                        "type": "way",
+                        "ref": 3,
+                        "role": "outer",
+                        "geometry": [{"lat": 47.0, "lon": 19.0}, {"lat": 47.5, "lon": 19.5}],
+                    },
+                    {"type": "node", "ref": 4, "role": "admin_centre", "lat": 47.2, "lon": 19.2},
+                ],
+                "tags": {"name": "Gazdagrét"},
+            },
+        ],
+    });
+
+    relation
+        .get_files()
+        .write_osm_json_streets(&ctx, &result.to_string())
+        .unwrap();
+
+    let conn = ctx.get_database_connection().unwrap();
+    // The point of the street is its middle node, not the center of its bounding box; the
+    // boundary is not a street.
+    let mut stmt = conn
+        .prepare("select osm_id, lat, lon from osm_streets where relation = 'gazdagret'")
+        .unwrap();
+    let mut rows = stmt.query([]).unwrap();
+    let mut actual: Vec<Vec<String>> = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        actual.push((0..3).map(|i| row.get(i).unwrap()).collect());
+    }
+    assert_eq!(actual, vec![vec!["1", "47.5", "19"]]);
+    let mut stmt = conn
+        .prepare("select kind, osm_type, osm_id, geometry from osm_geometries where relation = 'gazdagret' order by kind")
+        .unwrap();
+    let mut rows = stmt.query([]).unwrap();
+    let mut actual: Vec<Vec<String>> = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        actual.push((0..4).map(|i| row.get(i).unwrap()).collect());
+    }
+    assert_eq!(
+        actual,
+        vec![
+            vec![
+                "boundary",
+                "relation",
+                "2713748",
+                r#"{"coordinates":[[[19.0,47.0],[19.5,47.5]]],"type":"MultiLineString"}"#
+            ],
+            vec![
+                "street",
+                "way",
+                "1",
+                r#"{"coordinates":[[[19.0,47.0],[19.0,47.5],[19.5,47.5]]],"type":"MultiLineString"}"#
+            ],
+        ]
+    );
+}
+
+/// Tests RelationFiles::write_osm_json_housenumbers(), when the json has coordinates.
+#[test]
+fn test_write_osm_json_housenumbers_coordinates() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "osmrelation": 2713748,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+    let result = serde_json::json!({
+        "osm3s": {
+            "timestamp_osm_base": "2024-01-01T00:00:00Z",
+            "timestamp_areas_base": "2024-01-01T00:00:00Z",
+        },
+        "elements": [
+            {
+                "type": "node",
+                "id": 1,
+                "lat": 47.1,
+                "lon": 19.1,
+                "tags": {"addr:street": "Tűzkő utca", "addr:housenumber": "1"},
+            },
+            {
+                "type": "way",
+                "id": 2,
+                "center": {"lat": 47.2, "lon": 19.2},
+                "tags": {"addr:street": "Tűzkő utca", "addr:housenumber": "2"},
+            },
+        ],
+    });
+
+    relation
+        .get_files()
+        .write_osm_json_housenumbers(&ctx, &result.to_string())
+        .unwrap();
+
+    let conn = ctx.get_database_connection().unwrap();
+    let mut stmt = conn
+        .prepare("select osm_type, lat, lon from osm_housenumbers where relation = 'gazdagret' order by osm_id")
+        .unwrap();
+    let mut rows = stmt.query([]).unwrap();
+    let mut actual: Vec<Vec<String>> = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        actual.push((0..3).map(|i| row.get(i).unwrap()).collect());
+    }
+    assert_eq!(
+        actual,
+        vec![vec!["node", "47.1", "19.1"], vec!["way", "47.2", "19.2"]]
+    );
+}
+
 /// Tests write_whole_country(), when it gets non-JSON input.
 #[test]
 fn test_write_whole_country_non_json_input() {
@@ -13,12 +13,16 @@
 use crate::area_files;
 use crate::cache;
 use crate::context;
+use crate::housenumber_grammar;
 use crate::i18n::translate as tr;
 use crate::ranges;
+use crate::reference;
 use crate::stats;
 use crate::util;
 use crate::yattag;
 use anyhow::Context;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::fmt::Write as _;
 use std::ops::DerefMut;
@@ -65,9 +69,14 @@ pub struct RelationDict {
     additional_housenumbers: Option<bool>,
     pub alias: Option<Vec<String>>,
     pub filters: Option<HashMap<String, RelationFiltersDict>>,
+    pub housenumber_grammar: Option<String>,
     housenumber_letters: Option<bool>,
     inactive: Option<bool>,
     missing_streets: Option<String>,
+    notify_coverage_drop: Option<f64>,
+    pub notify_emails: Option<Vec<String>>,
+    notify_lints_increase: Option<u64>,
+    pub notify_webhooks: Option<Vec<String>>,
     osm_street_filters: Option<Vec<String>>,
     pub osmrelation: Option<u64>,
     pub refcounty: Option<String>,
@@ -82,9 +91,14 @@ impl Default for RelationDict {
         let additional_housenumbers = None;
         let alias = None;
         let filters = None;
+        let housenumber_grammar = None;
         let housenumber_letters = None;
         let inactive = None;
         let missing_streets = None;
+        let notify_coverage_drop = None;
+        let notify_emails = None;
+        let notify_lints_increase = None;
+        let notify_webhooks = None;
         let osm_street_filters = None;
         let osmrelation = None;
         let refcounty = None;
@@ -96,9 +110,14 @@ impl Default for RelationDict {
             additional_housenumbers,
             alias,
             filters,
+            housenumber_grammar,
             housenumber_letters,
             inactive,
             missing_streets,
+            notify_coverage_drop,
+            notify_emails,
+            notify_lints_increase,
+            notify_webhooks,
             osm_street_filters,
             osmrelation,
             refcounty,
@@ -183,6 +202,43 @@ impl RelationConfig {
         }
     }
 
+    /// Gets the name of the house number grammar, if the relation overrides the default.
+    fn get_housenumber_grammar(&self) -> Option<String> {
+        RelationConfig::get_property(
+            &self.parent.housenumber_grammar,
+            &self.dict.housenumber_grammar,
+        )
+    }
+
+    /// Gets the email addresses which are notified about regressions of this relation.
+    pub fn get_notify_emails(&self) -> Vec<String> {
+        RelationConfig::get_property(&self.parent.notify_emails, &self.dict.notify_emails)
+            .unwrap_or_default()
+    }
+
+    /// Gets the webhook URLs which are notified about regressions of this relation.
+    pub fn get_notify_webhooks(&self) -> Vec<String> {
+        RelationConfig::get_property(&self.parent.notify_webhooks, &self.dict.notify_webhooks)
+            .unwrap_or_default()
+    }
+
+    /// Gets the minimal coverage drop worth a notification, if the relation overrides the default.
+    pub fn get_notify_coverage_drop(&self) -> Option<f64> {
+        RelationConfig::get_property(
+            &self.parent.notify_coverage_drop,
+            &self.dict.notify_coverage_drop,
+        )
+    }
+
+    /// Gets the minimal lints increase worth a notification, if the relation overrides the
+    /// default.
+    pub fn get_notify_lints_increase(&self) -> Option<u64> {
+        RelationConfig::get_property(
+            &self.parent.notify_lints_increase,
+            &self.dict.notify_lints_increase,
+        )
+    }
+
     /// Do we care if 42/B is missing when 42/A is provided?
     fn should_check_housenumber_letters(&self) -> bool {
         RelationConfig::get_property(
@@ -227,6 +283,14 @@ impl RelationConfig {
         filters.get(street)
     }
 
+    /// Determines if a relation's street has ranges filters.
+    pub fn has_street_ranges(&self, street: &str) -> bool {
+        match self.get_filter_street(street) {
+            Some(filter) => filter.ranges.is_some(),
+            None => false,
+        }
+    }
+
     /// Determines in a relation's street is interpolation=all or not.
     pub fn get_street_is_even_odd(&self, street: &str) -> bool {
         let mut interpolation_all = false;
@@ -323,14 +387,235 @@ impl RelationConfig {
     }
 }
 
-/// Return type of Relation::get_missing_housenumbers().
-#[derive(serde::Deserialize, serde::Serialize)]
+/// Result of the missing house numbers analysis.
+#[derive(schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct MissingHousenumbers {
     pub ongoing_streets: util::NumberedStreets,
     pub done_streets: util::NumberedStreets,
 }
 
+/// House numbers of a relation, grouped by OSM street names.
+pub type StreetHousenumbers = BTreeMap<String, BTreeSet<String>>;
+
+/// A stored state of Relation::get_missing_housenumbers(), without the ranges and comments.
+#[derive(Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MissingHousenumbersSnapshot {
+    /// House numbers which are only in the reference.
+    pub missing: StreetHousenumbers,
+    /// House numbers which are both in OSM and in the reference.
+    pub done: StreetHousenumbers,
+}
+
+impl MissingHousenumbersSnapshot {
+    /// Creates a snapshot from the output of Relation::get_missing_housenumbers().
+    pub fn new(missing_housenumbers: &MissingHousenumbers) -> Self {
+        fn to_map(streets: &util::NumberedStreets) -> StreetHousenumbers {
+            let mut ret = StreetHousenumbers::new();
+            for street in streets {
+                let numbers = ret.entry(street.street.get_osm_name().into()).or_default();
+                for house_number in &street.house_numbers {
+                    numbers.insert(house_number.get_number().into());
+                }
+            }
+            ret
+        }
+        MissingHousenumbersSnapshot {
+            missing: to_map(&missing_housenumbers.ongoing_streets),
+            done: to_map(&missing_housenumbers.done_streets),
+        }
+    }
+
+    /// Decides if the snapshot contains a house number, either as missing or as done.
+    fn contains(&self, street: &str, housenumber: &str) -> bool {
+        [&self.missing, &self.done].iter().any(|streets| {
+            streets
+                .get(street)
+                .map(|numbers| numbers.contains(housenumber))
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// Changes of the missing house numbers since a date. House numbers are grouped by OSM street
+/// names.
+#[derive(schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MissingHousenumbersChanges {
+    /// The date of the old snapshot, e.g. 2020-05-10.
+    #[schemars(extend("format" = "date"))]
+    pub since: String,
+    /// Missing in the old snapshot, in OSM now.
+    pub newly_mapped: StreetHousenumbers,
+    /// Not in the old snapshot, missing now.
+    pub newly_missing: StreetHousenumbers,
+    /// In OSM in the old snapshot, missing now: probably deleted from OSM.
+    pub no_longer_mapped: StreetHousenumbers,
+    /// In the old snapshot, but not in the reference anymore.
+    pub removed_from_reference: StreetHousenumbers,
+}
+
+impl MissingHousenumbersChanges {
+    /// Compares an old and a new snapshot.
+    pub fn new(
+        since: &str,
+        old: &MissingHousenumbersSnapshot,
+        new: &MissingHousenumbersSnapshot,
+    ) -> Self {
+        let mut ret = MissingHousenumbersChanges {
+            since: since.into(),
+            newly_mapped: StreetHousenumbers::new(),
+            newly_missing: StreetHousenumbers::new(),
+            no_longer_mapped: StreetHousenumbers::new(),
+            removed_from_reference: StreetHousenumbers::new(),
+        };
+        fn add(map: &mut StreetHousenumbers, street: &str, housenumber: &str) {
+            map.entry(street.into())
+                .or_default()
+                .insert(housenumber.into());
+        }
+        for (street, numbers) in &new.done {
+            for number in numbers {
+                if old.missing.get(street).map(|i| i.contains(number)) == Some(true) {
+                    add(&mut ret.newly_mapped, street, number);
+                }
+            }
+        }
+        for (street, numbers) in &new.missing {
+            for number in numbers {
+                if old.done.get(street).map(|i| i.contains(number)) == Some(true) {
+                    add(&mut ret.no_longer_mapped, street, number);
+                } else if !old.contains(street, number) {
+                    add(&mut ret.newly_missing, street, number);
+                }
+            }
+        }
+        for (street, numbers) in old.missing.iter().chain(old.done.iter()) {
+            for number in numbers {
+                if !new.contains(street, number) {
+                    add(&mut ret.removed_from_reference, street, number);
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// Street and house number coverage of a relation on a day.
+#[derive(Debug, PartialEq, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CoverageHistoryEntry {
+    /// E.g. 2020-05-10.
+    #[schemars(extend("format" = "date"))]
+    pub date: String,
+    /// Street coverage percentage, None if it was not calculated.
+    pub streets: Option<String>,
+    /// House number coverage percentage, None if it was not calculated.
+    pub housenumbers: Option<String>,
+}
+
+/// The result of surveying a missing house number.
+#[derive(Clone, Debug, PartialEq, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SurveyStatus {
+    /// The house number doesn't exist, silenced like an 'invalid' filter.
+    ConfirmedAbsent,
+    /// There is a different number instead, silenced like an 'invalid' filter.
+    DifferentNumber,
+    /// The survey was inconclusive, the house number is still reported as missing.
+    NeedsResurvey,
+}
+
+impl TryFrom<&str> for SurveyStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "confirmed-absent" => Ok(SurveyStatus::ConfirmedAbsent),
+            "different-number" => Ok(SurveyStatus::DifferentNumber),
+            "needs-resurvey" => Ok(SurveyStatus::NeedsResurvey),
+            _ => Err(anyhow::anyhow!("invalid value: {value}")),
+        }
+    }
+}
+
+impl std::fmt::Display for SurveyStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SurveyStatus::ConfirmedAbsent => write!(f, "confirmed-absent"),
+            SurveyStatus::DifferentNumber => write!(f, "different-number"),
+            SurveyStatus::NeedsResurvey => write!(f, "needs-resurvey"),
+        }
+    }
+}
+
+/// An OSM object having a duplicated address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateAddressObject {
+    /// OSM identifier of the object.
+    pub osm_id: u64,
+    /// 'node', 'way' or 'relation'.
+    pub osm_type: String,
+    /// The addr:unit value.
+    pub unit: String,
+    /// The addr:door value.
+    pub door: String,
+}
+
+/// A street and house number, present on several OSM objects of a relation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateAddress {
+    /// The addr:street (or addr:place) value.
+    pub street: String,
+    /// The addr:housenumber value.
+    pub housenumber: String,
+    /// The objects having this address, at least two.
+    pub objects: Vec<DuplicateAddressObject>,
+    /// The objects have different addr:unit / addr:door values, so this is probably not an error.
+    pub legitimate: bool,
+}
+
+/// An OSM object of a relation, with an addr:city value which is not a valid settlement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidAddrCity {
+    /// OSM identifier of the object.
+    pub osm_id: String,
+    /// 'node', 'way' or 'relation'.
+    pub osm_type: String,
+    /// The addr:street value.
+    pub street: String,
+    /// The addr:housenumber value.
+    pub housenumber: String,
+    /// The invalid addr:city value.
+    pub city: String,
+    /// Name of the refsettlement of the street, empty if unknown.
+    pub suggested_city: String,
+}
+
+/// Survey feedback about a missing house number of a relation. confirmed-absent and
+/// different-number silence the house number like an 'invalid' filter.
+#[derive(Clone, Debug, PartialEq, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SurveyObservation {
+    /// OSM street name.
+    pub street: String,
+    /// House number, as it is missing from OSM, e.g. 42/a.
+    pub housenumber: String,
+    /// What the surveyor found on the ground.
+    pub status: SurveyStatus,
+    /// Free-form details, e.g. the number which is there instead.
+    #[serde(default)]
+    pub note: String,
+    /// Login of the surveyor, set from the credentials.
+    #[serde(default)]
+    pub surveyor: String,
+    /// Date of the survey, e.g. 2020-05-10, defaults to today.
+    #[serde(default)]
+    #[schemars(extend("format" = "date"))]
+    pub date: String,
+}
+
 #[derive(Clone, Debug, Ord, PartialOrd, derivative::Derivative)]
 #[derivative(Eq, PartialEq)]
 pub struct RelationLint {
@@ -347,10 +632,13 @@ pub struct RelationLint {
     pub object_type: String,
 }
 
-#[derive(Clone, Debug, Eq, Ord, PartialOrd, PartialEq)]
+#[derive(Clone, Debug, Eq, Ord, PartialOrd, PartialEq, schemars::JsonSchema)]
+#[schemars(rename_all = "kebab-case")]
 pub enum RelationLintSource {
     Range,
     Invalid,
+    Survey,
+    Postcode,
 }
 
 impl TryFrom<&str> for RelationLintSource {
@@ -360,6 +648,8 @@ impl TryFrom<&str> for RelationLintSource {
         match value {
             "range" => Ok(RelationLintSource::Range),
             "invalid" => Ok(RelationLintSource::Invalid),
+            "survey" => Ok(RelationLintSource::Survey),
+            "postcode" => Ok(RelationLintSource::Postcode),
             _ => Err(anyhow::anyhow!("invalid value: {value}")),
         }
     }
@@ -370,15 +660,21 @@ impl std::fmt::Display for RelationLintSource {
         match self {
             RelationLintSource::Range => write!(f, "range"),
             RelationLintSource::Invalid => write!(f, "invalid"),
+            RelationLintSource::Survey => write!(f, "survey"),
+            RelationLintSource::Postcode => write!(f, "postcode"),
         }
     }
 }
 
-#[derive(Clone, Debug, Eq, Ord, PartialOrd, PartialEq)]
+#[derive(Clone, Debug, Eq, Ord, PartialOrd, PartialEq, schemars::JsonSchema)]
+#[schemars(rename_all = "kebab-case")]
 pub enum RelationLintReason {
     CreatedInOsm,
     DeletedFromRef,
     OutOfRange,
+    MissingPostcode,
+    MalformedPostcode,
+    UnknownPostcode,
 }
 
 impl TryFrom<&str> for RelationLintReason {
@@ -389,6 +685,9 @@ impl TryFrom<&str> for RelationLintReason {
             "created-in-osm" => Ok(RelationLintReason::CreatedInOsm),
             "deleted-from-ref" => Ok(RelationLintReason::DeletedFromRef),
             "out-of-range" => Ok(RelationLintReason::OutOfRange),
+            "missing-postcode" => Ok(RelationLintReason::MissingPostcode),
+            "malformed-postcode" => Ok(RelationLintReason::MalformedPostcode),
+            "unknown-postcode" => Ok(RelationLintReason::UnknownPostcode),
             _ => Err(anyhow::anyhow!("invalid value: {value}")),
         }
     }
@@ -400,6 +699,9 @@ impl std::fmt::Display for RelationLintReason {
             RelationLintReason::CreatedInOsm => write!(f, "created-in-osm"),
             RelationLintReason::DeletedFromRef => write!(f, "deleted-from-ref"),
             RelationLintReason::OutOfRange => write!(f, "out-of-range"),
+            RelationLintReason::MissingPostcode => write!(f, "missing-postcode"),
+            RelationLintReason::MalformedPostcode => write!(f, "malformed-postcode"),
+            RelationLintReason::UnknownPostcode => write!(f, "unknown-postcode"),
         }
     }
 }
@@ -423,7 +725,6 @@ impl<'a> Relation<'a> {
         yaml_cache: &HashMap<String, serde_json::Value>,
     ) -> anyhow::Result<Relation<'a>> {
         let mut my_config = RelationDict::default();
-        let file = area_files::RelationFiles::new(name);
         let relation_path = format!("relation-{name}.yaml");
         // Intentionally don't require this cache to be present, it's fine to omit it for simple
         // relations.
@@ -432,6 +733,7 @@ impl<'a> Relation<'a> {
                 .context(format!("failed to parse '{relation_path}'"))?;
         }
         let config = RelationConfig::new(parent_config, &my_config);
+        let file = area_files::RelationFiles::new(name, parent_config.osmrelation);
         // osm street name -> house number list map, so we don't have to read the on-disk list of the
         // relation again and again for each street.
         let osm_housenumbers: HashMap<String, Vec<util::HouseNumber>> = HashMap::new();
@@ -465,6 +767,17 @@ impl<'a> Relation<'a> {
         &self.config
     }
 
+    /// Gets the rules to interpret house numbers: the relation's grammar or the default one.
+    fn get_housenumber_grammar(
+        &self,
+    ) -> anyhow::Result<Box<dyn housenumber_grammar::HouseNumberGrammar>> {
+        let name = match self.config.get_housenumber_grammar() {
+            Some(value) => value,
+            None => self.ctx.get_ini().get_housenumber_grammar(),
+        };
+        housenumber_grammar::get_grammar(&name)
+    }
+
     /// Sets the config interface.
     pub fn set_config(&mut self, config: &RelationConfig) {
         self.config = config.clone();
@@ -522,6 +835,22 @@ impl<'a> Relation<'a> {
         invalid_dict
     }
 
+    /// Gets a street name -> invalid map from the survey observations which confirm that a house
+    /// number is not there.
+    fn get_street_survey_invalid(&self) -> anyhow::Result<HashMap<String, Vec<String>>> {
+        let mut invalid_dict: HashMap<String, Vec<String>> = HashMap::new();
+        for observation in self.get_survey_observations()? {
+            if observation.status == SurveyStatus::NeedsResurvey {
+                continue;
+            }
+            invalid_dict
+                .entry(observation.street)
+                .or_default()
+                .push(observation.housenumber);
+        }
+        Ok(invalid_dict)
+    }
+
     /// Reads list of streets for an area from OSM.
     pub fn get_osm_streets(&self, sorted_result: bool) -> anyhow::Result<Vec<util::Street>> {
         let mut ret: Vec<util::Street> = Vec::new();
@@ -578,9 +907,22 @@ impl<'a> Relation<'a> {
 
             lines.push(line.to_string());
         }
+        // The boundary of the relation, for the map.
+        lines.push(format!("rel({});", self.config.get_osmrelation()));
+        lines.push("out geom;".to_string());
         Ok(lines.join("\n"))
     }
 
+    /// Produces a query for the OSM data source, which lists streets in relation.
+    pub fn get_osm_streets_source_query(&self) -> anyhow::Result<context::OsmQuery> {
+        Ok(context::OsmQuery {
+            kind: context::OsmQueryKind::Streets,
+            relation_name: self.name.to_string(),
+            osmrelation: self.config.get_osmrelation(),
+            overpass: self.get_osm_streets_json_query()?,
+        })
+    }
+
     /// Gets streets from reference.
     fn get_ref_streets(&self) -> anyhow::Result<Vec<String>> {
         let conn = self.ctx.get_database_connection()?;
@@ -603,7 +945,7 @@ impl<'a> Relation<'a> {
     }
 
     /// Gets the OSM house number list of a street.
-    fn get_osm_housenumbers(
+    pub fn get_osm_housenumbers(
         &mut self,
         street_name: &str,
     ) -> anyhow::Result<Vec<util::HouseNumber>> {
@@ -613,6 +955,7 @@ impl<'a> Relation<'a> {
             let street_ranges = self.get_street_ranges()?;
             let mut house_numbers: HashMap<String, Vec<util::HouseNumber>> = HashMap::new();
             let osm_housenumbers = self.file.get_osm_json_housenumbers(self.ctx)?;
+            let postcodes = reference::get_reference_postcodes(self.ctx)?;
             let mut lints: Vec<RelationLint> = Vec::new();
             for row in osm_housenumbers {
                 let mut street = &row.street;
@@ -621,6 +964,9 @@ impl<'a> Relation<'a> {
                         street = value;
                     }
                 }
+                if let Some(lint) = self.get_postcode_lint(&postcodes, street, &row) {
+                    lints.push(lint);
+                }
                 for house_number in row.housenumber.split(&[';', ',']) {
                     house_numbers
                         .entry(street.to_string())
@@ -643,44 +989,53 @@ impl<'a> Relation<'a> {
                     .insert(key, util::sort_numerically(&value));
             }
 
-            let streets_invalids = self.get_street_invalid();
-            for (street_name, housenumbers) in &self.osm_housenumbers {
-                let mut invalids: Vec<String> = Vec::new();
-                if let Some(value) = streets_invalids.get(street_name) {
-                    invalids.clone_from(value);
-                    invalids = self.normalize_invalids(street_name, &invalids)?;
-
-                    // housenumber letters: OSM data is already in the 42/A, do the same for the
-                    // invalid items as well, so contains() makes sense:
-                    invalids = invalids
-                        .iter()
-                        .map(
-                            |i| match util::HouseNumber::normalize_letter_suffix(i, "") {
+            let invalid_sources = [
+                (RelationLintSource::Invalid, self.get_street_invalid()),
+                (
+                    RelationLintSource::Survey,
+                    self.get_street_survey_invalid()?,
+                ),
+            ];
+            let grammar = self.get_housenumber_grammar()?;
+            for (source, streets_invalids) in &invalid_sources {
+                for (street_name, housenumbers) in &self.osm_housenumbers {
+                    let mut invalids: Vec<String> = Vec::new();
+                    if let Some(value) = streets_invalids.get(street_name) {
+                        invalids.clone_from(value);
+                        invalids = self.normalize_invalids(street_name, &invalids)?;
+
+                        // housenumber letters: OSM data is already normalized by the grammar
+                        // (e.g. 42/A), do the same for the invalid items as well, so contains()
+                        // makes sense:
+                        invalids = invalids
+                            .iter()
+                            .map(|i| match grammar.normalize_suffix(i) {
                                 Ok(value) => value,
                                 Err(_) => i.to_string(),
-                            },
-                        )
-                        .collect();
-                }
-                for housenumber in housenumbers {
-                    if invalids.contains(&housenumber.get_number().to_string()) {
-                        let relation_name = self.get_name();
-                        let street_name = street_name.to_string();
-                        let source = RelationLintSource::Invalid;
-                        let reason = RelationLintReason::CreatedInOsm;
-                        let id: u64 = housenumber.get_id().context("no osm id")?;
-                        let object_type = housenumber.get_object_type().context("no osm type")?;
-                        let housenumber = housenumber.get_number().to_string();
-                        let lint = RelationLint {
-                            relation_name,
-                            street_name,
-                            source,
-                            housenumber,
-                            reason,
-                            id,
-                            object_type,
-                        };
-                        self.lints.push(lint);
+                            })
+                            .collect();
+                    }
+                    for housenumber in housenumbers {
+                        if invalids.contains(&housenumber.get_number().to_string()) {
+                            let relation_name = self.get_name();
+                            let street_name = street_name.to_string();
+                            let source = source.clone();
+                            let reason = RelationLintReason::CreatedInOsm;
+                            let id: u64 = housenumber.get_id().context("no osm id")?;
+                            let object_type =
+                                housenumber.get_object_type().context("no osm type")?;
+                            let housenumber = housenumber.get_number().to_string();
+                            let lint = RelationLint {
+                                relation_name,
+                                street_name,
+                                source,
+                                housenumber,
+                                reason,
+                                id,
+                                object_type,
+                            };
+                            self.lints.push(lint);
+                        }
                     }
                 }
             }
@@ -694,6 +1049,42 @@ impl<'a> Relation<'a> {
         })
     }
 
+    /// Checks the addr:postcode of an OSM house number against the reference postcodes.
+    fn get_postcode_lint(
+        &self,
+        postcodes: &reference::ReferencePostcodes,
+        street: &str,
+        row: &util::OsmHouseNumber,
+    ) -> Option<RelationLint> {
+        // Only raise a lint in case the problem is actionable: has street name and house number.
+        if street.is_empty() || row.housenumber.is_empty() {
+            return None;
+        }
+
+        let mut problem = postcodes.check(&row.postcode);
+        if problem.is_none() && postcodes.has_settlements() {
+            problem = postcodes.check_settlement(
+                &row.postcode,
+                &self.config.get_refcounty(),
+                &self.config.get_street_refsettlement(street),
+            );
+        }
+        let reason = match problem? {
+            reference::PostcodeProblem::Missing => RelationLintReason::MissingPostcode,
+            reference::PostcodeProblem::Malformed => RelationLintReason::MalformedPostcode,
+            reference::PostcodeProblem::Unknown => RelationLintReason::UnknownPostcode,
+        };
+        Some(RelationLint {
+            relation_name: self.get_name(),
+            street_name: street.to_string(),
+            source: RelationLintSource::Postcode,
+            housenumber: row.housenumber.to_string(),
+            reason,
+            id: row.id,
+            object_type: row.object_type.to_string(),
+        })
+    }
+
     /// Determines what suffix should the Nth reference use for hours numbers.
     pub fn get_ref_suffix(index: usize) -> &'static str {
         match index {
@@ -731,6 +1122,7 @@ impl<'a> Relation<'a> {
     ) -> anyhow::Result<HashMap<String, Vec<util::HouseNumber>>> {
         let mut ret: HashMap<String, Vec<util::HouseNumber>> = HashMap::new();
         let mut lines: HashMap<String, Vec<String>> = HashMap::new();
+        let streets_survey_invalid = self.get_street_survey_invalid()?;
         let conn = self.ctx.get_database_connection()?;
         let mut stmt = conn.prepare(
             "select housenumber, comment from ref_housenumbers where county_code = ?1 and settlement_code = ?2 and street = ?3 order by housenumber")?;
@@ -764,7 +1156,11 @@ impl<'a> Relation<'a> {
             let mut street_invalid: Vec<String> = Vec::new();
             if let Some(value) = streets_invalid.get(osm_street_name) {
                 street_invalid.clone_from(value);
-
+            }
+            if let Some(value) = streets_survey_invalid.get(osm_street_name) {
+                street_invalid.extend(value.iter().cloned());
+            }
+            if !street_invalid.is_empty() {
                 // Simplify invalid items by default, so the 42a markup can be used, no matter what
                 // is the value of housenumber-letters.
                 street_invalid = self.normalize_invalids(osm_street_name, &street_invalid)?;
@@ -827,13 +1223,20 @@ impl<'a> Relation<'a> {
                 }
             }
 
-            if let Some(street_invalid) = streets_invalid.get(osm_street_name) {
+            for (source, streets_invalid) in [
+                (RelationLintSource::Invalid, &streets_invalid),
+                (RelationLintSource::Survey, &streets_survey_invalid),
+            ] {
+                let street_invalid = match streets_invalid.get(osm_street_name) {
+                    Some(value) => value,
+                    None => continue,
+                };
                 // This is the full list of invalid items, before removing the out of range ones.
                 for invalid in street_invalid {
                     if !used_invalids.contains(invalid) {
                         let relation_name = self.get_name();
                         let street_name = osm_street.get_osm_name().to_string();
-                        let source = RelationLintSource::Invalid;
+                        let source = source.clone();
                         let housenumber = invalid.to_string();
                         let mut reason = RelationLintReason::DeletedFromRef;
 
@@ -1223,6 +1626,16 @@ impl<'a> Relation<'a> {
         Ok(lines.join("\n"))
     }
 
+    /// Produces a query for the OSM data source, which lists housenumbers in relation.
+    pub fn get_osm_housenumbers_source_query(&self) -> anyhow::Result<context::OsmQuery> {
+        Ok(context::OsmQuery {
+            kind: context::OsmQueryKind::Housenumbers,
+            relation_name: self.name.to_string(),
+            osmrelation: self.config.get_osmrelation(),
+            overpass: self.get_osm_housenumbers_json_query()?,
+        })
+    }
+
     /// Returns invalid osm names and ref names.
     pub fn get_invalid_refstreets(&self) -> anyhow::Result<(Vec<String>, Vec<String>)> {
         let mut osm_invalids: Vec<String> = Vec::new();
@@ -1275,7 +1688,7 @@ impl<'a> Relation<'a> {
             .collect())
     }
 
-    pub fn get_ctx(&self) -> &context::Context {
+    pub fn get_ctx(&self) -> &'a context::Context {
         self.ctx
     }
 
@@ -1380,6 +1793,267 @@ impl<'a> Relation<'a> {
         let now = self.ctx.get_time().now();
         Ok(modified.to_offset(now.offset()))
     }
+
+    /// Records the current street and house number coverage for today.
+    pub fn write_coverage_history(&self) -> anyhow::Result<()> {
+        let format = time::format_description::parse("[year]-[month]-[day]")?;
+        let today = self.ctx.get_time().now().format(&format)?;
+        let streets = if self.has_osm_street_coverage()? {
+            self.get_osm_street_coverage()?
+        } else {
+            "".into()
+        };
+        let housenumbers = if self.has_osm_housenumber_coverage()? {
+            self.get_osm_housenumber_coverage()?
+        } else {
+            "".into()
+        };
+        if streets.is_empty() && housenumbers.is_empty() {
+            return Ok(());
+        }
+        let conn = self.ctx.get_database_connection()?;
+        conn.execute(
+            r#"insert into stats_coverages (date, relation, streets, housenumbers) values (?1, ?2, ?3, ?4)
+                 on conflict(date, relation) do update set streets = excluded.streets, housenumbers = excluded.housenumbers"#,
+            [&today, &self.name, &streets, &housenumbers],
+        )?;
+        Ok(())
+    }
+
+    /// Gets the recorded street and house number coverages, ordered by date.
+    pub fn get_coverage_history(&self) -> anyhow::Result<Vec<CoverageHistoryEntry>> {
+        let conn = self.ctx.get_database_connection()?;
+        let mut stmt = conn.prepare(
+            "select date, streets, housenumbers from stats_coverages where relation = ?1 order by date",
+        )?;
+        let mut rows = stmt.query([&self.name])?;
+        let mut ret: Vec<CoverageHistoryEntry> = Vec::new();
+        let to_option = |value: String| if value.is_empty() { None } else { Some(value) };
+        while let Some(row) = rows.next()? {
+            ret.push(CoverageHistoryEntry {
+                date: row.get(0)?,
+                streets: to_option(row.get(1)?),
+                housenumbers: to_option(row.get(2)?),
+            });
+        }
+        Ok(ret)
+    }
+
+    /// Records survey feedback about a house number, replacing the previous one for the same house
+    /// number.
+    pub fn set_survey_observation(&self, observation: &SurveyObservation) -> anyhow::Result<()> {
+        self.invalidate_missing_housenumbers_cache()?;
+        let conn = self.ctx.get_database_connection()?;
+        conn.execute(
+            r#"insert into survey_observations (relation, street, housenumber, status, note, surveyor, date) values (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 on conflict(relation, street, housenumber) do update set status = excluded.status, note = excluded.note, surveyor = excluded.surveyor, date = excluded.date"#,
+            [
+                &self.name,
+                &observation.street,
+                &observation.housenumber,
+                &observation.status.to_string(),
+                &observation.note,
+                &observation.surveyor,
+                &observation.date,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Removes the survey feedback about a house number, returns if there was such feedback.
+    pub fn remove_survey_observation(
+        &self,
+        street: &str,
+        housenumber: &str,
+    ) -> anyhow::Result<bool> {
+        self.invalidate_missing_housenumbers_cache()?;
+        let conn = self.ctx.get_database_connection()?;
+        let count = conn.execute(
+            "delete from survey_observations where relation = ?1 and street = ?2 and housenumber = ?3",
+            [&self.name, street, housenumber],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Gets the survey feedback of this relation, ordered by street and house number.
+    pub fn get_survey_observations(&self) -> anyhow::Result<Vec<SurveyObservation>> {
+        let conn = self.ctx.get_database_connection()?;
+        let mut stmt = conn.prepare(
+            "select street, housenumber, status, note, surveyor, date from survey_observations where relation = ?1",
+        )?;
+        let mut rows = stmt.query([&self.name])?;
+        let mut ret: Vec<SurveyObservation> = Vec::new();
+        while let Some(row) = rows.next()? {
+            ret.push(SurveyObservation {
+                street: row.get(0)?,
+                housenumber: row.get(1)?,
+                status: SurveyStatus::try_from(row.get::<_, String>(2)?.as_str())?,
+                note: row.get(3)?,
+                surveyor: row.get(4)?,
+                date: row.get(5)?,
+            });
+        }
+        ret.sort_by_key(|i| {
+            (
+                util::get_sort_key(&i.street),
+                util::split_house_number(&i.housenumber),
+            )
+        });
+        Ok(ret)
+    }
+
+    /// Gets the addresses of this relation which are present on multiple OSM objects, ordered by
+    /// street and house number.
+    pub fn get_duplicate_addresses(&self) -> anyhow::Result<Vec<DuplicateAddress>> {
+        let mut addresses: HashMap<(String, String), Vec<DuplicateAddressObject>> = HashMap::new();
+        {
+            let conn = self.ctx.get_database_connection()?;
+            let mut stmt = conn.prepare(
+                "select osm_id, osm_type, street, place, housenumber, unit, door from osm_housenumbers where relation = ?1",
+            )?;
+            let mut rows = stmt.query([&self.name])?;
+            while let Some(row) = rows.next()? {
+                let mut street: String = row.get(2)?;
+                if street.is_empty() {
+                    street = row.get(3)?;
+                }
+                let housenumber: String = row.get(4)?;
+                if street.is_empty() || housenumber.is_empty() {
+                    continue;
+                }
+                let osm_id: String = row.get(0)?;
+                let object = DuplicateAddressObject {
+                    osm_id: osm_id.parse()?,
+                    osm_type: row.get(1)?,
+                    unit: row.get(5)?,
+                    door: row.get(6)?,
+                };
+                addresses
+                    .entry((street, housenumber))
+                    .or_default()
+                    .push(object);
+            }
+        }
+
+        let mut ret: Vec<DuplicateAddress> = Vec::new();
+        for ((street, housenumber), mut objects) in addresses {
+            objects.sort_by_key(|i| (i.osm_type.to_string(), i.osm_id));
+            objects.dedup();
+            if objects.len() < 2 {
+                continue;
+            }
+
+            let mut units: Vec<(&str, &str)> = objects
+                .iter()
+                .map(|i| (i.unit.as_str(), i.door.as_str()))
+                .collect();
+            units.sort();
+            units.dedup();
+            let legitimate = units.len() == objects.len();
+            ret.push(DuplicateAddress {
+                street,
+                housenumber,
+                objects,
+                legitimate,
+            });
+        }
+        ret.sort_by_key(|i| {
+            (
+                util::get_sort_key(&i.street),
+                util::split_house_number(&i.housenumber),
+            )
+        });
+        Ok(ret)
+    }
+
+    /// Makes sure that the next get_missing_housenumbers_json() recalculates the missing house
+    /// numbers and the lints.
+    fn invalidate_missing_housenumbers_cache(&self) -> anyhow::Result<()> {
+        let conn = self.ctx.get_database_connection()?;
+        conn.execute(
+            "delete from mtimes where page = ?1",
+            [format!("missing-housenumbers-cache/{}", self.name)],
+        )?;
+        Ok(())
+    }
+
+    /// Stores a snapshot of the missing housenumbers for today, unless nothing changed since the
+    /// previous snapshot.
+    pub fn write_missing_housenumbers_snapshot(
+        &self,
+        missing_housenumbers: &MissingHousenumbers,
+    ) -> anyhow::Result<()> {
+        let format = time::format_description::parse("[year]-[month]-[day]")?;
+        let today = self.ctx.get_time().now().format(&format)?;
+        let snapshot = MissingHousenumbersSnapshot::new(missing_housenumbers);
+        let conn = self.ctx.get_database_connection()?;
+        let previous: Option<String> = {
+            let mut stmt = conn.prepare(
+                "select json from missing_housenumbers_snapshots where relation = ?1 and date < ?2 order by date desc limit 1",
+            )?;
+            let mut rows = stmt.query([&self.name, &today])?;
+            match rows.next()? {
+                Some(row) => Some(row.get(0)?),
+                None => None,
+            }
+        };
+        if let Some(previous) = previous {
+            let previous: MissingHousenumbersSnapshot = serde_json::from_str(&previous)?;
+            if previous == snapshot {
+                conn.execute(
+                    "delete from missing_housenumbers_snapshots where relation = ?1 and date = ?2",
+                    [&self.name, &today],
+                )?;
+                return Ok(());
+            }
+        }
+        conn.execute(
+            r#"insert into missing_housenumbers_snapshots (relation, date, json) values (?1, ?2, ?3)
+                 on conflict(relation, date) do update set json = excluded.json"#,
+            [&self.name, &today, &serde_json::to_string(&snapshot)?],
+        )?;
+        Ok(())
+    }
+
+    /// Finds the last snapshot of the missing housenumbers on or before `date`, or the first one
+    /// if all are newer. Returns the date of the snapshot and the snapshot itself.
+    pub fn get_missing_housenumbers_snapshot(
+        &self,
+        date: &str,
+    ) -> anyhow::Result<Option<(String, MissingHousenumbersSnapshot)>> {
+        let conn = self.ctx.get_database_connection()?;
+        let queries = [
+            "select date, json from missing_housenumbers_snapshots where relation = ?1 and date <= ?2 order by date desc limit 1",
+            "select date, json from missing_housenumbers_snapshots where relation = ?1 and date > ?2 order by date limit 1",
+        ];
+        for query in queries {
+            let mut stmt = conn.prepare(query)?;
+            let mut rows = stmt.query([&self.name, date])?;
+            if let Some(row) = rows.next()? {
+                let date: String = row.get(0)?;
+                let json: String = row.get(1)?;
+                return Ok(Some((date, serde_json::from_str(&json)?)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Compares the current missing housenumbers with the snapshot of `since`, e.g. 2020-05-10.
+    pub fn get_missing_housenumbers_changes(
+        &mut self,
+        since: &str,
+    ) -> anyhow::Result<MissingHousenumbersChanges> {
+        let json = cache::get_missing_housenumbers_json(self)
+            .context("get_missing_housenumbers_json() failed")?;
+        let missing_housenumbers: MissingHousenumbers = serde_json::from_str(&json)?;
+        let new = MissingHousenumbersSnapshot::new(&missing_housenumbers);
+        let (date, old) = match self.get_missing_housenumbers_snapshot(since)? {
+            Some(value) => value,
+            // The cache was created before we started to store snapshots.
+            None => (since.to_string(), MissingHousenumbersSnapshot::default()),
+        };
+        Ok(MissingHousenumbersChanges::new(&date, &old, &new))
+    }
 }
 
 /// List of relations from data/relations.yaml.
@@ -1628,6 +2302,11 @@ impl<'a> Relations<'a> {
         Ok(())
     }
 
+    /// Forget about all relations which are not in the given set of names.
+    pub fn limit_to_names(&mut self, names: &BTreeSet<String>) {
+        self.dict.retain(|name, _| names.contains(name));
+    }
+
     /// Produces refsettlement IDs of a refcounty.
     pub fn refcounty_get_refsettlement_ids(&self, refcounty_name: &str) -> Vec<String> {
         let refcounty = match self.refsettlement_names.get(refcounty_name) {
@@ -1641,6 +2320,47 @@ impl<'a> Relations<'a> {
         ret
     }
 
+    /// Gets the objects of a relation with an invalid addr:city, based on the whole-country
+    /// findings and the house numbers of the relation.
+    pub fn get_invalid_addr_cities(
+        &self,
+        relation: &Relation<'_>,
+    ) -> anyhow::Result<Vec<InvalidAddrCity>> {
+        let mut ret: Vec<InvalidAddrCity> = Vec::new();
+        let conn = self.ctx.get_database_connection()?;
+        let mut stmt = conn.prepare(
+            "select distinct s.osm_id, s.osm_type, s.street, s.housenumber, s.city from stats_invalid_addr_cities s join osm_housenumbers h on h.osm_id = s.osm_id and h.osm_type = s.osm_type and h.street = s.street where h.relation = ?1",
+        )?;
+        let mut rows = stmt.query([relation.get_name()])?;
+        let refcounty = relation.get_config().get_refcounty();
+        while let Some(row) = rows.next()? {
+            let street: String = row.get(2)?;
+            let suggested_city = match relation
+                .get_config()
+                .get_street_refsettlement(&street)
+                .first()
+            {
+                Some(refsettlement) => self.refsettlement_get_name(&refcounty, refsettlement),
+                None => "".into(),
+            };
+            ret.push(InvalidAddrCity {
+                osm_id: row.get(0)?,
+                osm_type: row.get(1)?,
+                street,
+                housenumber: row.get(3)?,
+                city: row.get(4)?,
+                suggested_city,
+            });
+        }
+        ret.sort_by_key(|i| {
+            (
+                util::get_sort_key(&i.street),
+                util::split_house_number(&i.housenumber),
+            )
+        });
+        Ok(ret)
+    }
+
     /// Provide an alias -> real name map of relations.
     pub fn get_aliases(&mut self) -> anyhow::Result<HashMap<String, String>> {
         let mut ret: HashMap<String, String> = HashMap::new();
@@ -1706,57 +2426,19 @@ struct LintedHouseNumber<'a> {
 /// Expands numbers_nofilter into a list of numbers, returns ret_numbers otherwise.
 fn normalize_expand(
     relation: &Relation<'_>,
-    separator: &str,
+    grammar: &dyn housenumber_grammar::HouseNumberGrammar,
     normalizer: &ranges::Ranges,
     numbers_nofilter: &[i64],
-    mut ret_numbers: Vec<i64>,
+    ret_numbers: Vec<i64>,
     street_name: &str,
     lhn: LintedHouseNumber<'_>,
 ) -> Vec<i64> {
-    if separator != "-" {
-        return ret_numbers;
-    }
-
     let street_is_even_odd = relation.get_config().get_street_is_even_odd(street_name);
-    let (should_expand, new_stop) = util::should_expand_range(numbers_nofilter, street_is_even_odd);
-    if should_expand {
-        let relation_name = &relation.get_name();
-        let start = numbers_nofilter[0];
-        let stop = new_stop;
-        if stop == 0 {
-            ret_numbers = [start]
-                .iter()
-                .filter(|number| {
-                    normalizer_contains(
-                        **number,
-                        normalizer,
-                        relation_name,
-                        street_name,
-                        lhn.lints,
-                        lhn.osm_housenumber,
-                    )
-                })
-                .cloned()
-                .collect();
-        } else if street_is_even_odd {
-            // Assume that e.g. 2-6 actually means 2, 4 and 6, not only 2 and 4.
-            // Closed interval, even only or odd only case.
-            ret_numbers = (start..stop + 2)
-                .step_by(2)
-                .filter(|number| {
-                    normalizer_contains(
-                        *number,
-                        normalizer,
-                        relation_name,
-                        street_name,
-                        lhn.lints,
-                        lhn.osm_housenumber,
-                    )
-                })
-                .collect();
-        } else {
-            // Closed interval, but mixed even and odd.
-            ret_numbers = (start..stop + 1)
+    match grammar.expand_range(numbers_nofilter, street_is_even_odd) {
+        Some(numbers) => {
+            let relation_name = &relation.get_name();
+            numbers
+                .into_iter()
                 .filter(|number| {
                     normalizer_contains(
                         *number,
@@ -1767,11 +2449,10 @@ fn normalize_expand(
                         lhn.osm_housenumber,
                     )
                 })
-                .collect();
+                .collect()
         }
+        None => ret_numbers,
     }
-
-    ret_numbers
 }
 
 /// Strips down string input to bare minimum that can be interpreted as an
@@ -1792,14 +2473,8 @@ fn normalize<'a>(
         house_numbers = iter.next().unwrap().into();
         comment = iter.next().unwrap().into();
     }
-    let separator: &str;
-    if house_numbers.contains(';') {
-        separator = ";";
-    } else if house_numbers.contains(',') {
-        separator = ",";
-    } else {
-        separator = "-";
-    }
+    let grammar = relation.get_housenumber_grammar()?;
+    let separator = grammar.get_separator(&house_numbers);
 
     // Determine suffix which is not normalized away.
     let mut suffix: String = "".into();
@@ -1812,7 +2487,7 @@ fn normalize<'a>(
 
     let (mut ret_numbers, ret_numbers_nofilter) = util::split_house_number_by_separator(
         &house_numbers,
-        separator,
+        separator.value,
         &normalizer,
         &relation.get_name(),
         street_name,
@@ -1820,14 +2495,14 @@ fn normalize<'a>(
         osm_housenumber,
     );
 
-    {
+    if separator.is_range {
         let lhn = LintedHouseNumber {
             lints,
             osm_housenumber,
         };
         ret_numbers = normalize_expand(
             relation,
-            separator,
+            grammar.as_ref(),
             &normalizer,
             &ret_numbers_nofilter,
             ret_numbers,
@@ -1836,12 +2511,13 @@ fn normalize<'a>(
         );
     }
 
-    let check_housenumber_letters =
-        ret_numbers.len() == 1 && relation.config.should_check_housenumber_letters();
-    let ret: Vec<util::HouseNumber> = if check_housenumber_letters
-        && util::HouseNumber::has_letter_suffix(&house_numbers, &suffix)
-    {
-        normalize_housenumber_letters(&house_numbers, &suffix, &comment)?
+    let bare_house_number = &house_numbers[..house_numbers.len() - suffix.len()];
+    let check_housenumber_letters = ret_numbers.len() == 1
+        && ((relation.config.should_check_housenumber_letters()
+            && grammar.has_letter_suffix(bare_house_number))
+            || grammar.is_compound(bare_house_number));
+    let ret: Vec<util::HouseNumber> = if check_housenumber_letters {
+        normalize_housenumber_letters(grammar.as_ref(), &house_numbers, &suffix, &comment)?
     } else {
         ret_numbers
             .iter()
@@ -1867,11 +2543,13 @@ fn normalize<'a>(
 
 /// Handles the part of normalize() that deals with housenumber letters.
 fn normalize_housenumber_letters(
+    grammar: &dyn housenumber_grammar::HouseNumberGrammar,
     house_numbers: &str,
     suffix: &str,
     comment: &str,
 ) -> anyhow::Result<Vec<util::HouseNumber>> {
-    let normalized = util::HouseNumber::normalize_letter_suffix(house_numbers, suffix)?;
+    let bare_house_number = &house_numbers[..house_numbers.len() - suffix.len()];
+    let normalized = grammar.normalize_suffix(bare_house_number)? + suffix;
     Ok(vec![util::HouseNumber::new(
         &normalized,
         &normalized,
@@ -1907,15 +2585,25 @@ pub fn make_turbo_query_for_street_objs(
     relation: &Relation<'_>,
     streets: &[util::Street],
 ) -> String {
+    let ids: Vec<(String, u64)> = streets
+        .iter()
+        .map(|street| (street.get_osm_type().to_string(), street.get_osm_id()))
+        .collect();
+    make_turbo_query_for_objs(relation, &ids)
+}
+
+/// Creates an overpass query that shows the given objects, with their child objects, so their
+/// location is known.
+pub fn make_turbo_query_for_objs(relation: &Relation<'_>, objects: &[(String, u64)]) -> String {
     let header = r#"[out:json][timeout:425];
 rel(@RELATION@)->.searchRelation;
 area(@AREA@)->.searchArea;
 ("#;
     let mut query = util::process_template(header, relation.config.get_osmrelation());
-    let mut ids = Vec::new();
-    for street in streets {
-        ids.push((street.get_osm_type(), street.get_osm_id().to_string()));
-    }
+    let mut ids: Vec<(&String, String)> = objects
+        .iter()
+        .map(|(osm_type, osm_id)| (osm_type, osm_id.to_string()))
+        .collect();
     ids.sort();
     ids.dedup();
     for (osm_type, osm_id) in ids {
@@ -11,6 +11,7 @@
 //! Tests for the areas module.
 
 use super::*;
+use crate::reference;
 use std::io::Write;
 use std::rc::Rc;
 
@@ -40,6 +41,95 @@ fn test_normalize() {
     assert_eq!(actual, vec!["139"])
 }
 
+/// Tests normalize(): the house number grammar of the relation is used.
+#[test]
+fn test_normalize_grammars() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "hu": {
+            },
+            "de": {
+                "housenumber-grammar": "de",
+            },
+            "us": {
+                "housenumber-grammar": "us",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let mut relations = Relations::new(&ctx).unwrap();
+    let cases = [
+        ("hu", "2-6", vec!["2", "4", "6"]),
+        ("hu", "1;3", vec!["1", "3"]),
+        ("hu", "42a", vec!["42"]),
+        ("de", "12-14", vec!["12", "14"]),
+        ("de", "12a", vec!["12"]),
+        ("us", "37-12", vec!["37-12"]),
+        ("us", "123 1/2", vec!["123 1/2"]),
+        ("us", "12B", vec!["12"]),
+        ("us", "1,3", vec!["1", "3"]),
+    ];
+    for (relation_name, house_numbers, expected) in cases {
+        let relation = relations.get_relation(relation_name).unwrap();
+        let normalizers = relation.get_street_ranges().unwrap();
+        let house_numbers = normalize(
+            &relation,
+            house_numbers,
+            "mystreet",
+            &normalizers,
+            &mut None,
+            None,
+        )
+        .unwrap();
+        let actual: Vec<_> = house_numbers.iter().map(|i| i.get_number()).collect();
+        assert_eq!(actual, expected, "{relation_name}");
+    }
+}
+
+/// Tests normalize(): the house number letters of a non-default grammar.
+#[test]
+fn test_normalize_grammar_letters() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "de": {
+                "housenumber-grammar": "de",
+                "housenumber-letters": true,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let mut relations = Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("de").unwrap();
+    let normalizers = relation.get_street_ranges().unwrap();
+
+    let house_numbers = normalize(
+        &relation,
+        "12 A*",
+        "mystreet",
+        &normalizers,
+        &mut None,
+        None,
+    )
+    .unwrap();
+
+    let actual: Vec<_> = house_numbers.iter().map(|i| i.get_number()).collect();
+    assert_eq!(actual, vec!["12a*"]);
+}
+
 /// Tests normalize: when the number is not in range.
 #[test]
 fn test_normalize_not_in_range() {
@@ -672,7 +762,10 @@ fn test_relation_get_osm_streets_json_query() {
     let relation_name = "gazdagret";
     let relation = relations.get_relation(relation_name).unwrap();
     let ret = relation.get_osm_streets_json_query().unwrap();
-    assert_eq!(ret, "[out:json];\naaa 42 bbb 3600000042 ccc");
+    assert_eq!(
+        ret,
+        "[out:json];\naaa 42 bbb 3600000042 ccc\nrel(42);\nout geom;"
+    );
 }
 
 /// Tests Relation.get_osm_housenumbers_query().
@@ -1208,8 +1301,7 @@ way{color:blue; width:4;}
 fn test_relation_get_ref_streets() {
     let mut ctx = context::tests::make_test_context().unwrap();
     {
-        let ref_streets = ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -1375,6 +1467,129 @@ fn test_relation_get_missing_housenumbers() {
     );
 }
 
+/// Builds a street -> housenumbers map from string literals.
+fn make_street_housenumbers(streets: &[(&str, &[&str])]) -> StreetHousenumbers {
+    streets
+        .iter()
+        .map(|(street, numbers)| {
+            (
+                street.to_string(),
+                numbers.iter().map(|i| i.to_string()).collect(),
+            )
+        })
+        .collect()
+}
+
+/// Tests MissingHousenumbersChanges::new().
+#[test]
+fn test_missing_housenumbers_changes_new() {
+    let old = MissingHousenumbersSnapshot {
+        missing: make_street_housenumbers(&[("mystreet", &["1", "2", "3"])]),
+        done: make_street_housenumbers(&[("mystreet", &["4", "5"])]),
+    };
+    let new = MissingHousenumbersSnapshot {
+        missing: make_street_housenumbers(&[("mystreet", &["2", "5", "6"])]),
+        done: make_street_housenumbers(&[("mystreet", &["1", "4"])]),
+    };
+
+    let changes = MissingHousenumbersChanges::new("2020-05-01", &old, &new);
+
+    assert_eq!(changes.since, "2020-05-01");
+    assert_eq!(
+        changes.newly_mapped,
+        make_street_housenumbers(&[("mystreet", &["1"])])
+    );
+    assert_eq!(
+        changes.newly_missing,
+        make_street_housenumbers(&[("mystreet", &["6"])])
+    );
+    assert_eq!(
+        changes.no_longer_mapped,
+        make_street_housenumbers(&[("mystreet", &["5"])])
+    );
+    assert_eq!(
+        changes.removed_from_reference,
+        make_street_housenumbers(&[("mystreet", &["3"])])
+    );
+}
+
+/// Tests Relation::write_missing_housenumbers_snapshot().
+#[test]
+fn test_relation_write_missing_housenumbers_snapshot() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "myrelation": {
+                "refcounty": "0",
+                "refsettlement": "0",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        // Old state: both 1 and 2 were missing.
+        conn.execute_batch(
+            r#"insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'mystreet', '1', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'mystreet', '2', '');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('myrelation', '2', 'mystreet', '', '', '', '', '');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '1', 'mystreet', '1', '', '', '', '', '', '', '', '', '', 'node');
+             insert into missing_housenumbers_snapshots (relation, date, json) values ('myrelation', '2020-05-01', '{"missing":{"mystreet":["1","2"]},"done":{}}');"#,
+        )
+        .unwrap();
+    }
+    let mut relations = Relations::new(&ctx).unwrap();
+    let mut relation = relations.get_relation("myrelation").unwrap();
+
+    let changes = relation
+        .get_missing_housenumbers_changes("2020-05-03")
+        .unwrap();
+
+    assert_eq!(changes.since, "2020-05-01");
+    assert_eq!(
+        changes.newly_mapped,
+        make_street_housenumbers(&[("mystreet", &["1"])])
+    );
+    assert!(changes.newly_missing.is_empty());
+    // The cache update wrote a new snapshot for today.
+    let (date, snapshot) = relation
+        .get_missing_housenumbers_snapshot("2020-05-10")
+        .unwrap()
+        .unwrap();
+    assert_eq!(date, "2020-05-10");
+    assert_eq!(
+        snapshot.done,
+        make_street_housenumbers(&[("mystreet", &["1"])])
+    );
+    // Asking for a date before the first snapshot gives the first snapshot.
+    let (date, _snapshot) = relation
+        .get_missing_housenumbers_snapshot("2020-01-01")
+        .unwrap()
+        .unwrap();
+    assert_eq!(date, "2020-05-01");
+
+    // Writing the same state again is a no-op for a new day, since nothing changed.
+    let missing_housenumbers = relation.get_missing_housenumbers().unwrap();
+    relation
+        .write_missing_housenumbers_snapshot(&missing_housenumbers)
+        .unwrap();
+    let conn = ctx.get_database_connection().unwrap();
+    let mut stmt = conn
+        .prepare(
+            "select count(*) from missing_housenumbers_snapshots where relation = 'myrelation'",
+        )
+        .unwrap();
+    let mut rows = stmt.query([]).unwrap();
+    let count: i64 = rows.next().unwrap().unwrap().get(0).unwrap();
+    assert_eq!(count, 2);
+}
+
 /// Tests Relation::get_missing_housenumbers(), the case when 'invalid' contains hyphens.
 #[test]
 fn test_relation_get_missing_housenumbers_invalid_hyphens() {
@@ -1486,14 +1701,14 @@ fn test_relation_get_lints() {
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '10', '');
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '2', '');
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '9', '');
-            insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Törökugrató utca', '1', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '2', 'Törökugrató utca', '2', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '3', 'Tűzkő utca', '9', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '4', 'Tűzkő utca', '10', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '5', 'OSM Name 1', '1', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '6', 'OSM Name 1', '2', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '7', 'Only In OSM utca', '1', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '8', 'Second Only In OSM utca', '1', '', '', '', '', '', '', '', '', '', 'node');
+            insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Törökugrató utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '2', 'Törökugrató utca', '2', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '3', 'Tűzkő utca', '9', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '4', 'Tűzkő utca', '10', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '5', 'OSM Name 1', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '6', 'OSM Name 1', '2', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '7', 'Only In OSM utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '8', 'Second Only In OSM utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
              insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');",
         )
         .unwrap();
@@ -1552,7 +1767,7 @@ fn test_relation_get_lints_hn_letters() {
         let conn = ctx.get_database_connection().unwrap();
         conn.execute_batch(
             "insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tolvajos tanya', '52/b', '');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '1', '', '52/b', '', 'Tolvajos tanya', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '1', '', '52/b', '1111', 'Tolvajos tanya', '', '', '', '', '', '', '', 'node');
              insert into mtimes (page, last_modified) values ('housenumbers/myrelation', '0');",
         )
         .unwrap();
@@ -1575,6 +1790,79 @@ fn test_relation_get_lints_hn_letters() {
     assert_eq!(lint.reason, RelationLintReason::CreatedInOsm);
 }
 
+/// Tests Relation::get_lints(), the housenumber-letters=true case with non-default grammars.
+#[test]
+fn test_relation_get_lints_hn_letters_grammars() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "de": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "housenumber-grammar": "de",
+            },
+            "us": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "housenumber-grammar": "us",
+            },
+        },
+        "relation-de.yaml": {
+            "filters": {
+                "Hauptstraße": {
+                    "invalid": [ "12 A" ],
+                }
+            },
+            "housenumber-letters": true,
+        },
+        "relation-us.yaml": {
+            "filters": {
+                "Main Street": {
+                    "invalid": [ "12b" ],
+                }
+            },
+            "housenumber-letters": true,
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('de', '1', 'Hauptstraße', '12a', '', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('us', '2', 'Main Street', '12B', '', '', '', '', '', '', '', '', '', 'node');
+             insert into mtimes (page, last_modified) values ('housenumbers/de', '0');
+             insert into mtimes (page, last_modified) values ('housenumbers/us', '0');",
+        )
+        .unwrap();
+    }
+    let mut relations = Relations::new(&ctx).unwrap();
+    let cases = [("de", "Hauptstraße", "12a"), ("us", "Main Street", "12B")];
+    for (relation_name, street_name, housenumber) in cases {
+        let mut relation = relations.get_relation(relation_name).unwrap();
+        let _missing_housenumbers = relation.get_missing_housenumbers().unwrap();
+
+        let lints: Vec<_> = relation
+            .get_lints()
+            .into_iter()
+            .filter(|i| i.reason == RelationLintReason::CreatedInOsm)
+            .collect();
+
+        // Previously the invalid item was normalized to 12/A or 12/B, so this was empty.
+        assert_eq!(lints.len(), 1, "{relation_name}");
+        let lint = lints[0].clone();
+        assert_eq!(lint.street_name, street_name);
+        assert_eq!(lint.source, RelationLintSource::Invalid);
+        assert_eq!(lint.housenumber, housenumber);
+        assert_eq!(lint.reason, RelationLintReason::CreatedInOsm);
+    }
+}
+
 /// Tests Relation::get_lints(), the out-of-range case.
 #[test]
 fn test_relation_get_lints_out_of_range() {
@@ -1636,6 +1924,89 @@ fn test_relation_get_lints_out_of_range() {
     assert_eq!(RelationLintReason::OutOfRange.to_string(), "out-of-range");
 }
 
+/// Tests Relation::get_lints(), the postcode case.
+#[test]
+fn test_relation_get_lints_postcode() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "01",
+                "refsettlement": "011",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let zipcounts = context::tests::TestFileSystem::make_file();
+    zipcounts
+        .borrow_mut()
+        .write_all(
+            b"ZIP\tCNT\tCOUNTY_CODE\tSETTLEMENT_CODE\n1111\t100\t01\t011\n2040\t50\t13\t007\n",
+        )
+        .unwrap();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("data/yamls.cache", &yamls_cache_value),
+            ("workdir/refs/irsz_count_20190717.tsv", &zipcounts),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Tűzkő utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '2', 'Tűzkő utca', '2', '', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '3', 'Tűzkő utca', '3', '1111a', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '4', 'Tűzkő utca', '4', '2040', '', '', '', '', '', '', '', '', 'way');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '5', '', '5', '', '', '', '', '', '', '', '', '', 'node');",
+        )
+        .unwrap();
+    }
+    let mut relations = Relations::new(&ctx).unwrap();
+    let mut relation = relations.get_relation("gazdagret").unwrap();
+
+    relation.get_osm_housenumbers("Tűzkő utca").unwrap();
+
+    let actual: Vec<_> = relation
+        .get_lints()
+        .iter()
+        .map(|i| {
+            (
+                i.source.clone(),
+                i.housenumber.clone(),
+                i.reason.clone(),
+                i.id,
+            )
+        })
+        .collect();
+    // 1 has a valid postcode, 5 has no street, so those are not actionable.
+    assert_eq!(
+        actual,
+        vec![
+            (
+                RelationLintSource::Postcode,
+                "2".to_string(),
+                RelationLintReason::MissingPostcode,
+                2
+            ),
+            (
+                RelationLintSource::Postcode,
+                "3".to_string(),
+                RelationLintReason::MalformedPostcode,
+                3
+            ),
+            (
+                RelationLintSource::Postcode,
+                "4".to_string(),
+                RelationLintReason::UnknownPostcode,
+                4
+            ),
+        ]
+    );
+}
+
 /// Tests Relation::write_lints().
 #[test]
 fn test_relation_write_lints() {
@@ -1678,14 +2049,14 @@ fn test_relation_write_lints() {
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '10', '');
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '2', '');
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '9', '');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Törökugrató utca', '1', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '2', 'Törökugrató utca', '2', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '3', 'Tűzkő utca', '9', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '4', 'Tűzkő utca', '10', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '5', 'OSM Name 1', '1', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '6', 'OSM Name 1', '2', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '7', 'Only In OSM utca', '1', '', '', '', '', '', '', '', '', '', 'node');
-             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '8', 'Second Only In OSM utca', '1', '', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Törökugrató utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '2', 'Törökugrató utca', '2', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '3', 'Tűzkő utca', '9', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '4', 'Tűzkő utca', '10', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '5', 'OSM Name 1', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '6', 'OSM Name 1', '2', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '7', 'Only In OSM utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '8', 'Second Only In OSM utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
              insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');",
         )
         .unwrap();
@@ -2064,8 +2435,7 @@ fn test_relation_get_missing_housenumbers_letter_suffix_normalize_semicolon() {
 fn test_relation_get_missing_streets() {
     let mut ctx = context::tests::make_test_context().unwrap();
     {
-        let ref_streets = ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -2122,8 +2492,7 @@ fn test_relation_get_missing_streets() {
 fn test_relation_get_additional_streets() {
     let mut ctx = context::tests::make_test_context().unwrap();
     {
-        let ref_streets = ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -2530,8 +2899,7 @@ fn test_relation_write_missing_housenumbers_sorting() {
 fn test_write_missing_streets() {
     let mut ctx = context::tests::make_test_context().unwrap();
     {
-        let ref_streets = ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -3599,3 +3967,206 @@ fn test_relation_get_osm_housenumber_split() {
     assert_eq!(housenumbers[0].get_number(), "12/A");
     assert_eq!(housenumbers[1].get_number(), "12/B");
 }
+
+/// Tests Relation::set_survey_observation() and how missing house numbers and lints use it.
+#[test]
+fn test_relation_survey_observations() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "0",
+                "refsettlement": "0",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '1', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '2', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '3', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '4', '');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Tűzkő utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '4', 'Tűzkő utca', '4', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');",
+        )
+        .unwrap();
+    }
+    let mut relations = Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+    let observations = [
+        ("2", SurveyStatus::ConfirmedAbsent),
+        ("3", SurveyStatus::NeedsResurvey),
+        // Stale: mapped in OSM since then.
+        ("4", SurveyStatus::DifferentNumber),
+        // Stale: not in the reference.
+        ("5", SurveyStatus::ConfirmedAbsent),
+    ];
+    for (housenumber, status) in observations {
+        relation
+            .set_survey_observation(&SurveyObservation {
+                street: "Tűzkő utca".into(),
+                housenumber: housenumber.into(),
+                status,
+                note: "".into(),
+                surveyor: "alice".into(),
+                date: "2020-05-01".into(),
+            })
+            .unwrap();
+    }
+    assert_eq!(relation.get_survey_observations().unwrap().len(), 4);
+    assert_eq!(
+        relation
+            .remove_survey_observation("Tűzkő utca", "42")
+            .unwrap(),
+        false
+    );
+
+    let mut relation = relations.get_relation("gazdagret").unwrap();
+    let missing_housenumbers = relation.get_missing_housenumbers().unwrap();
+
+    // 2 is silenced, 3 needs a resurvey, so it's still missing.
+    assert_eq!(missing_housenumbers.ongoing_streets.len(), 1);
+    let numbers: Vec<_> = missing_housenumbers.ongoing_streets[0]
+        .house_numbers
+        .iter()
+        .map(|i| i.get_number())
+        .collect();
+    assert_eq!(numbers, ["3"]);
+    let mut lints: Vec<_> = relation
+        .get_lints()
+        .iter()
+        .map(|i| (i.source.clone(), i.housenumber.clone(), i.reason.clone()))
+        .collect();
+    lints.sort();
+    assert_eq!(
+        lints,
+        [
+            (
+                RelationLintSource::Survey,
+                "4".to_string(),
+                RelationLintReason::CreatedInOsm
+            ),
+            (
+                RelationLintSource::Survey,
+                "5".to_string(),
+                RelationLintReason::DeletedFromRef
+            ),
+        ]
+    );
+}
+
+/// Tests Relations::get_invalid_addr_cities().
+#[test]
+fn test_relations_get_invalid_addr_cities() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "myrelation": {
+                "refcounty": "01",
+                "refsettlement": "011",
+            },
+        },
+        "refsettlement-names.yaml": {
+            "01": {
+                "011": "Mycity",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '1', 'mystreet', '2', '', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '2', 'mystreet', '10', '', '', '', '', '', '', '', '', '', 'way');
+             insert into stats_invalid_addr_cities (osm_id, osm_type, postcode, city, street, housenumber, user) values ('2', 'way', '1111', 'Mycyty', 'mystreet', '10', 'myuser');
+             insert into stats_invalid_addr_cities (osm_id, osm_type, postcode, city, street, housenumber, user) values ('1', 'node', '1111', 'mycity', 'mystreet', '2', 'myuser');
+             insert into stats_invalid_addr_cities (osm_id, osm_type, postcode, city, street, housenumber, user) values ('1', 'way', '1111', 'Othercity', 'mystreet', '2', 'myuser');
+             insert into stats_invalid_addr_cities (osm_id, osm_type, postcode, city, street, housenumber, user) values ('3', 'node', '1111', 'Othercity', 'mystreet', '4', 'myuser');",
+        )
+        .unwrap();
+    }
+    let mut relations = Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("myrelation").unwrap();
+
+    let actual = relations.get_invalid_addr_cities(&relation).unwrap();
+
+    // The objects of other relations are not listed, numbers are sorted numerically.
+    let invalid = |osm_id: &str, osm_type: &str, housenumber: &str, city: &str| InvalidAddrCity {
+        osm_id: osm_id.into(),
+        osm_type: osm_type.into(),
+        street: "mystreet".into(),
+        housenumber: housenumber.into(),
+        city: city.into(),
+        suggested_city: "Mycity".into(),
+    };
+    assert_eq!(
+        actual,
+        vec![
+            invalid("1", "node", "2", "mycity"),
+            invalid("2", "way", "10", "Mycyty"),
+        ]
+    );
+}
+
+/// Tests Relation::get_duplicate_addresses().
+#[test]
+fn test_relation_get_duplicate_addresses() {
+    let ctx = context::tests::make_test_context().unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '1', 'mystreet', '10', '', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '2', 'mystreet', '10', '', '', '', '', '', '', '', '', '', 'way');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '3', 'mystreet', '2', '', '', '', '', '', '', '', 'A', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '4', 'mystreet', '2', '', '', '', '', '', '', '', 'B', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '5', 'mystreet', '3', '', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('myrelation', '6', '', '3', '', 'myplace', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('otherrelation', '7', 'mystreet', '3', '', '', '', '', '', '', '', '', '', 'node');",
+        )
+        .unwrap();
+    }
+    let mut relations = Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("myrelation").unwrap();
+
+    let actual = relation.get_duplicate_addresses().unwrap();
+
+    // 3 is not a duplicate: different street/place, and 7 is in an other relation.
+    let object = |osm_id, osm_type: &str, unit: &str| DuplicateAddressObject {
+        osm_id,
+        osm_type: osm_type.into(),
+        unit: unit.into(),
+        door: "".into(),
+    };
+    assert_eq!(
+        actual,
+        vec![
+            DuplicateAddress {
+                street: "mystreet".into(),
+                housenumber: "2".into(),
+                objects: vec![object(3, "node", "A"), object(4, "node", "B")],
+                legitimate: true,
+            },
+            DuplicateAddress {
+                street: "mystreet".into(),
+                housenumber: "10".into(),
+                objects: vec![object(1, "node", ""), object(2, "way", "")],
+                legitimate: false,
+            },
+        ]
+    );
+}
@@ -5,6 +5,7 @@
  */
 
 import * as config from './config';
+import * as map from './map';
 import 'sorttable'; // only for its side-effects
 import * as stats from './stats';
 
@@ -333,6 +334,9 @@ document.addEventListener("DOMContentLoaded", async function() {
     initRedirects();
     initTriggerUpdate();
     stats.initStats();
+    stats.initCoverageHistory();
+    map.initMap();
+    map.initCoverageMap();
 });
 
 // vim: shiftwidth=4 softtabstop=4 expandtab:
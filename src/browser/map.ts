@@ -0,0 +1,269 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+import * as L from 'leaflet';
+
+function getString(key: string) {
+    return document.getElementById(key).getAttribute("data-value");
+}
+
+// Colors of the house number and street statuses, see wsgi_map::map_view_geojson().
+const statusColors: { [status: string]: string } = {
+    "matched": "#2ca02c",
+    "additional": "#1f77b4",
+    "lint": "#d62728",
+    "incomplete": "#ff7f0e",
+    "complete": "#7f7f7f",
+};
+
+// MapProperties is the properties of one feature of /osm/map/<relation>/view-result.geojson.
+interface MapProperties {
+    kind: string;
+    osm_type: string;
+    osm_id: string;
+    name?: string;
+    status?: string;
+}
+
+function getStyle(feature: GeoJSON.Feature<GeoJSON.Geometry, MapProperties>): L.PathOptions {
+    const properties = feature.properties;
+    if (properties.kind == "boundary") {
+        return { color: "#000000", weight: 2, dashArray: "4 4" };
+    }
+    return { color: statusColors[properties.status], weight: 4 };
+}
+
+function addPopup(feature: GeoJSON.Feature<GeoJSON.Geometry, MapProperties>, layer: L.Layer) {
+    const properties = feature.properties;
+    if (properties.kind == "boundary") {
+        return;
+    }
+
+    const popup = document.createElement("div");
+    const name = document.createElement("b");
+    name.textContent = properties.name;
+    popup.appendChild(name);
+    popup.appendChild(document.createElement("br"));
+    popup.appendChild(document.createTextNode(getString("str-map-" + properties.status)));
+    popup.appendChild(document.createElement("br"));
+    const link = document.createElement("a");
+    link.href = "https://www.openstreetmap.org/" + properties.osm_type + "/" + properties.osm_id;
+    link.textContent = properties.osm_type + "/" + properties.osm_id;
+    popup.appendChild(link);
+    layer.bindPopup(popup);
+}
+
+function addLegend(map: L.Map) {
+    const legend = new L.Control({ position: "bottomright" });
+    legend.onAdd = function() {
+        const div = L.DomUtil.create("div", "map-legend");
+        for (const status of Object.keys(statusColors)) {
+            const item = document.createElement("div");
+            const swatch = document.createElement("span");
+            swatch.className = "map-legend-swatch";
+            swatch.style.backgroundColor = statusColors[status];
+            item.appendChild(swatch);
+            item.appendChild(document.createTextNode(getString("str-map-" + status)));
+            div.appendChild(item);
+        }
+        return div;
+    };
+    legend.addTo(map);
+}
+
+async function initMap(): Promise<void>
+{
+    const element = document.getElementById("map");
+    if (!element) {
+        // Not on a map page.
+        return;
+    }
+
+    const response = await window.fetch(element.getAttribute("data-url"));
+    const collection = await<Promise<GeoJSON.FeatureCollection<GeoJSON.Geometry, MapProperties>>> response.json();
+
+    const map = L.map(element);
+    L.tileLayer("https://tile.openstreetmap.org/{z}/{x}/{y}.png", {
+        maxZoom: 19,
+        attribution: '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a>',
+    }).addTo(map);
+    const layer = L.geoJSON(collection, {
+        style: getStyle,
+        pointToLayer: function(feature: GeoJSON.Feature<GeoJSON.Point, MapProperties>, latlng: L.LatLng) {
+            const color = statusColors[feature.properties.status];
+            return L.circleMarker(latlng, { radius: 5, color: color, fillColor: color, fillOpacity: 0.8 });
+        },
+        onEachFeature: addPopup,
+    }).addTo(map);
+    // Draw the house numbers on top of the streets.
+    layer.eachLayer(function(child: L.Layer) {
+        if (child instanceof L.CircleMarker) {
+            child.bringToFront();
+        }
+    });
+    const bounds = layer.getBounds();
+    if (bounds.isValid()) {
+        map.fitBounds(bounds);
+    } else {
+        map.setView([47.4979, 19.0402], 12);
+    }
+    addLegend(map);
+}
+
+// Coverage buckets of the coverage map: lower limit in percent and color, best first.
+const coverageColors: [number, string][] = [
+    [100, "#1a9850"],
+    [95, "#91cf60"],
+    [90, "#d9ef8b"],
+    [80, "#fee08b"],
+    [50, "#fc8d59"],
+    [0, "#d73027"],
+];
+
+const unknownCoverageColor = "#bdbdbd";
+
+// CoverageProperties is the properties of one feature of /osm/coverage-map/view-result.geojson.
+interface CoverageProperties {
+    name: string;
+    housenumber_coverage: number | null;
+    street_coverage: number | null;
+    missing_housenumbers_url: string;
+    missing_streets_url: string;
+}
+
+// The coverage kind which is shown on the coverage map: "housenumbers" or "streets".
+let coverageKind = "housenumbers";
+
+function getCoverage(properties: CoverageProperties): number | null {
+    if (coverageKind == "streets") {
+        return properties.street_coverage;
+    }
+    return properties.housenumber_coverage;
+}
+
+function getCoverageColor(coverage: number | null): string {
+    if (coverage === null) {
+        return unknownCoverageColor;
+    }
+    for (const [limit, color] of coverageColors) {
+        if (coverage >= limit) {
+            return color;
+        }
+    }
+    return unknownCoverageColor;
+}
+
+function getCoverageStyle(feature: GeoJSON.Feature<GeoJSON.Geometry, CoverageProperties>): L.PathOptions {
+    const color = getCoverageColor(getCoverage(feature.properties));
+    return { color: "#555555", weight: 1, fillColor: color, fillOpacity: 0.7 };
+}
+
+function addCoveragePopup(feature: GeoJSON.Feature<GeoJSON.Geometry, CoverageProperties>, layer: L.Layer) {
+    const properties = feature.properties;
+    const formatCoverage = function(coverage: number | null) {
+        if (coverage === null) {
+            return getString("str-coverage-unknown");
+        }
+        return coverage.toFixed(2) + "%";
+    };
+    const popup = document.createElement("div");
+    const name = document.createElement("b");
+    name.textContent = properties.name;
+    popup.appendChild(name);
+    const rows: [string, number | null, string][] = [
+        ["str-coverage-housenumbers", properties.housenumber_coverage, properties.missing_housenumbers_url],
+        ["str-coverage-streets", properties.street_coverage, properties.missing_streets_url],
+    ];
+    for (const [key, coverage, url] of rows) {
+        popup.appendChild(document.createElement("br"));
+        popup.appendChild(document.createTextNode(getString(key) + ": "));
+        const link = document.createElement("a");
+        link.href = url;
+        link.textContent = formatCoverage(coverage);
+        popup.appendChild(link);
+    }
+    layer.bindPopup(popup);
+}
+
+function addCoverageControls(map: L.Map, layer: L.GeoJSON) {
+    const toggle = new L.Control({ position: "topright" });
+    toggle.onAdd = function() {
+        const div = L.DomUtil.create("div", "map-legend");
+        for (const kind of ["housenumbers", "streets"]) {
+            const label = document.createElement("label");
+            const input = document.createElement("input");
+            input.type = "radio";
+            input.name = "coverage-kind";
+            input.value = kind;
+            input.checked = kind == coverageKind;
+            input.addEventListener("change", function() {
+                coverageKind = kind;
+                layer.setStyle(getCoverageStyle);
+            });
+            label.appendChild(input);
+            label.appendChild(document.createTextNode(getString("str-coverage-" + kind)));
+            div.appendChild(label);
+            div.appendChild(document.createElement("br"));
+        }
+        L.DomEvent.disableClickPropagation(div);
+        return div;
+    };
+    toggle.addTo(map);
+
+    const legend = new L.Control({ position: "bottomright" });
+    legend.onAdd = function() {
+        const div = L.DomUtil.create("div", "map-legend");
+        const items: [string, string][] = coverageColors.map(([limit, color]) => [
+            limit == 100 ? "100%" : "≥ " + limit + "%",
+            color,
+        ]);
+        items.push([getString("str-coverage-unknown"), unknownCoverageColor]);
+        for (const [text, color] of items) {
+            const item = document.createElement("div");
+            const swatch = document.createElement("span");
+            swatch.className = "map-legend-swatch";
+            swatch.style.backgroundColor = color;
+            item.appendChild(swatch);
+            item.appendChild(document.createTextNode(text));
+            div.appendChild(item);
+        }
+        return div;
+    };
+    legend.addTo(map);
+}
+
+async function initCoverageMap(): Promise<void>
+{
+    const element = document.getElementById("coverage-map");
+    if (!element) {
+        // Not on the coverage map page.
+        return;
+    }
+
+    const response = await window.fetch(element.getAttribute("data-url"));
+    const collection = await<Promise<GeoJSON.FeatureCollection<GeoJSON.Geometry, CoverageProperties>>> response.json();
+
+    const map = L.map(element);
+    L.tileLayer("https://tile.openstreetmap.org/{z}/{x}/{y}.png", {
+        maxZoom: 19,
+        attribution: '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a>',
+    }).addTo(map);
+    const layer = L.geoJSON(collection, {
+        style: getCoverageStyle,
+        onEachFeature: addCoveragePopup,
+    }).addTo(map);
+    const bounds = layer.getBounds();
+    if (bounds.isValid()) {
+        map.fitBounds(bounds);
+    } else {
+        map.setView([47.1625, 19.5033], 7);
+    }
+    addCoverageControls(map, layer);
+}
+
+export { initMap, initCoverageMap };
+
+// vim: shiftwidth=4 softtabstop=4 expandtab:
@@ -586,6 +586,69 @@ async function initStats(): Promise<void>
     return;
 }
 
-export { initStats };
+// CoverageHistoryEntry is one item of /api/v1/relations/<relation>/coverage-history.
+interface CoverageHistoryEntry {
+    date: string;
+    streets: string | null;
+    housenumbers: string | null;
+}
+
+function addCoverageHistoryChart(canvas: HTMLCanvasElement, history: Array<CoverageHistoryEntry>) {
+    const toNumber = function(x: string | null) { return x === null ? null : Number(x); };
+    const data = {
+        labels: history.map(function(x: CoverageHistoryEntry) { return x.date; }),
+        datasets: [
+            {
+                label: getString("str-coverage-history-streets"),
+                borderColor: "rgba(0, 0, 255, 0.5)",
+                backgroundColor: "rgba(0, 0, 255, 0.5)",
+                data: history.map(function(x: CoverageHistoryEntry) { return toNumber(x.streets); }),
+            },
+            {
+                label: getString("str-coverage-history-housenumbers"),
+                borderColor: "rgba(0, 255, 0, 0.5)",
+                backgroundColor: "rgba(0, 255, 0, 0.5)",
+                data: history.map(function(x: CoverageHistoryEntry) { return toNumber(x.housenumbers); }),
+            },
+        ]
+    };
+    new Chart(canvas.getContext("2d"), {
+        type: "line",
+        data: data,
+        options: {
+            plugins: {
+                datalabels: {
+                    display: false,
+                }
+            },
+            scales: {
+                y: {
+                    suggestedMin: 0,
+                    suggestedMax: 100,
+                    title: {
+                        display: true,
+                        text: getString("str-coverage-history-y-axis"),
+                    },
+                }
+            },
+        }
+    });
+}
+
+async function initCoverageHistory(): Promise<void>
+{
+    const canvas = <HTMLCanvasElement>document.getElementById("coverage-history");
+    if (!canvas) {
+        // Not on a missing house numbers page with coverage history.
+        return;
+    }
+
+    const response = await window.fetch(canvas.getAttribute("data-url"));
+    const history = await<Promise<Array<CoverageHistoryEntry>>> response.json();
+    addCoverageHistoryChart(canvas, history);
+    return;
+}
+
+export { initStats, initCoverageHistory };
 
 // vim: shiftwidth=4 softtabstop=4 expandtab:
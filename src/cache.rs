@@ -96,6 +96,7 @@ pub fn get_missing_housenumbers_json(relation: &mut areas::Relation<'_>) -> anyh
         relation.get_ctx(),
         &format!("missing-housenumbers-cache/{}", &relation.get_name()),
     )?;
+    relation.write_missing_housenumbers_snapshot(&missing_housenumbers)?;
 
     relation.write_lints()?;
 
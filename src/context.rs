@@ -14,6 +14,7 @@ use anyhow::Context as _;
 use once_cell::unsync::OnceCell;
 use std::cell::RefCell;
 use std::cell::RefMut;
+use std::collections::HashMap;
 use std::io::Read;
 use std::io::Write;
 use std::path::Path;
@@ -76,10 +77,30 @@ pub trait Database {
 
 pub use system::StdDatabase;
 
+/// A bidirectional byte stream, e.g. a TCP connection.
+pub trait Connection: Read + Write {}
+
+impl<T: Read + Write> Connection for T {}
+
 /// Network interface.
 pub trait Network {
     /// Opens an URL. Empty data means HTTP GET, otherwise it means a HTTP POST.
     fn urlopen(&self, url: &str, data: &str) -> anyhow::Result<String>;
+
+    /// Sends JSON data in a HTTP POST. A 4xx or 5xx status is an error.
+    fn post_json(&self, url: &str, data: &str) -> anyhow::Result<String>;
+
+    /// Opens a TCP connection to `address` (host:port).
+    fn connect(&self, address: &str) -> anyhow::Result<Box<dyn Connection>>;
+
+    /// Opens multiple URLs, possibly in parallel. Each request is an URL and data pair, like in
+    /// urlopen().
+    fn urlopen_many(&self, requests: &[(String, String)]) -> Vec<anyhow::Result<String>> {
+        requests
+            .iter()
+            .map(|(url, data)| self.urlopen(url, data))
+            .collect()
+    }
 }
 
 pub use system::StdNetwork;
@@ -112,6 +133,64 @@ pub trait Subprocess {
 
 pub use system::StdSubprocess;
 
+/// The kind of data an OsmSource is asked for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OsmQueryKind {
+    /// Streets of a relation, see data/streets-template.overpassql.
+    Streets,
+    /// House numbers of a relation, see data/street-housenumbers-template.overpassql.
+    Housenumbers,
+    /// House numbers of the whole country, see data/street-housenumbers-hungary.overpassql.
+    WholeCountry,
+}
+
+impl OsmQueryKind {
+    /// Gets a short name of the kind, e.g. for file names.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OsmQueryKind::Streets => "streets",
+            OsmQueryKind::Housenumbers => "housenumbers",
+            OsmQueryKind::WholeCountry => "whole-country",
+        }
+    }
+}
+
+/// A query for an OsmSource: both the Overpass query and its meaning, so sources without an
+/// Overpass interpreter can answer it as well.
+#[derive(Clone, Debug)]
+pub struct OsmQuery {
+    /// The kind of the requested data.
+    pub kind: OsmQueryKind,
+    /// Name of the relation, empty for the whole country.
+    pub relation_name: String,
+    /// OSM id of the relation boundary, 0 for the whole country.
+    pub osmrelation: u64,
+    /// The query in Overpass QL, with JSON output.
+    pub overpass: String,
+}
+
+/// OSM data source interface.
+pub trait OsmSource {
+    /// Runs a query, returns the result in the Overpass JSON format.
+    fn query(&self, ctx: &Context, query: &OsmQuery) -> anyhow::Result<String>;
+
+    /// Returns how many seconds to wait before the next query, 0 means no wait is needed.
+    fn need_sleep(&self, ctx: &Context) -> i32;
+
+    /// Runs multiple queries, possibly in parallel.
+    fn query_many(&self, ctx: &Context, queries: &[OsmQuery]) -> Vec<anyhow::Result<String>> {
+        queries.iter().map(|query| self.query(ctx, query)).collect()
+    }
+
+    /// Returns how many queries can be started right now.
+    fn available_slots(&self, _ctx: &Context) -> usize {
+        1
+    }
+}
+
+pub use system::ReplayOsmSource;
+pub use system::StdOsmSource;
+
 /// Unit testing interface.
 pub trait Unit {
     /// Injects a fake error.
@@ -131,17 +210,64 @@ pub struct IniConfig {
 #[derive(Default, serde::Deserialize)]
 pub struct WsgiConfig {
     /// Space-separated list of housenumber references.
+    #[serde(default)]
     pub reference_housenumbers: String,
     /// Street reference file path.
+    #[serde(default)]
     pub reference_street: String,
     /// City counts reference file path.
+    #[serde(default)]
     pub reference_citycounts: String,
     /// ZIP counts reference file path.
+    #[serde(default)]
     pub reference_zipcounts: String,
+    /// Reference sources with an explicit format and column mapping, replacing
+    /// reference_housenumbers and reference_street.
+    #[serde(default)]
+    pub reference_sources: Vec<ReferenceSourceConfig>,
     uri_prefix: Option<String>,
     tcp_port: Option<String>,
     overpass_uri: Option<String>,
     cron_update_inactive: Option<String>,
+    cron_jobs: Option<String>,
+    osm_source: Option<String>,
+    osm_source_path: Option<String>,
+    replication_dir: Option<String>,
+    surveyors: Option<String>,
+    housenumber_grammar: Option<String>,
+    site_url: Option<String>,
+    notify_emails: Option<String>,
+    notify_webhooks: Option<String>,
+    notify_coverage_drop: Option<String>,
+    notify_lints_increase: Option<String>,
+    smtp_server: Option<String>,
+    smtp_from: Option<String>,
+}
+
+/// One [[wsgi.reference_sources]] table in the config file.
+#[derive(Clone, Default, serde::Deserialize)]
+pub struct ReferenceSourceConfig {
+    /// What the source provides: housenumbers or streets.
+    pub kind: String,
+    /// File format: tsv, csv, geojson or openaddresses.
+    pub format: String,
+    /// File path, relative to the root unless absolute.
+    pub path: String,
+    /// Provenance label, stored next to the imported rows.
+    pub label: Option<String>,
+    /// Column (or GeoJSON property) of the street name.
+    pub street_column: Option<String>,
+    /// Column of the house number.
+    pub housenumber_column: Option<String>,
+    /// Column of the comment, if any.
+    pub comment_column: Option<String>,
+    /// Column of the first area key, matched against refcounty.
+    pub refcounty_column: Option<String>,
+    /// Column of the second area key, matched against refsettlement.
+    pub refsettlement_column: Option<String>,
+    /// House numbers from a supplementary source are marked as such in the output.
+    #[serde(default)]
+    pub supplementary: bool,
 }
 
 /// Configuration file reader.
@@ -186,6 +312,19 @@ impl Ini {
         Ok(format!("{}/{}", self.root, relpath))
     }
 
+    /// Gets the explicitly configured reference sources.
+    pub fn get_reference_sources(&self) -> &[ReferenceSourceConfig] {
+        &self.config.wsgi.reference_sources
+    }
+
+    /// Resolves a path from the config file: relative paths are relative to the root.
+    pub fn get_abspath(&self, path: &str) -> String {
+        if path.starts_with('/') {
+            return path.to_string();
+        }
+        format!("{}/{}", self.root, path)
+    }
+
     /// Gets the abs path of ref citycounts.
     pub fn get_reference_citycounts_path(&self) -> anyhow::Result<String> {
         let relpath = &self.config.wsgi.reference_citycounts;
@@ -227,6 +366,105 @@ impl Ini {
         let value = self.get_with_fallback(&self.config.wsgi.cron_update_inactive, "False");
         value == "True"
     }
+
+    /// Gets the maximum number of parallel OSM queries the cron job may run.
+    pub fn get_cron_jobs(&self) -> anyhow::Result<usize> {
+        Ok(self
+            .get_with_fallback(&self.config.wsgi.cron_jobs, "1")
+            .parse::<usize>()?)
+    }
+
+    /// Gets the type of the OSM data source: overpass, extract or replay.
+    pub fn get_osm_source(&self) -> String {
+        self.get_with_fallback(&self.config.wsgi.osm_source, "overpass")
+    }
+
+    /// Gets the abs path of the OSM extract file or the replay directory.
+    pub fn get_osm_source_path(&self) -> String {
+        let path = self.get_with_fallback(&self.config.wsgi.osm_source_path, "");
+        if path.starts_with('/') {
+            return path;
+        }
+        format!("{}/{}", self.root, path)
+    }
+
+    /// Gets the abs path of the local replication directory with OsmChange diffs.
+    pub fn get_replication_dir(&self) -> String {
+        let path = self.get_with_fallback(&self.config.wsgi.replication_dir, "workdir/replication");
+        if path.starts_with('/') {
+            return path;
+        }
+        format!("{}/{}", self.root, path)
+    }
+
+    /// Gets the name of the default house number grammar: hu, de or us.
+    pub fn get_housenumber_grammar(&self) -> String {
+        self.get_with_fallback(&self.config.wsgi.housenumber_grammar, "hu")
+    }
+
+    /// Gets the login -> token map of surveyors who may record survey observations.
+    pub fn get_surveyors(&self) -> HashMap<String, String> {
+        let value = self.get_with_fallback(&self.config.wsgi.surveyors, "");
+        value
+            .split(' ')
+            .filter_map(|i| i.split_once(':'))
+            .map(|(login, token)| (login.to_string(), token.to_string()))
+            .collect()
+    }
+
+    /// Gets the public URL of the site, without the URI prefix, used for absolute links in feeds.
+    pub fn get_site_url(&self) -> String {
+        self.get_with_fallback(&self.config.wsgi.site_url, "https://osm-gimmisn.vmiklos.hu")
+    }
+
+    /// Gets the email addresses which are notified about regressions of all relations.
+    pub fn get_notify_emails(&self) -> Vec<String> {
+        let value = self.get_with_fallback(&self.config.wsgi.notify_emails, "");
+        value.split_whitespace().map(|i| i.to_string()).collect()
+    }
+
+    /// Gets the webhook URLs which are notified about regressions of all relations.
+    pub fn get_notify_webhooks(&self) -> Vec<String> {
+        let value = self.get_with_fallback(&self.config.wsgi.notify_webhooks, "");
+        value.split_whitespace().map(|i| i.to_string()).collect()
+    }
+
+    /// Gets the minimal drop of a coverage (in percentage points) which is worth a notification.
+    pub fn get_notify_coverage_drop(&self) -> anyhow::Result<f64> {
+        Ok(self
+            .get_with_fallback(&self.config.wsgi.notify_coverage_drop, "1")
+            .parse::<f64>()?)
+    }
+
+    /// Gets the minimal increase of the number of lints which is worth a notification.
+    pub fn get_notify_lints_increase(&self) -> anyhow::Result<u64> {
+        Ok(self
+            .get_with_fallback(&self.config.wsgi.notify_lints_increase, "10")
+            .parse::<u64>()?)
+    }
+
+    /// Gets the host:port of the SMTP server to send notification emails.
+    pub fn get_smtp_server(&self) -> String {
+        self.get_with_fallback(&self.config.wsgi.smtp_server, "localhost:25")
+    }
+
+    /// Gets the sender address of notification emails.
+    pub fn get_smtp_from(&self) -> String {
+        self.get_with_fallback(&self.config.wsgi.smtp_from, "osm-gimmisn@localhost")
+    }
+}
+
+/// Creates the OSM data source, as configured in workdir/wsgi.ini.
+fn make_osm_source(ini: &Ini) -> anyhow::Result<Rc<dyn OsmSource>> {
+    let source = ini.get_osm_source();
+    match source.as_str() {
+        "overpass" => Ok(Rc::new(StdOsmSource {})),
+        "extract" => Ok(Rc::new(crate::import_osm::ExtractOsmSource::new(
+            &ini.get_osm_source_path(),
+        ))),
+        "replay" => Ok(Rc::new(ReplayOsmSource::new(&ini.get_osm_source_path()))),
+        _ => Err(anyhow::anyhow!("unknown osm_source: '{source}'")),
+    }
 }
 
 /// Context owns global state which is set up once and then read everywhere.
@@ -234,6 +472,7 @@ pub struct Context {
     root: String,
     ini: Ini,
     network: Rc<dyn Network>,
+    osm_source: Rc<dyn OsmSource>,
     time: Rc<dyn Time>,
     subprocess: Rc<dyn Subprocess>,
     unit: Rc<dyn Unit>,
@@ -255,11 +494,13 @@ impl Context {
         let file_system: Rc<dyn FileSystem> = Rc::new(StdFileSystem {});
         let database: Rc<dyn Database> = Rc::new(StdDatabase {});
         let ini = Ini::new(&file_system, &format!("{root}/workdir/wsgi.ini"), &root)?;
+        let osm_source = make_osm_source(&ini)?;
         let connection = OnceCell::new();
         Ok(Context {
             root,
             ini,
             network,
+            osm_source,
             time,
             subprocess,
             unit,
@@ -279,6 +520,11 @@ impl Context {
         &self.ini
     }
 
+    /// Sets the ini file.
+    pub fn set_ini(&mut self, ini: Ini) {
+        self.ini = ini;
+    }
+
     /// Gets the network implementation.
     pub fn get_network(&self) -> &Rc<dyn Network> {
         &self.network
@@ -289,6 +535,16 @@ impl Context {
         self.network = network;
     }
 
+    /// Gets the OSM data source implementation.
+    pub fn get_osm_source(&self) -> &Rc<dyn OsmSource> {
+        &self.osm_source
+    }
+
+    /// Sets the OSM data source implementation.
+    pub fn set_osm_source(&mut self, osm_source: &Rc<dyn OsmSource>) {
+        self.osm_source = osm_source.clone();
+    }
+
     /// Gets the time implementation.
     pub fn get_time(&self) -> &Rc<dyn Time> {
         &self.time
@@ -112,6 +112,114 @@ impl Network for StdNetwork {
         let ret = buf.text()?;
         Ok(ret)
     }
+
+    fn post_json(&self, url: &str, data: &str) -> anyhow::Result<String> {
+        let mut buf = isahc::Request::post(url)
+            .header("Content-Type", "application/json")
+            .redirect_policy(isahc::config::RedirectPolicy::Limit(1))
+            .timeout(Duration::from_secs(60))
+            .body(data)?
+            .send()?;
+        let status = buf.status();
+        let ret = buf.text()?;
+        if status.is_client_error() || status.is_server_error() {
+            return Err(anyhow::anyhow!(
+                "'{url}' failed with status {status}: '{ret}'"
+            ));
+        }
+        Ok(ret)
+    }
+
+    fn connect(&self, address: &str) -> anyhow::Result<Box<dyn Connection>> {
+        let timeout = Duration::from_secs(60);
+        let socket_address = std::net::ToSocketAddrs::to_socket_addrs(address)?
+            .next()
+            .context(format!("failed to resolve '{address}'"))?;
+        let stream = std::net::TcpStream::connect_timeout(&socket_address, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        Ok(Box::new(stream))
+    }
+
+    fn urlopen_many(&self, requests: &[(String, String)]) -> Vec<anyhow::Result<String>> {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = requests
+                .iter()
+                .map(|(url, data)| scope.spawn(move || StdNetwork {}.urlopen(url, data)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| match handle.join() {
+                    Ok(result) => result,
+                    Err(_) => Err(anyhow::anyhow!("urlopen thread panicked")),
+                })
+                .collect()
+        })
+    }
+}
+
+/// OSM data source implementation, backed by Overpass.
+pub struct StdOsmSource {}
+
+// Real network is intentionally mocked.
+impl OsmSource for StdOsmSource {
+    fn query(&self, ctx: &Context, query: &OsmQuery) -> anyhow::Result<String> {
+        crate::overpass_query::overpass_query(ctx, &query.overpass)
+    }
+
+    fn need_sleep(&self, ctx: &Context) -> i32 {
+        crate::overpass_query::overpass_query_need_sleep(ctx)
+    }
+
+    fn query_many(&self, ctx: &Context, queries: &[OsmQuery]) -> Vec<anyhow::Result<String>> {
+        let queries: Vec<String> = queries.iter().map(|i| i.overpass.to_string()).collect();
+        crate::overpass_query::overpass_query_many(ctx, &queries)
+    }
+
+    fn available_slots(&self, ctx: &Context) -> usize {
+        crate::overpass_query::overpass_query_available_slots(ctx)
+    }
+}
+
+/// OSM data source implementation, replaying earlier Overpass JSON results from a directory.
+pub struct ReplayOsmSource {
+    dir: String,
+}
+
+impl ReplayOsmSource {
+    /// Creates a new ReplayOsmSource.
+    pub fn new(dir: &str) -> Self {
+        ReplayOsmSource {
+            dir: dir.to_string(),
+        }
+    }
+
+    /// Gets the path of the recorded result of a query, e.g. <dir>/streets-gazdagret.json.
+    pub fn get_path(&self, query: &OsmQuery) -> String {
+        if query.relation_name.is_empty() {
+            return format!("{}/{}.json", self.dir, query.kind.as_str());
+        }
+
+        format!(
+            "{}/{}-{}.json",
+            self.dir,
+            query.kind.as_str(),
+            query.relation_name
+        )
+    }
+}
+
+impl OsmSource for ReplayOsmSource {
+    fn query(&self, ctx: &Context, query: &OsmQuery) -> anyhow::Result<String> {
+        let path = self.get_path(query);
+        ctx.get_file_system()
+            .read_to_string(&path)
+            .context(format!("failed to replay '{path}'"))
+    }
+
+    fn need_sleep(&self, _ctx: &Context) -> i32 {
+        0
+    }
 }
 
 /// Time implementation, backed by the the actual time.
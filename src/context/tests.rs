@@ -267,15 +267,60 @@ impl URLRoute {
     }
 }
 
+/// Connection implementation, for test purposes: reads the replies of the server from a buffer
+/// and records what is sent to the server.
+#[derive(Clone)]
+pub struct TestConnection {
+    replies: Cursor<Vec<u8>>,
+    sent: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Read for TestConnection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.replies.read(buf)
+    }
+}
+
+impl Write for TestConnection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sent.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 /// Network implementation, for test purposes.
 pub struct TestNetwork {
     routes: Rc<RefCell<Vec<URLRoute>>>,
+    /// Address -> connection, as it looks before anything is read or written.
+    connections: RefCell<HashMap<String, TestConnection>>,
 }
 
 impl TestNetwork {
     pub fn new(routes: &[URLRoute]) -> Self {
         let routes = Rc::new(RefCell::new(routes.to_vec()));
-        TestNetwork { routes }
+        let connections = RefCell::new(HashMap::new());
+        TestNetwork {
+            routes,
+            connections,
+        }
+    }
+
+    /// Allows connecting to `address`, where the server sends `replies`. Returns the data which
+    /// will be sent to the server.
+    pub fn add_connection(&self, address: &str, replies: &str) -> Rc<RefCell<Vec<u8>>> {
+        let sent: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+        let connection = TestConnection {
+            replies: Cursor::new(replies.as_bytes().to_vec()),
+            sent: sent.clone(),
+        };
+        self.connections
+            .borrow_mut()
+            .insert(address.into(), connection);
+        sent
     }
 }
 
@@ -310,6 +355,18 @@ impl Network for TestNetwork {
         locked_routes.remove(remove.unwrap());
         Ok(ret)
     }
+
+    fn post_json(&self, url: &str, data: &str) -> anyhow::Result<String> {
+        self.urlopen(url, data)
+    }
+
+    fn connect(&self, address: &str) -> anyhow::Result<Box<dyn Connection>> {
+        let connections = self.connections.borrow();
+        let connection = connections
+            .get(address)
+            .context(format!("address missing from connection list: '{address}'"))?;
+        Ok(Box::new(connection.clone()))
+    }
 }
 
 /// Unit implementation, which intentionally fails.
@@ -471,3 +528,163 @@ fn test_file_system_list_dir() {
 
     assert_eq!(ret.is_empty(), true);
 }
+
+/// Creates an Ini instance with the given extra wsgi.ini lines.
+pub fn make_test_ini(ctx: &Context, lines: &str) -> Ini {
+    let wsgi_ini = TestFileSystem::make_file();
+    let content = format!(
+        "[wsgi]\nreference_housenumbers = ''\nreference_street = ''\nreference_citycounts = ''\nreference_zipcounts = ''\n{lines}"
+    );
+    wsgi_ini.borrow_mut().write_all(content.as_bytes()).unwrap();
+    let files = TestFileSystem::make_files(ctx, &[("workdir/wsgi.ini", &wsgi_ini)]);
+    let file_system = TestFileSystem::from_files(&files);
+    Ini::new(&file_system, &ctx.get_abspath("workdir/wsgi.ini"), "tests").unwrap()
+}
+
+/// Tests Ini.get_osm_source() and Ini.get_osm_source_path().
+#[test]
+fn test_ini_get_osm_source() {
+    let ctx = make_test_context().unwrap();
+
+    let ini = make_test_ini(&ctx, "");
+    assert_eq!(ini.get_osm_source(), "overpass");
+
+    let ini = make_test_ini(
+        &ctx,
+        "osm_source = 'replay'\nosm_source_path = 'workdir/replay'\n",
+    );
+    assert_eq!(ini.get_osm_source(), "replay");
+    assert_eq!(ini.get_osm_source_path(), "tests/workdir/replay");
+
+    let ini = make_test_ini(
+        &ctx,
+        "osm_source = 'extract'\nosm_source_path = '/srv/hungary-latest.osm.pbf'\n",
+    );
+    assert_eq!(ini.get_osm_source_path(), "/srv/hungary-latest.osm.pbf");
+}
+
+/// Tests Ini.get_replication_dir().
+#[test]
+fn test_ini_get_replication_dir() {
+    let ctx = make_test_context().unwrap();
+
+    let ini = make_test_ini(&ctx, "");
+    assert_eq!(ini.get_replication_dir(), "tests/workdir/replication");
+
+    let ini = make_test_ini(&ctx, "replication_dir = '/srv/replication'\n");
+    assert_eq!(ini.get_replication_dir(), "/srv/replication");
+}
+
+/// Tests Ini.get_cron_jobs().
+#[test]
+fn test_ini_get_cron_jobs() {
+    let ctx = make_test_context().unwrap();
+
+    let ini = make_test_ini(&ctx, "");
+    assert_eq!(ini.get_cron_jobs().unwrap(), 1);
+
+    let ini = make_test_ini(&ctx, "cron_jobs = '4'\n");
+    assert_eq!(ini.get_cron_jobs().unwrap(), 4);
+
+    let ini = make_test_ini(&ctx, "cron_jobs = 'many'\n");
+    assert!(ini.get_cron_jobs().is_err());
+}
+
+/// Tests make_osm_source().
+#[test]
+fn test_make_osm_source() {
+    let ctx = make_test_context().unwrap();
+
+    for source in ["overpass", "extract", "replay"] {
+        let ini = make_test_ini(&ctx, &format!("osm_source = '{source}'\n"));
+        assert!(make_osm_source(&ini).is_ok());
+    }
+
+    let ini = make_test_ini(&ctx, "osm_source = 'carrier-pigeon'\n");
+    assert!(make_osm_source(&ini).is_err());
+}
+
+/// Tests ReplayOsmSource.
+#[test]
+fn test_replay_osm_source() {
+    let mut ctx = make_test_context().unwrap();
+    let streets = TestFileSystem::make_file();
+    streets
+        .borrow_mut()
+        .write_all(b"{\"elements\":[]}")
+        .unwrap();
+    let files =
+        TestFileSystem::make_files(&ctx, &[("workdir/replay/streets-gazdagret.json", &streets)]);
+    let file_system = TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let replay = ReplayOsmSource::new(&ctx.get_abspath("workdir/replay"));
+    let osm_source: Rc<dyn OsmSource> = Rc::new(replay);
+    ctx.set_osm_source(&osm_source);
+    let mut query = OsmQuery {
+        kind: OsmQueryKind::Streets,
+        relation_name: "gazdagret".into(),
+        osmrelation: 42,
+        overpass: "".into(),
+    };
+
+    let ret = ctx.get_osm_source().query(&ctx, &query).unwrap();
+
+    assert_eq!(ret, "{\"elements\":[]}");
+    assert_eq!(ctx.get_osm_source().need_sleep(&ctx), 0);
+    query.kind = OsmQueryKind::Housenumbers;
+    assert!(ctx.get_osm_source().query(&ctx, &query).is_err());
+    query.kind = OsmQueryKind::WholeCountry;
+    query.relation_name = "".into();
+    let replay = ReplayOsmSource::new("workdir/replay");
+    assert_eq!(replay.get_path(&query), "workdir/replay/whole-country.json");
+}
+
+/// Tests Ini.get_surveyors().
+#[test]
+fn test_ini_get_surveyors() {
+    let ctx = make_test_context().unwrap();
+
+    let ini = make_test_ini(&ctx, "");
+    assert!(ini.get_surveyors().is_empty());
+
+    let ini = make_test_ini(&ctx, "surveyors = 'alice:secret bob:hunter2 invalid'\n");
+    let surveyors = ini.get_surveyors();
+    assert_eq!(surveyors.len(), 2);
+    assert_eq!(surveyors["alice"], "secret");
+    assert_eq!(surveyors["bob"], "hunter2");
+}
+
+/// Tests Ini.get_site_url().
+#[test]
+fn test_ini_get_site_url() {
+    let ctx = make_test_context().unwrap();
+
+    let ini = make_test_ini(&ctx, "");
+    assert_eq!(ini.get_site_url(), "https://osm-gimmisn.vmiklos.hu");
+
+    let ini = make_test_ini(&ctx, "site_url = 'http://localhost:8000'\n");
+    assert_eq!(ini.get_site_url(), "http://localhost:8000");
+}
+
+/// Tests the Ini.get_notify_*() and Ini.get_smtp_*() functions.
+#[test]
+fn test_ini_get_notify() {
+    let ctx = make_test_context().unwrap();
+
+    let ini = make_test_ini(&ctx, "");
+    assert!(ini.get_notify_emails().is_empty());
+    assert!(ini.get_notify_webhooks().is_empty());
+    assert_eq!(ini.get_notify_coverage_drop().unwrap(), 1.0);
+    assert_eq!(ini.get_notify_lints_increase().unwrap(), 10);
+    assert_eq!(ini.get_smtp_server(), "localhost:25");
+    assert_eq!(ini.get_smtp_from(), "osm-gimmisn@localhost");
+
+    let ini = make_test_ini(
+        &ctx,
+        "notify_emails = 'a@example.com b@example.com'\nnotify_webhooks = 'http://localhost/hook'\nnotify_coverage_drop = '0.5'\nnotify_lints_increase = '3'\n",
+    );
+    assert_eq!(ini.get_notify_emails(), ["a@example.com", "b@example.com"]);
+    assert_eq!(ini.get_notify_webhooks(), ["http://localhost/hook"]);
+    assert_eq!(ini.get_notify_coverage_drop().unwrap(), 0.5);
+    assert_eq!(ini.get_notify_lints_increase().unwrap(), 3);
+}
@@ -13,13 +13,20 @@
 use crate::area_files;
 use crate::areas;
 use crate::context;
-use crate::overpass_query;
+use crate::feeds;
+use crate::geometry;
+use crate::lints;
+use crate::notifications;
+use crate::replication;
 use crate::stats;
 use crate::util;
 use anyhow::Context;
 use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::hash::Hash as _;
+use std::hash::Hasher as _;
 use std::io::BufRead;
 use std::io::Write;
 use std::ops::DerefMut;
@@ -33,7 +40,7 @@ use std::{println as info, println as warn, println as error};
 /// Sleeps to respect overpass rate limit.
 fn overpass_sleep(ctx: &context::Context) {
     loop {
-        let sleep = overpass_query::overpass_query_need_sleep(ctx);
+        let sleep = ctx.get_osm_source().need_sleep(ctx);
         if sleep == 0 {
             break;
         }
@@ -47,80 +54,345 @@ fn should_retry(retry: i32) -> bool {
     retry < 20
 }
 
-/// Update the OSM street list of all relations.
-fn update_osm_streets(
+/// Clears the cron journal, so a new run can start.
+fn journal_clear(ctx: &context::Context) -> anyhow::Result<()> {
+    let conn = ctx.get_database_connection()?;
+    conn.execute("delete from cron_journal", [])?;
+    Ok(())
+}
+
+/// Decides if a stage (or a relation inside a stage) is already done in the cron journal.
+fn journal_is_done(ctx: &context::Context, stage: &str, relation: &str) -> anyhow::Result<bool> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(
+        "select status from cron_journal where stage = ?1 and relation = ?2 and status = 'done'",
+    )?;
+    let mut rows = stmt.query([stage, relation])?;
+    Ok(rows.next()?.is_some())
+}
+
+/// Marks a stage (or a relation inside a stage) as started in the cron journal.
+fn journal_start(ctx: &context::Context, stage: &str, relation: &str) -> anyhow::Result<()> {
+    let conn = ctx.get_database_connection()?;
+    conn.execute(
+        r#"insert into cron_journal (stage, relation, status, started, finished, error) values (?1, ?2, 'running', ?3, '', '')
+             on conflict(stage, relation) do update set status = excluded.status, started = excluded.started, finished = '', error = ''"#,
+        [
+            stage,
+            relation,
+            &ctx.get_time().now().unix_timestamp_nanos().to_string(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Marks a stage (or a relation inside a stage) as finished in the cron journal, failed in case
+/// there is an error.
+fn journal_finish(
     ctx: &context::Context,
-    relations: &mut areas::Relations<'_>,
-    update: bool,
+    stage: &str,
+    relation: &str,
+    error: Option<&str>,
 ) -> anyhow::Result<()> {
-    let active_names = relations.get_active_names();
-    for relation_name in active_names.context("get_active_names() failed")? {
-        let relation = relations.get_relation(&relation_name)?;
-        if !update && stats::has_sql_mtime(ctx, &format!("streets/{}", relation_name))? {
-            continue;
+    let status = match error {
+        Some(_) => "failed",
+        None => "done",
+    };
+    let conn = ctx.get_database_connection()?;
+    conn.execute(
+        "update cron_journal set status = ?1, finished = ?2, error = ?3 where stage = ?4 and relation = ?5",
+        [
+            status,
+            &ctx.get_time().now().unix_timestamp_nanos().to_string(),
+            error.unwrap_or_default(),
+            stage,
+            relation,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Runs a stage (or the part of a stage for one relation) and records it in the cron journal.
+/// Already done work is skipped, which only happens when resuming a previous run.
+fn run_journaled(
+    ctx: &context::Context,
+    stage: &str,
+    relation: &str,
+    f: impl FnOnce() -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if journal_is_done(ctx, stage, relation)? {
+        info!("run_journaled: skipping already done '{stage}' for '{relation}'");
+        return Ok(());
+    }
+
+    journal_start(ctx, stage, relation)?;
+    let ret = f();
+    let error = ret.as_ref().err().map(|err| format!("{err:?}"));
+    journal_finish(ctx, stage, relation, error.as_deref())?;
+    ret
+}
+
+/// One download of the OSM streets or house numbers of a relation.
+struct RefreshJob {
+    relation_name: String,
+    kind: context::OsmQueryKind,
+    /// Number of failed tries so far.
+    retry: i32,
+    /// Seconds to wait before the next try.
+    delay: u64,
+}
+
+impl RefreshJob {
+    fn new(relation_name: &str, kind: context::OsmQueryKind) -> Self {
+        RefreshJob {
+            relation_name: relation_name.to_string(),
+            kind,
+            retry: 0,
+            delay: 0,
         }
-        info!("update_osm_streets, json: start: {relation_name}");
-        let mut retry = 0;
-        while should_retry(retry) {
-            if retry > 0 {
-                info!("update_osm_streets, json: try #{retry}");
+    }
+
+    /// Gets the name of the cron journal stage of this job.
+    fn get_stage(&self) -> String {
+        format!("osm-{}", self.kind.as_str())
+    }
+}
+
+/// Calculates the exponential backoff with jitter before the next try of a job, in seconds. The
+/// jitter is derived from the job, so that failing jobs don't retry at the same time.
+fn get_backoff(job: &RefreshJob) -> u64 {
+    let base = 2_u64.pow(job.retry.clamp(0, 8) as u32);
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (&job.relation_name, job.kind.as_str(), job.retry).hash(&mut hasher);
+    base + hasher.finish() % (base / 2 + 1)
+}
+
+/// Gets the relation-kind pairs which failed during the previous run.
+fn get_failures(ctx: &context::Context) -> anyhow::Result<HashSet<(String, String)>> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare("select relation, kind from cron_failures")?;
+    let mut rows = stmt.query([])?;
+    let mut ret: HashSet<(String, String)> = HashSet::new();
+    while let Some(row) = rows.next()? {
+        ret.insert((row.get(0)?, row.get(1)?));
+    }
+    Ok(ret)
+}
+
+/// Records a failed try of a job in the failure log.
+fn add_failure(ctx: &context::Context, job: &RefreshJob, error: &str) -> anyhow::Result<()> {
+    let conn = ctx.get_database_connection()?;
+    conn.execute(
+        r#"insert into cron_failures (relation, kind, failures, last_error, last_failure) values (?1, ?2, 1, ?3, ?4)
+             on conflict(relation, kind) do update set failures = failures + 1, last_error = excluded.last_error, last_failure = excluded.last_failure"#,
+        [
+            &job.relation_name,
+            job.kind.as_str(),
+            error,
+            &ctx.get_time().now().unix_timestamp_nanos().to_string(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Removes a job from the failure log, after it succeeded.
+fn remove_failure(ctx: &context::Context, job: &RefreshJob) -> anyhow::Result<()> {
+    let conn = ctx.get_database_connection()?;
+    conn.execute(
+        "delete from cron_failures where relation = ?1 and kind = ?2",
+        [&job.relation_name, job.kind.as_str()],
+    )?;
+    Ok(())
+}
+
+/// Runs OSM downloads for relations: up to cron_jobs at the same time if the OSM source has
+/// enough free slots, retrying failed jobs with a backoff. Jobs which failed during the previous
+/// run are started first. Fails if some jobs were given up, after all jobs are processed.
+fn refresh_relations(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    mut jobs: Vec<RefreshJob>,
+) -> anyhow::Result<()> {
+    let failures = get_failures(ctx)?;
+    // Stable sort, so the order is kept otherwise.
+    jobs.sort_by_key(|job| {
+        !failures.contains(&(job.relation_name.to_string(), job.kind.as_str().to_string()))
+    });
+    let mut pending: VecDeque<RefreshJob> = jobs.into();
+    let mut given_up: Vec<String> = Vec::new();
+    let max_jobs = ctx.get_ini().get_cron_jobs()?.max(1);
+    while !pending.is_empty() {
+        let wait = pending.iter().map(|job| job.delay).min().unwrap_or(0);
+        if wait > 0 {
+            info!("refresh_relations: waiting for {wait} seconds before retrying");
+            ctx.get_time().sleep(wait);
+            for job in pending.iter_mut() {
+                job.delay -= wait;
             }
-            retry += 1;
+        }
+
+        let mut slots = ctx.get_osm_source().available_slots(ctx);
+        if slots == 0 {
             overpass_sleep(ctx);
-            let query = relation.get_osm_streets_json_query()?;
-            let buf = match overpass_query::overpass_query(ctx, &query) {
+            slots = 1;
+        }
+        let mut batch: Vec<RefreshJob> = Vec::new();
+        let mut rest: VecDeque<RefreshJob> = VecDeque::new();
+        for job in pending {
+            if job.delay == 0 && batch.len() < slots.min(max_jobs) {
+                batch.push(job);
+            } else {
+                rest.push_back(job);
+            }
+        }
+        pending = rest;
+
+        let mut queries: Vec<context::OsmQuery> = Vec::new();
+        for job in &batch {
+            let relation = relations.get_relation(&job.relation_name)?;
+            info!(
+                "update_osm_{}, json: start: {}",
+                job.kind.as_str(),
+                job.relation_name
+            );
+            if job.retry > 0 {
+                info!("update_osm_{}, json: try #{}", job.kind.as_str(), job.retry);
+            } else {
+                journal_start(ctx, &job.get_stage(), &job.relation_name)?;
+            }
+            let query = match job.kind {
+                context::OsmQueryKind::Streets => relation.get_osm_streets_source_query()?,
+                _ => relation.get_osm_housenumbers_source_query()?,
+            };
+            queries.push(query);
+        }
+        let results = ctx.get_osm_source().query_many(ctx, &queries);
+        for (mut job, result) in batch.into_iter().zip(results) {
+            let kind = job.kind.as_str();
+            let buf = match result {
                 Ok(value) => value,
                 Err(err) => {
-                    info!("update_osm_streets, json: http error: {err:?}");
+                    info!("update_osm_{kind}, json: http error: {err:?}");
+                    add_failure(ctx, &job, &format!("{err:?}"))?;
+                    job.retry += 1;
+                    if should_retry(job.retry) {
+                        job.delay = get_backoff(&job);
+                        pending.push_back(job);
+                    } else {
+                        error!("update_osm_{kind}, json: giving up: {}", job.relation_name);
+                        journal_finish(
+                            ctx,
+                            &job.get_stage(),
+                            &job.relation_name,
+                            Some(&format!("{err:?}")),
+                        )?;
+                        given_up.push(format!("{}/{kind}", job.relation_name));
+                    }
                     continue;
                 }
             };
-            relation
-                .get_files()
-                .write_osm_json_streets(ctx, &buf)
-                .context("write_osm_json_streets() failed")?;
-            break;
+            let relation = relations.get_relation(&job.relation_name)?;
+            match job.kind {
+                context::OsmQueryKind::Streets => relation
+                    .get_files()
+                    .write_osm_json_streets(ctx, &buf)
+                    .context("write_osm_json_streets() failed")?,
+                _ => relation
+                    .get_files()
+                    .write_osm_json_housenumbers(ctx, &buf)?,
+            }
+            remove_failure(ctx, &job)?;
+            journal_finish(ctx, &job.get_stage(), &job.relation_name, None)?;
+            info!("update_osm_{kind}, json: end: {}", job.relation_name);
         }
-        info!("update_osm_streets, json: end: {relation_name}");
+    }
+
+    if !given_up.is_empty() {
+        return Err(anyhow::anyhow!(
+            "refresh_relations: gave up on: {}",
+            given_up.join(", ")
+        ));
     }
 
     Ok(())
 }
 
+/// Update the OSM street list of all relations.
+fn update_osm_streets(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    update: bool,
+) -> anyhow::Result<()> {
+    let mut jobs: Vec<RefreshJob> = Vec::new();
+    let active_names = relations.get_active_names();
+    for relation_name in active_names.context("get_active_names() failed")? {
+        if !update && stats::has_sql_mtime(ctx, &format!("streets/{}", relation_name))? {
+            continue;
+        }
+        if journal_is_done(ctx, "osm-streets", &relation_name)? {
+            continue;
+        }
+        jobs.push(RefreshJob::new(
+            &relation_name,
+            context::OsmQueryKind::Streets,
+        ));
+    }
+
+    refresh_relations(ctx, relations, jobs)
+}
+
 /// Update the OSM housenumber list of all relations.
 fn update_osm_housenumbers(
     ctx: &context::Context,
     relations: &mut areas::Relations<'_>,
     update: bool,
 ) -> anyhow::Result<()> {
+    let mut jobs: Vec<RefreshJob> = Vec::new();
     for relation_name in relations.get_active_names()? {
-        let relation = relations.get_relation(&relation_name)?;
         if !update && stats::has_sql_mtime(ctx, &format!("housenumbers/{}", relation_name))? {
             continue;
         }
-        info!("update_osm_housenumbers, json: start: {relation_name}");
-        let mut retry = 0;
-        while should_retry(retry) {
-            if retry > 0 {
-                info!("update_osm_housenumbers, json: try #{retry}");
-            }
-            retry += 1;
-            overpass_sleep(ctx);
-            let query = relation.get_osm_housenumbers_json_query()?;
-            let buf = match overpass_query::overpass_query(ctx, &query) {
-                Ok(value) => value,
-                Err(err) => {
-                    info!("update_osm_housenumbers, json: http error: {err:?}");
-                    continue;
-                }
-            };
-            relation
-                .get_files()
-                .write_osm_json_housenumbers(ctx, &buf)?;
-            break;
+        if journal_is_done(ctx, "osm-housenumbers", &relation_name)? {
+            continue;
+        }
+        jobs.push(RefreshJob::new(
+            &relation_name,
+            context::OsmQueryKind::Housenumbers,
+        ));
+    }
+
+    refresh_relations(ctx, relations, jobs)
+}
+
+/// Applies new replication diffs, then updates the stats of the affected relations only.
+fn update_osm_diffs(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+) -> anyhow::Result<()> {
+    info!("update_osm_diffs: start");
+    let changes = replication::apply_diffs(ctx).context("apply_diffs() failed")?;
+    relations.limit_to_names(&changes.get_relations());
+    let mut jobs: Vec<RefreshJob> = Vec::new();
+    for relation_name in relations.get_active_names()? {
+        if !changes.stale.contains(&relation_name) {
+            continue;
         }
-        info!("update_osm_housenumbers, json: end: {relation_name}");
+        jobs.push(RefreshJob::new(
+            &relation_name,
+            context::OsmQueryKind::Streets,
+        ));
+        jobs.push(RefreshJob::new(
+            &relation_name,
+            context::OsmQueryKind::Housenumbers,
+        ));
     }
+    // Stale relations are remembered by apply_diffs() till they are downloaded again.
+    refresh_relations(ctx, relations, jobs)?;
+    replication::clear_stale_relations(ctx, &changes.stale)?;
+    update_missing_streets(relations, /*update=*/ true)?;
+    update_missing_housenumbers(relations, /*update=*/ true)?;
+    update_additional_streets(ctx, relations, /*update=*/ true)?;
+    info!("update_osm_diffs: end");
 
     Ok(())
 }
@@ -138,6 +410,7 @@ fn update_missing_housenumbers(
         let mut relation = relations
             .get_relation(&relation_name)
             .context("get_relation() failed")?;
+        let ctx = relation.get_ctx();
         if !update && relation.has_osm_housenumber_coverage()? {
             continue;
         }
@@ -146,9 +419,12 @@ fn update_missing_housenumbers(
             continue;
         }
 
-        relation
-            .write_missing_housenumbers()
-            .context("write_missing_housenumbers() failed")?;
+        run_journaled(ctx, "missing-housenumbers", &relation_name, || {
+            relation
+                .write_missing_housenumbers()
+                .context("write_missing_housenumbers() failed")?;
+            Ok(())
+        })?;
     }
     info!("update_missing_housenumbers: end");
 
@@ -171,7 +447,15 @@ fn update_missing_streets(
             continue;
         }
 
-        relation.write_missing_streets()?;
+        run_journaled(
+            relation.get_ctx(),
+            "missing-streets",
+            &relation_name,
+            || {
+                relation.write_missing_streets()?;
+                Ok(())
+            },
+        )?;
     }
     info!("update_missing_streets: end");
 
@@ -195,13 +479,67 @@ fn update_additional_streets(
             continue;
         }
 
-        relation.write_additional_streets()?;
+        run_journaled(ctx, "additional-streets", &relation_name, || {
+            relation.write_additional_streets()?;
+            Ok(())
+        })?;
     }
     info!("update_additional_streets: end");
 
     Ok(())
 }
 
+/// Notifies the subscribers of the relations about regressions, needs to be called before the
+/// coverage history is updated.
+fn update_notifications(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+) -> anyhow::Result<()> {
+    info!("update_notifications: start");
+    for relation_name in relations.get_active_names()? {
+        let relation = relations.get_relation(&relation_name)?;
+        run_journaled(ctx, "notifications", &relation_name, || {
+            notifications::notify_regressions(&relation).context("notify_regressions() failed")
+        })?;
+    }
+    info!("update_notifications: end");
+
+    Ok(())
+}
+
+/// Records the changes of the relations for feeds, needs to be called before the coverage history
+/// is updated.
+fn update_feeds(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+) -> anyhow::Result<()> {
+    info!("update_feeds: start");
+    for relation_name in relations.get_active_names()? {
+        let relation = relations.get_relation(&relation_name)?;
+        run_journaled(ctx, "feeds", &relation_name, || {
+            feeds::record_relation_events(&relation).context("record_relation_events() failed")
+        })?;
+    }
+    info!("update_feeds: end");
+
+    Ok(())
+}
+
+/// Records the street and house number coverage of the relations into the stats_coverages SQL
+/// table.
+fn update_coverage_history(relations: &mut areas::Relations<'_>) -> anyhow::Result<()> {
+    info!("update_coverage_history: start");
+    for relation_name in relations.get_active_names()? {
+        let relation = relations.get_relation(&relation_name)?;
+        relation
+            .write_coverage_history()
+            .context("write_coverage_history() failed")?;
+    }
+    info!("update_coverage_history: end");
+
+    Ok(())
+}
+
 /// Writes a daily citycount rows into the stats_citycounts SQL table.
 fn write_city_count_path(
     ctx: &context::Context,
@@ -388,7 +726,12 @@ pub fn update_stats_overpass(ctx: &context::Context) -> anyhow::Result<()> {
 
         lines.push(line.to_string());
     }
-    let json_query = lines.join("\n");
+    let json_query = context::OsmQuery {
+        kind: context::OsmQueryKind::WholeCountry,
+        relation_name: "".into(),
+        osmrelation: 0,
+        overpass: lines.join("\n"),
+    };
     info!("update_stats_overpass: json, talking to overpass");
     let mut retry = 0;
     while should_retry(retry) {
@@ -397,7 +740,7 @@ pub fn update_stats_overpass(ctx: &context::Context) -> anyhow::Result<()> {
         }
         retry += 1;
         overpass_sleep(ctx);
-        let response = match overpass_query::overpass_query(ctx, &json_query) {
+        let response = match ctx.get_osm_source().query(ctx, &json_query) {
             Ok(value) => value,
             Err(err) => {
                 info!("update_stats_overpass: http error: {err}");
@@ -448,16 +791,65 @@ fn our_main_inner(
     mode: &String,
     update: bool,
     overpass: bool,
+    resume: bool,
 ) -> anyhow::Result<()> {
+    if !resume {
+        journal_clear(ctx)?;
+    }
     if mode == "all" || mode == "stats" {
-        update_stats(ctx, overpass).context("update_stats failed")?;
+        run_journaled(ctx, "stats", "", || {
+            update_stats(ctx, overpass).context("update_stats failed")
+        })?;
     }
+    // A download which was given up leaves its stage unfinished, so --resume retries it, but the
+    // rest of the relations are still processed.
+    let mut download_error: Option<anyhow::Error> = None;
     if mode == "all" || mode == "relations" {
-        update_osm_streets(ctx, relations, update)?;
-        update_osm_housenumbers(ctx, relations, update)?;
-        update_missing_streets(relations, update)?;
-        update_missing_housenumbers(relations, update)?;
-        update_additional_streets(ctx, relations, update)?;
+        if let Err(err) = run_journaled(ctx, "osm-streets", "", || {
+            update_osm_streets(ctx, relations, update)
+        }) {
+            error!("our_main_inner: {err:?}");
+            download_error.get_or_insert(err);
+        }
+        if let Err(err) = run_journaled(ctx, "osm-housenumbers", "", || {
+            update_osm_housenumbers(ctx, relations, update)
+        }) {
+            error!("our_main_inner: {err:?}");
+            download_error.get_or_insert(err);
+        }
+        run_journaled(ctx, "boundaries", "", || geometry::write_boundaries(ctx))?;
+        run_journaled(ctx, "missing-streets", "", || {
+            update_missing_streets(relations, update)
+        })?;
+        run_journaled(ctx, "missing-housenumbers", "", || {
+            update_missing_housenumbers(relations, update)
+        })?;
+        run_journaled(ctx, "lints", "", || lints::write_lints_challenge(ctx))?;
+        run_journaled(ctx, "additional-streets", "", || {
+            update_additional_streets(ctx, relations, update)
+        })?;
+        run_journaled(ctx, "notifications", "", || {
+            update_notifications(ctx, relations)
+        })?;
+        run_journaled(ctx, "feeds", "", || update_feeds(ctx, relations))?;
+        run_journaled(ctx, "coverage-history", "", || {
+            update_coverage_history(relations)
+        })?;
+    }
+    if mode == "diffs" {
+        run_journaled(ctx, "diffs", "", || update_osm_diffs(ctx, relations))?;
+        run_journaled(ctx, "lints", "", || lints::write_lints_challenge(ctx))?;
+        run_journaled(ctx, "notifications", "", || {
+            update_notifications(ctx, relations)
+        })?;
+        run_journaled(ctx, "feeds", "", || update_feeds(ctx, relations))?;
+        run_journaled(ctx, "coverage-history", "", || {
+            update_coverage_history(relations)
+        })?;
+    }
+
+    if let Some(err) = download_error {
+        return Err(err);
     }
 
     let pid = std::process::id();
@@ -500,11 +892,15 @@ pub fn our_main(
     let mode = clap::Arg::new("mode")
         .long("mode")
         .default_value("relations")
-        .help("only perform the given sub-task or all of them [all, stats or relations]");
+        .help("only perform the given sub-task or all of them [all, stats, relations or diffs]");
     let no_overpass = clap::Arg::new("no-overpass") // default: true
         .long("no-overpass")
         .action(clap::ArgAction::SetTrue)
         .help("when updating stats, don't perform any overpass update");
+    let resume = clap::Arg::new("resume")
+        .long("resume")
+        .action(clap::ArgAction::SetTrue)
+        .help("continue the previous run: skip the stages and relations it already finished");
     let args = [
         refcounty,
         refsettlement,
@@ -512,6 +908,7 @@ pub fn our_main(
         no_update,
         mode,
         no_overpass,
+        resume,
     ];
     let app = clap::Command::new("osm-gimmisn");
     let args = app.args(&args).try_get_matches_from(argv)?;
@@ -532,12 +929,14 @@ pub fn our_main(
     relations.limit_to_refarea(&refarea)?;
     let update = !args.get_one::<bool>("no-update").unwrap();
     let overpass = !args.get_one::<bool>("no-overpass").unwrap();
+    let resume = *args.get_one::<bool>("resume").unwrap();
     our_main_inner(
         ctx,
         &mut relations,
         args.get_one("mode").unwrap(),
         update,
         overpass,
+        resume,
     )
     .context("our_main_inner failed")?;
     let duration = ctx.get_time().now() - start;
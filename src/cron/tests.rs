@@ -11,6 +11,7 @@
 //! Tests for the cron module.
 
 use super::*;
+use crate::reference;
 use context::FileSystem;
 use std::cell::RefCell;
 use std::io::Seek;
@@ -133,8 +134,7 @@ fn test_update_missing_housenumbers() {
 fn test_update_missing_streets() {
     let mut ctx = context::tests::make_test_context().unwrap();
     {
-        let ref_streets = ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -191,13 +191,150 @@ fn test_update_missing_streets() {
     assert_eq!(relation2.has_osm_street_coverage().unwrap(), false);
 }
 
+/// Tests update_notifications(): the lints baseline of the relations is written.
+#[test]
+fn test_update_notifications() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'range', '1', 'out-of-range', '', '');",
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    update_notifications(&ctx, &mut relations).unwrap();
+
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        let count: i64 = conn
+            .query_row(
+                "select count from relation_notify_lints where relation = 'gazdagret'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+    assert_eq!(
+        journal_is_done(&ctx, "notifications", "gazdagret").unwrap(),
+        true
+    );
+}
+
+/// Tests update_feeds(): relations which are already done are skipped when resuming.
+#[test]
+fn test_update_feeds_resume() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+            },
+            "ujbuda": {
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute(
+            "insert into cron_journal (stage, relation, status, started, finished, error) values ('feeds', 'gazdagret', 'done', '0', '0', '')",
+            [],
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    update_feeds(&ctx, &mut relations).unwrap();
+
+    // Only ujbuda got its 'created' event.
+    let conn = ctx.get_database_connection().unwrap();
+    let relations: Vec<String> = conn
+        .prepare("select relation from relation_events where kind = 'created'")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(relations, vec!["ujbuda".to_string()]);
+}
+
+/// Tests update_coverage_history().
+#[test]
+fn test_update_coverage_history() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "01",
+                "refsettlement": "011",
+            },
+            "ujbuda": {
+                "refcounty": "01",
+                "refsettlement": "011",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_street_coverages (relation_name, coverage, last_modified) values ('gazdagret', '50.00', '0');
+             insert into osm_housenumber_coverages (relation_name, coverage, last_modified) values ('gazdagret', '25.00', '0');",
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    update_coverage_history(&mut relations).unwrap();
+
+    let relation = relations.get_relation("gazdagret").unwrap();
+    let history = relation.get_coverage_history().unwrap();
+    assert_eq!(
+        history,
+        vec![areas::CoverageHistoryEntry {
+            date: "2020-05-10".into(),
+            streets: Some("50.00".into()),
+            housenumbers: Some("25.00".into()),
+        }]
+    );
+    // No coverage yet: no history entry.
+    let relation = relations.get_relation("ujbuda").unwrap();
+    assert!(relation.get_coverage_history().unwrap().is_empty());
+}
+
 /// Tests update_additional_streets().
 #[test]
 fn test_update_additional_streets() {
     let mut ctx = context::tests::make_test_context().unwrap();
     {
-        let ref_streets = ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -410,7 +547,7 @@ fn test_update_osm_housenumbers_http_error() {
     let file_system_rc: Rc<dyn FileSystem> = Rc::new(file_system);
     ctx.set_file_system(&file_system_rc);
     let mut relations = areas::Relations::new(&ctx).unwrap();
-    update_osm_housenumbers(&ctx, &mut relations, /*update=*/ true).unwrap();
+    assert!(update_osm_housenumbers(&ctx, &mut relations, /*update=*/ true).is_err());
     // Make sure that in case we keep getting errors we give up at some stage and
     // leave the last state unchanged.
     assert_eq!(
@@ -560,6 +697,387 @@ fn test_update_osm_streets() {
     );
 }
 
+/// Tests update_osm_streets() and update_osm_housenumbers(): the case when the data comes from a
+/// local extract, not from Overpass.
+#[test]
+fn test_update_osm_streets_extract() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let osm_source: Rc<dyn context::OsmSource> = Rc::new(crate::import_osm::ExtractOsmSource::new(
+        "src/fixtures/file-system/extract.osm",
+    ));
+    ctx.set_osm_source(&osm_source);
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    // The Overpass queries are not used, but they are still generated.
+    let template_value = context::tests::TestFileSystem::make_file();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("data/yamls.cache", &yamls_cache_value),
+            ("data/streets-template.overpassql", &template_value),
+            (
+                "data/street-housenumbers-template.overpassql",
+                &template_value,
+            ),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    update_osm_streets(&ctx, &mut relations, /*update=*/ true).unwrap();
+    update_osm_housenumbers(&ctx, &mut relations, /*update=*/ true).unwrap();
+
+    let relation = relations.get_relation("gazdagret").unwrap();
+    let streets = relation.get_files().get_osm_json_streets(&ctx).unwrap();
+    assert_eq!(streets.len(), 3);
+    let housenumbers = relation
+        .get_files()
+        .get_osm_json_housenumbers(&ctx)
+        .unwrap();
+    assert_eq!(housenumbers.len(), 3);
+}
+
+/// Tests our_main_inner(): the diffs case, a new street is added to a relation which only had a
+/// house number on that street.
+#[test]
+fn test_our_main_diffs() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let osm_source: Rc<dyn context::OsmSource> = Rc::new(crate::import_osm::ExtractOsmSource::new(
+        "src/fixtures/file-system/extract.osm",
+    ));
+    ctx.set_osm_source(&osm_source);
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "osmrelation": 42,
+                "refcounty": "01",
+                "refsettlement": "011",
+            },
+            "ujbuda": {
+                "osmrelation": 43,
+                "refcounty": "01",
+                "refsettlement": "012",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let template_value = context::tests::TestFileSystem::make_file();
+    let state_value = context::tests::TestFileSystem::make_file();
+    state_value
+        .borrow_mut()
+        .write_all(b"sequenceNumber=2\ntimestamp=2024-01-02T00\\:00\\:00Z\n")
+        .unwrap();
+    let lints_value = context::tests::TestFileSystem::make_file();
+    let diff_value = context::tests::TestFileSystem::make_file();
+    diff_value
+        .borrow_mut()
+        .write_all(
+            br#"<osmChange version="0.6">
+  <create>
+    <way id="102" version="1">
+      <nd ref="5"/>
+      <nd ref="6"/>
+      <tag k="highway" v="residential"/>
+      <tag k="name" v="Inside utca"/>
+    </way>
+  </create>
+</osmChange>
+"#,
+        )
+        .unwrap();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("data/yamls.cache", &yamls_cache_value),
+            ("data/streets-template.overpassql", &template_value),
+            (
+                "data/street-housenumbers-template.overpassql",
+                &template_value,
+            ),
+            ("workdir/replication/state.txt", &state_value),
+            ("workdir/replication/000/000/002.osc", &diff_value),
+            ("workdir/relation-lints.geojson", &lints_value),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_replication (sequence) values (1);
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '9', 'Inside utca', '1', '', '', '', '', '', '', '', '', '', 'node');",
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    our_main_inner(
+        &ctx,
+        &mut relations,
+        /*mode=*/ &"diffs".to_string(),
+        /*update=*/ true,
+        /*overpass=*/ true,
+        /*resume=*/ false,
+    )
+    .unwrap();
+
+    // gazdagret is downloaded again from the extract.
+    let relation = relations.get_relation("gazdagret").unwrap();
+    let streets = relation.get_files().get_osm_json_streets(&ctx).unwrap();
+    assert_eq!(streets.len(), 3);
+    assert_eq!(relation.has_osm_street_coverage().unwrap(), true);
+    // ujbuda is not affected.
+    assert_eq!(relations.get_names(), vec!["gazdagret".to_string()]);
+    assert_eq!(stats::has_sql_mtime(&ctx, "streets/ujbuda").unwrap(), false);
+}
+
+/// Tests get_backoff().
+#[test]
+fn test_get_backoff() {
+    let mut job = RefreshJob::new("myrelation", context::OsmQueryKind::Streets);
+    job.retry = 1;
+    let backoff = get_backoff(&job);
+    assert!((2..=3).contains(&backoff));
+    // Same job, same jitter.
+    assert_eq!(get_backoff(&job), backoff);
+
+    job.retry = 30;
+    assert!((256..=384).contains(&get_backoff(&job)));
+}
+
+/// Tests refresh_relations(): previous failures go first, errors are retried with a backoff, then
+/// the failure is logged.
+#[test]
+fn test_refresh_relations() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let routes = vec![
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/status",
+            /*data_path=*/ "",
+            /*result_path=*/ "src/fixtures/network/overpass-status-happy.txt",
+        ),
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/interpreter",
+            /*data_path=*/ "",
+            /*result_path=*/ "src/fixtures/network/overpass-streets-gazdagret.json",
+        ),
+    ];
+    let network = context::tests::TestNetwork::new(&routes);
+    let network_rc: Rc<dyn context::Network> = Rc::new(network);
+    ctx.set_network(network_rc);
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "a": {
+                "osmrelation": 42,
+            },
+            "b": {
+                "osmrelation": 43,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let template_value = context::tests::TestFileSystem::make_file();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("data/yamls.cache", &yamls_cache_value),
+            ("data/streets-template.overpassql", &template_value),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into cron_failures (relation, kind, failures, last_error, last_failure) values ('b', 'streets', 1, 'timeout', '0');",
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    relations.activate_all(true);
+
+    let ret = update_osm_streets(&ctx, &mut relations, /*update=*/ true);
+
+    // a is given up.
+    assert_eq!(
+        ret.unwrap_err().to_string(),
+        "refresh_relations: gave up on: a/streets"
+    );
+    // b failed last time, so it got the only successful answer.
+    assert_eq!(stats::has_sql_mtime(&ctx, "streets/b").unwrap(), true);
+    assert_eq!(stats::has_sql_mtime(&ctx, "streets/a").unwrap(), false);
+    let conn = ctx.get_database_connection().unwrap();
+    let failures: Vec<(String, i64)> = conn
+        .prepare("select relation, failures from cron_failures")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(failures, vec![("a".to_string(), 20)]);
+    let journal: Vec<(String, String)> = conn
+        .prepare("select relation, status from cron_journal where stage = 'osm-streets' order by relation")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(
+        journal,
+        vec![
+            ("a".to_string(), "failed".to_string()),
+            ("b".to_string(), "done".to_string())
+        ]
+    );
+    let time = ctx
+        .get_time()
+        .as_any()
+        .downcast_ref::<context::tests::TestTime>()
+        .unwrap();
+    assert!((256..=384).contains(&time.get_sleep()));
+}
+
+/// Tests refresh_relations(): a given up download leaves its stage unfinished, so resuming
+/// retries it.
+#[test]
+fn test_refresh_relations_resume() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let routes = vec![context::tests::URLRoute::new(
+        /*url=*/ "https://overpass-api.de/api/status",
+        /*data_path=*/ "",
+        /*result_path=*/ "src/fixtures/network/overpass-status-happy.txt",
+    )];
+    let network = context::tests::TestNetwork::new(&routes);
+    let network_rc: Rc<dyn context::Network> = Rc::new(network);
+    ctx.set_network(network_rc);
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "a": {
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let template_value = context::tests::TestFileSystem::make_file();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("data/yamls.cache", &yamls_cache_value),
+            ("data/streets-template.overpassql", &template_value),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+
+    // The first run gives up.
+    {
+        let mut relations = areas::Relations::new(&ctx).unwrap();
+        relations.activate_all(true);
+        let ret = run_journaled(&ctx, "osm-streets", "", || {
+            update_osm_streets(&ctx, &mut relations, /*update=*/ true)
+        });
+        assert!(ret.is_err());
+    }
+    assert_eq!(journal_is_done(&ctx, "osm-streets", "").unwrap(), false);
+    assert_eq!(stats::has_sql_mtime(&ctx, "streets/a").unwrap(), false);
+
+    // The resumed run retries the stage.
+    let routes = vec![
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/status",
+            /*data_path=*/ "",
+            /*result_path=*/ "src/fixtures/network/overpass-status-happy.txt",
+        ),
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/interpreter",
+            /*data_path=*/ "",
+            /*result_path=*/ "src/fixtures/network/overpass-streets-gazdagret.json",
+        ),
+    ];
+    let network = context::tests::TestNetwork::new(&routes);
+    let network_rc: Rc<dyn context::Network> = Rc::new(network);
+    ctx.set_network(network_rc);
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    relations.activate_all(true);
+    run_journaled(&ctx, "osm-streets", "", || {
+        update_osm_streets(&ctx, &mut relations, /*update=*/ true)
+    })
+    .unwrap();
+    assert_eq!(journal_is_done(&ctx, "osm-streets", "").unwrap(), true);
+    assert_eq!(journal_is_done(&ctx, "osm-streets", "a").unwrap(), true);
+    assert_eq!(stats::has_sql_mtime(&ctx, "streets/a").unwrap(), true);
+}
+
+/// Tests refresh_relations(): the case when there are no free Overpass slots.
+#[test]
+fn test_refresh_relations_no_slots() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let routes = vec![
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/status",
+            /*data_path=*/ "",
+            /*result_path=*/ "src/fixtures/network/overpass-status-wait.txt",
+        ),
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/status",
+            /*data_path=*/ "",
+            /*result_path=*/ "src/fixtures/network/overpass-status-wait.txt",
+        ),
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/status",
+            /*data_path=*/ "",
+            /*result_path=*/ "src/fixtures/network/overpass-status-happy.txt",
+        ),
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/interpreter",
+            /*data_path=*/ "",
+            /*result_path=*/ "src/fixtures/network/overpass-streets-gazdagret.json",
+        ),
+    ];
+    let network = context::tests::TestNetwork::new(&routes);
+    let network_rc: Rc<dyn context::Network> = Rc::new(network);
+    ctx.set_network(network_rc);
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let template_value = context::tests::TestFileSystem::make_file();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("data/yamls.cache", &yamls_cache_value),
+            ("data/streets-template.overpassql", &template_value),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    update_osm_streets(&ctx, &mut relations, /*update=*/ true).unwrap();
+
+    assert_eq!(
+        stats::has_sql_mtime(&ctx, "streets/gazdagret").unwrap(),
+        true
+    );
+    let time = ctx
+        .get_time()
+        .as_any()
+        .downcast_ref::<context::tests::TestTime>()
+        .unwrap();
+    assert_eq!(time.get_sleep(), 12);
+}
+
 /// Tests update_osm_streets(): the case when we keep getting HTTP errors.
 #[test]
 fn test_update_osm_streets_http_error() {
@@ -608,7 +1126,7 @@ fn test_update_osm_streets_http_error() {
     ctx.set_file_system(&file_system_rc);
     let mut relations = areas::Relations::new(&ctx).unwrap();
 
-    update_osm_streets(&ctx, &mut relations, /*update=*/ true).unwrap();
+    assert!(update_osm_streets(&ctx, &mut relations, /*update=*/ true).is_err());
 
     // Make sure that in case we keep getting errors we give up at some stage and
     // leave the last state unchanged.
@@ -850,10 +1368,8 @@ fn test_update_stats_no_overpass() {
 #[test]
 fn test_our_main() {
     let mut ctx = context::tests::make_test_context().unwrap();
-    let ref_streets = ctx.get_ini().get_reference_street_path().unwrap();
-    util::build_street_reference_index(&ctx, &ref_streets).unwrap();
-    let references = ctx.get_ini().get_reference_housenumber_paths().unwrap();
-    util::build_reference_index(&ctx, &references).unwrap();
+    reference::build_street_reference_index(&ctx).unwrap();
+    reference::build_reference_index(&ctx).unwrap();
     let routes = vec![
         context::tests::URLRoute::new(
             /*url=*/ "https://overpass-api.de/api/interpreter",
@@ -890,6 +1406,8 @@ fn test_our_main() {
         .borrow_mut()
         .write_all(b"housenr aaa @RELATION@ bbb @AREA@ ccc\n")
         .unwrap();
+    let boundaries = context::tests::TestFileSystem::make_file();
+    let lints_value = context::tests::TestFileSystem::make_file();
     let files = context::tests::TestFileSystem::make_files(
         &ctx,
         &[
@@ -899,6 +1417,8 @@ fn test_our_main() {
                 "data/street-housenumbers-template.overpassql",
                 &housenr_template,
             ),
+            ("workdir/boundaries.geojson", &boundaries),
+            ("workdir/relation-lints.geojson", &lints_value),
         ],
     );
     let mut file_system = context::tests::TestFileSystem::new();
@@ -925,6 +1445,7 @@ fn test_our_main() {
         /*mode=*/ &"relations".to_string(),
         /*update=*/ true,
         /*overpass=*/ true,
+        /*resume=*/ false,
     )
     .unwrap();
 
@@ -958,6 +1479,10 @@ fn test_our_main() {
             .unwrap();
         assert_eq!(count, "3".to_string());
     }
+    // write_boundaries() is called.
+    assert!(!boundaries.borrow().get_ref().is_empty());
+    // write_lints_challenge() is called.
+    assert!(journal_is_done(&ctx, "lints", "").unwrap());
 }
 
 /// Tests our_main(): the stats case.
@@ -1005,6 +1530,7 @@ fn test_our_main_stats() {
         /*mode=*/ &"stats".to_string(),
         /*update=*/ false,
         /*overpass=*/ true,
+        /*resume=*/ false,
     )
     .unwrap();
 
@@ -1012,6 +1538,62 @@ fn test_our_main_stats() {
     assert_eq!(guard.seek(SeekFrom::Current(0)).unwrap() > 0, true);
 }
 
+/// Tests run_journaled(): the error case is recorded in the journal.
+#[test]
+fn test_run_journaled_error() {
+    let ctx = context::tests::make_test_context().unwrap();
+
+    let ret = run_journaled(&ctx, "stats", "", || Err(anyhow::anyhow!("myerror")));
+
+    assert!(ret.is_err());
+    let conn = ctx.get_database_connection().unwrap();
+    let (status, error): (String, String) = conn
+        .query_row(
+            "select status, error from cron_journal where stage = 'stats'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(status, "failed");
+    assert_eq!(error, "myerror");
+}
+
+/// Tests our_main_inner(): the resume case, already finished stages are skipped.
+#[test]
+fn test_our_main_resume() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let stats_value = context::tests::TestFileSystem::make_file();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("workdir/stats/stats.json", &stats_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute(
+            "insert into cron_journal (stage, relation, status, started, finished, error) values ('stats', '', 'done', '0', '0', '')",
+            [],
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    our_main_inner(
+        &ctx,
+        &mut relations,
+        /*mode=*/ &"stats".to_string(),
+        /*update=*/ false,
+        /*overpass=*/ true,
+        /*resume=*/ true,
+    )
+    .unwrap();
+
+    // update_stats() is not called.
+    let mut guard = stats_value.borrow_mut();
+    assert_eq!(guard.seek(SeekFrom::Current(0)).unwrap(), 0);
+}
+
 /// Tests main().
 #[test]
 fn test_main() {
@@ -0,0 +1,392 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The feeds module records changes of relations during cron and provides them as Atom feeds.
+
+use crate::areas;
+use crate::context;
+use crate::i18n::translate as tr;
+use crate::webframe;
+use crate::wsgi;
+use crate::yattag;
+use anyhow::Context;
+use std::collections::BTreeSet;
+
+/// A lint, as remembered by the feeds.
+#[derive(Clone, Eq, Ord, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+struct FeedLint {
+    street_name: String,
+    housenumber: String,
+    source: String,
+    reason: String,
+}
+
+/// Details of a 'coverage' event: the old and the new percentage, None if it did not change.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct CoverageChange {
+    housenumbers: Option<(String, String)>,
+    streets: Option<(String, String)>,
+}
+
+/// Details of a 'lints' event.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct LintsChange {
+    added: Vec<FeedLint>,
+    resolved: Vec<FeedLint>,
+}
+
+/// One entry of an Atom feed.
+struct FeedEntry {
+    id: String,
+    updated: time::OffsetDateTime,
+    title: String,
+    link: String,
+    content: String,
+}
+
+/// Inserts a new row into the relation_events table.
+fn add_event(
+    ctx: &context::Context,
+    relation_name: &str,
+    kind: &str,
+    details: &str,
+) -> anyhow::Result<()> {
+    let timestamp = ctx.get_time().now().unix_timestamp_nanos().to_string();
+    let conn = ctx.get_database_connection()?;
+    conn.execute(
+        "insert into relation_events (relation, timestamp, kind, details) values (?1, ?2, ?3, ?4)",
+        [relation_name, &timestamp, kind, details],
+    )?;
+    Ok(())
+}
+
+/// Records a 'coverage' event if the coverage differs from the last one in the coverage history.
+fn record_coverage_event(relation: &areas::Relation<'_>) -> anyhow::Result<()> {
+    let history = relation.get_coverage_history()?;
+    let last = match history.last() {
+        Some(value) => value,
+        None => {
+            // First run for this relation, nothing to compare with.
+            return Ok(());
+        }
+    };
+    let streets = if relation.has_osm_street_coverage()? {
+        Some(relation.get_osm_street_coverage()?)
+    } else {
+        None
+    };
+    let housenumbers = if relation.has_osm_housenumber_coverage()? {
+        Some(relation.get_osm_housenumber_coverage()?)
+    } else {
+        None
+    };
+    let get_change = |old: &Option<String>, new: Option<String>| match (old, new) {
+        (Some(old), Some(new)) if *old != new => Some((old.to_string(), new)),
+        _ => None,
+    };
+    let change = CoverageChange {
+        housenumbers: get_change(&last.housenumbers, housenumbers),
+        streets: get_change(&last.streets, streets),
+    };
+    if change.housenumbers.is_none() && change.streets.is_none() {
+        return Ok(());
+    }
+
+    add_event(
+        relation.get_ctx(),
+        &relation.get_name(),
+        "coverage",
+        &serde_json::to_string(&change)?,
+    )
+}
+
+/// Reads the lints of a relation, using `sql` which selects the lint columns.
+fn get_feed_lints(
+    ctx: &context::Context,
+    sql: &str,
+    relation_name: &str,
+) -> anyhow::Result<BTreeSet<FeedLint>> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(sql)?;
+    let mut rows = stmt.query([relation_name])?;
+    let mut ret: BTreeSet<FeedLint> = BTreeSet::new();
+    while let Some(row) = rows.next()? {
+        ret.insert(FeedLint {
+            street_name: row.get(0)?,
+            housenumber: row.get(1)?,
+            source: row.get(2)?,
+            reason: row.get(3)?,
+        });
+    }
+    Ok(ret)
+}
+
+/// Records a 'lints' event if lints were added or resolved since they were last reported.
+fn record_lints_event(relation: &areas::Relation<'_>) -> anyhow::Result<()> {
+    let ctx = relation.get_ctx();
+    let relation_name = relation.get_name();
+    let current = get_feed_lints(
+        ctx,
+        "select street_name, housenumber, source, reason from relation_lints where relation_name = ?1",
+        &relation_name,
+    )?;
+    let reported = get_feed_lints(
+        ctx,
+        "select street_name, housenumber, source, reason from relation_event_lints where relation = ?1",
+        &relation_name,
+    )?;
+    let change = LintsChange {
+        added: current.difference(&reported).cloned().collect(),
+        resolved: reported.difference(&current).cloned().collect(),
+    };
+    if change.added.is_empty() && change.resolved.is_empty() {
+        return Ok(());
+    }
+
+    add_event(
+        ctx,
+        &relation_name,
+        "lints",
+        &serde_json::to_string(&change)?,
+    )?;
+    let mut conn = ctx.get_database_connection()?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "delete from relation_event_lints where relation = ?1",
+        [&relation_name],
+    )?;
+    for lint in current {
+        tx.execute(
+            "insert into relation_event_lints (relation, street_name, housenumber, source, reason) values (?1, ?2, ?3, ?4, ?5)",
+            [&relation_name, &lint.street_name, &lint.housenumber, &lint.source, &lint.reason],
+        )?;
+    }
+    Ok(tx.commit()?)
+}
+
+/// Records a 'created' event the first time cron sees a relation, i.e. when it has no coverage
+/// history and no such event yet. Relations which existed before events were recorded have a
+/// 'created' event with a zero timestamp, see sql::init().
+fn record_created_event(relation: &areas::Relation<'_>) -> anyhow::Result<()> {
+    if !relation.get_coverage_history()?.is_empty() {
+        return Ok(());
+    }
+    let ctx = relation.get_ctx();
+    let relation_name = relation.get_name();
+    let created: bool = {
+        let conn = ctx.get_database_connection()?;
+        conn.query_row(
+            "select exists(select 1 from relation_events where relation = ?1 and kind = 'created')",
+            [&relation_name],
+            |row| row.get(0),
+        )?
+    };
+    if created {
+        return Ok(());
+    }
+
+    add_event(ctx, &relation_name, "created", "{}")
+}
+
+/// Records the changes of a relation since the previous cron run, needs to be called before the
+/// coverage history is updated.
+pub fn record_relation_events(relation: &areas::Relation<'_>) -> anyhow::Result<()> {
+    record_created_event(relation).context("record_created_event() failed")?;
+    record_coverage_event(relation).context("record_coverage_event() failed")?;
+    record_lints_event(relation).context("record_lints_event() failed")?;
+    Ok(())
+}
+
+/// Formats a lint of a 'lints' event for the content of a feed entry.
+fn format_feed_lint(lint: &FeedLint) -> String {
+    let reason = match areas::RelationLintReason::try_from(lint.reason.as_str()) {
+        Ok(value) => webframe::get_lint_reason_string(&value),
+        Err(_) => lint.reason.to_string(),
+    };
+    format!("{} {}: {}", lint.street_name, lint.housenumber, reason)
+}
+
+/// Builds feed entries from the recorded events of a relation.
+fn get_event_entries(
+    ctx: &context::Context,
+    relation_name: &str,
+) -> anyhow::Result<Vec<FeedEntry>> {
+    let site_url = ctx.get_ini().get_site_url();
+    let prefix = ctx.get_ini().get_uri_prefix();
+    let page = format!("{site_url}{prefix}/missing-housenumbers/{relation_name}");
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(
+        "select id, timestamp, kind, details from relation_events where relation = ?1 and timestamp != '0' order by id desc limit 50",
+    )?;
+    let mut rows = stmt.query([relation_name])?;
+    let mut ret: Vec<FeedEntry> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let timestamp: String = row.get(1)?;
+        let updated = time::OffsetDateTime::from_unix_timestamp_nanos(timestamp.parse()?)?;
+        let kind: String = row.get(2)?;
+        let details: String = row.get(3)?;
+        let (title, link, lines) = if kind == "created" {
+            let title = tr("{0}: new area").replace("{0}", relation_name);
+            let lines = vec![tr("The area is now available.")];
+            (title, format!("{page}/view-result"), lines)
+        } else if kind == "coverage" {
+            let change: CoverageChange = serde_json::from_str(&details)?;
+            let mut lines: Vec<String> = Vec::new();
+            if let Some((old, new)) = change.housenumbers {
+                lines.push(
+                    tr("House number coverage: {0}% → {1}%")
+                        .replace("{0}", &old)
+                        .replace("{1}", &new),
+                );
+            }
+            if let Some((old, new)) = change.streets {
+                lines.push(
+                    tr("Street coverage: {0}% → {1}%")
+                        .replace("{0}", &old)
+                        .replace("{1}", &new),
+                );
+            }
+            let title = tr("{0}: coverage changed").replace("{0}", relation_name);
+            (title, format!("{page}/view-result"), lines)
+        } else {
+            // kind == "lints"
+            let change: LintsChange = serde_json::from_str(&details)?;
+            let mut lines: Vec<String> = Vec::new();
+            for lint in &change.added {
+                lines.push(format!("+ {}", format_feed_lint(lint)));
+            }
+            for lint in &change.resolved {
+                lines.push(format!("- {}", format_feed_lint(lint)));
+            }
+            let title = tr("{0}: {1} new lints, {2} resolved lints")
+                .replace("{0}", relation_name)
+                .replace("{1}", &change.added.len().to_string())
+                .replace("{2}", &change.resolved.len().to_string());
+            (title, format!("{page}/view-lints"), lines)
+        };
+        ret.push(FeedEntry {
+            id: format!("{link}#event-{id}"),
+            updated,
+            title,
+            link,
+            content: lines.join("\n"),
+        });
+    }
+    Ok(ret)
+}
+
+/// Expected request_uri: e.g. /osm/feed/ormezo/view-result.atom or
+/// /osm/feed/filter-for/refcounty/01/whole-county/view-result.atom.
+///
+/// Coverage changes, added or resolved lints and new relations, newest first.
+pub fn feed_view_atom(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<String> {
+    let site_url = ctx.get_ini().get_site_url();
+    let prefix = ctx.get_ini().get_uri_prefix();
+    let (relation_names, title, page) = if request_uri.contains("/filter-for/") {
+        let (filter_for, refcounty) =
+            wsgi::setup_filter_for(request_uri, Box::new(wsgi::filter_for_everything))?;
+        let mut relation_names: Vec<String> = Vec::new();
+        for relation_name in relations.get_active_names()? {
+            let relation = relations.get_relation(&relation_name)?;
+            if filter_for(false, &relation) {
+                relation_names.push(relation_name);
+            }
+        }
+        let mut title = relations.refcounty_get_name(&refcounty);
+        if title.is_empty() {
+            title = tr("All areas");
+        }
+        let page = request_uri
+            .replace("/feed/", "/")
+            .trim_end_matches("/view-result.atom")
+            .to_string();
+        (relation_names, title, page)
+    } else {
+        let mut tokens = request_uri.split('/');
+        tokens.next_back();
+        let relation_name = tokens.next_back().context("no relation_name")?;
+        if !relations.get_names().contains(&relation_name.to_string()) {
+            return Err(anyhow::anyhow!("no such relation: {relation_name}"));
+        }
+        let page = format!("{prefix}/missing-housenumbers/{relation_name}/view-result");
+        (
+            vec![relation_name.to_string()],
+            relation_name.to_string(),
+            page,
+        )
+    };
+
+    let mut entries: Vec<FeedEntry> = Vec::new();
+    for relation_name in &relation_names {
+        entries.append(&mut get_event_entries(ctx, relation_name)?);
+    }
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.updated));
+    entries.truncate(50);
+
+    let format = time::format_description::well_known::Rfc3339;
+    let feed_updated = match entries.first() {
+        Some(entry) => entry.updated,
+        None => ctx.get_time().now(),
+    };
+    let self_url = format!("{site_url}{request_uri}");
+    let doc = yattag::Doc::new();
+    doc.append_value("<?xml version='1.0' encoding='UTF-8'?>".into());
+    {
+        let feed = doc.tag("feed", &[("xmlns", "http://www.w3.org/2005/Atom")]);
+        {
+            let feed_title = feed.tag("title", &[]);
+            feed_title.text(&format!("{} - {}", tr("Where to map?"), title));
+        }
+        {
+            let id = feed.tag("id", &[]);
+            id.text(&self_url);
+        }
+        feed.stag("link", &[("rel", "self"), ("href", &self_url)]);
+        feed.stag("link", &[("href", &format!("{site_url}{page}"))]);
+        {
+            let updated = feed.tag("updated", &[]);
+            updated.text(&feed_updated.format(&format)?);
+        }
+        {
+            let author = feed.tag("author", &[]);
+            let name = author.tag("name", &[]);
+            name.text("osm-gimmisn");
+        }
+        for entry in entries {
+            let entry_tag = feed.tag("entry", &[]);
+            {
+                let title = entry_tag.tag("title", &[]);
+                title.text(&entry.title);
+            }
+            {
+                let id = entry_tag.tag("id", &[]);
+                id.text(&entry.id);
+            }
+            entry_tag.stag("link", &[("href", &entry.link)]);
+            {
+                let updated = entry_tag.tag("updated", &[]);
+                updated.text(&entry.updated.format(&format)?);
+            }
+            {
+                let content = entry_tag.tag("content", &[("type", "text")]);
+                content.text(&entry.content);
+            }
+        }
+    }
+    Ok(doc.get_value())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,321 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the feeds module.
+
+use super::*;
+use crate::wsgi;
+
+/// Gets the kind and details of the recorded events of a relation.
+fn get_events(ctx: &context::Context, relation_name: &str) -> Vec<(String, serde_json::Value)> {
+    let conn = ctx.get_database_connection().unwrap();
+    let mut stmt = conn
+        .prepare("select kind, details from relation_events where relation = ?1 order by id")
+        .unwrap();
+    let mut rows = stmt.query([relation_name]).unwrap();
+    let mut ret = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        let details: String = row.get(1).unwrap();
+        ret.push((row.get(0).unwrap(), serde_json::from_str(&details).unwrap()));
+    }
+    ret
+}
+
+/// Tests record_relation_events().
+#[test]
+fn test_record_relation_events() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "01",
+                "refsettlement": "011",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_street_coverages (relation_name, coverage, last_modified) values ('gazdagret', '50.00', '0');
+             insert into osm_housenumber_coverages (relation_name, coverage, last_modified) values ('gazdagret', '25.00', '0');
+             insert into stats_coverages (date, relation, streets, housenumbers) values ('2020-05-09', 'gazdagret', '40.00', '25.00');
+             insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'range', '1', 'out-of-range', '', '');",
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+
+    record_relation_events(&relation).unwrap();
+
+    // Only the street coverage changed, and the lint is new.
+    let events = get_events(&ctx, "gazdagret");
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].0, "coverage");
+    assert_eq!(
+        events[0].1,
+        serde_json::json!({"housenumbers": null, "streets": ["40.00", "50.00"]})
+    );
+    assert_eq!(events[1].0, "lints");
+    assert_eq!(events[1].1["added"][0]["housenumber"], "1");
+    assert_eq!(events[1].1["resolved"], serde_json::json!([]));
+
+    // No changes since the last run: no new events.
+    relation.write_coverage_history().unwrap();
+    record_relation_events(&relation).unwrap();
+    assert_eq!(get_events(&ctx, "gazdagret").len(), 2);
+
+    // The lint is resolved.
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute("delete from relation_lints", []).unwrap();
+    }
+    record_relation_events(&relation).unwrap();
+    let events = get_events(&ctx, "gazdagret");
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[2].1["added"], serde_json::json!([]));
+    assert_eq!(events[2].1["resolved"][0]["street_name"], "Tűzkő utca");
+}
+
+/// Tests record_relation_events(): a relation without a coverage history is new, but there is
+/// nothing to compare its coverage with.
+#[test]
+fn test_record_relation_events_no_history() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_street_coverages (relation_name, coverage, last_modified) values ('gazdagret', '50.00', '0');",
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+
+    record_relation_events(&relation).unwrap();
+
+    let events = get_events(&ctx, "gazdagret");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].0, "created");
+
+    // Still no coverage history, but the relation is not new anymore.
+    record_relation_events(&relation).unwrap();
+    assert_eq!(get_events(&ctx, "gazdagret").len(), 1);
+}
+
+/// Tests record_relation_events(): a relation which was downloaded before events were recorded is
+/// not new.
+#[test]
+fn test_record_relation_events_existing() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        // As seeded by sql::init().
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into relation_events (relation, timestamp, kind, details) values ('gazdagret', '0', 'created', '{}');",
+        )
+        .unwrap();
+    }
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+
+    record_relation_events(&relation).unwrap();
+
+    // No new 'created' event, only the seeded one.
+    let conn = ctx.get_database_connection().unwrap();
+    let timestamps: Vec<String> = conn
+        .prepare("select timestamp from relation_events where relation = 'gazdagret'")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(timestamps, vec!["0".to_string()]);
+}
+
+/// Sets up two relations in two counties, with recorded events.
+fn make_test_wsgi() -> wsgi::tests::TestWsgi {
+    let mut test_wsgi = wsgi::tests::TestWsgi::new();
+    test_wsgi.set_content_type("application/atom+xml; charset=utf-8");
+    let ctx = test_wsgi.get_ctx();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "01",
+                "refsettlement": "011",
+            },
+            "ujbuda": {
+                "refcounty": "02",
+                "refsettlement": "011",
+            },
+        },
+        "refcounty-names.yaml": {
+            "01": "Budapest",
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            r#"insert into relation_events (relation, timestamp, kind, details) values ('gazdagret', '1588975200000000000', 'created', '{}');
+             insert into relation_events (relation, timestamp, kind, details) values ('ujbuda', '1588975200000000000', 'created', '{}');
+             insert into relation_events (relation, timestamp, kind, details) values ('gazdagret', '1589068800000000000', 'coverage', '{"housenumbers":["25.00","30.00"],"streets":null}');
+             insert into relation_events (relation, timestamp, kind, details) values ('gazdagret', '1589072400000000000', 'lints', '{"added":[{"street_name":"Tűzkő utca","housenumber":"1","source":"range","reason":"out-of-range"}],"resolved":[]}');
+             insert into relation_events (relation, timestamp, kind, details) values ('ujbuda', '1589068800000000000', 'coverage', '{"housenumbers":null,"streets":["10.00","20.00"]}');"#,
+        )
+        .unwrap();
+    }
+    test_wsgi
+}
+
+/// Finds the string values of an xpath, ignoring the Atom namespace.
+fn find_all(package: &sxd_document::Package, path: &str) -> Vec<String> {
+    let document = package.as_document();
+    match sxd_xpath::evaluate_xpath(&document, path).unwrap() {
+        sxd_xpath::Value::Nodeset(nodeset) => nodeset
+            .document_order()
+            .iter()
+            .map(|i| i.string_value())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Tests feed_view_atom(): the feed of a relation.
+#[test]
+fn test_feed_view_atom() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let root = test_wsgi.get_dom_for_path("/feed/gazdagret/view-result.atom");
+
+    assert_eq!(
+        find_all(&root, "/*[local-name()='feed']/*[local-name()='title']"),
+        ["Where to map? - gazdagret"]
+    );
+    assert_eq!(
+        find_all(&root, "/*[local-name()='feed']/*[local-name()='updated']"),
+        ["2020-05-10T01:00:00Z"]
+    );
+    let entry = "//*[local-name()='entry']";
+    assert_eq!(
+        find_all(&root, &format!("{entry}/*[local-name()='title']")),
+        [
+            "gazdagret: 1 new lints, 0 resolved lints",
+            "gazdagret: coverage changed",
+            "gazdagret: new area"
+        ]
+    );
+    assert_eq!(
+        find_all(&root, &format!("{entry}/*[local-name()='content']")),
+        [
+            "+ Tűzkő utca 1: out of range",
+            "House number coverage: 25.00% → 30.00%",
+            "The area is now available."
+        ]
+    );
+    assert_eq!(
+        find_all(&root, &format!("{entry}/*[local-name()='link']/@href"))[0],
+        "https://osm-gimmisn.vmiklos.hu/osm/missing-housenumbers/gazdagret/view-lints"
+    );
+    assert_eq!(
+        find_all(&root, &format!("{entry}/*[local-name()='updated']"))[2],
+        "2020-05-08T22:00:00Z"
+    );
+}
+
+/// Tests feed_view_atom(): the feed of a refcounty.
+#[test]
+fn test_feed_view_atom_refcounty() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let root =
+        test_wsgi.get_dom_for_path("/feed/filter-for/refcounty/01/whole-county/view-result.atom");
+
+    assert_eq!(
+        find_all(&root, "/*[local-name()='feed']/*[local-name()='title']"),
+        ["Where to map? - Budapest"]
+    );
+    assert_eq!(
+        find_all(
+            &root,
+            "/*[local-name()='feed']/*[local-name()='link'][not(@rel)]/@href"
+        ),
+        ["https://osm-gimmisn.vmiklos.hu/osm/filter-for/refcounty/01/whole-county"]
+    );
+    // ujbuda is in an other county.
+    assert_eq!(
+        find_all(&root, "//*[local-name()='entry']/*[local-name()='title']"),
+        [
+            "gazdagret: 1 new lints, 0 resolved lints",
+            "gazdagret: coverage changed",
+            "gazdagret: new area"
+        ]
+    );
+}
+
+/// Tests feed_view_atom(): the seeded 'created' event of a relation is not shown.
+#[test]
+fn test_feed_view_atom_existing() {
+    let mut test_wsgi = make_test_wsgi();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "delete from relation_events where relation = 'ujbuda' and kind = 'created';
+             insert into relation_events (relation, timestamp, kind, details) values ('ujbuda', '0', 'created', '{}');",
+        )
+        .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/feed/ujbuda/view-result.atom");
+
+    assert_eq!(
+        find_all(&root, "//*[local-name()='entry']/*[local-name()='title']"),
+        ["ujbuda: coverage changed"]
+    );
+}
@@ -0,0 +1,513 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The geometry module finds the location of OSM objects and places missing house numbers along
+//! their streets.
+
+use crate::areas;
+use crate::cache;
+use crate::context;
+use crate::util;
+use anyhow::Context as _;
+use std::collections::HashMap;
+
+/// A WGS84 coordinate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    /// Latitude.
+    pub lat: f64,
+    /// Longitude.
+    pub lon: f64,
+}
+
+impl Point {
+    /// Creates a new point.
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Point { lat, lon }
+    }
+
+    /// Linear interpolation between two points, `ratio` is 0 for self and 1 for `other`.
+    fn lerp(&self, other: &Point, ratio: f64) -> Point {
+        Point::new(
+            self.lat + (other.lat - self.lat) * ratio,
+            self.lon + (other.lon - self.lon) * ratio,
+        )
+    }
+
+    /// Rounds to 7 decimal digits, the precision of OSM coordinates.
+    pub fn round(&self) -> Point {
+        let round = |value: f64| (value * 1e7).round() / 1e7;
+        Point::new(round(self.lat), round(self.lon))
+    }
+
+    /// Approximate distance, in degrees of latitude. Good enough to compare distances inside a
+    /// relation.
+    fn distance(&self, other: &Point) -> f64 {
+        let scale = self.lat.to_radians().cos();
+        let dlat = other.lat - self.lat;
+        let dlon = (other.lon - self.lon) * scale;
+        (dlat * dlat + dlon * dlon).sqrt()
+    }
+}
+
+/// Finds the position along a line which is the closest to a point. Returns the distance from
+/// the start of the line and the distance from the point.
+fn project_to_line(line: &[Point], point: &Point) -> (f64, f64) {
+    let mut best = (0_f64, f64::MAX);
+    let mut start = 0_f64;
+    for segment in line.windows(2) {
+        let (a, b) = (&segment[0], &segment[1]);
+        let length = a.distance(b);
+        let ratio = if length > 0_f64 {
+            let scale = a.lat.to_radians().cos().powi(2);
+            let dot = (point.lat - a.lat) * (b.lat - a.lat)
+                + (point.lon - a.lon) * (b.lon - a.lon) * scale;
+            (dot / (length * length)).clamp(0_f64, 1_f64)
+        } else {
+            0_f64
+        };
+        let distance = a.lerp(b, ratio).distance(point);
+        if distance < best.1 {
+            best = (start + length * ratio, distance);
+        }
+        start += length;
+    }
+    best
+}
+
+/// Gets the length of a line.
+fn get_line_length(line: &[Point]) -> f64 {
+    line.windows(2).map(|i| i[0].distance(&i[1])).sum()
+}
+
+/// Gets the point at a given distance from the start of a line.
+fn get_point_on_line(line: &[Point], offset: f64) -> Option<Point> {
+    let mut start = 0_f64;
+    for segment in line.windows(2) {
+        let (a, b) = (&segment[0], &segment[1]);
+        let length = a.distance(b);
+        if length > 0_f64 && start + length >= offset {
+            return Some(a.lerp(b, ((offset - start) / length).max(0_f64)));
+        }
+        start += length;
+    }
+    line.last().cloned()
+}
+
+/// How the position of a missing house number was found.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Placement {
+    /// Interpolated between two existing house numbers of the street.
+    Interpolated,
+    /// At the closest existing house number of the street.
+    Nearest,
+    /// Along the street, which has no usable existing house numbers.
+    Street,
+}
+
+impl std::fmt::Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Placement::Interpolated => write!(f, "interpolated"),
+            Placement::Nearest => write!(f, "nearest"),
+            Placement::Street => write!(f, "street"),
+        }
+    }
+}
+
+/// A missing house number with its probable position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocatedHousenumber {
+    /// OSM street name.
+    pub street: String,
+    /// The house number, e.g. 42/A.
+    pub housenumber: String,
+    /// The probable position.
+    pub point: Point,
+    /// How the position was found.
+    pub placement: Placement,
+}
+
+/// Places the missing house numbers of one street. `known` is a list of existing house numbers
+/// (only their numeric part) and their positions, `missing` is ordered.
+pub fn locate_street_housenumbers(
+    street: &str,
+    lines: &[Vec<Point>],
+    known: &[(i32, Point)],
+    missing: &[String],
+) -> Vec<LocatedHousenumber> {
+    let mut ret: Vec<LocatedHousenumber> = Vec::new();
+    let longest_line = lines.iter().max_by(|a, b| {
+        get_line_length(a)
+            .partial_cmp(&get_line_length(b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    for (index, housenumber) in missing.iter().enumerate() {
+        let (number, _suffix) = util::split_house_number(housenumber);
+        // Prefer the same side of the street.
+        let same_side: Vec<&(i32, Point)> =
+            known.iter().filter(|i| i.0 % 2 == number % 2).collect();
+        let candidates: Vec<&(i32, Point)> = if same_side.is_empty() {
+            known.iter().collect()
+        } else {
+            same_side
+        };
+        let lower = candidates
+            .iter()
+            .filter(|i| i.0 <= number)
+            .max_by_key(|i| i.0);
+        let upper = candidates
+            .iter()
+            .filter(|i| i.0 > number)
+            .min_by_key(|i| i.0);
+        let located = match (lower, upper) {
+            (Some(lower), Some(upper)) if lower.0 != number => {
+                let ratio = f64::from(number - lower.0) / f64::from(upper.0 - lower.0);
+                Some((
+                    interpolate_along_lines(lines, &lower.1, &upper.1, ratio),
+                    Placement::Interpolated,
+                ))
+            }
+            (Some(nearest), _) | (None, Some(nearest)) => Some((nearest.1, Placement::Nearest)),
+            (None, None) => longest_line.and_then(|line| {
+                // Spread the missing house numbers evenly along the street.
+                let ratio = (index as f64 + 0.5) / missing.len() as f64;
+                get_point_on_line(line, get_line_length(line) * ratio)
+                    .map(|point| (point, Placement::Street))
+            }),
+        };
+        if let Some((point, placement)) = located {
+            ret.push(LocatedHousenumber {
+                street: street.to_string(),
+                housenumber: housenumber.to_string(),
+                point: point.round(),
+                placement,
+            });
+        }
+    }
+    ret
+}
+
+/// Interpolates between two points, following the street if both points are close to the same
+/// line.
+fn interpolate_along_lines(lines: &[Vec<Point>], from: &Point, to: &Point, ratio: f64) -> Point {
+    let closest_line = |point: &Point| {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| (index, project_to_line(line, point)))
+            .min_by(|a, b| {
+                a.1 .1
+                    .partial_cmp(&b.1 .1)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    };
+    if let (Some((from_line, (from_offset, _))), Some((to_line, (to_offset, _)))) =
+        (closest_line(from), closest_line(to))
+    {
+        if from_line == to_line && from_offset != to_offset {
+            let offset = from_offset + (to_offset - from_offset) * ratio;
+            if let Some(point) = get_point_on_line(&lines[from_line], offset) {
+                return point;
+            }
+        }
+    }
+    from.lerp(to, ratio)
+}
+
+/// Parses the coordinates of a GeoJSON MultiLineString geometry into lines.
+fn geojson_to_lines(geometry: &str) -> anyhow::Result<Vec<Vec<Point>>> {
+    let geometry: serde_json::Value = serde_json::from_str(geometry)?;
+    let coordinates: Vec<Vec<[f64; 2]>> = serde_json::from_value(geometry["coordinates"].clone())?;
+    Ok(coordinates
+        .iter()
+        .map(|line| line.iter().map(|i| Point::new(i[1], i[0])).collect())
+        .collect())
+}
+
+/// Parses a stored lat/lon pair, None if the location is not known.
+pub fn parse_point(lat: &str, lon: &str) -> Option<Point> {
+    match (lat.parse::<f64>(), lon.parse::<f64>()) {
+        (Ok(lat), Ok(lon)) => Some(Point::new(lat, lon)),
+        _ => None,
+    }
+}
+
+/// Gets the lines of a street of a relation: the stored street geometry if the street query
+/// returned one, otherwise the stored point of the street, as a line with a single point.
+fn get_street_lines(
+    ctx: &context::Context,
+    relation_name: &str,
+    street_name: &str,
+) -> anyhow::Result<Vec<Vec<Point>>> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(
+        "select osm_streets.lat, osm_streets.lon, osm_geometries.geometry from osm_streets
+         left join osm_geometries on osm_geometries.relation = osm_streets.relation
+             and osm_geometries.kind = 'street'
+             and osm_geometries.osm_type = osm_streets.osm_type
+             and osm_geometries.osm_id = osm_streets.osm_id
+         where osm_streets.relation = ?1 and osm_streets.name = ?2",
+    )?;
+    let mut rows = stmt.query([relation_name, street_name])?;
+    let mut ret: Vec<Vec<Point>> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let geometry: Option<String> = row.get(2)?;
+        if let Some(geometry) = geometry {
+            ret.extend(geojson_to_lines(&geometry)?);
+            continue;
+        }
+        let lat: String = row.get(0)?;
+        let lon: String = row.get(1)?;
+        if let Some(point) = parse_point(&lat, &lon) {
+            ret.push(vec![point]);
+        }
+    }
+    Ok(ret)
+}
+
+/// Gets the existing house numbers of a street of a relation (only their numeric part) with their
+/// stored points.
+fn get_known_housenumbers(
+    ctx: &context::Context,
+    relation_name: &str,
+    street_name: &str,
+) -> anyhow::Result<Vec<(i32, Point)>> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(
+        "select housenumber, lat, lon from osm_housenumbers where relation = ?1 and street = ?2",
+    )?;
+    let mut rows = stmt.query([relation_name, street_name])?;
+    let mut ret: Vec<(i32, Point)> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let housenumber: String = row.get(0)?;
+        let lat: String = row.get(1)?;
+        let lon: String = row.get(2)?;
+        let point = match parse_point(&lat, &lon) {
+            Some(value) => value,
+            None => continue,
+        };
+        // E.g. '1;3' or '1-3'.
+        for part in housenumber.split([';', ',', '-']) {
+            let (number, _suffix) = util::split_house_number(part.trim());
+            if number > 0 {
+                ret.push((number, point));
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Places the missing house numbers of a relation along their streets, using the locations of the
+/// streets and the existing house numbers, as stored by cron.
+pub fn locate_missing_housenumbers(
+    relation: &mut areas::Relation<'_>,
+) -> anyhow::Result<Vec<LocatedHousenumber>> {
+    let ctx = relation.get_ctx();
+    let json = cache::get_missing_housenumbers_json(relation)
+        .context("get_missing_housenumbers_json() failed")?;
+    let missing_housenumbers: areas::MissingHousenumbers = serde_json::from_str(&json)?;
+
+    let relation_name = relation.get_name();
+    let mut ret = Vec::new();
+    for ongoing_street in &missing_housenumbers.ongoing_streets {
+        let street_name = ongoing_street.street.get_osm_name();
+        let lines = get_street_lines(ctx, &relation_name, street_name)?;
+        let known = get_known_housenumbers(ctx, &relation_name, street_name)?;
+        let mut numbers: Vec<String> = ongoing_street
+            .house_numbers
+            .iter()
+            .map(|i| i.get_number().to_string())
+            .collect();
+        numbers.sort_by_key(|i| util::split_house_number(i));
+        numbers.dedup();
+        ret.extend(locate_street_housenumbers(
+            street_name,
+            &lines,
+            &known,
+            &numbers,
+        ));
+    }
+    Ok(ret)
+}
+
+/// Converts lines to a GeoJSON geometry.
+pub fn lines_to_geojson(lines: &[Vec<Point>]) -> serde_json::Value {
+    let coordinates: Vec<Vec<[f64; 2]>> = lines
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|point| {
+                    let point = point.round();
+                    [point.lon, point.lat]
+                })
+                .collect()
+        })
+        .collect();
+    serde_json::json!({
+        "type": "MultiLineString",
+        "coordinates": coordinates,
+    })
+}
+
+/// Gets the path of the cached boundary polygons of all relations.
+pub fn get_boundaries_path(ctx: &context::Context) -> String {
+    format!("{}/boundaries.geojson", ctx.get_ini().get_workdir())
+}
+
+/// Joins lines which share an endpoint into closed rings, e.g. the member ways of a boundary
+/// relation. A chain which can't be closed is closed with a straight segment.
+fn lines_to_rings(lines: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    let mut pending: Vec<Vec<Point>> = lines.iter().filter(|i| i.len() >= 2).cloned().collect();
+    let mut rings: Vec<Vec<Point>> = Vec::new();
+    while let Some(mut ring) = pending.pop() {
+        loop {
+            let first = ring[0];
+            let last = ring[ring.len() - 1];
+            if ring.len() > 2 && first == last {
+                break;
+            }
+            let index = pending
+                .iter()
+                .position(|line| line[0] == last || line[line.len() - 1] == last);
+            match index {
+                Some(index) => {
+                    let mut line = pending.remove(index);
+                    if line[0] != last {
+                        line.reverse();
+                    }
+                    ring.extend(line.into_iter().skip(1));
+                }
+                None => {
+                    ring.push(first);
+                    break;
+                }
+            }
+        }
+        if ring.len() >= 4 {
+            rings.push(ring);
+        }
+    }
+    rings
+}
+
+/// Decides if a point is inside a closed ring, using ray casting.
+fn ring_contains(ring: &[Point], point: &Point) -> bool {
+    let mut inside = false;
+    for pair in ring.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if (from.lat > point.lat) != (to.lat > point.lat) {
+            let lon = from.lon + (point.lat - from.lat) / (to.lat - from.lat) * (to.lon - from.lon);
+            if point.lon < lon {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Simplifies a ring for an overview map: rounds to about 10 meters and drops repeated points.
+fn simplify_ring(ring: &[Point]) -> Vec<[f64; 2]> {
+    let round = |value: f64| (value * 1e4).round() / 1e4;
+    let mut ret: Vec<[f64; 2]> = Vec::new();
+    for point in ring {
+        let coordinate = [round(point.lon), round(point.lat)];
+        if ret.last() != Some(&coordinate) {
+            ret.push(coordinate);
+        }
+    }
+    ret
+}
+
+/// Turns rings into the coordinates of a GeoJSON MultiPolygon: a ring inside an odd number of
+/// other rings is a hole of the outer ring containing it, e.g. an enclave.
+fn rings_to_polygons(rings: &[Vec<Point>]) -> Vec<Vec<Vec<[f64; 2]>>> {
+    let containers: Vec<Vec<usize>> = rings
+        .iter()
+        .enumerate()
+        .map(|(index, ring)| {
+            (0..rings.len())
+                .filter(|other| *other != index && ring_contains(&rings[*other], &ring[0]))
+                .collect()
+        })
+        .collect();
+    let mut polygons: Vec<Vec<Vec<[f64; 2]>>> = Vec::new();
+    let mut outer_indexes: HashMap<usize, usize> = HashMap::new();
+    for (index, ring) in rings.iter().enumerate() {
+        if !containers[index].len().is_multiple_of(2) {
+            continue;
+        }
+        let ring = simplify_ring(ring);
+        if ring.len() < 4 {
+            continue;
+        }
+        outer_indexes.insert(index, polygons.len());
+        polygons.push(vec![ring]);
+    }
+    for (index, ring) in rings.iter().enumerate() {
+        if containers[index].len().is_multiple_of(2) {
+            continue;
+        }
+        let ring = simplify_ring(ring);
+        if ring.len() < 4 {
+            continue;
+        }
+        let outer = containers[index]
+            .iter()
+            .find_map(|container| outer_indexes.get(container));
+        if let Some(outer) = outer {
+            polygons[*outer].push(ring);
+        }
+    }
+    polygons
+}
+
+/// Writes the boundary polygons of all relations to a GeoJSON file, for the coverage map. The
+/// boundary lines come from the osm_geometries SQL table.
+pub fn write_boundaries(ctx: &context::Context) -> anyhow::Result<()> {
+    let mut features: Vec<serde_json::Value> = Vec::new();
+    {
+        let conn = ctx.get_database_connection()?;
+        let mut stmt = conn.prepare(
+            "select relation, geometry from osm_geometries where kind = 'boundary' order by relation",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let relation_name: String = row.get(0)?;
+            let geometry: String = row.get(1)?;
+            let lines = geojson_to_lines(&geometry)?;
+            let polygons = rings_to_polygons(&lines_to_rings(&lines));
+            if polygons.is_empty() {
+                continue;
+            }
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": polygons,
+                },
+                "properties": {
+                    "name": relation_name,
+                },
+            }));
+        }
+    }
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    ctx.get_file_system().write_from_string(
+        &serde_json::to_string(&collection)?,
+        &get_boundaries_path(ctx),
+    )
+}
+
+#[cfg(test)]
+mod tests;
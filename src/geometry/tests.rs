@@ -0,0 +1,188 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the geometry module.
+
+use super::*;
+
+/// Asserts that two points are the same, ignoring rounding errors.
+fn assert_point_eq(actual: &Point, expected: &Point) {
+    assert!(
+        (actual.lat - expected.lat).abs() < 1e-9 && (actual.lon - expected.lon).abs() < 1e-9,
+        "{actual:?} != {expected:?}"
+    );
+}
+
+/// Tests locate_street_housenumbers().
+#[test]
+fn test_locate_street_housenumbers() {
+    let lines = vec![vec![Point::new(47.0, 19.0), Point::new(47.0, 19.01)]];
+    let known = vec![(1, Point::new(47.0, 19.0)), (5, Point::new(47.0, 19.004))];
+    let missing: Vec<String> = vec!["2".into(), "3".into(), "7".into()];
+
+    let located = locate_street_housenumbers("mystreet", &lines, &known, &missing);
+
+    assert_eq!(located.len(), 3);
+    // No even house numbers: use the odd ones.
+    assert_eq!(located[0].housenumber, "2");
+    assert_eq!(located[0].placement, Placement::Interpolated);
+    assert_point_eq(&located[0].point, &Point::new(47.0, 19.001));
+    assert_eq!(located[1].housenumber, "3");
+    assert_eq!(located[1].placement, Placement::Interpolated);
+    assert_point_eq(&located[1].point, &Point::new(47.0, 19.002));
+    // Only a lower house number is known.
+    assert_eq!(located[2].housenumber, "7");
+    assert_eq!(located[2].placement, Placement::Nearest);
+    assert_point_eq(&located[2].point, &Point::new(47.0, 19.004));
+}
+
+/// Tests locate_street_housenumbers(), the case when the addresses are not on the street line.
+#[test]
+fn test_locate_street_housenumbers_along_street() {
+    // An L-shaped street, the house numbers are next to the two ends of the street.
+    let lines = vec![vec![
+        Point::new(47.0, 19.0),
+        Point::new(47.0, 19.01),
+        Point::new(47.01, 19.01),
+    ]];
+    let known = vec![
+        (1, Point::new(47.0001, 19.0)),
+        (9, Point::new(47.01, 19.0101)),
+    ];
+    let missing: Vec<String> = vec!["5".into()];
+
+    let located = locate_street_housenumbers("mystreet", &lines, &known, &missing);
+
+    assert_eq!(located.len(), 1);
+    assert_eq!(located[0].placement, Placement::Interpolated);
+    // The result is on the street, not on the straight line between the two house numbers.
+    let (_offset, distance) = project_to_line(&lines[0], &located[0].point);
+    assert!(distance < 1e-9);
+    let straight = known[0].1.lerp(&known[1].1, 0.5);
+    assert!(located[0].point.distance(&straight) > 0.001);
+}
+
+/// Tests locate_street_housenumbers(), the case when the street has no house numbers.
+#[test]
+fn test_locate_street_housenumbers_no_known() {
+    let lines = vec![
+        vec![Point::new(47.0, 19.0), Point::new(47.0, 19.001)],
+        vec![Point::new(47.0, 19.0), Point::new(47.0, 19.01)],
+    ];
+    let missing: Vec<String> = vec!["1".into(), "2".into()];
+
+    let located = locate_street_housenumbers("mystreet", &lines, &[], &missing);
+
+    // Spread along the longest line.
+    assert_eq!(located.len(), 2);
+    assert_eq!(located[0].placement, Placement::Street);
+    assert_point_eq(&located[0].point, &Point::new(47.0, 19.0025));
+    assert_point_eq(&located[1].point, &Point::new(47.0, 19.0075));
+
+    // No geometry at all.
+    let located = locate_street_housenumbers("mystreet", &[], &[], &missing);
+    assert!(located.is_empty());
+}
+
+/// Tests get_street_lines() and get_known_housenumbers(): stored locations are used, the map
+/// geometry of a street is preferred over its point.
+#[test]
+fn test_get_street_lines() {
+    let ctx = context::tests::make_test_context().unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            r#"insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '10', 'Tűzkő utca', '', '', '', '', 'way', '47', '19.005');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '13', 'Tűzkő utca', '', '', '', '', 'way', '47.1', '19.1');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '14', 'Tűzkő utca', '', '', '', '', 'way');
+             insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('gazdagret', 'street', 'way', '10', '{"type":"MultiLineString","coordinates":[[[19.0,47.0],[19.01,47.0]]]}');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '11', 'Tűzkő utca', '1;3', '', '', '', '', '', '', '', '', '', 'node', '47', '19');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '12', 'Tűzkő utca', '5', '', '', '', '', '', '', '', '', '', 'way');"#,
+        )
+        .unwrap();
+    }
+
+    let mut lines = get_street_lines(&ctx, "gazdagret", "Tűzkő utca").unwrap();
+    let known = get_known_housenumbers(&ctx, "gazdagret", "Tűzkő utca").unwrap();
+
+    // Way 14 has no known location.
+    lines.sort_by_key(|line| line.len());
+    assert_eq!(
+        lines,
+        vec![
+            vec![Point::new(47.1, 19.1)],
+            vec![Point::new(47.0, 19.0), Point::new(47.0, 19.01)]
+        ]
+    );
+    // Way 12 has no known location.
+    assert_eq!(
+        known,
+        vec![(1, Point::new(47.0, 19.0)), (3, Point::new(47.0, 19.0))]
+    );
+}
+
+/// Tests write_boundaries().
+#[test]
+fn test_write_boundaries() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let boundaries = context::tests::TestFileSystem::make_file();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("workdir/boundaries.geojson", &boundaries)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        // The outer ring of gazdagret is split into 2 ways, one of them in the reverse direction,
+        // and it has a hole. ujbuda has a single line, which is not closed.
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('gazdagret', 'boundary', 'relation', '42', '{\"type\":\"MultiLineString\",\"coordinates\":[[[19.0,47.0],[19.1,47.0],[19.1,47.1]],[[19.0,47.0],[19.0,47.1],[19.1,47.1]],[[19.04,47.04],[19.06,47.04],[19.06,47.06],[19.04,47.04]]]}');
+             insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('gazdagret', 'street', 'way', '10', '{\"type\":\"MultiLineString\",\"coordinates\":[[[19.0,47.0],[19.01,47.0]]]}');
+             insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('ujbuda', 'boundary', 'relation', '43', '{\"type\":\"MultiLineString\",\"coordinates\":[[[19.0,47.0],[19.1,47.0],[19.1,47.1]]]}');
+             insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('empty', 'boundary', 'relation', '44', '{\"type\":\"MultiLineString\",\"coordinates\":[[[19.0,47.0],[19.0,47.0]]]}');",
+        )
+        .unwrap();
+    }
+
+    write_boundaries(&ctx).unwrap();
+
+    let path = get_boundaries_path(&ctx);
+    let root: serde_json::Value =
+        serde_json::from_str(&ctx.get_file_system().read_to_string(&path).unwrap()).unwrap();
+    let features = root["features"].as_array().unwrap();
+    // empty has no area, so it's left out.
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0]["properties"]["name"], "gazdagret");
+    assert_eq!(features[0]["geometry"]["type"], "MultiPolygon");
+    assert_eq!(
+        features[0]["geometry"]["coordinates"],
+        serde_json::json!([[
+            [
+                [19.0, 47.0],
+                [19.0, 47.1],
+                [19.1, 47.1],
+                [19.1, 47.0],
+                [19.0, 47.0]
+            ],
+            [
+                [19.04, 47.04],
+                [19.06, 47.04],
+                [19.06, 47.06],
+                [19.04, 47.04]
+            ]
+        ]])
+    );
+    assert_eq!(features[1]["properties"]["name"], "ujbuda");
+    assert_eq!(
+        features[1]["geometry"]["coordinates"],
+        serde_json::json!([[[[19.0, 47.0], [19.1, 47.0], [19.1, 47.1], [19.0, 47.0]]]])
+    );
+}
@@ -0,0 +1,203 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The housenumber_grammar module contains the country-specific rules to interpret house number
+//! strings: separators, range expansion and the normalized form of suffixes.
+
+use crate::util;
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref DE_NUMBER_PER_LETTER: regex::Regex =
+        regex::Regex::new(r"^([0-9]+)( |/)?([A-Za-z])$").unwrap();
+    static ref DE_NUMBER_PER_NUMBER: regex::Regex =
+        regex::Regex::new(r"^([0-9]+)/([0-9]+)$").unwrap();
+    static ref US_NUMBER_PER_LETTER: regex::Regex =
+        regex::Regex::new(r"^([0-9]+) ?([A-Za-z])$").unwrap();
+    static ref US_HYPHENATED: regex::Regex = regex::Regex::new(r"^([0-9]+) ?- ?([0-9]+)$").unwrap();
+    static ref US_FRACTIONAL: regex::Regex =
+        regex::Regex::new(r"^([0-9]+) +([0-9]+/[0-9]+)$").unwrap();
+}
+
+/// Separates house numbers in a single house number string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Separator {
+    /// The separator itself.
+    pub value: &'static str,
+    /// If the separated numbers are the two ends of a range.
+    pub is_range: bool,
+}
+
+impl Separator {
+    /// Creates a separator of a list, like '1;3'.
+    fn list(value: &'static str) -> Self {
+        Separator {
+            value,
+            is_range: false,
+        }
+    }
+
+    /// Creates a separator of a range, like '1-5'.
+    fn range(value: &'static str) -> Self {
+        Separator {
+            value,
+            is_range: true,
+        }
+    }
+}
+
+/// Rules to interpret house number strings.
+pub trait HouseNumberGrammar {
+    /// Determines how the numbers are separated in a house number string.
+    fn get_separator(&self, house_numbers: &str) -> Separator;
+
+    /// Expands the two ends of a range, returns None if the range should not be expanded.
+    fn expand_range(&self, numbers: &[i64], street_is_even_odd: bool) -> Option<Vec<i64>>;
+
+    /// Determines if the input is a single house number with a suffix that is relevant when
+    /// house number letters are checked.
+    fn has_letter_suffix(&self, house_number: &str) -> bool;
+
+    /// Determines if the input is a single house number with a suffix that is always relevant,
+    /// regardless of checking house number letters.
+    fn is_compound(&self, _house_number: &str) -> bool {
+        false
+    }
+
+    /// Turns a house number with a suffix into its normalized form, which is used for comparison.
+    fn normalize_suffix(&self, house_number: &str) -> anyhow::Result<String>;
+}
+
+/// Hungarian house numbers: '42/A', '2-6' is 2, 4 and 6 on a street with even and odd sides.
+pub struct HungarianGrammar {}
+
+impl HouseNumberGrammar for HungarianGrammar {
+    fn get_separator(&self, house_numbers: &str) -> Separator {
+        if house_numbers.contains(';') {
+            Separator::list(";")
+        } else if house_numbers.contains(',') {
+            Separator::list(",")
+        } else {
+            Separator::range("-")
+        }
+    }
+
+    fn expand_range(&self, numbers: &[i64], street_is_even_odd: bool) -> Option<Vec<i64>> {
+        let (should_expand, stop) = util::should_expand_range(numbers, street_is_even_odd);
+        if !should_expand {
+            return None;
+        }
+
+        let start = numbers[0];
+        if stop == 0 {
+            Some(vec![start])
+        } else if street_is_even_odd {
+            // Assume that e.g. 2-6 actually means 2, 4 and 6, not only 2 and 4.
+            // Closed interval, even only or odd only case.
+            Some((start..stop + 2).step_by(2).collect())
+        } else {
+            // Closed interval, but mixed even and odd.
+            Some((start..stop + 1).collect())
+        }
+    }
+
+    fn has_letter_suffix(&self, house_number: &str) -> bool {
+        util::HouseNumber::has_letter_suffix(house_number, "")
+    }
+
+    fn normalize_suffix(&self, house_number: &str) -> anyhow::Result<String> {
+        util::HouseNumber::normalize_letter_suffix(house_number, "")
+    }
+}
+
+/// German house numbers: '12a', '12-14' is a single building with both numbers.
+pub struct GermanGrammar {}
+
+impl HouseNumberGrammar for GermanGrammar {
+    fn get_separator(&self, house_numbers: &str) -> Separator {
+        if house_numbers.contains(';') {
+            Separator::list(";")
+        } else if house_numbers.contains(',') {
+            Separator::list(",")
+        } else {
+            Separator::list("-")
+        }
+    }
+
+    fn expand_range(&self, _numbers: &[i64], _street_is_even_odd: bool) -> Option<Vec<i64>> {
+        None
+    }
+
+    fn has_letter_suffix(&self, house_number: &str) -> bool {
+        DE_NUMBER_PER_LETTER.is_match(house_number) || DE_NUMBER_PER_NUMBER.is_match(house_number)
+    }
+
+    fn normalize_suffix(&self, house_number: &str) -> anyhow::Result<String> {
+        if let Some(cap) = DE_NUMBER_PER_LETTER.captures(house_number) {
+            return Ok(format!("{}{}", &cap[1], cap[3].to_lowercase()));
+        }
+        if let Some(cap) = DE_NUMBER_PER_NUMBER.captures(house_number) {
+            return Ok(format!("{}/{}", &cap[1], &cap[2]));
+        }
+        Err(anyhow!("ValueError"))
+    }
+}
+
+/// US house numbers: '12B', '37-12' (hyphenated) and '123 1/2' (fractional) are single numbers.
+pub struct UsGrammar {}
+
+impl HouseNumberGrammar for UsGrammar {
+    fn get_separator(&self, house_numbers: &str) -> Separator {
+        if house_numbers.contains(',') {
+            Separator::list(",")
+        } else {
+            Separator::list(";")
+        }
+    }
+
+    fn expand_range(&self, _numbers: &[i64], _street_is_even_odd: bool) -> Option<Vec<i64>> {
+        None
+    }
+
+    fn has_letter_suffix(&self, house_number: &str) -> bool {
+        US_NUMBER_PER_LETTER.is_match(house_number) || self.is_compound(house_number)
+    }
+
+    fn is_compound(&self, house_number: &str) -> bool {
+        US_HYPHENATED.is_match(house_number) || US_FRACTIONAL.is_match(house_number)
+    }
+
+    fn normalize_suffix(&self, house_number: &str) -> anyhow::Result<String> {
+        if let Some(cap) = US_NUMBER_PER_LETTER.captures(house_number) {
+            return Ok(format!("{}{}", &cap[1], cap[2].to_uppercase()));
+        }
+        if let Some(cap) = US_HYPHENATED.captures(house_number) {
+            return Ok(format!("{}-{}", &cap[1], &cap[2]));
+        }
+        if let Some(cap) = US_FRACTIONAL.captures(house_number) {
+            return Ok(format!("{} {}", &cap[1], &cap[2]));
+        }
+        Err(anyhow!("ValueError"))
+    }
+}
+
+/// Gets a grammar by name: hu, de or us.
+pub fn get_grammar(name: &str) -> anyhow::Result<Box<dyn HouseNumberGrammar>> {
+    match name {
+        "hu" => Ok(Box::new(HungarianGrammar {})),
+        "de" => Ok(Box::new(GermanGrammar {})),
+        "us" => Ok(Box::new(UsGrammar {})),
+        _ => Err(anyhow!("unknown house number grammar: '{name}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,104 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the housenumber_grammar module.
+
+use super::*;
+
+/// Tests get_separator().
+#[test]
+fn test_get_separator() {
+    let cases = [
+        ("hu", "1;3", Separator::list(";")),
+        ("hu", "1,3", Separator::list(",")),
+        ("hu", "2-6", Separator::range("-")),
+        ("de", "1;3", Separator::list(";")),
+        ("de", "12-14", Separator::list("-")),
+        ("us", "1,3", Separator::list(",")),
+        ("us", "37-12", Separator::list(";")),
+    ];
+    for (name, house_numbers, expected) in cases {
+        let grammar = get_grammar(name).unwrap();
+        assert_eq!(
+            grammar.get_separator(house_numbers),
+            expected,
+            "{name}: {house_numbers}"
+        );
+    }
+}
+
+/// Tests expand_range().
+#[test]
+fn test_expand_range() {
+    let cases: [(&str, [i64; 2], bool, Option<Vec<i64>>); 7] = [
+        // Even-odd street: only one side.
+        ("hu", [2, 6], true, Some(vec![2, 4, 6])),
+        ("hu", [2, 6], false, Some(vec![2, 3, 4, 5, 6])),
+        // 42-1: -1 is just a suffix.
+        ("hu", [42, 1], true, Some(vec![42])),
+        // Parity mismatch.
+        ("hu", [2, 5], true, None),
+        // Too large.
+        ("hu", [2, 100], true, None),
+        ("de", [12, 14], true, None),
+        ("us", [37, 12], true, None),
+    ];
+    for (name, numbers, street_is_even_odd, expected) in cases {
+        let grammar = get_grammar(name).unwrap();
+        assert_eq!(
+            grammar.expand_range(&numbers, street_is_even_odd),
+            expected,
+            "{name}: {numbers:?}"
+        );
+    }
+}
+
+/// Tests has_letter_suffix(), is_compound() and normalize_suffix().
+#[test]
+fn test_normalize_suffix() {
+    // Grammar, input, normalized form (if it has a suffix), is compound.
+    let cases = [
+        ("hu", "42a", Some("42/A"), false),
+        ("hu", "42 A", Some("42/A"), false),
+        ("hu", "42/1", Some("42/1"), false),
+        ("hu", "42", None, false),
+        ("de", "12a", Some("12a"), false),
+        ("de", "12 A", Some("12a"), false),
+        ("de", "12/1", Some("12/1"), false),
+        ("de", "12-14", None, false),
+        ("us", "12B", Some("12B"), false),
+        ("us", "12 b", Some("12B"), false),
+        ("us", "37-12", Some("37-12"), true),
+        ("us", "37 - 12", Some("37-12"), true),
+        ("us", "123 1/2", Some("123 1/2"), true),
+        ("us", "123", None, false),
+    ];
+    for (name, house_number, expected, compound) in cases {
+        let grammar = get_grammar(name).unwrap();
+        assert_eq!(
+            grammar.has_letter_suffix(house_number),
+            expected.is_some(),
+            "{name}: {house_number}"
+        );
+        assert_eq!(
+            grammar.is_compound(house_number),
+            compound,
+            "{name}: {house_number}"
+        );
+        let actual = grammar.normalize_suffix(house_number).ok();
+        assert_eq!(actual.as_deref(), expected, "{name}: {house_number}");
+    }
+}
+
+/// Tests get_grammar(), the unknown grammar case.
+#[test]
+fn test_get_grammar_unknown() {
+    assert!(get_grammar("fr").is_err());
+}
@@ -0,0 +1,763 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The import_osm module fills the OSM streets and house numbers of relations from a local
+//! planet or country extract, as an alternative to Overpass queries.
+
+use crate::area_files;
+use crate::areas;
+use crate::context;
+use crate::osm_extract;
+use anyhow::Context as _;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Write;
+use std::rc::Rc;
+
+#[cfg(not(test))]
+use log::info;
+
+#[cfg(test)]
+use std::println as info;
+
+lazy_static::lazy_static! {
+    static ref HIGHWAY_EXCLUDES: regex::Regex = regex::Regex::new("platform|bus_stop|footway|steps|raceway|service|pedestrian|proposed|abandoned|rest_area|path|corridor").unwrap();
+    static ref SERVICE_EXCLUDES: regex::Regex = regex::Regex::new("parking_aisle|drive-through|slipway").unwrap();
+    static ref FOOTWAYS: regex::Regex = regex::Regex::new("footway|steps|pedestrian").unwrap();
+    static ref PATH_NAMES: regex::Regex = regex::Regex::new("utca|út|köz|árok|lépcső|gát|part|tér|tere|sétány|park|dűlő|sor|kert").unwrap();
+    static ref PARKING_AISLE_NAMES: regex::Regex = regex::Regex::new("utca|köz|sétány|tér|tere").unwrap();
+    static ref PARKING_NAMES: regex::Regex = regex::Regex::new(" utca$| köz$| sétány$| tér$| tere$| udvar$").unwrap();
+}
+
+/// Tag filters, the Overpass way.
+struct Tags<'a> {
+    tags: &'a HashMap<String, String>,
+}
+
+impl<'a> Tags<'a> {
+    /// [key]
+    fn has(&self, key: &str) -> bool {
+        self.tags.contains_key(key)
+    }
+
+    /// [key=value]
+    fn is(&self, key: &str, value: &str) -> bool {
+        self.tags.get(key).map(|i| i.as_str()) == Some(value)
+    }
+
+    /// [key~regex]
+    fn matches(&self, key: &str, regex: &regex::Regex) -> bool {
+        match self.tags.get(key) {
+            Some(value) => regex.is_match(value),
+            None => false,
+        }
+    }
+
+    /// [key!~regex], also matches when the key is missing.
+    fn not_matches(&self, key: &str, regex: &regex::Regex) -> bool {
+        !self.matches(key, regex)
+    }
+}
+
+/// Decides if a way is a street. Keep this in sync with data/streets-template.overpassql.
+pub fn is_street_way(tags: &HashMap<String, String>) -> bool {
+    let tags = Tags { tags };
+    let named = tags.has("name");
+    let no_bridge = !tags.has("bridge");
+    // all but service, footway, path + avoid bridges and special highways
+    (tags.has("highway") && named && no_bridge && tags.not_matches("highway", &HIGHWAY_EXCLUDES))
+        // service roads with names, avoid parking ways, drive-through
+        || (tags.is("highway", "service")
+            && named
+            && no_bridge
+            && tags.not_matches("service", &SERVICE_EXCLUDES))
+        // avoid underpass and bridge footways, platforms
+        || (tags.matches("highway", &FOOTWAYS)
+            && named
+            && tags.tags.get("layer").map(|i| !i.contains('-')).unwrap_or(true)
+            && no_bridge
+            && !tags.is("indoor", "yes")
+            && !tags.is("public_transport", "platform"))
+        // path with valid street name, try to exclude hiking path with comments in name tag
+        || (tags.is("highway", "path")
+            && tags.matches("name", &PATH_NAMES)
+            && tags.tags.get("name").map(|i| !i.contains("garázssor")).unwrap_or(true))
+        // include parking aisle with valid street name
+        || (tags.is("highway", "service")
+            && tags.is("service", "parking_aisle")
+            && tags.matches("name", &PARKING_AISLE_NAMES))
+        // include amenity parking  with valid street name
+        || (tags.is("amenity", "parking")
+            && !tags.has("highway")
+            && tags.matches("name", &PARKING_NAMES)
+            && !tags.has("addr:street")
+            && tags.tags.get("name").map(|i| !i.contains("gazdasági udvar")).unwrap_or(true))
+        // include parks with names
+        || (tags.is("leisure", "park") && named)
+        // include squares
+        || (tags.is("place", "square")
+            && !tags.is("leisure", "park")
+            && !tags.is("highway", "pedestrian")
+            && named)
+}
+
+/// Decides if a relation is a street. Keep this in sync with data/streets-template.overpassql.
+pub fn is_street_relation(tags: &HashMap<String, String>) -> bool {
+    let tags = Tags { tags };
+    tags.has("name") && (tags.is("leisure", "park") || tags.is("leisure", "square"))
+}
+
+/// Decides if a member way of the boundary relation is a street.
+pub fn is_boundary_street(tags: &HashMap<String, String>) -> bool {
+    let tags = Tags { tags };
+    tags.has("highway") && tags.has("name") && !tags.has("bridge")
+}
+
+/// Decides if an object has an address. Keep this in sync with
+/// data/street-housenumbers-template.overpassql.
+pub fn is_housenumber(tags: &HashMap<String, String>) -> bool {
+    [
+        "addr:street",
+        "addr:housenumber",
+        "addr:postcode",
+        "addr:conscriptionnumber",
+    ]
+    .iter()
+    .any(|key| tags.contains_key(*key))
+}
+
+/// Boundary of a relation, as a set of segments.
+struct Polygon {
+    segments: Vec<((f64, f64), (f64, f64))>,
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Polygon {
+    fn new(segments: Vec<((f64, f64), (f64, f64))>) -> Self {
+        let mut min = (f64::MAX, f64::MAX);
+        let mut max = (f64::MIN, f64::MIN);
+        for (from, to) in &segments {
+            for (lat, lon) in [from, to] {
+                min = (min.0.min(*lat), min.1.min(*lon));
+                max = (max.0.max(*lat), max.1.max(*lon));
+            }
+        }
+        Polygon { segments, min, max }
+    }
+
+    /// Even-odd rule, so inner rings are handled without knowing the member roles.
+    fn contains(&self, lat: f64, lon: f64) -> bool {
+        if lat < self.min.0 || lat > self.max.0 || lon < self.min.1 || lon > self.max.1 {
+            return false;
+        }
+
+        let mut inside = false;
+        for ((lat1, lon1), (lat2, lon2)) in &self.segments {
+            if (*lat1 > lat) != (*lat2 > lat) {
+                let cross = lon1 + (lat - lat1) * (lon2 - lon1) / (lat2 - lat1);
+                if lon < cross {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+}
+
+/// Falls back to the modification time of the extract if it has no timestamp.
+fn get_timestamp(
+    ctx: &context::Context,
+    path: &str,
+    timestamp: Option<time::OffsetDateTime>,
+) -> anyhow::Result<time::OffsetDateTime> {
+    match timestamp {
+        Some(value) => Ok(value),
+        None => ctx.get_file_system().getmtime(path),
+    }
+}
+
+/// Converts objects to the Overpass JSON format.
+fn to_overpass_json(objects: &[&osm_extract::Object], extract: &Extract) -> anyhow::Result<String> {
+    let elements: Vec<serde_json::Value> = objects
+        .iter()
+        .map(|object| {
+            let mut element = serde_json::json!({
+                "type": object.object_type.to_string(),
+                "id": object.id,
+                "timestamp": object.timestamp,
+                "user": object.user,
+                "tags": object.tags,
+            });
+            match object.object_type {
+                osm_extract::ObjectType::Node => {
+                    element["lat"] = object.lat.into();
+                    element["lon"] = object.lon.into();
+                }
+                osm_extract::ObjectType::Way => {
+                    element["nodes"] = object.nodes.clone().into();
+                    element["geometry"] = serde_json::json!(extract.get_way_geometry(object));
+                }
+                osm_extract::ObjectType::Relation => {
+                    let members: Vec<serde_json::Value> = object
+                        .members
+                        .iter()
+                        .map(|member| {
+                            let mut value = serde_json::json!({
+                                "type": member.object_type.to_string(),
+                                "ref": member.id,
+                                "role": member.role,
+                            });
+                            if let Some(geometry) = extract.get_member_geometry(member) {
+                                value["geometry"] = serde_json::json!(geometry);
+                            }
+                            value
+                        })
+                        .collect();
+                    element["members"] = members.into();
+                }
+            }
+            if let Some(bounds) = extract.get_bounds(object) {
+                element["bounds"] = serde_json::json!({
+                    "minlat": bounds.minlat,
+                    "minlon": bounds.minlon,
+                    "maxlat": bounds.maxlat,
+                    "maxlon": bounds.maxlon,
+                });
+            }
+            element
+        })
+        .collect();
+    let timestamp = extract
+        .timestamp
+        .format(&time::format_description::well_known::Rfc3339)?;
+    let result = serde_json::json!({
+        "version": 0.6,
+        "generator": "osm-gimmisn",
+        "osm3s": {
+            "timestamp_osm_base": timestamp,
+            "timestamp_areas_base": timestamp,
+        },
+        "elements": elements,
+    });
+    Ok(serde_json::to_string(&result)?)
+}
+
+/// Objects of an extract, which are relevant for the import.
+struct Extract {
+    /// Unix timestamp of the extract.
+    timestamp: time::OffsetDateTime,
+    /// Relation id -> boundary relation.
+    boundaries: HashMap<u64, osm_extract::Object>,
+    /// Street and address relations.
+    relations: Vec<osm_extract::Object>,
+    /// Way id -> boundary, street, address or relation member way.
+    ways: HashMap<u64, osm_extract::Object>,
+    /// Address nodes.
+    nodes: Vec<osm_extract::Object>,
+    /// Node id -> (lat, lon), for the nodes of stored ways.
+    coords: HashMap<u64, (f64, f64)>,
+    /// House numbers of the whole country.
+    whole_country: Vec<osm_extract::Object>,
+}
+
+impl Extract {
+    /// Reads the extract in 3 passes: relations, ways, then nodes, so only the interesting subset
+    /// is kept in memory.
+    fn new(
+        ctx: &context::Context,
+        path: &str,
+        osmrelations: &HashSet<u64>,
+        whole_country: bool,
+    ) -> anyhow::Result<Self> {
+        let mut boundaries: HashMap<u64, osm_extract::Object> = HashMap::new();
+        let mut relations: Vec<osm_extract::Object> = Vec::new();
+        let mut whole_country_objects: Vec<osm_extract::Object> = Vec::new();
+        let mut wanted_ways: HashSet<u64> = HashSet::new();
+        info!("import_osm: reading relations from {path}");
+        let timestamp = osm_extract::read_extract(ctx, path, &mut |object| {
+            if object.object_type != osm_extract::ObjectType::Relation {
+                return Ok(());
+            }
+            if whole_country && object.tags.contains_key("addr:housenumber") {
+                whole_country_objects.push(object.clone());
+            }
+            let boundary = osmrelations.contains(&object.id);
+            let street = is_street_relation(&object.tags) || is_housenumber(&object.tags);
+            if boundary || street {
+                for member in &object.members {
+                    if member.object_type == osm_extract::ObjectType::Way {
+                        wanted_ways.insert(member.id);
+                    }
+                }
+            }
+            if street {
+                relations.push(object.clone());
+            }
+            if boundary {
+                boundaries.insert(object.id, object);
+            }
+            Ok(())
+        })?;
+        let timestamp = get_timestamp(ctx, path, timestamp)?;
+
+        info!("import_osm: reading ways from {path}");
+        let mut ways: HashMap<u64, osm_extract::Object> = HashMap::new();
+        osm_extract::read_extract(ctx, path, &mut |object| {
+            if object.object_type != osm_extract::ObjectType::Way {
+                return Ok(());
+            }
+            if whole_country && object.tags.contains_key("addr:housenumber") {
+                whole_country_objects.push(object.clone());
+            }
+            if !osmrelations.is_empty()
+                && (wanted_ways.contains(&object.id)
+                    || is_street_way(&object.tags)
+                    || is_housenumber(&object.tags))
+            {
+                ways.insert(object.id, object);
+            }
+            Ok(())
+        })?;
+
+        info!("import_osm: reading nodes from {path}");
+        let wanted_nodes: HashSet<u64> = ways
+            .values()
+            .flat_map(|way| way.nodes.iter().cloned())
+            .collect();
+        let mut nodes: Vec<osm_extract::Object> = Vec::new();
+        let mut coords: HashMap<u64, (f64, f64)> = HashMap::new();
+        osm_extract::read_extract(ctx, path, &mut |object| {
+            if object.object_type != osm_extract::ObjectType::Node {
+                return Ok(());
+            }
+            if wanted_nodes.contains(&object.id) {
+                coords.insert(object.id, (object.lat, object.lon));
+            }
+            if whole_country && object.tags.contains_key("addr:housenumber") {
+                whole_country_objects.push(object.clone());
+            }
+            if !osmrelations.is_empty() && is_housenumber(&object.tags) {
+                nodes.push(object);
+            }
+            Ok(())
+        })?;
+
+        Ok(Extract {
+            timestamp,
+            boundaries,
+            relations,
+            ways,
+            nodes,
+            coords,
+            whole_country: whole_country_objects,
+        })
+    }
+
+    /// Builds the boundary polygon of a relation, None if the relation is not in the extract.
+    fn get_polygon(&self, osmrelation: u64) -> Option<Polygon> {
+        let boundary = self.boundaries.get(&osmrelation)?;
+        let mut segments = Vec::new();
+        for member in &boundary.members {
+            if member.object_type != osm_extract::ObjectType::Way {
+                continue;
+            }
+            let way = match self.ways.get(&member.id) {
+                Some(value) => value,
+                None => continue,
+            };
+            let coords: Vec<(f64, f64)> = way
+                .nodes
+                .iter()
+                .filter_map(|node| self.coords.get(node).cloned())
+                .collect();
+            for pair in coords.windows(2) {
+                segments.push((pair[0], pair[1]));
+            }
+        }
+        Some(Polygon::new(segments))
+    }
+
+    /// Decides if a way has a node inside the polygon, like Overpass does for (area).
+    fn is_way_inside(&self, way: &osm_extract::Object, polygon: &Polygon) -> bool {
+        way.nodes.iter().any(|node| match self.coords.get(node) {
+            Some((lat, lon)) => polygon.contains(*lat, *lon),
+            None => false,
+        })
+    }
+
+    /// Decides if a relation has a member way inside the polygon.
+    fn is_relation_inside(&self, relation: &osm_extract::Object, polygon: &Polygon) -> bool {
+        relation.members.iter().any(|member| {
+            member.object_type == osm_extract::ObjectType::Way
+                && match self.ways.get(&member.id) {
+                    Some(way) => self.is_way_inside(way, polygon),
+                    None => false,
+                }
+        })
+    }
+
+    /// Gets the bounding box of a way or relation, like Overpass does for `out geom;`.
+    fn get_bounds(&self, object: &osm_extract::Object) -> Option<area_files::OverpassBounds> {
+        let nodes: Vec<u64> = match object.object_type {
+            osm_extract::ObjectType::Node => return None,
+            osm_extract::ObjectType::Way => object.nodes.clone(),
+            osm_extract::ObjectType::Relation => object
+                .members
+                .iter()
+                .filter(|member| member.object_type == osm_extract::ObjectType::Way)
+                .filter_map(|member| self.ways.get(&member.id))
+                .flat_map(|way| way.nodes.iter().cloned())
+                .collect(),
+        };
+        let mut bounds: Option<area_files::OverpassBounds> = None;
+        for (lat, lon) in nodes.iter().filter_map(|node| self.coords.get(node)) {
+            bounds = Some(match bounds {
+                Some(bounds) => area_files::OverpassBounds {
+                    minlat: bounds.minlat.min(*lat),
+                    minlon: bounds.minlon.min(*lon),
+                    maxlat: bounds.maxlat.max(*lat),
+                    maxlon: bounds.maxlon.max(*lon),
+                },
+                None => area_files::OverpassBounds {
+                    minlat: *lat,
+                    minlon: *lon,
+                    maxlat: *lat,
+                    maxlon: *lon,
+                },
+            });
+        }
+        bounds
+    }
+
+    /// Gets the nodes of a way, like Overpass does for `out geom;`.
+    fn get_way_geometry(&self, way: &osm_extract::Object) -> Vec<area_files::OverpassCenter> {
+        way.nodes
+            .iter()
+            .filter_map(|node| self.coords.get(node))
+            .map(|(lat, lon)| area_files::OverpassCenter {
+                lat: *lat,
+                lon: *lon,
+            })
+            .collect()
+    }
+
+    /// Gets the geometry of a relation member, None if it's not a known way.
+    fn get_member_geometry(
+        &self,
+        member: &osm_extract::Member,
+    ) -> Option<Vec<area_files::OverpassCenter>> {
+        if member.object_type != osm_extract::ObjectType::Way {
+            return None;
+        }
+        self.ways
+            .get(&member.id)
+            .map(|way| self.get_way_geometry(way))
+    }
+
+    fn get_result(&self, objects: Vec<&osm_extract::Object>) -> area_files::OverpassResult {
+        let elements = objects
+            .into_iter()
+            .map(|object| {
+                let is_node = object.object_type == osm_extract::ObjectType::Node;
+                area_files::OverpassElement {
+                    id: object.id,
+                    osm_type: object.object_type.to_string(),
+                    user: Some(object.user.to_string()),
+                    timestamp: Some(object.timestamp.to_string()),
+                    tags: area_files::OverpassTags::from_map(&object.tags),
+                    lat: if is_node { Some(object.lat) } else { None },
+                    lon: if is_node { Some(object.lon) } else { None },
+                    center: None,
+                    bounds: self.get_bounds(object),
+                    geometry: match object.object_type {
+                        osm_extract::ObjectType::Way => Some(self.get_way_geometry(object)),
+                        _ => None,
+                    },
+                    nodes: match object.object_type {
+                        osm_extract::ObjectType::Way => Some(object.nodes.clone()),
+                        _ => None,
+                    },
+                    members: match object.object_type {
+                        osm_extract::ObjectType::Relation => Some(
+                            object
+                                .members
+                                .iter()
+                                .map(|member| area_files::OverpassMember {
+                                    geometry: self.get_member_geometry(member),
+                                })
+                                .collect(),
+                        ),
+                        _ => None,
+                    },
+                }
+            })
+            .collect();
+        area_files::OverpassResult {
+            osm3s: area_files::OverpassTimes {
+                timestamp_osm_base: self.timestamp,
+                timestamp_areas_base: self.timestamp,
+            },
+            elements,
+        }
+    }
+
+    /// Collects the streets of a relation.
+    fn get_streets(&self, osmrelation: u64, polygon: &Polygon) -> Vec<&osm_extract::Object> {
+        let mut objects: Vec<&osm_extract::Object> = Vec::new();
+        for way in self.ways.values() {
+            if is_street_way(&way.tags) && self.is_way_inside(way, polygon) {
+                objects.push(way);
+            }
+        }
+        for relation in &self.relations {
+            if is_street_relation(&relation.tags) && self.is_relation_inside(relation, polygon) {
+                objects.push(relation);
+            }
+        }
+        // Area boundary streets.
+        if let Some(boundary) = self.boundaries.get(&osmrelation) {
+            for member in &boundary.members {
+                if member.object_type != osm_extract::ObjectType::Way {
+                    continue;
+                }
+                if let Some(way) = self.ways.get(&member.id) {
+                    if is_boundary_street(&way.tags) {
+                        objects.push(way);
+                    }
+                }
+            }
+            // The boundary itself, for the map.
+            objects.push(boundary);
+        }
+        objects.sort_by_key(|object| (object.object_type.to_string(), object.id));
+        objects.dedup_by_key(|object| (object.object_type.to_string(), object.id));
+        objects
+    }
+
+    /// Collects the house numbers of a relation.
+    fn get_housenumbers(&self, polygon: &Polygon) -> Vec<&osm_extract::Object> {
+        let mut objects: Vec<&osm_extract::Object> = Vec::new();
+        for node in &self.nodes {
+            if polygon.contains(node.lat, node.lon) {
+                objects.push(node);
+            }
+        }
+        for way in self.ways.values() {
+            if is_housenumber(&way.tags) && self.is_way_inside(way, polygon) {
+                objects.push(way);
+            }
+        }
+        for relation in &self.relations {
+            if is_housenumber(&relation.tags) && self.is_relation_inside(relation, polygon) {
+                objects.push(relation);
+            }
+        }
+        objects.sort_by_key(|object| (object.object_type.to_string(), object.id));
+        objects
+    }
+}
+
+/// Imports the streets and house numbers of relations and/or the house numbers of the whole
+/// country from an extract.
+fn import_extract(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    path: &str,
+    mode: &str,
+) -> anyhow::Result<()> {
+    let mut osmrelations: HashSet<u64> = HashSet::new();
+    let mut relation_names: Vec<String> = Vec::new();
+    if mode == "all" || mode == "relations" {
+        relation_names = relations.get_active_names()?;
+        for relation_name in &relation_names {
+            let relation = relations.get_relation(relation_name)?;
+            osmrelations.insert(relation.get_config().get_osmrelation());
+        }
+    }
+    let whole_country = mode == "all" || mode == "whole-country";
+    let extract = Extract::new(ctx, path, &osmrelations, whole_country)?;
+
+    for relation_name in relation_names {
+        let relation = relations.get_relation(&relation_name)?;
+        let osmrelation = relation.get_config().get_osmrelation();
+        let polygon = match extract.get_polygon(osmrelation) {
+            Some(value) => value,
+            None => {
+                info!("import_osm: relation {osmrelation} of {relation_name} is not in the extract, skipping");
+                continue;
+            }
+        };
+        info!("import_osm: writing {relation_name}");
+        let files = relation.get_files();
+        files
+            .write_osm_streets(
+                ctx,
+                extract.get_result(extract.get_streets(osmrelation, &polygon)),
+            )
+            .context("write_osm_streets() failed")?;
+        files
+            .write_osm_housenumbers(ctx, extract.get_result(extract.get_housenumbers(&polygon)))
+            .context("write_osm_housenumbers() failed")?;
+    }
+
+    if whole_country {
+        info!("import_osm: writing the whole country");
+        let objects = extract.whole_country.iter().collect();
+        area_files::write_whole_country_result(ctx, extract.get_result(objects))?;
+    }
+
+    Ok(())
+}
+
+/// Objects of an extract for the boundaries of all relations, so per-relation queries don't have
+/// to read the extract again.
+struct ExtractIndex {
+    /// Modification time of the extract when the index was built.
+    mtime: time::OffsetDateTime,
+    /// Relation ids of the boundaries in the index.
+    osmrelations: HashSet<u64>,
+    extract: Extract,
+}
+
+/// OSM data source implementation, backed by a local extract.
+pub struct ExtractOsmSource {
+    path: String,
+    /// Built on the first per-relation query, rebuilt when the extract changes.
+    index: RefCell<Option<Rc<ExtractIndex>>>,
+}
+
+impl ExtractOsmSource {
+    /// Creates a new ExtractOsmSource.
+    pub fn new(path: &str) -> Self {
+        ExtractOsmSource {
+            path: path.to_string(),
+            index: RefCell::new(None),
+        }
+    }
+
+    /// Gets an index which contains the boundary of `osmrelation`, building it if needed.
+    fn get_index(
+        &self,
+        ctx: &context::Context,
+        osmrelation: u64,
+    ) -> anyhow::Result<Rc<ExtractIndex>> {
+        let mtime = ctx.get_file_system().getmtime(&self.path)?;
+        if let Some(ref index) = *self.index.borrow() {
+            if index.mtime == mtime && index.osmrelations.contains(&osmrelation) {
+                return Ok(index.clone());
+            }
+        }
+
+        let mut osmrelations: HashSet<u64> = HashSet::from([osmrelation]);
+        let mut relations = areas::Relations::new(ctx)?;
+        for relation_name in relations.get_names() {
+            let relation = relations.get_relation(&relation_name)?;
+            osmrelations.insert(relation.get_config().get_osmrelation());
+        }
+        let extract = Extract::new(ctx, &self.path, &osmrelations, false)?;
+        let index = Rc::new(ExtractIndex {
+            mtime,
+            osmrelations,
+            extract,
+        });
+        *self.index.borrow_mut() = Some(index.clone());
+        Ok(index)
+    }
+}
+
+impl context::OsmSource for ExtractOsmSource {
+    fn query(&self, ctx: &context::Context, query: &context::OsmQuery) -> anyhow::Result<String> {
+        match query.kind {
+            context::OsmQueryKind::Streets | context::OsmQueryKind::Housenumbers => {
+                let index = self.get_index(ctx, query.osmrelation)?;
+                let extract = &index.extract;
+                let polygon = extract.get_polygon(query.osmrelation).context(format!(
+                    "relation {} is not in the extract",
+                    query.osmrelation
+                ))?;
+                let objects = if query.kind == context::OsmQueryKind::Streets {
+                    extract.get_streets(query.osmrelation, &polygon)
+                } else {
+                    extract.get_housenumbers(&polygon)
+                };
+                to_overpass_json(&objects, extract)
+            }
+            context::OsmQueryKind::WholeCountry => {
+                let extract = Extract::new(ctx, &self.path, &HashSet::new(), true)?;
+                let objects: Vec<&osm_extract::Object> = extract.whole_country.iter().collect();
+                to_overpass_json(&objects, &extract)
+            }
+        }
+    }
+
+    fn need_sleep(&self, _ctx: &context::Context) -> i32 {
+        0
+    }
+}
+
+/// Inner main() that is allowed to fail.
+pub fn our_main(
+    argv: &[String],
+    _stream: &mut dyn Write,
+    ctx: &context::Context,
+) -> anyhow::Result<()> {
+    let mut relations = areas::Relations::new(ctx)?;
+
+    let path = clap::Arg::new("path")
+        .required(true)
+        .help("path of the .osm.pbf or .osm extract");
+    let refcounty = clap::Arg::new("refcounty")
+        .long("refcounty")
+        .help("limit the list of relations to a given refcounty");
+    let refsettlement = clap::Arg::new("refsettlement")
+        .long("refsettlement")
+        .help("limit the list of relations to a given refsettlement");
+    let refarea = clap::Arg::new("refarea")
+        .long("refarea")
+        .help("limit the list of relations to a given area name");
+    let mode = clap::Arg::new("mode")
+        .long("mode")
+        .default_value("all")
+        .value_parser(["all", "relations", "whole-country"])
+        .help("only import relations, only the whole country or both");
+    let args = [path, refcounty, refsettlement, refarea, mode];
+    let app = clap::Command::new("osm-gimmisn");
+    let args = app.args(&args).try_get_matches_from(argv)?;
+
+    // Unlike Overpass, a local extract is cheap to query, so update inactive relations as well.
+    relations.activate_all(true);
+    let refcounty: Option<&String> = args.get_one("refcounty");
+    relations.limit_to_refcounty(&refcounty)?;
+    let refsettlement: Option<&String> = args.get_one("refsettlement");
+    relations.limit_to_refsettlement(&refsettlement)?;
+    let refarea: Option<&String> = args.get_one("refarea");
+    relations.limit_to_refarea(&refarea)?;
+    let path: &String = args.get_one("path").unwrap();
+    let mode: &String = args.get_one("mode").unwrap();
+    import_extract(ctx, &mut relations, path, mode)?;
+
+    ctx.get_unit().make_error()
+}
+
+/// Similar to plain main(), but with an interface that allows testing.
+pub fn main(argv: &[String], stream: &mut dyn Write, ctx: &context::Context) -> i32 {
+    match our_main(argv, stream, ctx) {
+        Ok(_) => 0,
+        Err(err) => {
+            stream.write_all(format!("{err:?}\n").as_bytes()).unwrap();
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,533 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the import_osm module.
+
+use super::*;
+use crate::stats;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Creates a test context with 2 relations, and optionally an in-memory extract.
+fn make_test_context(extract: Option<(&str, &[u8])>) -> context::Context {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "osmrelation": 42,
+                "refcounty": "01",
+                "refsettlement": "011",
+            },
+            "ujbuda": {
+                "osmrelation": 43,
+                "refcounty": "01",
+                "refsettlement": "012",
+                "inactive": true,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let mut files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let mut mtimes: HashMap<String, Rc<RefCell<time::OffsetDateTime>>> = HashMap::new();
+    if let Some((path, content)) = extract {
+        let file = context::tests::TestFileSystem::make_file();
+        file.borrow_mut().write_all(content).unwrap();
+        files.insert(path.to_string(), file);
+        mtimes.insert(
+            path.to_string(),
+            Rc::new(RefCell::new(time::macros::datetime!(2020-05-10 0:00 UTC))),
+        );
+    }
+    let mut file_system = context::tests::TestFileSystem::new();
+    file_system.set_files(&files);
+    file_system.set_mtimes(&mtimes);
+    let file_system_rc: Rc<dyn context::FileSystem> = Rc::new(file_system);
+    ctx.set_file_system(&file_system_rc);
+    ctx
+}
+
+/// Runs main() and returns its exit code.
+fn run_main(ctx: &context::Context, args: &[&str]) -> i32 {
+    let mut argv = vec!["".to_string()];
+    argv.extend(args.iter().map(|i| i.to_string()));
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+    main(&argv, &mut buf, ctx)
+}
+
+/// Gets the (osm_type, osm_id) pairs of a table for a relation.
+fn get_ids(ctx: &context::Context, table: &str, relation: &str) -> Vec<(String, String)> {
+    let conn = ctx.get_database_connection().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "select osm_type, osm_id from {table} where relation = ?1 order by osm_type, cast(osm_id as integer)"
+        ))
+        .unwrap();
+    let mut rows = stmt.query([relation]).unwrap();
+    let mut ret = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        ret.push((row.get(0).unwrap(), row.get(1).unwrap()));
+    }
+    ret
+}
+
+/// Gets the (osm_type, osm_id, user, fixme) rows of the whole_country table.
+fn get_whole_country(ctx: &context::Context) -> Vec<(String, String, String, String)> {
+    let conn = ctx.get_database_connection().unwrap();
+    let mut stmt = conn
+        .prepare("select osm_type, osm_id, user, fixme from whole_country order by osm_type, cast(osm_id as integer)")
+        .unwrap();
+    let mut rows = stmt.query([]).unwrap();
+    let mut ret = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        ret.push((
+            row.get(0).unwrap(),
+            row.get(1).unwrap(),
+            row.get(2).unwrap(),
+            row.get(3).unwrap(),
+        ));
+    }
+    ret
+}
+
+/// Converts a list of (type, id) literals to owned strings.
+fn to_ids(ids: &[(&str, &str)]) -> Vec<(String, String)> {
+    ids.iter()
+        .map(|(osm_type, osm_id)| (osm_type.to_string(), osm_id.to_string()))
+        .collect()
+}
+
+/// Asserts the result of importing src/fixtures/file-system/extract.osm.
+fn assert_imported(ctx: &context::Context) {
+    // 103 and 104 are not streets: outside the boundary and a bridge.
+    assert_eq!(
+        get_ids(ctx, "osm_streets", "gazdagret"),
+        to_ids(&[("relation", "200"), ("way", "100"), ("way", "102")])
+    );
+    // 10 is outside the boundary.
+    assert_eq!(
+        get_ids(ctx, "osm_housenumbers", "gazdagret"),
+        to_ids(&[("node", "9"), ("relation", "201"), ("way", "105")])
+    );
+    // 43 is not in the extract.
+    assert!(!stats::has_sql_mtime(ctx, "streets/ujbuda").unwrap());
+    assert!(!stats::has_sql_mtime(ctx, "housenumbers/ujbuda").unwrap());
+    let osm_base = stats::get_sql_mtime(ctx, "streets/gazdagret/osm-base").unwrap();
+    assert_eq!(osm_base, time::macros::datetime!(2024-01-01 0:00 UTC));
+    let conn = ctx.get_database_connection().unwrap();
+    let bounds: (String, String, String, String) = conn
+        .query_row(
+            "select min_lat, min_lon, max_lat, max_lon from osm_streets where osm_type = 'way' and osm_id = '100'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        bounds,
+        ("47".into(), "19".into(), "47.1".into(), "19.1".into())
+    );
+    let point: (String, String) = conn
+        .query_row(
+            "select lat, lon from osm_housenumbers where osm_type = 'node' and osm_id = '9'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(point, ("47.05".into(), "19.05".into()));
+    // The relation's location is a node of its member way.
+    let point: (String, String) = conn
+        .query_row(
+            "select lat, lon from osm_housenumbers where osm_type = 'relation' and osm_id = '201'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(point, ("47.07".into(), "19.07".into()));
+    // The boundary is stored for the map, but it's not a street.
+    let kinds: (String, String) = conn
+        .query_row(
+            "select group_concat(kind), group_concat(osm_id) from (select kind, osm_id from osm_geometries where relation = 'gazdagret' and osm_type = 'relation' order by kind)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(kinds, ("boundary,street".into(), "42,200".into()));
+    assert!(!conn
+        .query_row(
+            "select count(*) from osm_streets where osm_type = 'relation' and osm_id = '42'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|count| count > 0)
+        .unwrap());
+}
+
+/// Tests main(): importing an XML extract.
+#[test]
+fn test_main() {
+    let ctx = make_test_context(None);
+
+    let ret = run_main(&ctx, &["src/fixtures/file-system/extract.osm"]);
+
+    assert_eq!(ret, 0);
+    assert_imported(&ctx);
+    let whole_country = get_whole_country(&ctx);
+    assert_eq!(whole_country.len(), 4);
+    assert_eq!(
+        whole_country[0],
+        (
+            "node".to_string(),
+            "9".to_string(),
+            "Mapper & Co".to_string(),
+            r#"check "this""#.to_string()
+        )
+    );
+    assert_eq!(whole_country[1].1, "10");
+    assert_eq!(whole_country[2].1, "201");
+    assert_eq!(whole_country[3].1, "105");
+}
+
+/// Tests main(): importing a PBF extract, which should give the same result as the XML one.
+#[test]
+fn test_main_pbf() {
+    let mut objects: Vec<osm_extract::Object> = Vec::new();
+    let timestamp = osm_extract::read_extract(
+        &context::tests::make_test_context().unwrap(),
+        "src/fixtures/file-system/extract.osm",
+        &mut |object| {
+            objects.push(object);
+            Ok(())
+        },
+    )
+    .unwrap();
+    let pbf = osm_extract::tests::write_pbf(&objects, timestamp);
+    let ctx = make_test_context(Some(("extract.osm.pbf", &pbf)));
+
+    let ret = run_main(&ctx, &["extract.osm.pbf"]);
+
+    assert_eq!(ret, 0);
+    assert_imported(&ctx);
+    assert_eq!(get_whole_country(&ctx).len(), 4);
+}
+
+/// Tests main(): the extract has no timestamp, the file modification time is used instead.
+#[test]
+fn test_main_no_timestamp() {
+    let content = r#"<osm version="0.6">
+<node id="1" lat="47.0" lon="19.0"/>
+<node id="2" lat="47.0" lon="19.1"/>
+<node id="3" lat="47.1" lon="19.1"/>
+<way id="100"><nd ref="1"/><nd ref="2"/><nd ref="3"/><nd ref="1"/></way>
+<relation id="42"><member type="way" ref="100" role="outer"/><member type="way" ref="101" role="outer"/></relation>
+</osm>
+"#;
+    let ctx = make_test_context(Some(("extract.osm", content.as_bytes())));
+
+    let ret = run_main(&ctx, &["--mode", "relations", "extract.osm"]);
+
+    assert_eq!(ret, 0);
+    assert!(get_ids(&ctx, "osm_streets", "gazdagret").is_empty());
+    let osm_base = stats::get_sql_mtime(&ctx, "streets/gazdagret/osm-base").unwrap();
+    assert_eq!(osm_base, time::macros::datetime!(2020-05-10 0:00 UTC));
+}
+
+/// Tests main(): only importing relations.
+#[test]
+fn test_main_relations() {
+    let ctx = make_test_context(None);
+
+    let ret = run_main(
+        &ctx,
+        &[
+            "--mode",
+            "relations",
+            "--refcounty",
+            "01",
+            "--refsettlement",
+            "011",
+            "src/fixtures/file-system/extract.osm",
+        ],
+    );
+
+    assert_eq!(ret, 0);
+    assert_eq!(get_ids(&ctx, "osm_streets", "gazdagret").len(), 3);
+    assert!(get_whole_country(&ctx).is_empty());
+}
+
+/// Tests main(): only importing the whole country.
+#[test]
+fn test_main_whole_country() {
+    let ctx = make_test_context(None);
+
+    let ret = run_main(
+        &ctx,
+        &[
+            "--mode",
+            "whole-country",
+            "src/fixtures/file-system/extract.osm",
+        ],
+    );
+
+    assert_eq!(ret, 0);
+    assert!(get_ids(&ctx, "osm_streets", "gazdagret").is_empty());
+    assert!(!stats::has_sql_mtime(&ctx, "streets/gazdagret").unwrap());
+    assert_eq!(get_whole_country(&ctx).len(), 4);
+}
+
+/// Tests main(): the error case.
+#[test]
+fn test_main_error() {
+    let mut ctx = make_test_context(None);
+    let unit = context::tests::TestUnit::new();
+    let unit_rc: Rc<dyn context::Unit> = Rc::new(unit);
+    ctx.set_unit(&unit_rc);
+    let argv = vec![
+        "".to_string(),
+        "src/fixtures/file-system/extract.osm".to_string(),
+    ];
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+
+    let ret = main(&argv, &mut buf, &ctx);
+
+    assert_eq!(ret, 1);
+    assert!(String::from_utf8(buf.into_inner())
+        .unwrap()
+        .contains("TestError"));
+}
+
+/// Tests main(): the extract doesn't exist.
+#[test]
+fn test_main_no_such_file() {
+    let ctx = make_test_context(None);
+
+    let ret = run_main(&ctx, &["no-such-file.osm"]);
+
+    assert_eq!(ret, 1);
+}
+
+/// Builds a tag map from key-value pairs.
+fn make_tags(tags: &[(&str, &str)]) -> HashMap<String, String> {
+    tags.iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Tests is_street_way().
+#[test]
+fn test_is_street_way() {
+    let streets = [
+        vec![("highway", "residential"), ("name", "Kossuth utca")],
+        vec![("highway", "service"), ("name", "Mű út")],
+        vec![
+            ("highway", "footway"),
+            ("name", "Lépcső köz"),
+            ("layer", "1"),
+        ],
+        vec![("highway", "path"), ("name", "Duna part")],
+        vec![
+            ("highway", "service"),
+            ("service", "parking_aisle"),
+            ("name", "Kossuth utca"),
+        ],
+        vec![("amenity", "parking"), ("name", "Petőfi tér")],
+        vec![("leisure", "park"), ("name", "Városliget")],
+        vec![("place", "square"), ("name", "Kossuth tér")],
+    ];
+    for tags in streets {
+        assert!(is_street_way(&make_tags(&tags)), "not a street: {tags:?}");
+    }
+
+    let not_streets = [
+        vec![("highway", "residential")],
+        vec![
+            ("highway", "residential"),
+            ("name", "Híd"),
+            ("bridge", "yes"),
+        ],
+        vec![("highway", "platform"), ("name", "Megálló")],
+        vec![
+            ("highway", "service"),
+            ("service", "drive-through"),
+            ("name", "Mekis"),
+        ],
+        vec![
+            ("highway", "footway"),
+            ("name", "Aluljáró"),
+            ("layer", "-1"),
+        ],
+        vec![("highway", "footway"), ("name", "Pláza"), ("indoor", "yes")],
+        vec![("highway", "path"), ("name", "Kék túra")],
+        vec![("highway", "path"), ("name", "Alsó garázssor")],
+        vec![("amenity", "parking"), ("name", "Régi gazdasági udvar")],
+        vec![
+            ("amenity", "parking"),
+            ("name", "Petőfi tér"),
+            ("addr:street", "Petőfi tér"),
+        ],
+        vec![("place", "square")],
+    ];
+    for tags in not_streets {
+        assert!(!is_street_way(&make_tags(&tags)), "a street: {tags:?}");
+    }
+}
+
+/// Tests Polygon::contains().
+#[test]
+fn test_polygon_contains() {
+    let ring = |min: f64, max: f64| {
+        vec![
+            ((min, min), (min, max)),
+            ((min, max), (max, max)),
+            ((max, max), (max, min)),
+            ((max, min), (min, min)),
+        ]
+    };
+    let mut segments = ring(0_f64, 10_f64);
+    // Inner ring.
+    segments.extend(ring(4_f64, 6_f64));
+    let polygon = Polygon::new(segments);
+
+    assert!(polygon.contains(1_f64, 1_f64));
+    assert!(polygon.contains(3_f64, 5_f64));
+    assert!(!polygon.contains(5_f64, 5_f64));
+    assert!(!polygon.contains(11_f64, 5_f64));
+    assert!(!polygon.contains(5_f64, -1_f64));
+}
+
+/// Runs a query against src/fixtures/file-system/extract.osm and returns the (type, id) pairs of
+/// the result.
+fn query_extract(
+    ctx: &context::Context,
+    kind: context::OsmQueryKind,
+    osmrelation: u64,
+) -> anyhow::Result<(serde_json::Value, Vec<(String, u64)>)> {
+    let source = ExtractOsmSource::new("src/fixtures/file-system/extract.osm");
+    let query = context::OsmQuery {
+        kind,
+        relation_name: "gazdagret".into(),
+        osmrelation,
+        overpass: "".into(),
+    };
+    let buf = context::OsmSource::query(&source, ctx, &query)?;
+    // Make sure the result can be consumed the same way as an Overpass one.
+    let _: area_files::OverpassResult = serde_json::from_str(&buf)?;
+    let root: serde_json::Value = serde_json::from_str(&buf)?;
+    let mut ids: Vec<(String, u64)> = root["elements"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| {
+            (
+                i["type"].as_str().unwrap().to_string(),
+                i["id"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    ids.sort();
+    Ok((root, ids))
+}
+
+/// Converts a list of (type, id) literals to owned values.
+fn to_typed_ids(ids: &[(&str, u64)]) -> Vec<(String, u64)> {
+    ids.iter()
+        .map(|(osm_type, osm_id)| (osm_type.to_string(), *osm_id))
+        .collect()
+}
+
+/// Tests ExtractOsmSource.
+#[test]
+fn test_extract_osm_source() {
+    let ctx = make_test_context(None);
+
+    let (root, ids) = query_extract(&ctx, context::OsmQueryKind::Streets, 42).unwrap();
+    assert_eq!(root["osm3s"]["timestamp_osm_base"], "2024-01-01T00:00:00Z");
+    // The boundary relation is included, for the map.
+    assert_eq!(
+        ids,
+        to_typed_ids(&[
+            ("relation", 42),
+            ("relation", 200),
+            ("way", 100),
+            ("way", 102)
+        ])
+    );
+    let elements = root["elements"].as_array().unwrap();
+    let way = elements
+        .iter()
+        .find(|i| i["type"] == "way" && i["id"] == 100)
+        .unwrap();
+    assert_eq!(
+        way["bounds"],
+        serde_json::json!({"minlat": 47.0, "minlon": 19.0, "maxlat": 47.1, "maxlon": 19.1})
+    );
+    let way = elements
+        .iter()
+        .find(|i| i["type"] == "way" && i["id"] == 102)
+        .unwrap();
+    assert_eq!(
+        way["geometry"],
+        serde_json::json!([{"lat": 47.05, "lon": 19.02}, {"lat": 47.05, "lon": 19.08}])
+    );
+    let relation = elements
+        .iter()
+        .find(|i| i["type"] == "relation" && i["id"] == 200)
+        .unwrap();
+    assert_eq!(relation["members"][0]["ref"], 106);
+    assert_eq!(
+        relation["members"][0]["geometry"].as_array().unwrap().len(),
+        2
+    );
+
+    let (_, ids) = query_extract(&ctx, context::OsmQueryKind::Housenumbers, 42).unwrap();
+    assert_eq!(
+        ids,
+        to_typed_ids(&[("node", 9), ("relation", 201), ("way", 105)])
+    );
+
+    let (_, ids) = query_extract(&ctx, context::OsmQueryKind::WholeCountry, 0).unwrap();
+    assert_eq!(ids.len(), 4);
+
+    // Relation not in the extract.
+    assert!(query_extract(&ctx, context::OsmQueryKind::Streets, 43).is_err());
+
+    let source = ExtractOsmSource::new("src/fixtures/file-system/extract.osm");
+    assert_eq!(context::OsmSource::need_sleep(&source, &ctx), 0);
+}
+
+/// Tests ExtractOsmSource: per-relation queries share the index of the extract.
+#[test]
+fn test_extract_osm_source_index() {
+    let ctx = make_test_context(None);
+    let source = ExtractOsmSource::new("src/fixtures/file-system/extract.osm");
+    let mut query = context::OsmQuery {
+        kind: context::OsmQueryKind::Streets,
+        relation_name: "gazdagret".into(),
+        osmrelation: 42,
+        overpass: "".into(),
+    };
+    context::OsmSource::query(&source, &ctx, &query).unwrap();
+    let index = source.index.borrow().clone().unwrap();
+
+    query.kind = context::OsmQueryKind::Housenumbers;
+    context::OsmSource::query(&source, &ctx, &query).unwrap();
+
+    // No rebuild: the index is still the same.
+    assert!(Rc::ptr_eq(&index, source.index.borrow().as_ref().unwrap()));
+
+    // A relation which is not in the index: rebuild.
+    query.osmrelation = 4242;
+    assert!(context::OsmSource::query(&source, &ctx, &query).is_err());
+    let rebuilt = source.index.borrow().clone().unwrap();
+    assert!(!Rc::ptr_eq(&index, &rebuilt));
+    assert!(rebuilt.osmrelations.contains(&42));
+    assert!(rebuilt.osmrelations.contains(&4242));
+}
@@ -0,0 +1,359 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Infers ranges filters for streets which are split across multiple relations of the same
+//! refsettlement, based on which reference house numbers are mapped in which relation.
+
+use crate::areas;
+use crate::context;
+use crate::util;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// A proposed ranges filter item of a street in a relation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProposedRange {
+    /// The relation name.
+    pub relation_name: String,
+    /// The OSM street name, which is the key in the filters of the relation.
+    pub osm_street: String,
+    /// The first number, also decides if the range is even or odd.
+    pub start: i64,
+    /// The last number.
+    pub end: i64,
+}
+
+/// Two relations would both claim the same numbers of a street.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeConflict {
+    /// The first relation name.
+    pub first: String,
+    /// The second relation name.
+    pub second: String,
+    /// The first number of the overlap.
+    pub start: i64,
+    /// The last number of the overlap.
+    pub end: i64,
+}
+
+/// The inferred ranges of a single reference street.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreetRanges {
+    /// The reference county code.
+    pub refcounty: String,
+    /// The reference settlement code.
+    pub refsettlement: String,
+    /// The reference street name.
+    pub ref_street: String,
+    /// Proposed ranges, sorted by relation name and start.
+    pub ranges: Vec<ProposedRange>,
+    /// Overlapping ranges of different relations.
+    pub conflicts: Vec<RangeConflict>,
+    /// Reference numbers which are not covered by any proposed range.
+    pub gaps: Vec<i64>,
+}
+
+/// Builds one range per parity from the mapped numbers of a relation.
+fn get_parity_ranges(numbers: &BTreeSet<i64>) -> Vec<(i64, i64)> {
+    let mut ret: Vec<(i64, i64)> = Vec::new();
+    for is_odd in [true, false] {
+        let mut parity = numbers.iter().filter(|i| (*i % 2 == 1) == is_odd);
+        if let Some(start) = parity.next() {
+            let end = parity.next_back().unwrap_or(start);
+            ret.push((*start, *end));
+        }
+    }
+    ret.sort();
+    ret
+}
+
+/// Finds overlapping ranges of the same parity in different relations.
+fn get_conflicts(ranges: &[ProposedRange]) -> Vec<RangeConflict> {
+    let mut ret: Vec<RangeConflict> = Vec::new();
+    for (index, first) in ranges.iter().enumerate() {
+        for second in &ranges[index + 1..] {
+            if first.relation_name == second.relation_name || first.start % 2 != second.start % 2 {
+                continue;
+            }
+            let start = first.start.max(second.start);
+            let end = first.end.min(second.end);
+            if start <= end {
+                ret.push(RangeConflict {
+                    first: first.relation_name.to_string(),
+                    second: second.relation_name.to_string(),
+                    start,
+                    end,
+                });
+            }
+        }
+    }
+    ret
+}
+
+/// Finds reference numbers which are not covered by any range.
+fn get_gaps(ref_numbers: &BTreeSet<i64>, ranges: &[ProposedRange]) -> Vec<i64> {
+    ref_numbers
+        .iter()
+        .filter(|number| {
+            !ranges.iter().any(|range| {
+                range.start % 2 == *number % 2 && range.start <= **number && **number <= range.end
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Turns house numbers into the set of their numeric parts.
+fn get_numbers<'a>(house_numbers: impl Iterator<Item = &'a str>) -> BTreeSet<i64> {
+    house_numbers
+        .map(|i| util::split_house_number(i).0 as i64)
+        .filter(|i| *i > 0)
+        .collect()
+}
+
+/// Infers ranges for all streets which are present in multiple relations of the same
+/// refsettlement. Streets where all relations already have ranges are skipped. If
+/// `relation_name` is set, then only the streets of that relation are considered, comparing it to
+/// the relations of the same refcounty and refsettlement.
+pub fn infer_ranges(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    relation_name: Option<&str>,
+) -> anyhow::Result<Vec<StreetRanges>> {
+    // (refcounty, refsettlement, ref street) -> (relation index, OSM street) list.
+    type Key = (String, String, String);
+    let mut streets: BTreeMap<Key, Vec<(usize, String)>> = BTreeMap::new();
+    let mut relation_list = relations.get_relations()?;
+    let area = match relation_name {
+        Some(relation_name) => match relation_list.iter().find(|i| i.get_name() == relation_name) {
+            Some(relation) => Some((
+                relation.get_config().get_refcounty(),
+                relation.get_config().get_refsettlement(),
+            )),
+            None => return Ok(Vec::new()),
+        },
+        None => None,
+    };
+    for (index, relation) in relation_list.iter().enumerate() {
+        let config = relation.get_config();
+        let refcounty = config.get_refcounty();
+        if let Some((ref area_refcounty, ref area_refsettlement)) = area {
+            if refcounty != *area_refcounty || config.get_refsettlement() != *area_refsettlement {
+                continue;
+            }
+        }
+        for street in relation.get_osm_streets(/*sorted_result=*/ true)? {
+            let osm_street = street.get_osm_name();
+            let ref_street = config.get_ref_street_from_osm_street(osm_street);
+            for refsettlement in config.get_street_refsettlement(osm_street) {
+                let entry = streets
+                    .entry((refcounty.to_string(), refsettlement, ref_street.to_string()))
+                    .or_default();
+                if !entry.iter().any(|(i, _)| *i == index) {
+                    entry.push((index, osm_street.to_string()));
+                }
+            }
+        }
+    }
+
+    let mut ret: Vec<StreetRanges> = Vec::new();
+    for ((refcounty, refsettlement, ref_street), members) in streets {
+        if members.len() < 2 {
+            continue;
+        }
+        if let Some(relation_name) = relation_name {
+            if !members
+                .iter()
+                .any(|(index, _)| relation_list[*index].get_name() == relation_name)
+            {
+                continue;
+            }
+        }
+        if members.iter().all(|(index, osm_street)| {
+            relation_list[*index]
+                .get_config()
+                .has_street_ranges(osm_street)
+        }) {
+            continue;
+        }
+
+        let ref_numbers: BTreeSet<i64> = {
+            let conn = ctx.get_database_connection()?;
+            let mut stmt = conn.prepare(
+                "select housenumber from ref_housenumbers where county_code = ?1 and settlement_code = ?2 and street = ?3",
+            )?;
+            let mut rows = stmt.query([&refcounty, &refsettlement, &ref_street])?;
+            let mut housenumbers: Vec<String> = Vec::new();
+            while let Some(row) = rows.next()? {
+                housenumbers.push(row.get(0)?);
+            }
+            get_numbers(housenumbers.iter().map(|i| i.as_str()))
+        };
+        if ref_numbers.is_empty() {
+            continue;
+        }
+
+        let mut ranges: Vec<ProposedRange> = Vec::new();
+        for (index, osm_street) in &members {
+            let relation = &mut relation_list[*index];
+            let osm_housenumbers = relation.get_osm_housenumbers(osm_street)?;
+            let numbers: BTreeSet<i64> =
+                get_numbers(osm_housenumbers.iter().map(|i| i.get_number()))
+                    .intersection(&ref_numbers)
+                    .cloned()
+                    .collect();
+            for (start, end) in get_parity_ranges(&numbers) {
+                ranges.push(ProposedRange {
+                    relation_name: relation.get_name(),
+                    osm_street: osm_street.to_string(),
+                    start,
+                    end,
+                });
+            }
+        }
+        if ranges.is_empty() {
+            continue;
+        }
+        ranges.sort_by(|a, b| {
+            a.relation_name
+                .cmp(&b.relation_name)
+                .then_with(|| a.start.cmp(&b.start))
+        });
+
+        let conflicts = get_conflicts(&ranges);
+        let gaps = get_gaps(&ref_numbers, &ranges);
+        ret.push(StreetRanges {
+            refcounty,
+            refsettlement,
+            ref_street,
+            ranges,
+            conflicts,
+            gaps,
+        });
+    }
+    Ok(ret)
+}
+
+/// Formats the proposed ranges of one relation and street as YAML.
+fn to_yaml(osm_street: &str, ranges: &[&ProposedRange]) -> String {
+    let mut ret = format!("  '{}':\n    ranges:\n", osm_street.replace('\'', "''"));
+    for range in ranges {
+        ret += &format!(
+            "      - {{start: '{}', end: '{}'}}\n",
+            range.start, range.end
+        );
+    }
+    ret
+}
+
+/// Produces the report of the inferred ranges.
+pub fn to_report(streets: &[StreetRanges]) -> String {
+    let mut ret = String::new();
+    for street in streets {
+        ret += &format!(
+            "# {} ({}/{})\n",
+            street.ref_street, street.refcounty, street.refsettlement
+        );
+        let mut by_relation: BTreeMap<&str, Vec<&ProposedRange>> = BTreeMap::new();
+        for range in &street.ranges {
+            by_relation
+                .entry(&range.relation_name)
+                .or_default()
+                .push(range);
+        }
+        for (relation_name, ranges) in by_relation {
+            ret += &format!("relation-{relation_name}.yaml:\nfilters:\n");
+            ret += &to_yaml(&ranges[0].osm_street, &ranges);
+        }
+        for conflict in &street.conflicts {
+            ret += &format!(
+                "conflict: {} and {} both have {}-{}\n",
+                conflict.first, conflict.second, conflict.start, conflict.end
+            );
+        }
+        if !street.gaps.is_empty() {
+            let gaps: Vec<String> = street.gaps.iter().map(|i| i.to_string()).collect();
+            ret += &format!("gaps: {}\n", gaps.join(", "));
+        }
+        ret += "\n";
+    }
+    ret
+}
+
+/// Produces validator hints for a relation: proposed ranges for its split streets which have no
+/// ranges filter in `relation`.
+pub fn get_hints(
+    ctx: &context::Context,
+    relation_name: &str,
+    relation: &areas::RelationDict,
+) -> anyhow::Result<Vec<String>> {
+    let mut relations = areas::Relations::new(ctx)?;
+    let mut ret: Vec<String> = Vec::new();
+    for street in infer_ranges(ctx, &mut relations, Some(relation_name))? {
+        let ranges: Vec<&ProposedRange> = street
+            .ranges
+            .iter()
+            .filter(|i| i.relation_name == relation_name)
+            .collect();
+        if ranges.is_empty() {
+            continue;
+        }
+        let osm_street = &ranges[0].osm_street;
+        let has_ranges = match relation.filters {
+            Some(ref filters) => filters
+                .get(osm_street)
+                .map(|i| i.ranges.is_some())
+                .unwrap_or(false),
+            None => false,
+        };
+        if has_ranges {
+            continue;
+        }
+        let items: Vec<String> = ranges
+            .iter()
+            .map(|i| format!("{{start: '{}', end: '{}'}}", i.start, i.end))
+            .collect();
+        ret.push(format!(
+            "hint: '{osm_street}' is split across relations, consider 'filters.{osm_street}.ranges': [{}]",
+            items.join(", ")
+        ));
+    }
+    Ok(ret)
+}
+
+/// Inner main() that is allowed to fail.
+pub fn our_main(
+    argv: &[String],
+    stream: &mut dyn Write,
+    ctx: &context::Context,
+) -> anyhow::Result<()> {
+    let app = clap::Command::new("osm-gimmisn").override_usage("osm-gimmisn infer-ranges");
+    app.try_get_matches_from(argv)?;
+
+    let mut relations = areas::Relations::new(ctx)?;
+    let streets = infer_ranges(ctx, &mut relations, /*relation_name=*/ None)?;
+    stream.write_all(to_report(&streets).as_bytes())?;
+    Ok(())
+}
+
+/// Similar to plain main(), but with an interface that allows testing.
+pub fn main(argv: &[String], stream: &mut dyn Write, ctx: &context::Context) -> i32 {
+    match our_main(argv, stream, ctx) {
+        Ok(_) => 0,
+        Err(err) => {
+            stream.write_all(format!("{err:?}\n").as_bytes()).unwrap();
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests;
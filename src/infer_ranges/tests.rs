@@ -0,0 +1,180 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the infer_ranges module.
+
+use super::*;
+use std::io::Read;
+use std::io::Seek;
+
+/// Creates a context with two relations, both having the odd and even side of 'Hosszú utca'.
+/// `filters` is used as the filters of both relations.
+pub fn make_test_context(filters: serde_json::Value) -> anyhow::Result<context::Context> {
+    let mut ctx = context::tests::make_test_context()?;
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "north": {
+                "refcounty": "01",
+                "refsettlement": "011",
+                "osmrelation": 42,
+            },
+            "south": {
+                "refcounty": "01",
+                "refsettlement": "011",
+                "osmrelation": 43,
+            },
+        },
+        "relation-north.yaml": {
+            "filters": filters.clone(),
+        },
+        "relation-south.yaml": {
+            "filters": filters,
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection()?;
+        let mut sql = String::new();
+        for number in 1..=12 {
+            sql += &format!("insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('01', '011', 'Hosszú utca', '{number}', '');\n");
+        }
+        for relation in ["north", "south"] {
+            sql += &format!("insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('{relation}', '1', 'Hosszú utca', 'residential', '', '', '', 'way');\n");
+        }
+        // 5 is mapped in both relations, 12 is mapped in neither.
+        for (osm_id, (relation, number)) in [
+            ("north", "1"),
+            ("north", "3"),
+            ("north", "5"),
+            ("north", "2"),
+            ("north", "4/a"),
+            ("south", "5"),
+            ("south", "7"),
+            ("south", "11"),
+            ("south", "6"),
+            ("south", "10"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            sql += &format!("insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('{relation}', '{osm_id}', 'Hosszú utca', '{number}', '', '', '', '', '', '', '', '', '', 'node');\n");
+        }
+        conn.execute_batch(&sql)?;
+    }
+    Ok(ctx)
+}
+
+/// Tests infer_ranges().
+#[test]
+fn test_infer_ranges() {
+    let ctx = make_test_context(serde_json::json!({})).unwrap();
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    let actual = infer_ranges(&ctx, &mut relations, /*relation_name=*/ None).unwrap();
+
+    let range = |relation_name: &str, start, end| ProposedRange {
+        relation_name: relation_name.into(),
+        osm_street: "Hosszú utca".into(),
+        start,
+        end,
+    };
+    assert_eq!(
+        actual,
+        vec![StreetRanges {
+            refcounty: "01".into(),
+            refsettlement: "011".into(),
+            ref_street: "Hosszú utca".into(),
+            ranges: vec![
+                range("north", 1, 5),
+                range("north", 2, 4),
+                range("south", 5, 11),
+                range("south", 6, 10),
+            ],
+            conflicts: vec![RangeConflict {
+                first: "north".into(),
+                second: "south".into(),
+                start: 5,
+                end: 5,
+            }],
+            gaps: vec![12],
+        }]
+    );
+}
+
+/// Tests infer_ranges(): streets which have ranges in all relations are skipped.
+#[test]
+fn test_infer_ranges_configured() {
+    let filters = serde_json::json!({
+        "Hosszú utca": {
+            "ranges": [{"start": "1", "end": "11"}],
+        },
+    });
+    let ctx = make_test_context(filters).unwrap();
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    let actual = infer_ranges(&ctx, &mut relations, /*relation_name=*/ None).unwrap();
+
+    assert_eq!(actual, vec![]);
+}
+
+/// Tests infer_ranges(): only the streets of a single relation.
+#[test]
+fn test_infer_ranges_relation() {
+    let ctx = make_test_context(serde_json::json!({})).unwrap();
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+
+    let actual = infer_ranges(&ctx, &mut relations, Some("north")).unwrap();
+
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].ref_street, "Hosszú utca");
+
+    let actual = infer_ranges(&ctx, &mut relations, Some("nosuchrelation")).unwrap();
+
+    assert_eq!(actual, vec![]);
+}
+
+/// Tests main().
+#[test]
+fn test_main() {
+    let argv = vec!["".to_string()];
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+    let ctx = make_test_context(serde_json::json!({})).unwrap();
+
+    let ret = main(&argv, &mut buf, &ctx);
+
+    assert_eq!(ret, 0);
+    buf.rewind().unwrap();
+    let mut actual: Vec<u8> = Vec::new();
+    buf.read_to_end(&mut actual).unwrap();
+    let expected = r#"# Hosszú utca (01/011)
+relation-north.yaml:
+filters:
+  'Hosszú utca':
+    ranges:
+      - {start: '1', end: '5'}
+      - {start: '2', end: '4'}
+relation-south.yaml:
+filters:
+  'Hosszú utca':
+    ranges:
+      - {start: '5', end: '11'}
+      - {start: '6', end: '10'}
+conflict: north and south both have 5-5
+gaps: 12
+
+"#;
+    assert_eq!(String::from_utf8(actual).unwrap(), expected);
+}
@@ -17,18 +17,32 @@ mod cache;
 pub mod cache_yamls;
 pub mod context;
 pub mod cron;
+mod feeds;
+mod geometry;
+mod housenumber_grammar;
 mod i18n;
+pub mod import_osm;
+pub mod infer_ranges;
+mod lints;
 pub mod missing_housenumbers;
+mod notifications;
+pub mod openapi;
+mod osm_extract;
 mod overpass_query;
 pub mod parse_access_log;
 mod ranges;
+mod reference;
+mod replication;
 mod sql;
 mod stats;
+pub mod suggest_refstreets;
 pub mod sync_ref;
 pub mod util;
 pub mod validator;
 mod webframe;
 pub mod wsgi;
 mod wsgi_additional;
+mod wsgi_api;
 mod wsgi_json;
+mod wsgi_map;
 mod yattag;
@@ -0,0 +1,210 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The lints module exports relation lints as tasks for cooperative tasking tools, e.g.
+//! MapRoulette.
+
+use crate::areas;
+use crate::context;
+use crate::geometry;
+use crate::i18n::translate as tr;
+
+#[cfg(not(test))]
+use log::info;
+
+#[cfg(test)]
+use std::println as info;
+
+/// The record separator, starts a text in a GeoJSON text sequence.
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+/// A row of the relation_lints table.
+struct Lint {
+    relation_name: String,
+    street_name: String,
+    source: areas::RelationLintSource,
+    housenumber: String,
+    reason: areas::RelationLintReason,
+    object: Option<(String, u64)>,
+    /// Location of the object or, as a fallback, of the street, as stored by cron.
+    point: Option<geometry::Point>,
+}
+
+impl Lint {
+    /// Describes what to check and fix to resolve this lint.
+    fn get_instruction(&self) -> String {
+        let instruction = match (&self.reason, &self.source) {
+            (areas::RelationLintReason::CreatedInOsm, areas::RelationLintSource::Range) => tr(
+                "{0} {1} is mapped in OSM, but it is outside the house number ranges in the settings of {2}. If the OSM object is correct, extend the ranges, otherwise fix the address in OSM.",
+            ),
+            (areas::RelationLintReason::CreatedInOsm, areas::RelationLintSource::Survey) => tr(
+                "{0} {1} is mapped in OSM, but a survey observation of {2} says that it is not there. If the OSM object is correct, remove the survey observation, otherwise fix the address in OSM.",
+            ),
+            (areas::RelationLintReason::CreatedInOsm, _) => tr(
+                "{0} {1} is mapped in OSM, but it is listed as an invalid house number in the settings of {2}. If the OSM object is correct, remove it from the invalid list, otherwise fix the address in OSM.",
+            ),
+            (areas::RelationLintReason::DeletedFromRef, areas::RelationLintSource::Survey) => tr(
+                "{0} {1} has a survey observation in {2}, but the reference no longer has it. Remove the survey observation.",
+            ),
+            (areas::RelationLintReason::OutOfRange, areas::RelationLintSource::Survey) => tr(
+                "{0} {1} has a survey observation in {2}, but it is already outside the house number ranges. Remove the survey observation.",
+            ),
+            (areas::RelationLintReason::MissingPostcode, _) => tr(
+                "{0} {1} is mapped in OSM in {2}, but it has no postcode. Add the addr:postcode tag.",
+            ),
+            (areas::RelationLintReason::MalformedPostcode, _) => tr(
+                "{0} {1} is mapped in OSM in {2}, but its postcode is malformed. Fix the addr:postcode tag.",
+            ),
+            (areas::RelationLintReason::UnknownPostcode, _) => tr(
+                "{0} {1} is mapped in OSM in {2}, but its postcode is not a postcode of the settlement according to the reference. Fix the addr:postcode tag.",
+            ),
+            (areas::RelationLintReason::DeletedFromRef, _) => tr(
+                "{0} {1} is listed as an invalid house number in the settings of {2}, but the reference no longer has it. Remove it from the invalid list.",
+            ),
+            (areas::RelationLintReason::OutOfRange, _) => tr(
+                "{0} {1} is listed as an invalid house number in the settings of {2}, but it is already outside the house number ranges. Remove it from the invalid list.",
+            ),
+        };
+        instruction
+            .replace("{0}", &self.street_name)
+            .replace("{1}", &self.housenumber)
+            .replace("{2}", &self.relation_name)
+    }
+}
+
+/// Gets the stored location of the first matching row of a query, if there is one.
+fn query_point(
+    stmt: &mut rusqlite::Statement<'_>,
+    params: &[&str],
+) -> anyhow::Result<Option<geometry::Point>> {
+    let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+    while let Some(row) = rows.next()? {
+        let lat: String = row.get(0)?;
+        let lon: String = row.get(1)?;
+        if let Some(point) = geometry::parse_point(&lat, &lon) {
+            return Ok(Some(point));
+        }
+    }
+    Ok(None)
+}
+
+/// Gets the lints of one relation or of all relations, located at their object or, as a
+/// fallback, at their street.
+fn get_lints(ctx: &context::Context, relation_name: Option<&str>) -> anyhow::Result<Vec<Lint>> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(
+        "select relation_name, street_name, source, housenumber, reason, object_id, object_type from relation_lints where ?1 is null or relation_name = ?1 order by relation_name, id",
+    )?;
+    let mut objects_stmt = conn.prepare(
+        "select lat, lon from osm_housenumbers where relation = ?1 and osm_type = ?2 and osm_id = ?3",
+    )?;
+    let mut streets_stmt =
+        conn.prepare("select lat, lon from osm_streets where relation = ?1 and name = ?2")?;
+    let mut rows = stmt.query([relation_name])?;
+    let mut ret = Vec::new();
+    while let Some(row) = rows.next()? {
+        let relation_name: String = row.get(0)?;
+        let street_name: String = row.get(1)?;
+        let object_id: String = row.get(5)?;
+        let object_type: String = row.get(6)?;
+        let object = match object_id.as_str() {
+            "" | "0" => None,
+            id => Some((object_type, id.parse::<u64>()?)),
+        };
+        let mut point = match object {
+            Some((ref object_type, _)) => query_point(
+                &mut objects_stmt,
+                &[&relation_name, object_type, &object_id],
+            )?,
+            None => None,
+        };
+        if point.is_none() {
+            point = query_point(&mut streets_stmt, &[&relation_name, &street_name])?;
+        }
+        ret.push(Lint {
+            relation_name,
+            street_name,
+            source: areas::RelationLintSource::try_from(row.get::<_, String>(2)?.as_str())?,
+            housenumber: row.get(3)?,
+            reason: areas::RelationLintReason::try_from(row.get::<_, String>(4)?.as_str())?,
+            object,
+            point,
+        });
+    }
+    Ok(ret)
+}
+
+/// Exports the lints of one relation or of all relations as a GeoJSON text sequence (RFC 8142),
+/// one task per line, using the locations stored by cron. Lints without a known location are
+/// skipped, their number is returned as well.
+pub fn get_lints_challenge(
+    ctx: &context::Context,
+    relation_name: Option<&str>,
+) -> anyhow::Result<(String, usize)> {
+    let lints = get_lints(ctx, relation_name)?;
+
+    let mut ret = String::new();
+    let mut skipped = 0;
+    for lint in lints {
+        let point = match lint.point {
+            Some(value) => value.round(),
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
+        let mut properties = serde_json::json!({
+            "relation": lint.relation_name,
+            "street": lint.street_name,
+            "housenumber": lint.housenumber,
+            "source": lint.source.to_string(),
+            "reason": lint.reason.to_string(),
+            "instruction": lint.get_instruction(),
+        });
+        if let Some((osm_type, osm_id)) = &lint.object {
+            properties["@id"] = serde_json::json!(format!("{osm_type}/{osm_id}"));
+        }
+        let feature = serde_json::json!({
+            "type": "Feature",
+            "id": format!(
+                "{}/{}/{}/{}",
+                lint.relation_name, lint.street_name, lint.housenumber, lint.reason
+            ),
+            "geometry": {
+                "type": "Point",
+                "coordinates": [point.lon, point.lat],
+            },
+            "properties": properties,
+        });
+        ret.push(RECORD_SEPARATOR);
+        ret += &serde_json::to_string(&feature)?;
+        ret.push('\n');
+    }
+    Ok((ret, skipped))
+}
+
+/// Gets the path of the lints of the whole country, as a GeoJSON text sequence.
+pub fn get_lints_challenge_path(ctx: &context::Context) -> String {
+    format!("{}/relation-lints.geojson", ctx.get_ini().get_workdir())
+}
+
+/// Writes the lints of the whole country to a file, so serving it doesn't have to go through all
+/// lints.
+pub fn write_lints_challenge(ctx: &context::Context) -> anyhow::Result<()> {
+    let (output, skipped) = get_lints_challenge(ctx, /*relation_name=*/ None)?;
+    if skipped > 0 {
+        info!("write_lints_challenge: skipped {skipped} lints without a known location");
+    }
+    ctx.get_file_system()
+        .write_from_string(&output, &get_lints_challenge_path(ctx))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,90 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the lints module.
+
+use super::*;
+
+/// Tests get_lints_challenge(), the whole country case.
+#[test]
+fn test_get_lints_challenge() {
+    let ctx = context::tests::make_test_context().unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'invalid', '5', 'created-in-osm', '13', 'node');
+             insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'invalid', '7', 'deleted-from-ref', '0', '');
+             insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('ujbuda', 'No Geometry utca', 'invalid', '1', 'out-of-range', '', '');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '10', 'Tűzkő utca', '', '', '', '', '', '47', '19.005');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '13', 'Tűzkő utca', '5', '', '', '', '', '', '', '', '', '', 'node', '47.5', '19.05');",
+        )
+        .unwrap();
+    }
+
+    let (ret, skipped) = get_lints_challenge(&ctx, /*relation_name=*/ None).unwrap();
+
+    let lines: Vec<&str> = ret.lines().collect();
+    // The lint without a location is skipped.
+    assert_eq!(lines.len(), 2);
+    assert_eq!(skipped, 1);
+    assert!(lines.iter().all(|i| i.starts_with(RECORD_SEPARATOR)));
+    let features: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|i| serde_json::from_str(i.trim_start_matches(RECORD_SEPARATOR)).unwrap())
+        .collect();
+    // Located at the object itself.
+    assert_eq!(features[0]["properties"]["@id"], "node/13");
+    assert_eq!(features[0]["properties"]["reason"], "created-in-osm");
+    assert_eq!(
+        features[0]["geometry"]["coordinates"],
+        serde_json::json!([19.05, 47.5])
+    );
+    // Located at the street.
+    assert!(features[1]["properties"]["@id"].is_null());
+    assert_eq!(features[1]["properties"]["reason"], "deleted-from-ref");
+    assert_eq!(
+        features[1]["properties"]["instruction"],
+        "Tűzkő utca 7 is listed as an invalid house number in the settings of gazdagret, but the reference no longer has it. Remove it from the invalid list."
+    );
+    assert_eq!(
+        features[1]["geometry"]["coordinates"],
+        serde_json::json!([19.005, 47.0])
+    );
+}
+
+/// Tests write_lints_challenge().
+#[test]
+fn test_write_lints_challenge() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let lints = context::tests::TestFileSystem::make_file();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("workdir/relation-lints.geojson", &lints)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'invalid', '7', 'deleted-from-ref', '0', '');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '10', 'Tűzkő utca', '', '', '', '', 'way', '47', '19.005');",
+        )
+        .unwrap();
+    }
+
+    write_lints_challenge(&ctx).unwrap();
+
+    let output = ctx
+        .get_file_system()
+        .read_to_string(&get_lints_challenge_path(&ctx))
+        .unwrap();
+    assert_eq!(output.lines().count(), 1);
+    assert!(output.contains(r#""coordinates":[19.005,47.0]"#));
+}
@@ -91,9 +91,13 @@ lazy_static::lazy_static! {
         let mut ret: HashMap<String, Handler> = HashMap::new();
         ret.insert("cache-yamls".into(), osm_gimmisn::cache_yamls::main);
         ret.insert("cron".into(), cron_main);
+        ret.insert("import-osm".into(), osm_gimmisn::import_osm::main);
+        ret.insert("infer-ranges".into(), osm_gimmisn::infer_ranges::main);
         ret.insert("missing-housenumbers".into(), osm_gimmisn::missing_housenumbers::main);
+        ret.insert("openapi".into(), osm_gimmisn::openapi::main);
         ret.insert("parse-access-log".into(), osm_gimmisn::parse_access_log::main);
         ret.insert("rouille".into(), rouille_main);
+        ret.insert("suggest-refstreets".into(), osm_gimmisn::suggest_refstreets::main);
         ret.insert("sync-ref".into(), osm_gimmisn::sync_ref::main);
         ret.insert("validator".into(), osm_gimmisn::validator::main);
         ret
@@ -106,20 +110,32 @@ fn main() {
     let cache_yamls =
         clap::Command::new("cache-yamls").about("Caches YAML files from the data/ directory");
     let cron = clap::Command::new("cron").about("Performs nightly tasks");
+    let import_osm = clap::Command::new("import-osm")
+        .about("Imports OSM streets and house numbers from a local .osm.pbf or .osm extract");
+    let infer_ranges = clap::Command::new("infer-ranges")
+        .about("Proposes ranges filters for streets which are split across relations");
     let missing_housenumbers = clap::Command::new("missing-housenumbers")
         .about("Compares reference house numbers with OSM ones and shows the diff");
+    let openapi =
+        clap::Command::new("openapi").about("Dumps the OpenAPI description of the JSON endpoints");
     let parse_access_log = clap::Command::new("parse-access-log")
         .about("Parses the Apache access log of osm-gimmisn for 1 month");
     let rouille = clap::Command::new("rouille").about("Starts the web interface");
+    let suggest_refstreets = clap::Command::new("suggest-refstreets")
+        .about("Suggests refstreets entries for a relation, based on similar street names");
     let sync_ref = clap::Command::new("sync-ref")
         .about("Synchronizes the reference data from a public instance to a local dev instance");
     let validator = clap::Command::new("validator").about("Validates yaml files under data/");
     let subcommands = vec![
         cache_yamls,
         cron,
+        import_osm,
+        infer_ranges,
         missing_housenumbers,
+        openapi,
         parse_access_log,
         rouille,
+        suggest_refstreets,
         sync_ref,
         validator,
     ];
@@ -0,0 +1,283 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The notifications module notifies subscribers when cron finds that a relation regressed.
+
+use crate::areas;
+use crate::context;
+use anyhow::Context;
+use log::error;
+use std::io::BufRead;
+use std::io::Write;
+
+/// One regression of a relation, e.g. a drop of the house number coverage.
+#[derive(serde::Serialize)]
+struct Regression {
+    /// 'housenumber-coverage', 'street-coverage' or 'lints'.
+    kind: String,
+    old: f64,
+    new: f64,
+}
+
+/// The JSON payload of a webhook notification.
+#[derive(serde::Serialize)]
+struct Notification {
+    relation: String,
+    url: String,
+    regressions: Vec<Regression>,
+}
+
+/// Counts the lints of a relation, using `sql` which has a single relation parameter.
+fn count_lints(ctx: &context::Context, sql: &str, relation_name: &str) -> anyhow::Result<u64> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(sql)?;
+    let mut rows = stmt.query([relation_name])?;
+    let row = rows.next()?.context("no next row")?;
+    Ok(row.get(0)?)
+}
+
+/// Finds the regressions of a relation since the previous cron run, needs to be called before the
+/// coverage history is updated.
+fn get_regressions(relation: &areas::Relation<'_>) -> anyhow::Result<Vec<Regression>> {
+    let ctx = relation.get_ctx();
+    let relation_name = relation.get_name();
+    let mut ret: Vec<Regression> = Vec::new();
+    let history = relation.get_coverage_history()?;
+    let last = match history.last() {
+        Some(value) => value,
+        None => {
+            // First run for this relation, nothing to compare with.
+            return Ok(ret);
+        }
+    };
+
+    let coverage_drop = match relation.get_config().get_notify_coverage_drop() {
+        Some(value) => value,
+        None => ctx.get_ini().get_notify_coverage_drop()?,
+    };
+    if let Some(ref old) = last.housenumbers {
+        if relation.has_osm_housenumber_coverage()? {
+            let old: f64 = old.parse()?;
+            let new: f64 = relation.get_osm_housenumber_coverage()?.parse()?;
+            if new < old && old - new >= coverage_drop {
+                ret.push(Regression {
+                    kind: "housenumber-coverage".into(),
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+    if let Some(ref old) = last.streets {
+        if relation.has_osm_street_coverage()? {
+            let old: f64 = old.parse()?;
+            let new: f64 = relation.get_osm_street_coverage()?.parse()?;
+            if new < old && old - new >= coverage_drop {
+                ret.push(Regression {
+                    kind: "street-coverage".into(),
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+
+    let lints_increase = match relation.get_config().get_notify_lints_increase() {
+        Some(value) => value,
+        None => ctx.get_ini().get_notify_lints_increase()?,
+    };
+    let old = count_lints(
+        ctx,
+        "select coalesce((select count from relation_notify_lints where relation = ?1), 0)",
+        &relation_name,
+    )?;
+    let new = count_lints(
+        ctx,
+        "select count(*) from relation_lints where relation_name = ?1",
+        &relation_name,
+    )?;
+    if new > old && new - old >= lints_increase {
+        ret.push(Regression {
+            kind: "lints".into(),
+            old: old as f64,
+            new: new as f64,
+        });
+    }
+
+    Ok(ret)
+}
+
+/// Describes a regression in a human-readable way.
+fn format_regression(regression: &Regression) -> String {
+    match regression.kind.as_str() {
+        "housenumber-coverage" => format!(
+            "House number coverage dropped from {:.2}% to {:.2}%",
+            regression.old, regression.new
+        ),
+        "street-coverage" => format!(
+            "Street coverage dropped from {:.2}% to {:.2}%",
+            regression.old, regression.new
+        ),
+        _ => format!(
+            "Number of lints increased from {} to {}",
+            regression.old, regression.new
+        ),
+    }
+}
+
+/// Reads an SMTP reply and checks that its code is one of `expected`.
+fn read_smtp_reply(reader: &mut impl BufRead, expected: &[&str]) -> anyhow::Result<()> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(anyhow::anyhow!("smtp: connection closed"));
+        }
+        // Multiline replies look like '250-...', except their last line: '250 ...'.
+        if line.get(3..4) == Some("-") {
+            continue;
+        }
+        let code = line.get(0..3).unwrap_or("");
+        if expected.contains(&code) {
+            return Ok(());
+        }
+        return Err(anyhow::anyhow!(
+            "smtp: unexpected reply: '{}'",
+            line.trim_end()
+        ));
+    }
+}
+
+/// Sends an SMTP command and checks its reply.
+fn send_smtp_command(
+    connection: &mut std::io::BufReader<Box<dyn context::Connection>>,
+    command: &str,
+    expected: &[&str],
+) -> anyhow::Result<()> {
+    let writer = connection.get_mut();
+    writer.write_all(format!("{command}\r\n").as_bytes())?;
+    writer.flush()?;
+    read_smtp_reply(connection, expected).with_context(|| format!("'{command}' failed"))
+}
+
+/// Sends a plain text email to `server` (host:port), without TLS or authentication, so this is
+/// meant to be used with a local mail server.
+fn send_mail(
+    ctx: &context::Context,
+    to: &[String],
+    subject: &str,
+    body: &str,
+) -> anyhow::Result<()> {
+    let server = ctx.get_ini().get_smtp_server();
+    let from = ctx.get_ini().get_smtp_from();
+    let mut connection = std::io::BufReader::new(ctx.get_network().connect(&server)?);
+    read_smtp_reply(&mut connection, &["220"])?;
+    send_smtp_command(&mut connection, "HELO localhost", &["250"])?;
+    send_smtp_command(&mut connection, &format!("MAIL FROM:<{from}>"), &["250"])?;
+    for address in to {
+        send_smtp_command(
+            &mut connection,
+            &format!("RCPT TO:<{address}>"),
+            &["250", "251"],
+        )?;
+    }
+    send_smtp_command(&mut connection, "DATA", &["354"])?;
+    let date = ctx
+        .get_time()
+        .now()
+        .format(&time::format_description::well_known::Rfc2822)?;
+    let mut message = format!(
+        "From: {from}\r\nTo: {}\r\nSubject: {subject}\r\nDate: {date}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        to.join(", ")
+    );
+    for line in body.lines() {
+        // Dot-stuffing, so a line doesn't end the message early.
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    send_smtp_command(&mut connection, &message, &["250"])?;
+    send_smtp_command(&mut connection, "QUIT", &["221"])?;
+    Ok(())
+}
+
+/// Remembers the number of lints of a relation, to compare with during the next cron run.
+fn write_lints_baseline(relation: &areas::Relation<'_>) -> anyhow::Result<()> {
+    let ctx = relation.get_ctx();
+    let relation_name = relation.get_name();
+    let count = count_lints(
+        ctx,
+        "select count(*) from relation_lints where relation_name = ?1",
+        &relation_name,
+    )?;
+    let conn = ctx.get_database_connection()?;
+    conn.execute(
+        r#"insert into relation_notify_lints (relation, count) values (?1, ?2)
+             on conflict(relation) do update set count = excluded.count"#,
+        rusqlite::params![relation_name, count],
+    )?;
+    Ok(())
+}
+
+/// Notifies the subscribers of a relation if its coverage dropped or its lints jumped since the
+/// previous cron run. Failing notifications are logged, but they don't fail the cron run.
+pub fn notify_regressions(relation: &areas::Relation<'_>) -> anyhow::Result<()> {
+    send_notifications(relation)?;
+    write_lints_baseline(relation).context("write_lints_baseline() failed")
+}
+
+/// Sends the notifications of notify_regressions(), if the relation has subscribers.
+fn send_notifications(relation: &areas::Relation<'_>) -> anyhow::Result<()> {
+    let ctx = relation.get_ctx();
+    let mut emails = ctx.get_ini().get_notify_emails();
+    emails.append(&mut relation.get_config().get_notify_emails());
+    let mut webhooks = ctx.get_ini().get_notify_webhooks();
+    webhooks.append(&mut relation.get_config().get_notify_webhooks());
+    if emails.is_empty() && webhooks.is_empty() {
+        return Ok(());
+    }
+
+    let regressions = get_regressions(relation).context("get_regressions() failed")?;
+    if regressions.is_empty() {
+        return Ok(());
+    }
+
+    let relation_name = relation.get_name();
+    let site_url = ctx.get_ini().get_site_url();
+    let prefix = ctx.get_ini().get_uri_prefix();
+    let url = format!("{site_url}{prefix}/missing-housenumbers/{relation_name}/view-result");
+    let mut lines: Vec<String> = regressions.iter().map(format_regression).collect();
+    lines.push("".into());
+    lines.push(url.to_string());
+    let body = lines.join("\n");
+    let notification = Notification {
+        relation: relation_name.to_string(),
+        url,
+        regressions,
+    };
+    let payload = serde_json::to_string(&notification)?;
+    for webhook in webhooks {
+        if let Err(err) = ctx.get_network().post_json(&webhook, &payload) {
+            error!("notify_regressions: webhook '{webhook}' failed: {err:?}");
+        }
+    }
+    if !emails.is_empty() {
+        let subject = format!("osm-gimmisn: {relation_name} regressed");
+        if let Err(err) = send_mail(ctx, &emails, &subject, &body) {
+            error!("notify_regressions: sending email failed: {err:?}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,161 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the notifications module.
+
+use super::*;
+use std::rc::Rc;
+
+/// Replies of an SMTP server which accepts a single mail.
+const SMTP_REPLIES: &str = "220 localhost\r\n\
+250-localhost\r\n\
+250 HELP\r\n\
+250 ok\r\n\
+250 ok\r\n\
+354 go ahead\r\n\
+250 queued\r\n\
+221 bye\r\n";
+
+/// Sets up a relation with a coverage history, the current coverages and lints.
+fn make_test_context(ini_lines: &str) -> context::Context {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let ini = context::tests::make_test_ini(&ctx, ini_lines);
+    ctx.set_ini(ini);
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "01",
+                "refsettlement": "011",
+                "notify-webhooks": ["http://localhost/hook"],
+                "notify-lints-increase": 2,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into stats_coverages (date, relation, streets, housenumbers) values ('2020-05-09', 'gazdagret', '50.00', '80.00');
+             insert into osm_street_coverages (relation_name, coverage, last_modified) values ('gazdagret', '49.50', '0');
+             insert into osm_housenumber_coverages (relation_name, coverage, last_modified) values ('gazdagret', '70.00', '0');
+             insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'range', '1', 'out-of-range', '', '');
+             insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'range', '3', 'out-of-range', '', '');",
+        )
+        .unwrap();
+    }
+    ctx
+}
+
+/// Tests notify_regressions(): both the webhook and the email are sent.
+#[test]
+fn test_notify_regressions() {
+    let mut ctx =
+        make_test_context("notify_emails = 'admin@example.com'\nsmtp_server = 'localhost:25'\n");
+    let routes = vec![context::tests::URLRoute::new(
+        /*url=*/ "http://localhost/hook",
+        /*data_path=*/ "src/fixtures/network/notify-webhook.json",
+        /*result_path=*/ "src/fixtures/network/notify-webhook-result.txt",
+    )];
+    let network = context::tests::TestNetwork::new(&routes);
+    let sent = network.add_connection("localhost:25", SMTP_REPLIES);
+    let network_rc: Rc<dyn context::Network> = Rc::new(network);
+    ctx.set_network(network_rc);
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+
+    notify_regressions(&relation).unwrap();
+
+    let sent = String::from_utf8(sent.borrow().clone()).unwrap();
+    let received: Vec<String> = sent.lines().map(|i| i.to_string()).collect();
+    assert_eq!(received[0], "HELO localhost");
+    assert_eq!(received[1], "MAIL FROM:<osm-gimmisn@localhost>");
+    assert_eq!(received[2], "RCPT TO:<admin@example.com>");
+    assert_eq!(received[3], "DATA");
+    assert!(received.contains(&"Subject: osm-gimmisn: gazdagret regressed".to_string()));
+    assert!(received.contains(&"House number coverage dropped from 80.00% to 70.00%".to_string()));
+    assert!(received.contains(&"Number of lints increased from 0 to 2".to_string()));
+    // The street coverage only dropped by 0.5 percentage points, that's below the threshold.
+    assert!(!received.iter().any(|i| i.starts_with("Street coverage")));
+    assert_eq!(received[received.len() - 2], ".");
+    assert_eq!(received[received.len() - 1], "QUIT");
+}
+
+/// Tests notify_regressions(): a failing channel doesn't fail the cron run.
+#[test]
+fn test_notify_regressions_failing_webhook() {
+    let ctx = make_test_context("");
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+
+    // The network has no route for the webhook, so post_json() fails.
+    notify_regressions(&relation).unwrap();
+}
+
+/// Tests notify_regressions(): the lints baseline is updated, so the same lints are not reported
+/// again.
+#[test]
+fn test_notify_regressions_lints_baseline() {
+    let ctx = make_test_context("");
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+    assert_eq!(get_regressions(&relation).unwrap().len(), 2);
+
+    notify_regressions(&relation).unwrap();
+
+    // Only the coverage drop is left, the coverage history is not updated here.
+    let regressions = get_regressions(&relation).unwrap();
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].kind, "housenumber-coverage");
+}
+
+/// Tests get_regressions(): the relation overrides the thresholds.
+#[test]
+fn test_get_regressions_thresholds() {
+    let ctx = make_test_context("notify_coverage_drop = '20'\n");
+    let mut relations = areas::Relations::new(&ctx).unwrap();
+    let relation = relations.get_relation("gazdagret").unwrap();
+
+    let regressions = get_regressions(&relation).unwrap();
+
+    // The coverage drop is below 20 percentage points; the lints increase is not below 2.
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].kind, "lints");
+
+    // Lints are only compared to the ones which were seen during the previous run.
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into relation_notify_lints (relation, count) values ('gazdagret', 1);",
+        )
+        .unwrap();
+    }
+    assert!(get_regressions(&relation).unwrap().is_empty());
+}
+
+/// Tests send_mail(): the server rejects the connection.
+#[test]
+fn test_send_mail_rejected() {
+    let mut ctx = make_test_context("smtp_server = 'localhost:25'\n");
+    let network = context::tests::TestNetwork::new(&[]);
+    let sent = network.add_connection("localhost:25", "554 no service\r\n");
+    let network_rc: Rc<dyn context::Network> = Rc::new(network);
+    ctx.set_network(network_rc);
+
+    let ret = send_mail(&ctx, &["admin@example.com".to_string()], "subject", "body");
+
+    assert!(ret.is_err());
+    assert!(sent.borrow().is_empty());
+}
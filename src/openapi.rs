@@ -0,0 +1,399 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Provides an OpenAPI description of the JSON endpoints of the web interface.
+
+use crate::areas;
+use crate::context;
+use crate::wsgi_api;
+use std::io::Write;
+
+/// Refers to a schema under components/schemas.
+fn schema_ref(name: &str) -> serde_json::Value {
+    serde_json::json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+/// An array of a schema under components/schemas.
+fn schema_array_ref(name: &str) -> serde_json::Value {
+    serde_json::json!({ "type": "array", "items": schema_ref(name) })
+}
+
+/// A required string path parameter.
+fn path_parameter(name: &str, description: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" },
+    })
+}
+
+/// The relation name path parameter.
+fn relation_parameter() -> serde_json::Value {
+    path_parameter(
+        "relation",
+        "Name of the relation, e.g. 'gazdagret'. Relation aliases are accepted as well.",
+    )
+}
+
+/// A JSON response with the given schema.
+fn json_response(description: &str, schema: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "description": description,
+        "content": {
+            "application/json": {
+                "schema": schema,
+            },
+        },
+    })
+}
+
+/// A GET operation, with the error responses of the /api/v1/ namespace if `api` is true.
+fn get_operation(
+    summary: &str,
+    parameters: Vec<serde_json::Value>,
+    schema: serde_json::Value,
+    api: bool,
+) -> serde_json::Value {
+    let mut responses = serde_json::Map::new();
+    responses.insert("200".into(), json_response("Success", schema));
+    if api {
+        responses.insert(
+            "400".into(),
+            json_response("Malformed request", schema_ref("Error")),
+        );
+        responses.insert(
+            "404".into(),
+            json_response(
+                "Unknown relation or analysis, or no OSM data yet",
+                schema_ref("Error"),
+            ),
+        );
+    }
+    responses.insert(
+        "500".into(),
+        json_response("Internal error", schema_ref("Error")),
+    );
+    serde_json::json!({
+        "get": {
+            "summary": summary,
+            "parameters": parameters,
+            "responses": responses,
+        },
+    })
+}
+
+/// A POST or DELETE operation of the /api/v1/ namespace with a JSON request body, which requires
+/// surveyor credentials.
+fn surveyor_operation(
+    summary: &str,
+    parameters: Vec<serde_json::Value>,
+    request_schema: serde_json::Value,
+    schema: serde_json::Value,
+) -> serde_json::Value {
+    serde_json::json!({
+        "summary": summary,
+        "parameters": parameters,
+        "security": [{ "surveyor": [] }],
+        "requestBody": {
+            "required": true,
+            "content": {
+                "application/json": {
+                    "schema": request_schema,
+                },
+            },
+        },
+        "responses": {
+            "200": json_response("Success", schema),
+            "400": json_response("Malformed request", schema_ref("Error")),
+            "401": json_response("No valid surveyor credentials", schema_ref("Error")),
+            "403": json_response("The request was not sent from this site", schema_ref("Error")),
+            "404": json_response("Unknown relation or survey observation", schema_ref("Error")),
+            "415": json_response("The request body is not JSON", schema_ref("Error")),
+            "500": json_response("Internal error", schema_ref("Error")),
+        },
+    })
+}
+
+/// Describes the types which are serialized by the JSON endpoints, derived from the serde types
+/// themselves.
+fn get_schemas() -> serde_json::Value {
+    let mut generator = schemars::generate::SchemaSettings::openapi3().into_generator();
+    generator.subschema_for::<wsgi_api::ApiErrorJson>();
+    generator.subschema_for::<areas::MissingHousenumbers>();
+    generator.subschema_for::<areas::MissingHousenumbersChanges>();
+    generator.subschema_for::<wsgi_api::ApiMissingStreets>();
+    generator.subschema_for::<wsgi_api::ApiRelation>();
+    generator.subschema_for::<wsgi_api::ApiLint>();
+    generator.subschema_for::<wsgi_api::ApiInvalidAddrCity>();
+    generator.subschema_for::<wsgi_api::ApiCoverages>();
+    generator.subschema_for::<areas::CoverageHistoryEntry>();
+    generator.subschema_for::<areas::SurveyObservation>();
+    generator.subschema_for::<wsgi_api::ApiSurveyObservationKey>();
+    serde_json::Value::Object(generator.take_definitions(/*apply_transforms=*/ true))
+}
+
+/// Describes the JSON endpoints, relative to the URI prefix.
+fn get_paths() -> serde_json::Value {
+    let mut paths = serde_json::Map::new();
+    paths.insert(
+        "/api/v1/openapi.json".into(),
+        get_operation(
+            "This document",
+            vec![],
+            serde_json::json!({ "type": "object" }),
+            /*api=*/ true,
+        ),
+    );
+    paths.insert(
+        "/api/v1/relations".into(),
+        get_operation(
+            "Lists all relations",
+            vec![],
+            schema_array_ref("Relation"),
+            /*api=*/ true,
+        ),
+    );
+    paths.insert(
+        "/api/v1/relations/filter-for/refcounty/{refcounty}".into(),
+        get_operation(
+            "Lists the relations of a county",
+            vec![path_parameter(
+                "refcounty",
+                "Reference county code, e.g. '01'.",
+            )],
+            schema_array_ref("Relation"),
+            /*api=*/ true,
+        ),
+    );
+    paths.insert(
+        "/api/v1/relations/filter-for/refcounty/{refcounty}/refsettlement/{refsettlement}".into(),
+        get_operation(
+            "Lists the relations of a settlement",
+            vec![
+                path_parameter("refcounty", "Reference county code, e.g. '01'."),
+                path_parameter("refsettlement", "Reference settlement code, e.g. '011'."),
+            ],
+            schema_array_ref("Relation"),
+            /*api=*/ true,
+        ),
+    );
+    paths.insert(
+        "/api/v1/relations/filter-for/relations/{relations}".into(),
+        get_operation(
+            "Lists relations by their OSM identifiers",
+            vec![path_parameter(
+                "relations",
+                "Comma-separated list of OSM relation identifiers, e.g. '42,43'.",
+            )],
+            schema_array_ref("Relation"),
+            /*api=*/ true,
+        ),
+    );
+    paths.insert(
+        "/api/v1/relations/{relation}".into(),
+        get_operation(
+            "Describes a relation",
+            vec![relation_parameter()],
+            schema_ref("Relation"),
+            /*api=*/ true,
+        ),
+    );
+    let analyses = [
+        (
+            "missing-housenumbers",
+            "House numbers which are in the reference, but not in OSM",
+            schema_ref("MissingHousenumbers"),
+        ),
+        (
+            "additional-housenumbers",
+            "House numbers which are in OSM, but not in the reference",
+            schema_array_ref("NumberedStreet"),
+        ),
+        (
+            "missing-streets",
+            "Streets which are in the reference, but not in OSM",
+            schema_ref("MissingStreets"),
+        ),
+        (
+            "additional-streets",
+            "Streets which are in OSM, but not in the reference",
+            schema_array_ref("Street"),
+        ),
+        (
+            "lints",
+            "Filters of the relation which are probably no longer necessary",
+            schema_array_ref("Lint"),
+        ),
+        (
+            "invalid-addr-cities",
+            "OSM addresses of the relation with an addr:city which is not a valid settlement",
+            schema_array_ref("InvalidAddrCity"),
+        ),
+        (
+            "coverage",
+            "Street and house number coverage of the relation",
+            schema_ref("Coverages"),
+        ),
+        (
+            "coverage-history",
+            "Daily street and house number coverage of the relation, recorded by cron",
+            schema_array_ref("CoverageHistoryEntry"),
+        ),
+        (
+            "survey-observations",
+            "Survey feedback about the missing house numbers of the relation",
+            schema_array_ref("SurveyObservation"),
+        ),
+    ];
+    for (analysis, summary, schema) in analyses {
+        paths.insert(
+            format!("/api/v1/relations/{{relation}}/{analysis}"),
+            get_operation(
+                summary,
+                vec![relation_parameter()],
+                schema,
+                /*api=*/ true,
+            ),
+        );
+    }
+    let survey_observations = paths
+        .get_mut("/api/v1/relations/{relation}/survey-observations")
+        .and_then(|i| i.as_object_mut())
+        .expect("survey-observations is in analyses");
+    survey_observations.insert(
+        "post".into(),
+        surveyor_operation(
+            "Records survey feedback about a house number",
+            vec![relation_parameter()],
+            schema_ref("SurveyObservation"),
+            schema_array_ref("SurveyObservation"),
+        ),
+    );
+    survey_observations.insert(
+        "delete".into(),
+        surveyor_operation(
+            "Removes survey feedback about a house number",
+            vec![relation_parameter()],
+            schema_ref("SurveyObservationKey"),
+            schema_array_ref("SurveyObservation"),
+        ),
+    );
+    paths.insert(
+        "/streets/{relation}/update-result.json".into(),
+        get_operation(
+            "Updates the OSM streets of a relation from Overpass",
+            vec![relation_parameter()],
+            schema_ref("Error"),
+            /*api=*/ false,
+        ),
+    );
+    paths.insert(
+        "/street-housenumbers/{relation}/update-result.json".into(),
+        get_operation(
+            "Updates the OSM house numbers of a relation from Overpass",
+            vec![relation_parameter()],
+            schema_ref("Error"),
+            /*api=*/ false,
+        ),
+    );
+    paths.insert(
+        "/api/v1/relations/{relation}/missing-housenumbers-changes/{since}".into(),
+        get_operation(
+            "Changes of the missing house numbers since a date",
+            vec![
+                relation_parameter(),
+                path_parameter(
+                    "since",
+                    "Date in the YYYY-MM-DD format, the last snapshot on or before this is used.",
+                ),
+            ],
+            schema_ref("MissingHousenumbersChanges"),
+            /*api=*/ true,
+        ),
+    );
+    paths.insert(
+        "/missing-housenumbers/{relation}/view-result.json".into(),
+        get_operation(
+            "House numbers which are in the reference, but not in OSM",
+            vec![relation_parameter()],
+            schema_ref("MissingHousenumbers"),
+            /*api=*/ false,
+        ),
+    );
+    paths.insert(
+        "/additional-housenumbers/{relation}/view-result.json".into(),
+        get_operation(
+            "House numbers which are in OSM, but not in the reference",
+            vec![relation_parameter()],
+            schema_array_ref("NumberedStreet"),
+            /*api=*/ false,
+        ),
+    );
+    paths.insert(
+        "/lints/whole-country/invalid-addr-cities/update-result.json".into(),
+        get_operation(
+            "Updates the invalid addr:city values from Overpass",
+            vec![],
+            schema_ref("Error"),
+            /*api=*/ false,
+        ),
+    );
+    serde_json::Value::Object(paths)
+}
+
+/// Generates the OpenAPI document of the JSON endpoints.
+pub fn get_openapi(ctx: &context::Context) -> serde_json::Value {
+    serde_json::json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "osm-gimmisn",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Compares OSM streets and house numbers with reference data.",
+        },
+        "servers": [
+            { "url": ctx.get_ini().get_uri_prefix() },
+        ],
+        "paths": get_paths(),
+        "components": {
+            "schemas": get_schemas(),
+            "securitySchemes": {
+                "surveyor": { "type": "http", "scheme": "basic" },
+            },
+        },
+    })
+}
+
+/// Inner main() that is allowed to fail.
+pub fn our_main(
+    _argv: &[String],
+    stream: &mut dyn Write,
+    ctx: &context::Context,
+) -> anyhow::Result<()> {
+    let openapi = get_openapi(ctx);
+    stream.write_all(serde_json::to_string_pretty(&openapi)?.as_bytes())?;
+    stream.write_all(b"\n")?;
+    ctx.get_unit().make_error()
+}
+
+/// Similar to plain main(), but with an interface that allows testing.
+pub fn main(argv: &[String], stream: &mut dyn Write, ctx: &context::Context) -> i32 {
+    match our_main(argv, stream, ctx) {
+        Ok(_) => 0,
+        Err(err) => {
+            stream.write_all(format!("{err:?}\n").as_bytes()).unwrap();
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests;
@@ -0,0 +1,211 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the openapi module.
+
+use super::*;
+use crate::areas;
+use crate::util;
+use std::io::Read;
+use std::io::Seek;
+use std::rc::Rc;
+
+/// Collects all $ref values from a JSON value, recursively.
+fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                if key == "$ref" {
+                    refs.push(value.as_str().unwrap().into());
+                } else {
+                    collect_refs(value, refs);
+                }
+            }
+        }
+        serde_json::Value::Array(array) => {
+            for value in array {
+                collect_refs(value, refs);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Asserts that the keys of a serialized value match the properties of a schema, and that the
+/// required properties are all present.
+pub fn assert_schema_keys(schema_name: &str, value: &serde_json::Value) {
+    let schemas = get_schemas();
+    let mut properties: Vec<String> = schemas[schema_name]["properties"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect();
+    properties.sort();
+    let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, properties, "schema: {schema_name}");
+    // No required properties is not an error.
+    let required = schemas[schema_name]["required"].as_array().cloned();
+    for required in required.unwrap_or_default() {
+        let required = required.as_str().unwrap().to_string();
+        assert!(keys.contains(&required), "schema: {schema_name}");
+    }
+}
+
+/// Tests main().
+#[test]
+fn test_main() {
+    let argv = vec!["".to_string()];
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+    let ctx = context::tests::make_test_context().unwrap();
+
+    let ret = main(&argv, &mut buf, &ctx);
+
+    assert_eq!(ret, 0);
+    buf.rewind().unwrap();
+    let mut actual: Vec<u8> = Vec::new();
+    buf.read_to_end(&mut actual).unwrap();
+    let root: serde_json::Value = serde_json::from_slice(&actual).unwrap();
+    assert_eq!(root["openapi"], "3.0.3");
+    assert_eq!(root["servers"][0]["url"], "/osm");
+    let paths = root["paths"].as_object().unwrap();
+    assert!(paths.contains_key("/api/v1/relations/{relation}/missing-housenumbers"));
+    assert!(paths.contains_key("/missing-housenumbers/{relation}/view-result.json"));
+    assert_eq!(
+        paths["/api/v1/relations/{relation}/lints"]["get"]["parameters"][0]["name"],
+        "relation"
+    );
+}
+
+/// Tests main(), the error case.
+#[test]
+fn test_main_error() {
+    let argv = vec!["".to_string()];
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let unit = context::tests::TestUnit::new();
+    let unit_rc: Rc<dyn context::Unit> = Rc::new(unit);
+    ctx.set_unit(&unit_rc);
+
+    let ret = main(&argv, &mut buf, &ctx);
+
+    assert_eq!(ret, 1);
+}
+
+/// Tests that all schema references can be resolved.
+#[test]
+fn test_refs() {
+    let ctx = context::tests::make_test_context().unwrap();
+    let root = get_openapi(&ctx);
+    let mut refs: Vec<String> = Vec::new();
+
+    collect_refs(&root, &mut refs);
+
+    assert!(!refs.is_empty());
+    let schemas = root["components"]["schemas"].as_object().unwrap();
+    for reference in refs {
+        let name = reference.strip_prefix("#/components/schemas/").unwrap();
+        assert!(schemas.contains_key(name), "unresolved reference: {name}");
+    }
+}
+
+/// Tests that the schemas are in sync with the serde types they describe.
+#[test]
+fn test_serde_types() {
+    let street = util::Street::new("OSM Name", "Ref Name", true, 42);
+    let house_number = util::HouseNumber::new("1", "1-3", "");
+    let numbered_street = util::NumberedStreet {
+        street: street.clone(),
+        house_numbers: vec![house_number.clone()],
+    };
+    let missing_housenumbers = areas::MissingHousenumbers {
+        ongoing_streets: vec![numbered_street.clone()],
+        done_streets: Vec::new(),
+    };
+
+    assert_schema_keys("Street", &serde_json::to_value(&street).unwrap());
+    assert_schema_keys("HouseNumber", &serde_json::to_value(&house_number).unwrap());
+    assert_schema_keys(
+        "NumberedStreet",
+        &serde_json::to_value(&numbered_street).unwrap(),
+    );
+    assert_schema_keys(
+        "MissingHousenumbers",
+        &serde_json::to_value(&missing_housenumbers).unwrap(),
+    );
+    let changes = areas::MissingHousenumbersChanges {
+        since: "2020-05-10".into(),
+        newly_mapped: areas::StreetHousenumbers::new(),
+        newly_missing: areas::StreetHousenumbers::new(),
+        no_longer_mapped: areas::StreetHousenumbers::new(),
+        removed_from_reference: areas::StreetHousenumbers::new(),
+    };
+    assert_schema_keys(
+        "MissingHousenumbersChanges",
+        &serde_json::to_value(&changes).unwrap(),
+    );
+    let history_entry = areas::CoverageHistoryEntry {
+        date: "2020-05-10".into(),
+        streets: None,
+        housenumbers: Some("25.00".into()),
+    };
+    assert_schema_keys(
+        "CoverageHistoryEntry",
+        &serde_json::to_value(&history_entry).unwrap(),
+    );
+    let observation = areas::SurveyObservation {
+        street: "Tűzkő utca".into(),
+        housenumber: "1".into(),
+        status: areas::SurveyStatus::ConfirmedAbsent,
+        note: "".into(),
+        surveyor: "alice".into(),
+        date: "2020-05-10".into(),
+    };
+    assert_schema_keys(
+        "SurveyObservation",
+        &serde_json::to_value(&observation).unwrap(),
+    );
+}
+
+/// Tests that the lint enum schemas match the strings which are written to the API output.
+#[test]
+fn test_lint_enums() {
+    let schemas = get_schemas();
+
+    let sources: Vec<String> = [
+        areas::RelationLintSource::Range,
+        areas::RelationLintSource::Invalid,
+        areas::RelationLintSource::Survey,
+        areas::RelationLintSource::Postcode,
+    ]
+    .iter()
+    .map(|i| i.to_string())
+    .collect();
+    assert_eq!(
+        schemas["RelationLintSource"]["enum"],
+        serde_json::json!(sources)
+    );
+    let reasons: Vec<String> = [
+        areas::RelationLintReason::CreatedInOsm,
+        areas::RelationLintReason::DeletedFromRef,
+        areas::RelationLintReason::OutOfRange,
+        areas::RelationLintReason::MissingPostcode,
+        areas::RelationLintReason::MalformedPostcode,
+        areas::RelationLintReason::UnknownPostcode,
+    ]
+    .iter()
+    .map(|i| i.to_string())
+    .collect();
+    assert_eq!(
+        schemas["RelationLintReason"]["enum"],
+        serde_json::json!(reasons)
+    );
+}
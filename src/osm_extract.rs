@@ -0,0 +1,827 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The osm_extract module reads OSM objects from a local extract, either in the PBF or in the XML
+//! format.
+
+use crate::context;
+use anyhow::Context as _;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::Read;
+
+/// The type of an OSM object.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ObjectType {
+    Node,
+    Way,
+    Relation,
+}
+
+impl TryFrom<&str> for ObjectType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "node" => Ok(ObjectType::Node),
+            "way" => Ok(ObjectType::Way),
+            "relation" => Ok(ObjectType::Relation),
+            _ => Err(anyhow::anyhow!("invalid value: {value}")),
+        }
+    }
+}
+
+impl std::fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectType::Node => write!(f, "node"),
+            ObjectType::Way => write!(f, "way"),
+            ObjectType::Relation => write!(f, "relation"),
+        }
+    }
+}
+
+/// A member of a relation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Member {
+    pub object_type: ObjectType,
+    pub id: u64,
+    pub role: String,
+}
+
+/// An OSM object: node, way or relation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Object {
+    pub object_type: ObjectType,
+    pub id: u64,
+    /// Only used for nodes.
+    pub lat: f64,
+    /// Only used for nodes.
+    pub lon: f64,
+    pub tags: HashMap<String, String>,
+    /// Only used for ways.
+    pub nodes: Vec<u64>,
+    /// Only used for relations.
+    pub members: Vec<Member>,
+    pub user: String,
+    /// In the RFC 3339 format, e.g. 2020-05-10T00:00:00Z.
+    pub timestamp: String,
+}
+
+impl Object {
+    fn new(object_type: ObjectType, id: u64) -> Self {
+        Object {
+            object_type,
+            id,
+            lat: 0_f64,
+            lon: 0_f64,
+            tags: HashMap::new(),
+            nodes: Vec::new(),
+            members: Vec::new(),
+            user: "".into(),
+            timestamp: "".into(),
+        }
+    }
+}
+
+/// Callback that gets invoked for each object of an extract.
+pub type ObjectHandler<'a> = dyn FnMut(Object) -> anyhow::Result<()> + 'a;
+
+/// The kind of a change in an OsmChange file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChangeAction {
+    Create,
+    Modify,
+    Delete,
+}
+
+impl TryFrom<&str> for ChangeAction {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "create" => Ok(ChangeAction::Create),
+            "modify" => Ok(ChangeAction::Modify),
+            "delete" => Ok(ChangeAction::Delete),
+            _ => Err(anyhow::anyhow!("invalid value: {value}")),
+        }
+    }
+}
+
+/// Callback that gets invoked for each object of an OsmChange file.
+pub type ChangeHandler<'a> = dyn FnMut(ChangeAction, Object) -> anyhow::Result<()> + 'a;
+
+/// Parses an RFC 3339 timestamp.
+fn parse_timestamp(value: &str) -> anyhow::Result<time::OffsetDateTime> {
+    time::OffsetDateTime::parse(value, &time::format_description::well_known::Rfc3339)
+        .context(format!("failed to parse '{value}' as a timestamp"))
+}
+
+/// Formats a unix timestamp in milliseconds, the way Overpass does it.
+fn format_timestamp(millis: i64) -> anyhow::Result<String> {
+    let nanos = i128::from(millis) * 1_000_000;
+    let timestamp = time::OffsetDateTime::from_unix_timestamp_nanos(nanos)?;
+    Ok(timestamp.format(&time::format_description::well_known::Rfc3339)?)
+}
+
+/// Decides if the end of a partial XML tag is inside a quoted attribute value. Attribute values
+/// may be quoted with either '"' or '\'', and the other quote character has no special meaning
+/// inside them.
+fn is_in_quotes(buf: &[u8]) -> bool {
+    let mut quote: Option<u8> = None;
+    for i in buf {
+        match quote {
+            Some(q) if *i == q => quote = None,
+            None if *i == b'"' || *i == b'\'' => quote = Some(*i),
+            _ => (),
+        }
+    }
+    quote.is_some()
+}
+
+/// Reads the next XML tag, without the '<' and '>' delimiters. Returns None at the end of the
+/// input.
+fn read_xml_tag(read: &mut dyn BufRead) -> anyhow::Result<Option<String>> {
+    let mut buf: Vec<u8> = Vec::new();
+    // Skip text until the next tag.
+    if read.read_until(b'<', &mut buf)? == 0 || buf.last() != Some(&b'<') {
+        return Ok(None);
+    }
+    buf.clear();
+    loop {
+        if read.read_until(b'>', &mut buf)? == 0 || buf.last() != Some(&b'>') {
+            return Err(anyhow::anyhow!("unterminated tag"));
+        }
+        // A '>' inside a quoted attribute value or a comment doesn't end the tag.
+        let is_end = if buf.starts_with(b"!--") {
+            buf.ends_with(b"-->")
+        } else {
+            !is_in_quotes(&buf)
+        };
+        if is_end {
+            break;
+        }
+    }
+    buf.pop();
+    Ok(Some(String::from_utf8(buf)?))
+}
+
+/// Parses the inside of an XML tag into a name and an attribute map.
+fn parse_xml_tag(tag: &str) -> anyhow::Result<(String, HashMap<String, String>)> {
+    let tag = tag.trim_end_matches('/');
+    let (name, mut rest) = match tag.find(char::is_whitespace) {
+        Some(index) => (&tag[..index], &tag[index..]),
+        None => (tag, ""),
+    };
+    let mut attributes: HashMap<String, String> = HashMap::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let (key, value) = rest.split_once('=').context("missing '='")?;
+        let value = value.trim_start();
+        let quote = value.chars().next().context("missing value")?;
+        if quote != '"' && quote != '\'' {
+            return Err(anyhow::anyhow!("unquoted attribute value: {key}"));
+        }
+        let value = &value[1..];
+        let end = value.find(quote).context("unterminated attribute value")?;
+        attributes.insert(
+            key.trim().to_string(),
+            html_escape::decode_html_entities(&value[..end]).to_string(),
+        );
+        rest = &value[end + 1..];
+    }
+    Ok((name.to_string(), attributes))
+}
+
+/// Gets a required attribute of an XML element.
+fn get_attribute<'a>(
+    attributes: &'a HashMap<String, String>,
+    key: &str,
+) -> anyhow::Result<&'a String> {
+    attributes
+        .get(key)
+        .context(format!("missing attribute: {key}"))
+}
+
+/// Creates an object from the attributes of a node, way or relation XML element.
+fn make_xml_object(
+    object_type: ObjectType,
+    attributes: &HashMap<String, String>,
+    action: ChangeAction,
+) -> anyhow::Result<Object> {
+    let mut object = Object::new(object_type, get_attribute(attributes, "id")?.parse()?);
+    // Deleted nodes may come without coordinates.
+    if object_type == ObjectType::Node
+        && (action != ChangeAction::Delete || attributes.contains_key("lat"))
+    {
+        object.lat = get_attribute(attributes, "lat")?.parse()?;
+        object.lon = get_attribute(attributes, "lon")?.parse()?;
+    }
+    if let Some(user) = attributes.get("user") {
+        object.user = user.to_string();
+    }
+    if let Some(timestamp) = attributes.get("timestamp") {
+        object.timestamp = timestamp.to_string();
+    }
+    Ok(object)
+}
+
+/// Reads objects from an OSM XML or OsmChange stream. Objects outside a create, modify or delete
+/// block are reported as created. Returns the timestamp of the data, if known.
+fn read_xml(
+    read: &mut dyn BufRead,
+    handler: &mut ChangeHandler<'_>,
+) -> anyhow::Result<Option<time::OffsetDateTime>> {
+    let mut timestamp: Option<time::OffsetDateTime> = None;
+    let mut current: Option<Object> = None;
+    let mut action = ChangeAction::Create;
+    while let Some(tag) = read_xml_tag(read)? {
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            if ObjectType::try_from(name).is_ok() {
+                if let Some(object) = current.take() {
+                    handler(action, object)?;
+                }
+            } else if ChangeAction::try_from(name).is_ok() {
+                action = ChangeAction::Create;
+            }
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let (name, attributes) = parse_xml_tag(&tag)?;
+        match name.as_str() {
+            "osm" => {
+                // Written by e.g. osmium, based on the replication timestamp.
+                if let Some(value) = attributes.get("timestamp") {
+                    timestamp = Some(parse_timestamp(value)?);
+                }
+            }
+            "meta" => {
+                // Written by Overpass.
+                if let Some(value) = attributes.get("osm_base") {
+                    timestamp = Some(parse_timestamp(value)?);
+                }
+            }
+            "create" | "modify" | "delete" => {
+                action = ChangeAction::try_from(name.as_str())?;
+            }
+            "node" | "way" | "relation" => {
+                let object =
+                    make_xml_object(ObjectType::try_from(name.as_str())?, &attributes, action)?;
+                if self_closing {
+                    handler(action, object)?;
+                } else {
+                    current = Some(object);
+                }
+            }
+            "tag" => {
+                if let Some(ref mut object) = current {
+                    object.tags.insert(
+                        get_attribute(&attributes, "k")?.to_string(),
+                        get_attribute(&attributes, "v")?.to_string(),
+                    );
+                }
+            }
+            "nd" => {
+                if let Some(ref mut object) = current {
+                    object
+                        .nodes
+                        .push(get_attribute(&attributes, "ref")?.parse()?);
+                }
+            }
+            "member" => {
+                if let Some(ref mut object) = current {
+                    object.members.push(Member {
+                        object_type: ObjectType::try_from(
+                            get_attribute(&attributes, "type")?.as_str(),
+                        )?,
+                        id: get_attribute(&attributes, "ref")?.parse()?,
+                        role: attributes.get("role").cloned().unwrap_or_default(),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(timestamp)
+}
+
+/// A single value read from a protobuf message.
+enum ProtoValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Minimal protobuf decoder, just enough to read the OSM PBF format.
+struct ProtoReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ProtoReader { data, pos: 0 }
+    }
+
+    fn read_varint(&mut self) -> anyhow::Result<u64> {
+        let mut ret: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = *self.data.get(self.pos).context("truncated varint")?;
+            self.pos += 1;
+            if shift >= 64 {
+                return Err(anyhow::anyhow!("varint is too long"));
+            }
+            ret |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(ret);
+            }
+            shift += 7;
+        }
+    }
+
+    fn skip(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).context("overflow")?;
+        let ret = self.data.get(self.pos..end).context("truncated field")?;
+        self.pos = end;
+        Ok(ret)
+    }
+
+    /// Reads the next field number and value, returns None at the end of the message.
+    fn next_field(&mut self) -> anyhow::Result<Option<(u64, ProtoValue<'a>)>> {
+        if self.pos >= self.data.len() {
+            return Ok(None);
+        }
+        let key = self.read_varint()?;
+        let value = match key & 0x7 {
+            0 => ProtoValue::Varint(self.read_varint()?),
+            1 => {
+                self.skip(8)?;
+                ProtoValue::Fixed
+            }
+            2 => {
+                let len = usize::try_from(self.read_varint()?)?;
+                ProtoValue::Bytes(self.skip(len)?)
+            }
+            5 => {
+                self.skip(4)?;
+                ProtoValue::Fixed
+            }
+            wire_type => {
+                return Err(anyhow::anyhow!("unsupported wire type: {wire_type}"));
+            }
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+impl<'a> ProtoValue<'a> {
+    fn as_varint(&self) -> anyhow::Result<u64> {
+        match self {
+            ProtoValue::Varint(value) => Ok(*value),
+            _ => Err(anyhow::anyhow!("expected a varint")),
+        }
+    }
+
+    fn as_bytes(&self) -> anyhow::Result<&'a [u8]> {
+        match self {
+            ProtoValue::Bytes(value) => Ok(value),
+            _ => Err(anyhow::anyhow!("expected a length-delimited value")),
+        }
+    }
+
+    /// Reads a (possibly packed) repeated varint field.
+    fn as_varints(&self) -> anyhow::Result<Vec<u64>> {
+        match self {
+            ProtoValue::Varint(value) => Ok(vec![*value]),
+            ProtoValue::Bytes(value) => {
+                let mut reader = ProtoReader::new(value);
+                let mut ret: Vec<u64> = Vec::new();
+                while reader.pos < value.len() {
+                    ret.push(reader.read_varint()?);
+                }
+                Ok(ret)
+            }
+            ProtoValue::Fixed => Err(anyhow::anyhow!("expected varints")),
+        }
+    }
+}
+
+/// Decodes a zigzag-encoded signed integer.
+fn zigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Decodes a delta-encoded list of zigzag-encoded signed integers.
+fn delta_decode(values: &[u64]) -> Vec<i64> {
+    let mut sum: i64 = 0;
+    values
+        .iter()
+        .map(|value| {
+            sum += zigzag(*value);
+            sum
+        })
+        .collect()
+}
+
+/// Converts an OSM id to unsigned.
+fn to_id(id: i64) -> anyhow::Result<u64> {
+    u64::try_from(id).context(format!("negative id: {id}"))
+}
+
+/// State which is shared between the groups of a PBF PrimitiveBlock.
+struct PbfBlock {
+    strings: Vec<String>,
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+    date_granularity: i64,
+}
+
+impl PbfBlock {
+    fn get_string(&self, index: u64) -> anyhow::Result<String> {
+        let index = usize::try_from(index)?;
+        Ok(self
+            .strings
+            .get(index)
+            .context(format!("invalid string index: {index}"))?
+            .to_string())
+    }
+
+    fn get_lat(&self, lat: i64) -> f64 {
+        (self.lat_offset + self.granularity * lat) as f64 * 1e-9
+    }
+
+    fn get_lon(&self, lon: i64) -> f64 {
+        (self.lon_offset + self.granularity * lon) as f64 * 1e-9
+    }
+
+    fn get_timestamp(&self, timestamp: i64) -> anyhow::Result<String> {
+        format_timestamp(timestamp * self.date_granularity)
+    }
+
+    /// Sets tags from parallel key and value string index lists.
+    fn set_tags(&self, object: &mut Object, keys: &[u64], vals: &[u64]) -> anyhow::Result<()> {
+        if keys.len() != vals.len() {
+            return Err(anyhow::anyhow!("key and value count mismatch"));
+        }
+        for (key, val) in keys.iter().zip(vals.iter()) {
+            object
+                .tags
+                .insert(self.get_string(*key)?, self.get_string(*val)?);
+        }
+        Ok(())
+    }
+
+    /// Sets the user and timestamp of an object from an Info message.
+    fn set_info(&self, object: &mut Object, data: &[u8]) -> anyhow::Result<()> {
+        let mut reader = ProtoReader::new(data);
+        while let Some((field, value)) = reader.next_field()? {
+            match field {
+                2 => object.timestamp = self.get_timestamp(value.as_varint()? as i64)?,
+                5 => object.user = self.get_string(value.as_varint()?)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the common fields of ways and relations; returns the field numbers it didn't handle.
+    fn read_object<'a>(
+        &self,
+        object: &mut Object,
+        data: &'a [u8],
+    ) -> anyhow::Result<Vec<(u64, ProtoValue<'a>)>> {
+        let mut reader = ProtoReader::new(data);
+        let mut keys: Vec<u64> = Vec::new();
+        let mut vals: Vec<u64> = Vec::new();
+        let mut rest = Vec::new();
+        while let Some((field, value)) = reader.next_field()? {
+            match field {
+                1 => object.id = to_id(value.as_varint()? as i64)?,
+                2 => keys.append(&mut value.as_varints()?),
+                3 => vals.append(&mut value.as_varints()?),
+                4 => self.set_info(object, value.as_bytes()?)?,
+                _ => rest.push((field, value)),
+            }
+        }
+        self.set_tags(object, &keys, &vals)?;
+        Ok(rest)
+    }
+
+    fn read_node(&self, data: &[u8]) -> anyhow::Result<Object> {
+        let mut object = Object::new(ObjectType::Node, 0);
+        let mut reader = ProtoReader::new(data);
+        let mut keys: Vec<u64> = Vec::new();
+        let mut vals: Vec<u64> = Vec::new();
+        while let Some((field, value)) = reader.next_field()? {
+            match field {
+                1 => object.id = to_id(zigzag(value.as_varint()?))?,
+                2 => keys.append(&mut value.as_varints()?),
+                3 => vals.append(&mut value.as_varints()?),
+                4 => self.set_info(&mut object, value.as_bytes()?)?,
+                8 => object.lat = self.get_lat(zigzag(value.as_varint()?)),
+                9 => object.lon = self.get_lon(zigzag(value.as_varint()?)),
+                _ => (),
+            }
+        }
+        self.set_tags(&mut object, &keys, &vals)?;
+        Ok(object)
+    }
+
+    fn read_dense_nodes(&self, data: &[u8], handler: &mut ObjectHandler<'_>) -> anyhow::Result<()> {
+        let mut reader = ProtoReader::new(data);
+        let mut ids: Vec<u64> = Vec::new();
+        let mut lats: Vec<u64> = Vec::new();
+        let mut lons: Vec<u64> = Vec::new();
+        let mut keys_vals: Vec<u64> = Vec::new();
+        let mut timestamps: Vec<u64> = Vec::new();
+        let mut user_sids: Vec<u64> = Vec::new();
+        while let Some((field, value)) = reader.next_field()? {
+            match field {
+                1 => ids.append(&mut value.as_varints()?),
+                5 => {
+                    let mut info_reader = ProtoReader::new(value.as_bytes()?);
+                    while let Some((field, value)) = info_reader.next_field()? {
+                        match field {
+                            2 => timestamps.append(&mut value.as_varints()?),
+                            5 => user_sids.append(&mut value.as_varints()?),
+                            _ => (),
+                        }
+                    }
+                }
+                8 => lats.append(&mut value.as_varints()?),
+                9 => lons.append(&mut value.as_varints()?),
+                10 => keys_vals.append(&mut value.as_varints()?),
+                _ => (),
+            }
+        }
+        if lats.len() != ids.len() || lons.len() != ids.len() {
+            return Err(anyhow::anyhow!("dense node count mismatch"));
+        }
+        let ids = delta_decode(&ids);
+        let lats = delta_decode(&lats);
+        let lons = delta_decode(&lons);
+        let timestamps = delta_decode(&timestamps);
+        let user_sids = delta_decode(&user_sids);
+        let mut keys_vals = keys_vals.iter();
+        for (index, id) in ids.iter().enumerate() {
+            let mut object = Object::new(ObjectType::Node, to_id(*id)?);
+            object.lat = self.get_lat(lats[index]);
+            object.lon = self.get_lon(lons[index]);
+            if let Some(timestamp) = timestamps.get(index) {
+                object.timestamp = self.get_timestamp(*timestamp)?;
+            }
+            if let Some(user_sid) = user_sids.get(index) {
+                object.user = self.get_string(u64::try_from(*user_sid)?)?;
+            }
+            // Tags are a 0-terminated list of key-value string indexes per node.
+            while let Some(key) = keys_vals.next() {
+                if *key == 0 {
+                    break;
+                }
+                let val = keys_vals.next().context("missing dense value")?;
+                object
+                    .tags
+                    .insert(self.get_string(*key)?, self.get_string(*val)?);
+            }
+            handler(object)?;
+        }
+        Ok(())
+    }
+
+    fn read_way(&self, data: &[u8]) -> anyhow::Result<Object> {
+        let mut object = Object::new(ObjectType::Way, 0);
+        let mut refs: Vec<u64> = Vec::new();
+        for (field, value) in self.read_object(&mut object, data)? {
+            if field == 8 {
+                refs.append(&mut value.as_varints()?);
+            }
+        }
+        for id in delta_decode(&refs) {
+            object.nodes.push(to_id(id)?);
+        }
+        Ok(object)
+    }
+
+    fn read_relation(&self, data: &[u8]) -> anyhow::Result<Object> {
+        let mut object = Object::new(ObjectType::Relation, 0);
+        let mut roles: Vec<u64> = Vec::new();
+        let mut ids: Vec<u64> = Vec::new();
+        let mut types: Vec<u64> = Vec::new();
+        for (field, value) in self.read_object(&mut object, data)? {
+            match field {
+                8 => roles.append(&mut value.as_varints()?),
+                9 => ids.append(&mut value.as_varints()?),
+                10 => types.append(&mut value.as_varints()?),
+                _ => (),
+            }
+        }
+        if roles.len() != ids.len() || types.len() != ids.len() {
+            return Err(anyhow::anyhow!("relation member count mismatch"));
+        }
+        for (index, id) in delta_decode(&ids).iter().enumerate() {
+            let object_type = match types[index] {
+                0 => ObjectType::Node,
+                1 => ObjectType::Way,
+                2 => ObjectType::Relation,
+                value => {
+                    return Err(anyhow::anyhow!("invalid member type: {value}"));
+                }
+            };
+            object.members.push(Member {
+                object_type,
+                id: to_id(*id)?,
+                role: self.get_string(roles[index])?,
+            });
+        }
+        Ok(object)
+    }
+
+    fn read_group(&self, data: &[u8], handler: &mut ObjectHandler<'_>) -> anyhow::Result<()> {
+        let mut reader = ProtoReader::new(data);
+        while let Some((field, value)) = reader.next_field()? {
+            match field {
+                1 => handler(self.read_node(value.as_bytes()?)?)?,
+                2 => self.read_dense_nodes(value.as_bytes()?, handler)?,
+                3 => handler(self.read_way(value.as_bytes()?)?)?,
+                4 => handler(self.read_relation(value.as_bytes()?)?)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the objects of a PBF PrimitiveBlock.
+fn read_pbf_primitive_block(data: &[u8], handler: &mut ObjectHandler<'_>) -> anyhow::Result<()> {
+    let mut block = PbfBlock {
+        strings: Vec::new(),
+        granularity: 100,
+        lat_offset: 0,
+        lon_offset: 0,
+        date_granularity: 1000,
+    };
+    let mut groups: Vec<&[u8]> = Vec::new();
+    let mut reader = ProtoReader::new(data);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => {
+                let mut string_reader = ProtoReader::new(value.as_bytes()?);
+                while let Some((field, value)) = string_reader.next_field()? {
+                    if field == 1 {
+                        block
+                            .strings
+                            .push(String::from_utf8(value.as_bytes()?.to_vec())?);
+                    }
+                }
+            }
+            2 => groups.push(value.as_bytes()?),
+            17 => block.granularity = value.as_varint()? as i64,
+            18 => block.date_granularity = value.as_varint()? as i64,
+            19 => block.lat_offset = value.as_varint()? as i64,
+            20 => block.lon_offset = value.as_varint()? as i64,
+            _ => (),
+        }
+    }
+    for group in groups {
+        block.read_group(group, handler)?;
+    }
+    Ok(())
+}
+
+/// Reads a PBF HeaderBlock, returns the replication timestamp, if there is one.
+fn read_pbf_header_block(data: &[u8]) -> anyhow::Result<Option<time::OffsetDateTime>> {
+    let mut timestamp: Option<time::OffsetDateTime> = None;
+    let mut reader = ProtoReader::new(data);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            4 => {
+                let feature = std::str::from_utf8(value.as_bytes()?)?;
+                if !["OsmSchema-V0.6", "DenseNodes"].contains(&feature) {
+                    return Err(anyhow::anyhow!("unsupported required feature: {feature}"));
+                }
+            }
+            32 => {
+                let seconds = value.as_varint()? as i64;
+                timestamp = Some(time::OffsetDateTime::from_unix_timestamp(seconds)?);
+            }
+            _ => (),
+        }
+    }
+    Ok(timestamp)
+}
+
+/// Decompresses the content of a PBF Blob.
+fn read_pbf_blob(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut reader = ProtoReader::new(data);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => return Ok(value.as_bytes()?.to_vec()),
+            3 => {
+                let mut decoder = flate2::read::ZlibDecoder::new(value.as_bytes()?);
+                let mut ret: Vec<u8> = Vec::new();
+                decoder.read_to_end(&mut ret)?;
+                return Ok(ret);
+            }
+            2 => (),
+            _ => {
+                return Err(anyhow::anyhow!("unsupported blob compression: {field}"));
+            }
+        }
+    }
+    Err(anyhow::anyhow!("blob without data"))
+}
+
+/// Reads objects from an OSM PBF stream. Returns the timestamp of the data, if known.
+fn read_pbf(
+    read: &mut dyn Read,
+    handler: &mut ObjectHandler<'_>,
+) -> anyhow::Result<Option<time::OffsetDateTime>> {
+    let mut timestamp: Option<time::OffsetDateTime> = None;
+    loop {
+        let mut size = [0_u8; 4];
+        match read.read_exact(&mut size) {
+            Ok(_) => (),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        let mut header = vec![0_u8; usize::try_from(u32::from_be_bytes(size))?];
+        read.read_exact(&mut header)?;
+        let mut blob_type = String::new();
+        let mut blob_size: usize = 0;
+        let mut reader = ProtoReader::new(&header);
+        while let Some((field, value)) = reader.next_field()? {
+            match field {
+                1 => blob_type = String::from_utf8(value.as_bytes()?.to_vec())?,
+                3 => blob_size = usize::try_from(value.as_varint()?)?,
+                _ => (),
+            }
+        }
+        let mut blob = vec![0_u8; blob_size];
+        read.read_exact(&mut blob)?;
+        match blob_type.as_str() {
+            "OSMHeader" => timestamp = read_pbf_header_block(&read_pbf_blob(&blob)?)?,
+            "OSMData" => read_pbf_primitive_block(&read_pbf_blob(&blob)?, handler)?,
+            // Unknown blob types are to be ignored.
+            _ => (),
+        }
+    }
+    Ok(timestamp)
+}
+
+/// Reads all objects from a PBF (if the path ends with .pbf) or XML extract. Returns the
+/// timestamp of the data, if the extract has it.
+pub fn read_extract(
+    ctx: &context::Context,
+    path: &str,
+    handler: &mut ObjectHandler<'_>,
+) -> anyhow::Result<Option<time::OffsetDateTime>> {
+    let stream = ctx.get_file_system().open_read(path)?;
+    let mut guard = stream.borrow_mut();
+    let mut read = std::io::BufReader::new(&mut *guard);
+    if path.ends_with(".pbf") {
+        read_pbf(&mut read, handler).context(format!("failed to read '{path}' as PBF"))
+    } else {
+        read_xml(&mut read, &mut |_action, object| handler(object))
+            .context(format!("failed to read '{path}' as XML"))
+    }
+}
+
+/// Reads all changes from an OsmChange file, which is gzip-compressed if the path ends with .gz.
+/// Returns the timestamp of the data, if the file has it.
+pub fn read_change(
+    ctx: &context::Context,
+    path: &str,
+    handler: &mut ChangeHandler<'_>,
+) -> anyhow::Result<Option<time::OffsetDateTime>> {
+    let stream = ctx.get_file_system().open_read(path)?;
+    let mut guard = stream.borrow_mut();
+    let ret = if path.ends_with(".gz") {
+        let mut read = std::io::BufReader::new(flate2::read::GzDecoder::new(&mut *guard));
+        read_xml(&mut read, handler)
+    } else {
+        let mut read = std::io::BufReader::new(&mut *guard);
+        read_xml(&mut read, handler)
+    };
+    ret.context(format!("failed to read '{path}' as OsmChange"))
+}
+
+#[cfg(test)]
+pub mod tests;
@@ -0,0 +1,607 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the osm_extract module.
+
+use super::*;
+use std::io::Write as _;
+
+/// Appends a varint to a buffer.
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Minimal protobuf encoder, the counterpart of ProtoReader.
+#[derive(Default)]
+struct ProtoWriter {
+    buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+    fn varint(&mut self, field: u64, value: u64) {
+        write_varint(&mut self.buf, field << 3);
+        write_varint(&mut self.buf, value);
+    }
+
+    fn bytes(&mut self, field: u64, value: &[u8]) {
+        write_varint(&mut self.buf, (field << 3) | 2);
+        write_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn packed(&mut self, field: u64, values: &[u64]) {
+        let mut packed: Vec<u8> = Vec::new();
+        for value in values {
+            write_varint(&mut packed, *value);
+        }
+        self.bytes(field, &packed);
+    }
+}
+
+/// Encodes a signed integer as zigzag.
+fn to_zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Delta and zigzag encodes a list of signed integers.
+fn delta_encode(values: &[i64]) -> Vec<u64> {
+    let mut prev: i64 = 0;
+    values
+        .iter()
+        .map(|value| {
+            let ret = to_zigzag(value - prev);
+            prev = *value;
+            ret
+        })
+        .collect()
+}
+
+/// String table of a PrimitiveBlock under construction.
+struct StringTable {
+    strings: Vec<String>,
+}
+
+impl StringTable {
+    fn get(&mut self, value: &str) -> u64 {
+        match self.strings.iter().position(|i| i == value) {
+            Some(index) => index as u64,
+            None => {
+                self.strings.push(value.to_string());
+                (self.strings.len() - 1) as u64
+            }
+        }
+    }
+
+    /// Sorted tags, as key and value string index lists.
+    fn get_tags(&mut self, object: &Object) -> (Vec<u64>, Vec<u64>) {
+        let mut tags: Vec<_> = object.tags.iter().collect();
+        tags.sort();
+        let keys = tags.iter().map(|(key, _)| self.get(key)).collect();
+        let vals = tags.iter().map(|(_, val)| self.get(val)).collect();
+        (keys, vals)
+    }
+}
+
+/// Converts a timestamp string to unix seconds.
+fn to_seconds(timestamp: &str) -> i64 {
+    parse_timestamp(timestamp).unwrap().unix_timestamp()
+}
+
+/// Converts a coordinate to nanodegrees / the default granularity.
+fn to_coord(value: f64) -> i64 {
+    (value * 1e7).round() as i64
+}
+
+/// Encodes an Info message.
+fn write_info(strings: &mut StringTable, object: &Object) -> Vec<u8> {
+    let mut info = ProtoWriter::default();
+    info.varint(2, to_seconds(&object.timestamp) as u64);
+    info.varint(5, strings.get(&object.user));
+    info.buf
+}
+
+/// Writes a blob with its header to a PBF stream.
+fn write_blob(buf: &mut Vec<u8>, blob_type: &str, data: &[u8], compress: bool) {
+    let mut blob = ProtoWriter::default();
+    if compress {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        blob.varint(2, data.len() as u64);
+        blob.bytes(3, &encoder.finish().unwrap());
+    } else {
+        blob.bytes(1, data);
+    }
+    let mut header = ProtoWriter::default();
+    header.bytes(1, blob_type.as_bytes());
+    header.varint(3, blob.buf.len() as u64);
+    buf.extend_from_slice(&(header.buf.len() as u32).to_be_bytes());
+    buf.extend_from_slice(&header.buf);
+    buf.extend_from_slice(&blob.buf);
+}
+
+/// Encodes objects in the PBF format: the first node is a plain node, the other nodes are dense
+/// ones.
+pub fn write_pbf(objects: &[Object], timestamp: Option<time::OffsetDateTime>) -> Vec<u8> {
+    let mut strings = StringTable {
+        strings: vec!["".into()],
+    };
+    let nodes: Vec<&Object> = objects
+        .iter()
+        .filter(|i| i.object_type == ObjectType::Node)
+        .collect();
+    let mut groups: Vec<Vec<u8>> = Vec::new();
+
+    if let Some((first, rest)) = nodes.split_first() {
+        let mut node = ProtoWriter::default();
+        node.varint(1, to_zigzag(first.id as i64));
+        let (keys, vals) = strings.get_tags(first);
+        node.packed(2, &keys);
+        node.packed(3, &vals);
+        node.bytes(4, &write_info(&mut strings, first));
+        node.varint(8, to_zigzag(to_coord(first.lat)));
+        node.varint(9, to_zigzag(to_coord(first.lon)));
+        let mut group = ProtoWriter::default();
+        group.bytes(1, &node.buf);
+        groups.push(group.buf);
+
+        let mut dense = ProtoWriter::default();
+        let ids: Vec<i64> = rest.iter().map(|i| i.id as i64).collect();
+        dense.packed(1, &delta_encode(&ids));
+        let timestamps: Vec<i64> = rest.iter().map(|i| to_seconds(&i.timestamp)).collect();
+        let users: Vec<i64> = rest.iter().map(|i| strings.get(&i.user) as i64).collect();
+        let mut info = ProtoWriter::default();
+        info.packed(2, &delta_encode(&timestamps));
+        info.packed(5, &delta_encode(&users));
+        dense.bytes(5, &info.buf);
+        let lats: Vec<i64> = rest.iter().map(|i| to_coord(i.lat)).collect();
+        dense.packed(8, &delta_encode(&lats));
+        let lons: Vec<i64> = rest.iter().map(|i| to_coord(i.lon)).collect();
+        dense.packed(9, &delta_encode(&lons));
+        let mut keys_vals: Vec<u64> = Vec::new();
+        for node in rest {
+            let (keys, vals) = strings.get_tags(node);
+            for (key, val) in keys.iter().zip(vals.iter()) {
+                keys_vals.push(*key);
+                keys_vals.push(*val);
+            }
+            keys_vals.push(0);
+        }
+        dense.packed(10, &keys_vals);
+        let mut group = ProtoWriter::default();
+        group.bytes(2, &dense.buf);
+        groups.push(group.buf);
+    }
+
+    let mut group = ProtoWriter::default();
+    for object in objects.iter().filter(|i| i.object_type == ObjectType::Way) {
+        let mut way = ProtoWriter::default();
+        way.varint(1, object.id);
+        // Unpacked keys and values.
+        let (keys, vals) = strings.get_tags(object);
+        for key in keys {
+            way.varint(2, key);
+        }
+        for val in vals {
+            way.varint(3, val);
+        }
+        way.bytes(4, &write_info(&mut strings, object));
+        let refs: Vec<i64> = object.nodes.iter().map(|i| *i as i64).collect();
+        way.packed(8, &delta_encode(&refs));
+        group.bytes(3, &way.buf);
+    }
+    groups.push(group.buf);
+
+    let mut group = ProtoWriter::default();
+    for object in objects
+        .iter()
+        .filter(|i| i.object_type == ObjectType::Relation)
+    {
+        let mut relation = ProtoWriter::default();
+        relation.varint(1, object.id);
+        let (keys, vals) = strings.get_tags(object);
+        relation.packed(2, &keys);
+        relation.packed(3, &vals);
+        relation.bytes(4, &write_info(&mut strings, object));
+        let roles: Vec<u64> = object
+            .members
+            .iter()
+            .map(|i| strings.get(&i.role))
+            .collect();
+        relation.packed(8, &roles);
+        let ids: Vec<i64> = object.members.iter().map(|i| i.id as i64).collect();
+        relation.packed(9, &delta_encode(&ids));
+        let types: Vec<u64> = object
+            .members
+            .iter()
+            .map(|i| match i.object_type {
+                ObjectType::Node => 0,
+                ObjectType::Way => 1,
+                ObjectType::Relation => 2,
+            })
+            .collect();
+        relation.packed(10, &types);
+        group.bytes(4, &relation.buf);
+    }
+    groups.push(group.buf);
+
+    let mut block = ProtoWriter::default();
+    let mut string_table = ProtoWriter::default();
+    for string in &strings.strings {
+        string_table.bytes(1, string.as_bytes());
+    }
+    block.bytes(1, &string_table.buf);
+    for group in groups {
+        block.bytes(2, &group);
+    }
+
+    let mut header = ProtoWriter::default();
+    header.bytes(4, b"OsmSchema-V0.6");
+    header.bytes(4, b"DenseNodes");
+    if let Some(timestamp) = timestamp {
+        header.varint(32, timestamp.unix_timestamp() as u64);
+    }
+
+    let mut buf: Vec<u8> = Vec::new();
+    write_blob(&mut buf, "OSMHeader", &header.buf, /*compress=*/ false);
+    write_blob(&mut buf, "OSMData", &block.buf, /*compress=*/ true);
+    buf
+}
+
+/// Reads all objects of an extract.
+fn read_objects(
+    ctx: &context::Context,
+    path: &str,
+) -> anyhow::Result<(Vec<Object>, Option<time::OffsetDateTime>)> {
+    let mut objects: Vec<Object> = Vec::new();
+    let timestamp = read_extract(ctx, path, &mut |object| {
+        objects.push(object);
+        Ok(())
+    })?;
+    Ok((objects, timestamp))
+}
+
+/// Creates a context where `path` has the given content.
+pub fn make_test_context_with_file(path: &str, content: &[u8]) -> context::Context {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let file = context::tests::TestFileSystem::make_file();
+    file.borrow_mut().write_all(content).unwrap();
+    // The path is used as-is, like a command-line argument.
+    let mut files = HashMap::new();
+    files.insert(path.to_string(), file);
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    ctx
+}
+
+/// Tests read_extract(), the XML case.
+#[test]
+fn test_read_extract_xml() {
+    let ctx = context::tests::make_test_context().unwrap();
+
+    let (objects, timestamp) = read_objects(&ctx, "src/fixtures/file-system/extract.osm").unwrap();
+
+    assert_eq!(
+        timestamp.unwrap(),
+        time::macros::datetime!(2024-01-01 0:00 UTC)
+    );
+    assert_eq!(objects.len(), 23);
+    let node = &objects[8];
+    assert_eq!(node.object_type, ObjectType::Node);
+    assert_eq!(node.id, 9);
+    assert_eq!(node.lat, 47.05);
+    assert_eq!(node.lon, 19.05);
+    assert_eq!(node.user, "Mapper & Co");
+    assert_eq!(node.timestamp, "2020-05-10T12:30:00Z");
+    assert_eq!(node.tags["fixme"], r#"check "this""#);
+    let way = &objects[13];
+    assert_eq!(way.object_type, ObjectType::Way);
+    assert_eq!(way.id, 100);
+    assert_eq!(way.nodes, vec![1, 2, 3]);
+    assert_eq!(way.tags["name"], "Határ út");
+    let relation = &objects[20];
+    assert_eq!(relation.object_type, ObjectType::Relation);
+    assert_eq!(relation.id, 42);
+    assert_eq!(
+        relation.members[2],
+        Member {
+            object_type: ObjectType::Node,
+            id: 9,
+            role: "admin_centre".into()
+        }
+    );
+}
+
+/// Tests read_extract(), the XML case with an Overpass-style timestamp.
+#[test]
+fn test_read_extract_xml_overpass() {
+    let content = r#"<osm version="0.6">
+<meta osm_base='2020-05-10T00:00:00Z'/>
+<node id="1" lat="47" lon="19"></node>
+<node id="2" lat="47" lon="19"><tag k="a" v="b"/></node>
+<tag k="ignored" v="outside an object"/>
+<foo/>
+</osm>
+"#;
+    let ctx = make_test_context_with_file("extract.osm", content.as_bytes());
+
+    let (objects, timestamp) = read_objects(&ctx, "extract.osm").unwrap();
+
+    assert_eq!(
+        timestamp.unwrap(),
+        time::macros::datetime!(2020-05-10 0:00 UTC)
+    );
+    assert_eq!(objects.len(), 2);
+    assert!(objects[0].tags.is_empty());
+    assert_eq!(objects[1].tags["a"], "b");
+}
+
+/// Tests read_extract(), the XML case with single-quoted attribute values.
+#[test]
+fn test_read_extract_xml_single_quotes() {
+    let content = r#"<osm version='0.6'>
+<!-- don't break on an apostrophe in a comment -->
+<node id='1' lat='47' lon='19'><tag k='note' v='a "quoted" value > 1'/></node>
+<node id="2" lat="47" lon="19"><tag k="note" v="it's > 1"/></node>
+</osm>
+"#;
+    let ctx = make_test_context_with_file("extract.osm", content.as_bytes());
+
+    let (objects, _timestamp) = read_objects(&ctx, "extract.osm").unwrap();
+
+    assert_eq!(objects.len(), 2);
+    assert_eq!(objects[0].tags["note"], r#"a "quoted" value > 1"#);
+    assert_eq!(objects[1].tags["note"], "it's > 1");
+}
+
+/// Tests read_extract(), the XML case with invalid input.
+#[test]
+fn test_read_extract_xml_error() {
+    let inputs = [
+        "<osm",
+        r#"<node id=1 lat="0" lon="0"/>"#,
+        r#"<node id="1" lat="0" lon="0/>"#,
+        r#"<node id="1" lat="0" lon/>"#,
+        r#"<node id="1" lat="0" lon= />"#,
+        r#"<node id="1" lat="0"/>"#,
+        r#"<node id="a" lat="0" lon="0"/>"#,
+        r#"<osm timestamp="yesterday">"#,
+        r#"<way id="1"><nd/></way>"#,
+        r#"<relation id="1"><member type="area" ref="1"/></relation>"#,
+    ];
+    for input in inputs {
+        let ctx = make_test_context_with_file("extract.osm", input.as_bytes());
+
+        let ret = read_objects(&ctx, "extract.osm");
+
+        assert!(ret.is_err(), "no error for {input}");
+    }
+}
+
+/// Tests read_extract(), the PBF case.
+#[test]
+fn test_read_extract_pbf() {
+    let ctx = context::tests::make_test_context().unwrap();
+    let (expected, timestamp) = read_objects(&ctx, "src/fixtures/file-system/extract.osm").unwrap();
+    let pbf = write_pbf(&expected, timestamp);
+    let ctx = make_test_context_with_file("extract.osm.pbf", &pbf);
+
+    let (actual, actual_timestamp) = read_objects(&ctx, "extract.osm.pbf").unwrap();
+
+    assert_eq!(actual_timestamp, timestamp);
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(expected.iter()) {
+        assert!((actual.lat - expected.lat).abs() < 1e-7);
+        assert!((actual.lon - expected.lon).abs() < 1e-7);
+        let mut actual = actual.clone();
+        actual.lat = expected.lat;
+        actual.lon = expected.lon;
+        assert_eq!(&actual, expected);
+    }
+}
+
+/// Tests read_extract(), the PBF case without a replication timestamp.
+#[test]
+fn test_read_extract_pbf_no_timestamp() {
+    let pbf = write_pbf(&[], None);
+    let ctx = make_test_context_with_file("extract.osm.pbf", &pbf);
+
+    let (objects, timestamp) = read_objects(&ctx, "extract.osm.pbf").unwrap();
+
+    assert!(objects.is_empty());
+    assert!(timestamp.is_none());
+}
+
+/// Tests read_extract(), the PBF case with unsupported or invalid blobs.
+#[test]
+fn test_read_extract_pbf_error() {
+    let mut header = ProtoWriter::default();
+    header.bytes(4, b"HistoricalInformation");
+    let mut unsupported_feature: Vec<u8> = Vec::new();
+    write_blob(
+        &mut unsupported_feature,
+        "OSMHeader",
+        &header.buf,
+        /*compress=*/ false,
+    );
+
+    let mut blob = ProtoWriter::default();
+    blob.bytes(4, b"lzma");
+    let mut lzma = ProtoWriter::default();
+    lzma.bytes(1, b"OSMData");
+    lzma.varint(3, blob.buf.len() as u64);
+    let mut unsupported_compression: Vec<u8> = Vec::new();
+    unsupported_compression.extend_from_slice(&(lzma.buf.len() as u32).to_be_bytes());
+    unsupported_compression.extend_from_slice(&lzma.buf);
+    unsupported_compression.extend_from_slice(&blob.buf);
+
+    let mut empty = ProtoWriter::default();
+    empty.bytes(1, b"OSMData");
+    let mut no_data: Vec<u8> = Vec::new();
+    no_data.extend_from_slice(&(empty.buf.len() as u32).to_be_bytes());
+    no_data.extend_from_slice(&empty.buf);
+
+    let truncated: Vec<u8> = 10_u32.to_be_bytes().to_vec();
+
+    for input in [
+        unsupported_feature,
+        unsupported_compression,
+        no_data,
+        truncated,
+    ] {
+        let ctx = make_test_context_with_file("extract.osm.pbf", &input);
+
+        let ret = read_objects(&ctx, "extract.osm.pbf");
+
+        assert!(ret.is_err());
+    }
+}
+
+/// Tests read_extract(), the PBF case with an unknown blob type.
+#[test]
+fn test_read_extract_pbf_unknown_blob() {
+    let mut pbf: Vec<u8> = Vec::new();
+    write_blob(&mut pbf, "OSMFuture", b"", /*compress=*/ false);
+    let ctx = make_test_context_with_file("extract.osm.pbf", &pbf);
+
+    let (objects, _) = read_objects(&ctx, "extract.osm.pbf").unwrap();
+
+    assert!(objects.is_empty());
+}
+
+/// Tests ProtoReader, the error cases.
+#[test]
+fn test_proto_reader_error() {
+    // Truncated varint.
+    assert!(ProtoReader::new(&[0x80]).read_varint().is_err());
+    // Too long varint.
+    assert!(ProtoReader::new(&[0xff; 11]).read_varint().is_err());
+    // Unsupported wire type: start group.
+    assert!(ProtoReader::new(&[0x0b]).next_field().is_err());
+    // Truncated length-delimited field.
+    assert!(ProtoReader::new(&[0x0a, 0x02, 0x00]).next_field().is_err());
+    // Fixed fields are skipped.
+    let mut reader = ProtoReader::new(&[0x09, 0, 0, 0, 0, 0, 0, 0, 0, 0x0d, 0, 0, 0, 0]);
+    let (field, value) = reader.next_field().unwrap().unwrap();
+    assert_eq!(field, 1);
+    assert!(value.as_varint().is_err());
+    assert!(value.as_bytes().is_err());
+    assert!(value.as_varints().is_err());
+    let (field, _) = reader.next_field().unwrap().unwrap();
+    assert_eq!(field, 1);
+    assert!(reader.next_field().unwrap().is_none());
+}
+
+/// Tests zigzag() and delta_decode().
+#[test]
+fn test_zigzag() {
+    assert_eq!(zigzag(0), 0);
+    assert_eq!(zigzag(1), -1);
+    assert_eq!(zigzag(2), 1);
+    assert_eq!(zigzag(3), -2);
+    assert_eq!(
+        delta_decode(&[to_zigzag(5), to_zigzag(-2), to_zigzag(10)]),
+        vec![5, 3, 13]
+    );
+}
+
+/// Tests to_id().
+#[test]
+fn test_to_id() {
+    assert_eq!(to_id(42).unwrap(), 42);
+    assert!(to_id(-1).is_err());
+}
+
+/// Tests the Display and TryFrom impls of ObjectType.
+#[test]
+fn test_object_type() {
+    for object_type in [ObjectType::Node, ObjectType::Way, ObjectType::Relation] {
+        let string = object_type.to_string();
+        assert_eq!(ObjectType::try_from(string.as_str()).unwrap(), object_type);
+    }
+    assert!(ObjectType::try_from("area").is_err());
+}
+
+/// Tests read_change().
+#[test]
+fn test_read_change() {
+    let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6" generator="osmium/1.16.0">
+  <create>
+    <node id="1" version="1" lat="47.0" lon="19.0"/>
+  </create>
+  <modify>
+    <way id="2" version="2">
+      <nd ref="1"/>
+      <tag k="name" v="Új út"/>
+    </way>
+  </modify>
+  <delete>
+    <node id="3" version="3"/>
+  </delete>
+  <node id="4" version="1" lat="47.0" lon="19.0"/>
+</osmChange>
+"#;
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+    for (path, content) in [
+        ("000.osc", content.as_bytes().to_vec()),
+        ("000.osc.gz", compressed),
+    ] {
+        let ctx = make_test_context_with_file(path, &content);
+        let mut changes: Vec<(ChangeAction, ObjectType, u64)> = Vec::new();
+
+        let timestamp = read_change(&ctx, path, &mut |action, object| {
+            changes.push((action, object.object_type, object.id));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(timestamp, None);
+        assert_eq!(
+            changes,
+            vec![
+                (ChangeAction::Create, ObjectType::Node, 1),
+                (ChangeAction::Modify, ObjectType::Way, 2),
+                (ChangeAction::Delete, ObjectType::Node, 3),
+                (ChangeAction::Create, ObjectType::Node, 4),
+            ]
+        );
+    }
+}
+
+/// Tests read_change(): the case when the input is not gzip-compressed, despite the extension.
+#[test]
+fn test_read_change_error() {
+    let ctx = make_test_context_with_file("000.osc.gz", b"<osmChange>");
+
+    let ret = read_change(&ctx, "000.osc.gz", &mut |_action, _object| Ok(()));
+
+    assert!(ret.is_err());
+}
+
+/// Tests the TryFrom impl of ChangeAction.
+#[test]
+fn test_change_action() {
+    assert_eq!(
+        ChangeAction::try_from("modify").unwrap(),
+        ChangeAction::Modify
+    );
+    assert!(ChangeAction::try_from("upsert").is_err());
+}
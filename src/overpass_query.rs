@@ -19,6 +19,40 @@ pub fn overpass_query(ctx: &context::Context, query: &str) -> anyhow::Result<Str
     ctx.get_network().urlopen(&url, query)
 }
 
+/// Posts multiple query strings to the overpass API in parallel and returns the result strings.
+pub fn overpass_query_many(
+    ctx: &context::Context,
+    queries: &[String],
+) -> Vec<anyhow::Result<String>> {
+    let url = ctx.get_ini().get_overpass_uri() + "/api/interpreter";
+    let requests: Vec<(String, String)> = queries
+        .iter()
+        .map(|query| (url.to_string(), query.to_string()))
+        .collect();
+
+    ctx.get_network().urlopen_many(&requests)
+}
+
+/// Checks how many overpass queries can be started right now. Assumes one if the status is
+/// unknown.
+pub fn overpass_query_available_slots(ctx: &context::Context) -> usize {
+    let url = ctx.get_ini().get_overpass_uri() + "/api/status";
+    let status = match ctx.get_network().urlopen(&url, "") {
+        Ok(value) => value,
+        _ => {
+            return 1;
+        }
+    };
+    let re = regex::Regex::new(r"^(\d+) slots? available now").unwrap();
+    for line in status.lines() {
+        if let Some(cap) = re.captures(line) {
+            // This should never fail since the regex only allows numbers.
+            return cap[1].parse::<usize>().expect("parse() to usize failed");
+        }
+    }
+    0
+}
+
 /// Checks if we need to sleep before executing an overpass query.
 pub fn overpass_query_need_sleep(ctx: &context::Context) -> i32 {
     let url = ctx.get_ini().get_overpass_uri() + "/api/status";
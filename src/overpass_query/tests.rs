@@ -82,3 +82,58 @@ fn test_overpass_query() {
 
     assert_eq!(buf.starts_with("@id"), true);
 }
+
+/// Tests overpass_query_many().
+#[test]
+fn test_overpass_query_many() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let routes = vec![
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/interpreter",
+            /*data_path=*/ "src/fixtures/network/overpass-happy.overpassql",
+            /*result_path=*/ "src/fixtures/network/overpass-happy.csv",
+        ),
+        context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/interpreter",
+            /*data_path=*/ "",
+            /*result_path=*/ "",
+        ),
+    ];
+    let network = context::tests::TestNetwork::new(&routes);
+    let network_rc: Rc<dyn context::Network> = Rc::new(network);
+    ctx.set_network(network_rc);
+    let query = ctx
+        .get_file_system()
+        .read_to_string("src/fixtures/network/overpass-happy.overpassql")
+        .unwrap();
+
+    let ret = overpass_query_many(&ctx, &[query, "".into()]);
+
+    assert_eq!(ret.len(), 2);
+    assert_eq!(ret[0].as_ref().unwrap().starts_with("@id"), true);
+    assert_eq!(ret[1].is_err(), true);
+}
+
+/// Tests overpass_query_available_slots().
+#[test]
+fn test_overpass_query_available_slots() {
+    let cases = [
+        ("src/fixtures/network/overpass-status-happy.txt", 2),
+        ("src/fixtures/network/overpass-status-wait.txt", 0),
+        // Unknown status.
+        ("", 1),
+    ];
+    for (result_path, expected) in cases {
+        let mut ctx = context::tests::make_test_context().unwrap();
+        let routes = vec![context::tests::URLRoute::new(
+            /*url=*/ "https://overpass-api.de/api/status",
+            /*data_path=*/ "",
+            result_path,
+        )];
+        let network = context::tests::TestNetwork::new(&routes);
+        let network_rc: Rc<dyn context::Network> = Rc::new(network);
+        ctx.set_network(network_rc);
+
+        assert_eq!(overpass_query_available_slots(&ctx), expected);
+    }
+}
@@ -112,7 +112,7 @@ fn get_frequent_relations(
 }
 
 /// Builds a name -> create_date dictionary for relations.
-fn get_relation_create_dates(
+pub fn get_relation_create_dates(
     ctx: &context::Context,
 ) -> anyhow::Result<HashMap<String, time::OffsetDateTime>> {
     let mut ret: HashMap<String, time::OffsetDateTime> = HashMap::new();
@@ -0,0 +1,492 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The reference module imports reference streets and house numbers into the database, from the
+//! sources declared in workdir/wsgi.ini.
+
+use crate::context;
+use crate::util;
+use anyhow::Context as _;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::DerefMut;
+
+lazy_static! {
+    static ref NULL_END: regex::Regex = regex::Regex::new(r" null$").unwrap();
+}
+
+/// What a reference source provides.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferenceKind {
+    /// Streets with house numbers.
+    Housenumbers,
+    /// Streets only.
+    Streets,
+}
+
+impl TryFrom<&str> for ReferenceKind {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "housenumbers" => Ok(ReferenceKind::Housenumbers),
+            "streets" => Ok(ReferenceKind::Streets),
+            _ => Err(anyhow::anyhow!("invalid reference kind: {value:?}")),
+        }
+    }
+}
+
+/// The file format of a reference source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferenceFormat {
+    /// Tab-separated values with a header row.
+    Tsv,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// A GeoJSON feature collection, columns are feature properties.
+    GeoJson,
+    /// An OpenAddresses CSV file.
+    OpenAddresses,
+}
+
+impl TryFrom<&str> for ReferenceFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "tsv" => Ok(ReferenceFormat::Tsv),
+            "csv" => Ok(ReferenceFormat::Csv),
+            "geojson" => Ok(ReferenceFormat::GeoJson),
+            "openaddresses" => Ok(ReferenceFormat::OpenAddresses),
+            _ => Err(anyhow::anyhow!("invalid reference format: {value:?}")),
+        }
+    }
+}
+
+/// A reference source, with the defaults of its format resolved.
+#[derive(Clone, Debug)]
+pub struct ReferenceSource {
+    /// What the source provides.
+    pub kind: ReferenceKind,
+    /// File format.
+    pub format: ReferenceFormat,
+    /// Absolute path of the file.
+    pub path: String,
+    /// Provenance label, may be empty.
+    pub label: String,
+    /// Column of the street name.
+    pub street_column: String,
+    /// Column of the house number.
+    pub housenumber_column: String,
+    /// Column of the comment, if any.
+    pub comment_column: Option<String>,
+    /// Column of the refcounty area key, if any.
+    pub refcounty_column: Option<String>,
+    /// Column of the refsettlement area key, if any.
+    pub refsettlement_column: Option<String>,
+    /// House numbers from a supplementary source are always marked in the output.
+    pub supplementary: bool,
+}
+
+impl ReferenceSource {
+    /// Resolves a configured reference source.
+    fn from_config(
+        ini: &context::Ini,
+        config: &context::ReferenceSourceConfig,
+    ) -> anyhow::Result<Self> {
+        let kind = ReferenceKind::try_from(config.kind.as_str())?;
+        let format = ReferenceFormat::try_from(config.format.as_str())?;
+        let mut source = ReferenceSource::new(kind, format, &ini.get_abspath(&config.path));
+        source.label = config.label.clone().unwrap_or_default();
+        if let Some(ref value) = config.street_column {
+            source.street_column = value.to_string();
+        }
+        if let Some(ref value) = config.housenumber_column {
+            source.housenumber_column = value.to_string();
+        }
+        // An empty column name means the column is not available.
+        let optional = |value: &Option<String>, fallback: Option<String>| match value {
+            Some(value) if value.is_empty() => None,
+            Some(value) => Some(value.to_string()),
+            None => fallback,
+        };
+        source.comment_column = optional(&config.comment_column, source.comment_column);
+        source.refcounty_column = optional(&config.refcounty_column, source.refcounty_column);
+        source.refsettlement_column =
+            optional(&config.refsettlement_column, source.refsettlement_column);
+        source.supplementary = config.supplementary;
+        Ok(source)
+    }
+
+    /// Creates a source with the default columns of its format.
+    fn new(kind: ReferenceKind, format: ReferenceFormat, path: &str) -> Self {
+        let (refcounty, refsettlement, street, housenumber) = match format {
+            ReferenceFormat::OpenAddresses => ("REGION", "CITY", "STREET", "NUMBER"),
+            _ => ("COUNTY_CODE", "SETTLEMENT_CODE", "STREET", "HOUSENUMBER"),
+        };
+        ReferenceSource {
+            kind,
+            format,
+            path: path.into(),
+            label: String::new(),
+            street_column: street.into(),
+            housenumber_column: housenumber.into(),
+            comment_column: None,
+            refcounty_column: Some(refcounty.into()),
+            refsettlement_column: Some(refsettlement.into()),
+            supplementary: false,
+        }
+    }
+}
+
+/// Gets the reference sources: either the explicit [[wsgi.reference_sources]] tables or the
+/// reference_housenumbers and reference_street TSV files.
+pub fn get_reference_sources(ini: &context::Ini) -> anyhow::Result<Vec<ReferenceSource>> {
+    let configs = ini.get_reference_sources();
+    if !configs.is_empty() {
+        return configs
+            .iter()
+            .map(|config| {
+                ReferenceSource::from_config(ini, config)
+                    .with_context(|| format!("failed to parse reference source {:?}", config.path))
+            })
+            .collect();
+    }
+
+    let mut sources = Vec::new();
+    for path in ini.get_reference_housenumber_paths()? {
+        if path.ends_with('/') {
+            continue;
+        }
+        let mut source =
+            ReferenceSource::new(ReferenceKind::Housenumbers, ReferenceFormat::Tsv, &path);
+        if path
+            .split('/')
+            .next_back()
+            .unwrap()
+            .starts_with("hazszamok_kieg")
+        {
+            source.comment_column = Some("COMMENT".into());
+            source.supplementary = true;
+        }
+        sources.push(source);
+    }
+    let path = ini.get_reference_street_path()?;
+    if !path.ends_with('/') {
+        sources.push(ReferenceSource::new(
+            ReferenceKind::Streets,
+            ReferenceFormat::Tsv,
+            &path,
+        ));
+    }
+    Ok(sources)
+}
+
+/// One row of a reference source, in the layout of the ref_housenumbers table.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ReferenceRow {
+    refcounty: String,
+    refsettlement: String,
+    street: String,
+    housenumber: String,
+    comment: String,
+}
+
+/// Maps one record of a source to a reference row.
+fn make_row(source: &ReferenceSource, get: &dyn Fn(&str) -> Option<String>) -> ReferenceRow {
+    let get_optional = |column: &Option<String>| match column {
+        Some(column) => get(column).unwrap_or_default(),
+        None => String::new(),
+    };
+    let mut comment = get_optional(&source.comment_column);
+    if source.supplementary && comment.is_empty() {
+        // A non-empty comment marks the house number as coming from a supplementary source.
+        comment = " ".into();
+    }
+    ReferenceRow {
+        refcounty: get_optional(&source.refcounty_column),
+        refsettlement: get_optional(&source.refsettlement_column),
+        street: get(&source.street_column).unwrap_or_default(),
+        housenumber: match source.kind {
+            ReferenceKind::Housenumbers => get(&source.housenumber_column).unwrap_or_default(),
+            ReferenceKind::Streets => String::new(),
+        },
+        comment,
+    }
+}
+
+/// Reads a CSV-like source, passing each row to `handle_row`.
+fn read_csv_rows(
+    ctx: &context::Context,
+    source: &ReferenceSource,
+    handle_row: &mut dyn FnMut(ReferenceRow) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let delimiter = match source.format {
+        ReferenceFormat::Tsv => b'\t',
+        _ => b',',
+    };
+    let stream = ctx.get_file_system().open_read(&source.path)?;
+    let mut guard = stream.borrow_mut();
+    let read = std::io::BufReader::new(guard.deref_mut());
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .double_quote(true)
+        .flexible(true)
+        .from_reader(read);
+    let headers = reader.headers()?.clone();
+    let mut required = vec![&source.street_column];
+    if source.kind == ReferenceKind::Housenumbers {
+        required.push(&source.housenumber_column);
+    }
+    required.extend(source.comment_column.iter());
+    required.extend(source.refcounty_column.iter());
+    required.extend(source.refsettlement_column.iter());
+    for column in required {
+        if !headers.iter().any(|i| i == column) {
+            return Err(anyhow::anyhow!("no column {column:?} in {:?}", source.path));
+        }
+    }
+
+    let indexes: HashMap<&str, usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(index, column)| (column, index))
+        .collect();
+    for result in reader.records() {
+        let record = result?;
+        let get = |column: &str| -> Option<String> {
+            let index = indexes.get(column)?;
+            record.get(*index).map(|i| i.to_string())
+        };
+        handle_row(make_row(source, &get))?;
+    }
+    Ok(())
+}
+
+/// Reads a GeoJSON source, passing each row to `handle_row`.
+fn read_geojson_rows(
+    ctx: &context::Context,
+    source: &ReferenceSource,
+    handle_row: &mut dyn FnMut(ReferenceRow) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let data = ctx.get_file_system().read_to_string(&source.path)?;
+    let json: serde_json::Value = serde_json::from_str(&data)?;
+    let features = json["features"]
+        .as_array()
+        .with_context(|| format!("no features in {:?}", source.path))?;
+    for feature in features {
+        let properties = &feature["properties"];
+        let get = |column: &str| -> Option<String> {
+            match &properties[column] {
+                serde_json::Value::String(value) => Some(value.to_string()),
+                serde_json::Value::Number(value) => Some(value.to_string()),
+                _ => None,
+            }
+        };
+        handle_row(make_row(source, &get))?;
+    }
+    Ok(())
+}
+
+/// Reads all rows of a source, skipping rows without a street or house number. Rows are passed to
+/// `handle_row` while reading, so large sources are not kept in memory.
+fn read_rows(
+    ctx: &context::Context,
+    source: &ReferenceSource,
+    handle_row: &mut dyn FnMut(ReferenceRow) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut handle_row = |row: ReferenceRow| -> anyhow::Result<()> {
+        if row.street.is_empty()
+            || (source.kind == ReferenceKind::Housenumbers && row.housenumber.is_empty())
+        {
+            return Ok(());
+        }
+        handle_row(row)
+    };
+    match source.format {
+        ReferenceFormat::GeoJson => read_geojson_rows(ctx, source, &mut handle_row),
+        _ => read_csv_rows(ctx, source, &mut handle_row),
+    }
+}
+
+/// Checks if a reference table is imported already.
+fn is_imported(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<bool> {
+    let mut stmt = conn.prepare(&format!(
+        "select count(*) from (select 0 from {table} limit 1)"
+    ))?;
+    let count: i64 = stmt.query_row([], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+/// Builds an in-database index from the reference sources (house number version).
+pub fn build_reference_index(ctx: &context::Context) -> anyhow::Result<()> {
+    let sources = get_reference_sources(ctx.get_ini())?;
+    let mut conn = ctx.get_database_connection()?;
+    if is_imported(&conn, "ref_housenumbers")? {
+        return Ok(());
+    }
+
+    for source in sources
+        .iter()
+        .filter(|i| i.kind == ReferenceKind::Housenumbers)
+    {
+        let tx = conn.transaction()?;
+        read_rows(ctx, source, &mut |row| {
+            tx.execute(
+                "insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment, source) values (?1, ?2, ?3, ?4, ?5, ?6)",
+                [row.refcounty, row.refsettlement, row.street, row.housenumber, row.comment, source.label.clone()],
+            )?;
+            Ok(())
+        })?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Builds an in-database index from the reference sources (street version).
+pub fn build_street_reference_index(ctx: &context::Context) -> anyhow::Result<()> {
+    let sources = get_reference_sources(ctx.get_ini())?;
+    let mut conn = ctx.get_database_connection()?;
+    if is_imported(&conn, "ref_streets")? {
+        return Ok(());
+    }
+
+    for source in sources.iter().filter(|i| i.kind == ReferenceKind::Streets) {
+        let tx = conn.transaction()?;
+        read_rows(ctx, source, &mut |row| {
+            let street = NULL_END.replace(&row.street, "").to_string();
+            tx.execute(
+                "insert into ref_streets (county_code, settlement_code, street, source) values (?1, ?2, ?3, ?4)",
+                [row.refcounty, row.refsettlement, street, source.label.clone()],
+            )?;
+            Ok(())
+        })?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// What is wrong with the postcode of an OSM address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostcodeProblem {
+    /// No addr:postcode.
+    Missing,
+    /// Doesn't look like any reference postcode.
+    Malformed,
+    /// Not a postcode of the settlement, or not a postcode at all.
+    Unknown,
+}
+
+/// Postcodes of the reference zipcounts file.
+#[derive(Default)]
+pub struct ReferencePostcodes {
+    postcodes: HashSet<String>,
+    /// Shapes of the reference postcodes, e.g. '9999' for '1111'.
+    shapes: HashSet<String>,
+    /// (refcounty, refsettlement) -> postcodes, if the file has settlement columns.
+    settlements: HashMap<(String, String), HashSet<String>>,
+}
+
+/// Turns a postcode into its shape: digits are replaced with '9', letters with 'A'.
+fn get_postcode_shape(postcode: &str) -> String {
+    postcode
+        .chars()
+        .map(|i| {
+            if i.is_ascii_digit() {
+                '9'
+            } else if i.is_alphabetic() {
+                'A'
+            } else {
+                i
+            }
+        })
+        .collect()
+}
+
+impl ReferencePostcodes {
+    /// Checks an OSM postcode against all reference postcodes. Returns None if there is no
+    /// reference to check against.
+    pub fn check(&self, postcode: &str) -> Option<PostcodeProblem> {
+        if self.postcodes.is_empty() {
+            return None;
+        }
+        if postcode.is_empty() {
+            return Some(PostcodeProblem::Missing);
+        }
+        if !self.shapes.contains(&get_postcode_shape(postcode)) {
+            return Some(PostcodeProblem::Malformed);
+        }
+        if !self.postcodes.contains(postcode) {
+            return Some(PostcodeProblem::Unknown);
+        }
+        None
+    }
+
+    /// Checks an OSM postcode against the reference postcodes of a settlement. Returns None if
+    /// the reference doesn't know the postcodes of the settlement.
+    pub fn check_settlement(
+        &self,
+        postcode: &str,
+        refcounty: &str,
+        refsettlements: &[String],
+    ) -> Option<PostcodeProblem> {
+        let settlement_postcodes: Vec<&HashSet<String>> = refsettlements
+            .iter()
+            .filter_map(|refsettlement| {
+                self.settlements
+                    .get(&(refcounty.to_string(), refsettlement.to_string()))
+            })
+            .collect();
+        if settlement_postcodes.is_empty()
+            || settlement_postcodes.iter().any(|i| i.contains(postcode))
+        {
+            return None;
+        }
+        Some(PostcodeProblem::Unknown)
+    }
+
+    /// Determines if the reference knows the postcodes of settlements.
+    pub fn has_settlements(&self) -> bool {
+        !self.settlements.is_empty()
+    }
+}
+
+/// Reads the postcodes of the reference zipcounts file, an empty result in case there is no such
+/// file.
+pub fn get_reference_postcodes(ctx: &context::Context) -> anyhow::Result<ReferencePostcodes> {
+    let mut ret = ReferencePostcodes::default();
+    let path = ctx.get_ini().get_reference_zipcounts_path()?;
+    if path.ends_with('/') || !ctx.get_file_system().path_exists(&path) {
+        return Ok(ret);
+    }
+
+    let stream = ctx.get_file_system().open_read(&path)?;
+    let mut guard = stream.borrow_mut();
+    let mut read = guard.deref_mut();
+    let mut csv_reader = util::make_csv_reader(&mut read);
+    for result in csv_reader.deserialize() {
+        let row: util::ZipCount = result.context(format!("failed to read {path}"))?;
+        ret.shapes.insert(get_postcode_shape(&row.zip));
+        if !row.settlement_code.is_empty() {
+            ret.settlements
+                .entry((row.county_code, row.settlement_code))
+                .or_default()
+                .insert(row.zip.to_string());
+        }
+        ret.postcodes.insert(row.zip);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,289 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the reference module.
+
+use super::*;
+use std::io::Write;
+
+/// Tests build_reference_index().
+#[test]
+fn test_build_reference_index() {
+    let ctx = context::tests::make_test_context().unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch("delete from ref_housenumbers").unwrap();
+    }
+    build_reference_index(&ctx).unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        let mut stmt = conn
+            .prepare("select count(*) from ref_housenumbers")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        while let Some(row) = rows.next().unwrap() {
+            let count: i64 = row.get(0).unwrap();
+            // Empty table, so changes from 0 to 14 + 1.
+            assert_eq!(count, 15);
+        }
+    }
+
+    build_reference_index(&ctx).unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        let mut stmt = conn
+            .prepare("select count(*) from ref_housenumbers")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        while let Some(row) = rows.next().unwrap() {
+            let count: i64 = row.get(0).unwrap();
+            // Early return, so doesn't change from 15 to 30.
+            assert_eq!(count, 15);
+        }
+    }
+}
+
+/// Tests build_street_reference_index().
+#[test]
+fn test_build_street_reference_index() {
+    let ctx = context::tests::make_test_context().unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch("delete from ref_streets").unwrap();
+    }
+    build_street_reference_index(&ctx).unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        let mut stmt = conn.prepare("select count(*) from ref_streets").unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        while let Some(row) = rows.next().unwrap() {
+            let count: i64 = row.get(0).unwrap();
+            // Empty table, so changes from 0 to 6.
+            assert_eq!(count, 6);
+        }
+    }
+
+    build_street_reference_index(&ctx).unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        let mut stmt = conn.prepare("select count(*) from ref_streets").unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        while let Some(row) = rows.next().unwrap() {
+            let count: i64 = row.get(0).unwrap();
+            // Early return, so doesn't change from 6 to 12.
+            assert_eq!(count, 6);
+        }
+    }
+}
+
+/// Tests build_reference_index(), the hazszamok_kieg case.
+#[test]
+fn test_build_reference_index_supplementary() {
+    let ctx = context::tests::make_test_context().unwrap();
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch("delete from ref_housenumbers").unwrap();
+    }
+
+    build_reference_index(&ctx).unwrap();
+
+    let conn = ctx.get_database_connection().unwrap();
+    let comments: Vec<String> = conn
+        .prepare("select comment from ref_housenumbers where street = 'Márton Áron tér'")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|i| i.unwrap())
+        .collect();
+    assert_eq!(comments, ["comment"]);
+}
+
+/// Tests build_reference_index() and build_street_reference_index(), the configured sources
+/// case.
+#[test]
+fn test_build_reference_index_sources() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let ini = context::tests::make_test_ini(
+        &ctx,
+        &format!(
+            r#"[[wsgi.reference_sources]]
+kind = 'housenumbers'
+format = 'csv'
+path = '{}'
+label = 'National Address Register'
+street_column = 'street'
+housenumber_column = 'number'
+refcounty_column = ''
+refsettlement_column = 'municipality'
+[[wsgi.reference_sources]]
+kind = 'streets'
+format = 'geojson'
+path = '{}'
+refcounty_column = ''
+street_column = 'name'
+refsettlement_column = 'code'
+"#,
+            ctx.get_abspath("workdir/refs/addresses.csv"),
+            ctx.get_abspath("workdir/refs/streets.geojson")
+        ),
+    );
+    ctx.set_ini(ini);
+    let csv = context::tests::TestFileSystem::make_file();
+    csv.borrow_mut()
+        .write_all("street,number,municipality\nMain Street,1,42\nMain Street,,42\n".as_bytes())
+        .unwrap();
+    let geojson = context::tests::TestFileSystem::write_json_to_file(&serde_json::json!({
+        "type": "FeatureCollection",
+        "features": [
+            {"type": "Feature", "geometry": null, "properties": {"name": "Main Street", "code": 42}},
+        ]
+    }));
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("workdir/refs/addresses.csv", &csv),
+            ("workdir/refs/streets.geojson", &geojson),
+        ],
+    );
+    ctx.set_file_system(&context::tests::TestFileSystem::from_files(&files));
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch("delete from ref_housenumbers; delete from ref_streets")
+            .unwrap();
+    }
+
+    build_reference_index(&ctx).unwrap();
+    build_street_reference_index(&ctx).unwrap();
+
+    let conn = ctx.get_database_connection().unwrap();
+    let housenumbers: Vec<(String, String, String, String, String)> = conn
+        .prepare(
+            "select county_code, settlement_code, street, housenumber, source from ref_housenumbers",
+        )
+        .unwrap()
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+        })
+        .unwrap()
+        .map(|i| i.unwrap())
+        .collect();
+    // The row without a house number is skipped.
+    assert_eq!(
+        housenumbers,
+        [(
+            "".to_string(),
+            "42".to_string(),
+            "Main Street".to_string(),
+            "1".to_string(),
+            "National Address Register".to_string()
+        )]
+    );
+    let streets: Vec<(String, String, String)> = conn
+        .prepare("select county_code, settlement_code, street from ref_streets")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(|i| i.unwrap())
+        .collect();
+    assert_eq!(
+        streets,
+        [("".to_string(), "42".to_string(), "Main Street".to_string())]
+    );
+}
+
+/// Tests get_reference_sources(), the error cases.
+#[test]
+fn test_get_reference_sources_error() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let ini = context::tests::make_test_ini(
+        &ctx,
+        "[[wsgi.reference_sources]]\nkind = 'housenumbers'\nformat = 'xlsx'\npath = 'refs.xlsx'\n",
+    );
+    assert!(get_reference_sources(&ini).is_err());
+
+    // Missing column.
+    let ini = context::tests::make_test_ini(
+        &ctx,
+        &format!(
+            "[[wsgi.reference_sources]]\nkind = 'housenumbers'\nformat = 'openaddresses'\npath = '{}'\n",
+            ctx.get_abspath("workdir/refs/oa.csv")
+        ),
+    );
+    ctx.set_ini(ini);
+    let csv = context::tests::TestFileSystem::make_file();
+    csv.borrow_mut()
+        .write_all("LON,LAT,NUMBER,STREET,CITY\n19.0,47.0,1,Main Street,Town\n".as_bytes())
+        .unwrap();
+    let files = context::tests::TestFileSystem::make_files(&ctx, &[("workdir/refs/oa.csv", &csv)]);
+    ctx.set_file_system(&context::tests::TestFileSystem::from_files(&files));
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch("delete from ref_housenumbers").unwrap();
+    }
+    // REGION is not in the header.
+    assert!(build_reference_index(&ctx).is_err());
+}
+
+/// Tests ReferencePostcodes::check() and ReferencePostcodes::check_settlement().
+#[test]
+fn test_reference_postcodes_check() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let zipcounts = context::tests::TestFileSystem::make_file();
+    zipcounts
+        .borrow_mut()
+        .write_all(b"ZIP\tCNT\tCOUNTY_CODE\tSETTLEMENT_CODE\n1111\t100\t01\t011\n1121\t200\t01\t011\n2040\t50\t13\t007\n")
+        .unwrap();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("workdir/refs/irsz_count_20190717.tsv", &zipcounts)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    let postcodes = get_reference_postcodes(&ctx).unwrap();
+
+    assert_eq!(postcodes.has_settlements(), true);
+    let cases = [
+        ("1111", None),
+        ("", Some(PostcodeProblem::Missing)),
+        ("H-1111", Some(PostcodeProblem::Malformed)),
+        ("111", Some(PostcodeProblem::Malformed)),
+        ("9999", Some(PostcodeProblem::Unknown)),
+    ];
+    for (postcode, expected) in cases {
+        assert_eq!(postcodes.check(postcode), expected, "{postcode}");
+    }
+    let refsettlements = vec!["011".to_string()];
+    assert_eq!(
+        postcodes.check_settlement("1121", "01", &refsettlements),
+        None
+    );
+    assert_eq!(
+        postcodes.check_settlement("2040", "01", &refsettlements),
+        Some(PostcodeProblem::Unknown)
+    );
+    // No reference postcodes for the settlement: nothing to check against.
+    let refsettlements = vec!["012".to_string()];
+    assert_eq!(
+        postcodes.check_settlement("2040", "01", &refsettlements),
+        None
+    );
+}
+
+/// Tests get_reference_postcodes(), the no zipcounts file case.
+#[test]
+fn test_get_reference_postcodes_no_file() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let ini = context::tests::make_test_ini(&ctx, "");
+    ctx.set_ini(ini);
+
+    let postcodes = get_reference_postcodes(&ctx).unwrap();
+
+    assert_eq!(postcodes.check(""), None);
+}
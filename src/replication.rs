@@ -0,0 +1,576 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The replication module applies OsmChange diffs from a local replication directory (minutely,
+//! hourly or daily) on top of the OSM streets and house numbers which are already in the
+//! database.
+
+use crate::area_files;
+use crate::context;
+use crate::import_osm;
+use crate::osm_extract;
+use anyhow::Context as _;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+#[cfg(not(test))]
+use log::info;
+
+#[cfg(test)]
+use std::println as info;
+
+/// Relations touched by a set of diffs.
+#[derive(Default)]
+pub struct Changes {
+    /// Relations where OSM streets were updated in place.
+    pub streets: BTreeSet<String>,
+    /// Relations where OSM house numbers were updated in place.
+    pub housenumbers: BTreeSet<String>,
+    /// Relations which probably have a new street or house number: these can't be placed without
+    /// geometry, so they have to be downloaded again.
+    pub stale: BTreeSet<String>,
+    /// If the house numbers of the whole country changed.
+    pub whole_country: bool,
+}
+
+impl Changes {
+    /// Adds the relations of an other set of changes.
+    fn extend(&mut self, other: Changes) {
+        self.streets.extend(other.streets);
+        self.housenumbers.extend(other.housenumbers);
+        self.stale.extend(other.stale);
+        self.whole_country |= other.whole_country;
+    }
+
+    /// Gets all affected relations.
+    pub fn get_relations(&self) -> BTreeSet<String> {
+        let mut ret = self.streets.clone();
+        ret.extend(self.housenumbers.iter().cloned());
+        ret.extend(self.stale.iter().cloned());
+        ret
+    }
+}
+
+/// Gets the path of a diff in the osmosis directory layout, e.g. 000/001/234.osc.gz for 1234.
+fn get_diff_path(ctx: &context::Context, dir: &str, sequence: u64) -> String {
+    let prefix = format!(
+        "{}/{:03}/{:03}/{:03}",
+        dir,
+        sequence / 1_000_000,
+        sequence / 1_000 % 1_000,
+        sequence % 1_000
+    );
+    let path = format!("{prefix}.osc.gz");
+    if ctx.get_file_system().path_exists(&path) {
+        return path;
+    }
+    format!("{prefix}.osc")
+}
+
+/// Parses a state.txt file of a replication directory into a sequence number and a timestamp.
+fn read_state(ctx: &context::Context, path: &str) -> anyhow::Result<(u64, time::OffsetDateTime)> {
+    let content = ctx
+        .get_file_system()
+        .read_to_string(path)
+        .context(format!("failed to read '{path}'"))?;
+    let mut state: HashMap<String, String> = HashMap::new();
+    for line in content.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            // Java properties format, colons are escaped.
+            state.insert(key.trim().into(), value.trim().replace('\\', ""));
+        }
+    }
+    let sequence: u64 = state
+        .get("sequenceNumber")
+        .context("missing sequenceNumber")?
+        .parse()?;
+    let timestamp = time::OffsetDateTime::parse(
+        state.get("timestamp").context("missing timestamp")?,
+        &time::format_description::well_known::Rfc3339,
+    )?;
+    Ok((sequence, timestamp))
+}
+
+/// Gets the sequence number of the last applied diff, if there is one.
+fn get_last_sequence(ctx: &context::Context) -> anyhow::Result<Option<u64>> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare("select sequence from osm_replication")?;
+    let mut rows = stmt.query([])?;
+    match rows.next()? {
+        Some(row) => {
+            let sequence: i64 = row.get(0)?;
+            Ok(Some(u64::try_from(sequence)?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Sets the sequence number of the last applied diff.
+fn set_last_sequence(conn: &rusqlite::Connection, sequence: u64) -> anyhow::Result<()> {
+    conn.execute("delete from osm_replication", [])?;
+    conn.execute(
+        "insert into osm_replication (sequence) values (?1)",
+        [i64::try_from(sequence)?],
+    )?;
+    Ok(())
+}
+
+/// Decides if an object is a street. Keep this in sync with data/streets-template.overpassql.
+fn is_street(object: &osm_extract::Object) -> bool {
+    match object.object_type {
+        osm_extract::ObjectType::Way => {
+            import_osm::is_street_way(&object.tags) || import_osm::is_boundary_street(&object.tags)
+        }
+        osm_extract::ObjectType::Relation => import_osm::is_street_relation(&object.tags),
+        osm_extract::ObjectType::Node => false,
+    }
+}
+
+/// Gets a tag value of an object, empty string if the tag is missing.
+fn get_tag(object: &osm_extract::Object, key: &str) -> String {
+    object.tags.get(key).cloned().unwrap_or_default()
+}
+
+/// Node id -> (lat, lon) of the nodes of a diff, so new ways of the same diff can be located.
+type Locations = HashMap<u64, (f64, f64)>;
+
+/// Gets the known locations of an object: the location of a node or the locations of the nodes of
+/// a way which are in the same diff. Relations are not located.
+fn get_locations(object: &osm_extract::Object, locations: &Locations) -> Vec<(f64, f64)> {
+    match object.object_type {
+        osm_extract::ObjectType::Node => vec![(object.lat, object.lon)],
+        osm_extract::ObjectType::Way => object
+            .nodes
+            .iter()
+            .filter_map(|node| locations.get(node).cloned())
+            .collect(),
+        osm_extract::ObjectType::Relation => Vec::new(),
+    }
+}
+
+/// Finds the relations whose bounding box contains one of `points`. The bounding box of a
+/// relation is the union of the bounding boxes of its streets, as stored by cron.
+fn get_relations_at(
+    conn: &rusqlite::Connection,
+    points: &[(f64, f64)],
+) -> anyhow::Result<BTreeSet<String>> {
+    let mut stmt = conn.prepare(
+        "select relation from osm_relation_bounds
+         where min_lat <= ?1 and ?1 <= max_lat and min_lon <= ?2 and ?2 <= max_lon",
+    )?;
+    let mut ret: BTreeSet<String> = BTreeSet::new();
+    for (lat, lon) in points {
+        let mut rows = stmt.query(rusqlite::params![lat, lon])?;
+        while let Some(row) = rows.next()? {
+            ret.insert(row.get(0)?);
+        }
+    }
+    Ok(ret)
+}
+
+/// Runs a query returning a list of relation names.
+fn query_relations(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    params: &[&str],
+) -> anyhow::Result<BTreeSet<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+    let mut ret: BTreeSet<String> = BTreeSet::new();
+    while let Some(row) = rows.next()? {
+        ret.insert(row.get(0)?);
+    }
+    Ok(ret)
+}
+
+/// Applies a single change to the osm_streets table.
+fn apply_street_change(
+    conn: &rusqlite::Connection,
+    action: osm_extract::ChangeAction,
+    object: &osm_extract::Object,
+    locations: &Locations,
+    changes: &mut Changes,
+) -> anyhow::Result<()> {
+    let osm_id = object.id.to_string();
+    let osm_type = object.object_type.to_string();
+    let relations = query_relations(
+        conn,
+        "select relation from osm_streets where osm_type = ?1 and osm_id = ?2",
+        &[&osm_type, &osm_id],
+    )?;
+    if !relations.is_empty() {
+        if object.object_type == osm_extract::ObjectType::Way {
+            conn.execute("delete from osm_street_nodes where osm_id = ?1", [&osm_id])?;
+        }
+        if action == osm_extract::ChangeAction::Delete || !is_street(object) {
+            conn.execute(
+                "delete from osm_streets where osm_type = ?1 and osm_id = ?2",
+                [&osm_type, &osm_id],
+            )?;
+        } else {
+            for relation in &relations {
+                for node in &object.nodes {
+                    conn.execute(
+                        "insert into osm_street_nodes (relation, osm_id, node_id) values (?1, ?2, ?3)",
+                        rusqlite::params![relation, osm_id, i64::try_from(*node)?],
+                    )?;
+                }
+            }
+            conn.execute(
+                "update osm_streets set name = ?1, highway = ?2, service = ?3, surface = ?4, leisure = ?5 where osm_type = ?6 and osm_id = ?7",
+                [
+                    &get_tag(object, "name"),
+                    &get_tag(object, "highway"),
+                    &get_tag(object, "service"),
+                    &get_tag(object, "surface"),
+                    &get_tag(object, "leisure"),
+                    &osm_type,
+                    &osm_id,
+                ],
+            )?;
+        }
+        changes.streets.extend(relations);
+        return Ok(());
+    }
+
+    if action == osm_extract::ChangeAction::Delete || !is_street(object) {
+        return Ok(());
+    }
+
+    // A new street: interesting for the relations which contain it.
+    let points = get_locations(object, locations);
+    changes.stale.extend(get_relations_at(conn, &points)?);
+
+    // It may be not located, e.g. only the tags of an existing way changed: also consider the
+    // relations which have house numbers referring to it, but no street with that name yet.
+    let name = get_tag(object, "name");
+    let stale = query_relations(
+        conn,
+        "select distinct relation from osm_housenumbers where street = ?1
+         except select relation from osm_streets where name = ?1",
+        &[&name],
+    )?;
+    changes.stale.extend(stale);
+    Ok(())
+}
+
+/// Applies a single change to the osm_housenumbers table.
+fn apply_housenumber_change(
+    conn: &rusqlite::Connection,
+    action: osm_extract::ChangeAction,
+    object: &osm_extract::Object,
+    locations: &Locations,
+    changes: &mut Changes,
+) -> anyhow::Result<()> {
+    let osm_id = object.id.to_string();
+    let osm_type = object.object_type.to_string();
+    let relations = query_relations(
+        conn,
+        "select relation from osm_housenumbers where osm_type = ?1 and osm_id = ?2",
+        &[&osm_type, &osm_id],
+    )?;
+    let is_housenumber = import_osm::is_housenumber(&object.tags);
+    if !relations.is_empty() {
+        if action == osm_extract::ChangeAction::Delete || !is_housenumber {
+            conn.execute(
+                "delete from osm_housenumbers where osm_type = ?1 and osm_id = ?2",
+                [&osm_type, &osm_id],
+            )?;
+        } else {
+            conn.execute(
+                "update osm_housenumbers set street = ?1, housenumber = ?2, postcode = ?3, place = ?4, housename = ?5, conscriptionnumber = ?6, flats = ?7, floor = ?8, door = ?9, unit = ?10, name = ?11 where osm_type = ?12 and osm_id = ?13",
+                [
+                    &get_tag(object, "addr:street"),
+                    &get_tag(object, "addr:housenumber"),
+                    &get_tag(object, "addr:postcode"),
+                    &get_tag(object, "addr:place"),
+                    &get_tag(object, "addr:housename"),
+                    &get_tag(object, "addr:conscriptionnumber"),
+                    &get_tag(object, "addr:flats"),
+                    &get_tag(object, "addr:floor"),
+                    &get_tag(object, "addr:door"),
+                    &get_tag(object, "addr:unit"),
+                    &get_tag(object, "name"),
+                    &osm_type,
+                    &osm_id,
+                ],
+            )?;
+            // Ways and relations only change their location when their nodes move, which is not
+            // tracked here: the next full refresh updates them.
+            if object.object_type == osm_extract::ObjectType::Node {
+                conn.execute(
+                    "update osm_housenumbers set lat = ?1, lon = ?2 where osm_type = ?3 and osm_id = ?4",
+                    [
+                        &area_files::format_coord(Some(object.lat)),
+                        &area_files::format_coord(Some(object.lon)),
+                        &osm_type,
+                        &osm_id,
+                    ],
+                )?;
+            }
+        }
+        changes.housenumbers.extend(relations);
+        return Ok(());
+    }
+
+    if action == osm_extract::ChangeAction::Delete || !is_housenumber {
+        return Ok(());
+    }
+
+    // A new house number: interesting for the relations which contain it.
+    let points = get_locations(object, locations);
+    changes.stale.extend(get_relations_at(conn, &points)?);
+
+    // It may be not located: also consider the relations which have its street, limited to the
+    // ones which already have the same postcode, if there is one.
+    let street = get_tag(object, "addr:street");
+    let mut stale = if street.is_empty() {
+        query_relations(
+            conn,
+            "select distinct relation from osm_housenumbers where place = ?1",
+            &[&get_tag(object, "addr:place")],
+        )?
+    } else {
+        query_relations(
+            conn,
+            "select distinct relation from osm_streets where name = ?1",
+            &[&street],
+        )?
+    };
+    let postcode = get_tag(object, "addr:postcode");
+    if !postcode.is_empty() {
+        let with_postcode = query_relations(
+            conn,
+            "select distinct relation from osm_housenumbers where postcode = ?1",
+            &[&postcode],
+        )?;
+        stale.retain(|relation| with_postcode.contains(relation));
+    }
+    changes.stale.extend(stale);
+    Ok(())
+}
+
+/// Handles a node which moved: if it's a node of a stored street, the street may now enter or
+/// leave a relation, so the relations of the street are marked stale.
+fn apply_node_move(
+    conn: &rusqlite::Connection,
+    action: osm_extract::ChangeAction,
+    object: &osm_extract::Object,
+    changes: &mut Changes,
+) -> anyhow::Result<()> {
+    if action != osm_extract::ChangeAction::Modify
+        || object.object_type != osm_extract::ObjectType::Node
+    {
+        return Ok(());
+    }
+
+    let mut stmt =
+        conn.prepare_cached("select distinct relation from osm_street_nodes where node_id = ?1")?;
+    let mut rows = stmt.query([i64::try_from(object.id)?])?;
+    while let Some(row) = rows.next()? {
+        changes.stale.insert(row.get(0)?);
+    }
+    Ok(())
+}
+
+/// Applies a single change to the whole_country table.
+fn apply_whole_country_change(
+    conn: &rusqlite::Connection,
+    action: osm_extract::ChangeAction,
+    object: &osm_extract::Object,
+    changes: &mut Changes,
+) -> anyhow::Result<()> {
+    let osm_id = object.id.to_string();
+    let osm_type = object.object_type.to_string();
+    let deleted = conn.execute(
+        "delete from whole_country where osm_type = ?1 and osm_id = ?2",
+        [&osm_type, &osm_id],
+    )?;
+    if deleted > 0 {
+        changes.whole_country = true;
+    }
+    // Keep this in sync with data/street-housenumbers-hungary.overpassql.
+    if action == osm_extract::ChangeAction::Delete || !object.tags.contains_key("addr:housenumber")
+    {
+        return Ok(());
+    }
+
+    conn.execute(
+        "insert into whole_country (postcode, city, street, housenumber, user, osm_id, osm_type, timestamp, place, unit, name, fixme) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        [
+            &get_tag(object, "addr:postcode"),
+            &get_tag(object, "addr:city"),
+            &get_tag(object, "addr:street"),
+            &get_tag(object, "addr:housenumber"),
+            &object.user,
+            &osm_id,
+            &osm_type,
+            &object.timestamp,
+            &get_tag(object, "addr:place"),
+            &get_tag(object, "addr:unit"),
+            &get_tag(object, "name"),
+            &get_tag(object, "fixme"),
+        ],
+    )?;
+    changes.whole_country = true;
+    Ok(())
+}
+
+/// Applies one diff file in a single transaction, together with the bookkeeping of the affected
+/// relations, so an interrupted run doesn't lose track of them.
+fn apply_diff(
+    ctx: &context::Context,
+    path: &str,
+    sequence: u64,
+    timestamp: &time::OffsetDateTime,
+) -> anyhow::Result<Changes> {
+    let mut objects: Vec<(osm_extract::ChangeAction, osm_extract::Object)> = Vec::new();
+    osm_extract::read_change(ctx, path, &mut |action, object| {
+        objects.push((action, object));
+        Ok(())
+    })?;
+
+    let locations: Locations = objects
+        .iter()
+        .filter(|(action, object)| {
+            *action != osm_extract::ChangeAction::Delete
+                && object.object_type == osm_extract::ObjectType::Node
+        })
+        .map(|(_, object)| (object.id, (object.lat, object.lon)))
+        .collect();
+
+    let mut changes = Changes::default();
+    let mut conn = ctx.get_database_connection()?;
+    let tx = conn.transaction()?;
+    for (action, object) in objects {
+        apply_street_change(&tx, action, &object, &locations, &mut changes)?;
+        apply_housenumber_change(&tx, action, &object, &locations, &mut changes)?;
+        apply_node_move(&tx, action, &object, &mut changes)?;
+        apply_whole_country_change(&tx, action, &object, &mut changes)?;
+    }
+
+    let now = ctx.get_time().now();
+    for relation in &changes.streets {
+        set_mtime(&tx, &format!("streets/{relation}"), &now)?;
+        set_mtime(&tx, &format!("streets/{relation}/osm-base"), timestamp)?;
+    }
+    for relation in &changes.housenumbers {
+        set_mtime(&tx, &format!("housenumbers/{relation}"), &now)?;
+        set_mtime(&tx, &format!("housenumbers/{relation}/osm-base"), timestamp)?;
+    }
+    for relation in changes.get_relations() {
+        invalidate_caches(&tx, &relation)?;
+    }
+    for relation in &changes.stale {
+        tx.execute(
+            "insert or ignore into osm_stale_relations (relation) values (?1)",
+            [relation],
+        )?;
+    }
+    if changes.whole_country {
+        set_mtime(&tx, "whole-country/osm-base", timestamp)?;
+    }
+    set_last_sequence(&tx, sequence)?;
+    tx.commit()?;
+    Ok(changes)
+}
+
+/// Sets an mtime row to a given time.
+fn set_mtime(
+    conn: &rusqlite::Connection,
+    page: &str,
+    timestamp: &time::OffsetDateTime,
+) -> anyhow::Result<()> {
+    conn.execute(
+        r#"insert into mtimes (page, last_modified) values (?1, ?2)
+             on conflict(page) do update set last_modified = excluded.last_modified"#,
+        [page, &timestamp.unix_timestamp_nanos().to_string()],
+    )?;
+    Ok(())
+}
+
+/// Drops the cached analysis results of a relation.
+fn invalidate_caches(conn: &rusqlite::Connection, relation: &str) -> anyhow::Result<()> {
+    for (table, page) in [
+        ("missing_housenumbers_cache", "missing-housenumbers-cache"),
+        (
+            "additional_housenumbers_cache",
+            "additional-housenumbers-cache",
+        ),
+    ] {
+        conn.execute(
+            &format!("delete from {table} where relation = ?1"),
+            [relation],
+        )?;
+        conn.execute(
+            "delete from mtimes where page = ?1",
+            [format!("{page}/{relation}")],
+        )?;
+    }
+    Ok(())
+}
+
+/// Gets the relations which are marked stale by applied diffs, but were not downloaded again yet.
+fn get_stale_relations(ctx: &context::Context) -> anyhow::Result<BTreeSet<String>> {
+    let conn = ctx.get_database_connection()?;
+    query_relations(&conn, "select relation from osm_stale_relations", &[])
+}
+
+/// Forgets stale relations, after they were downloaded again.
+pub fn clear_stale_relations(
+    ctx: &context::Context,
+    relations: &BTreeSet<String>,
+) -> anyhow::Result<()> {
+    let conn = ctx.get_database_connection()?;
+    for relation in relations {
+        conn.execute(
+            "delete from osm_stale_relations where relation = ?1",
+            [relation],
+        )?;
+    }
+    Ok(())
+}
+
+/// Applies all new diffs from the replication directory. The first run only records the current
+/// sequence number, so that should happen right after a full import. The stale relations of
+/// previous runs which were not downloaded again are included in the result.
+pub fn apply_diffs(ctx: &context::Context) -> anyhow::Result<Changes> {
+    let mut changes = Changes::default();
+    let dir = ctx.get_ini().get_replication_dir();
+    let (sequence, timestamp) = read_state(ctx, &format!("{dir}/state.txt"))?;
+    let last_sequence = match get_last_sequence(ctx)? {
+        Some(value) => value,
+        None => {
+            info!("apply_diffs: no previous sequence, starting from {sequence}");
+            let conn = ctx.get_database_connection()?;
+            set_last_sequence(&conn, sequence)?;
+            return Ok(changes);
+        }
+    };
+
+    for diff_sequence in last_sequence + 1..=sequence {
+        let path = get_diff_path(ctx, &dir, diff_sequence);
+        info!("apply_diffs: applying {path}");
+        changes.extend(apply_diff(ctx, &path, diff_sequence, &timestamp)?);
+    }
+    changes.stale.extend(get_stale_relations(ctx)?);
+
+    info!(
+        "apply_diffs: applied up to {sequence}, {} relations affected",
+        changes.get_relations().len()
+    );
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,404 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the replication module.
+
+use super::*;
+use crate::stats;
+use std::io::Write;
+
+/// Creates an in-memory file with the given content.
+fn make_file(content: &[u8]) -> std::rc::Rc<std::cell::RefCell<std::io::Cursor<Vec<u8>>>> {
+    let file = context::tests::TestFileSystem::make_file();
+    file.borrow_mut().write_all(content).unwrap();
+    file
+}
+
+/// Counts the rows of a table matching a condition.
+fn count_rows(ctx: &context::Context, table: &str, condition: &str) -> i64 {
+    let conn = ctx.get_database_connection().unwrap();
+    conn.query_row(
+        &format!("select count(*) from {table} where {condition}"),
+        [],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+/// Tests get_diff_path().
+#[test]
+fn test_get_diff_path() {
+    let ctx = context::tests::make_test_context().unwrap();
+    let gz = make_file(b"");
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("replication/001/234/567.osc.gz", &gz)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    let mut ctx = ctx;
+    ctx.set_file_system(&file_system);
+    let dir = ctx.get_abspath("replication");
+
+    assert_eq!(
+        get_diff_path(&ctx, &dir, 1234567),
+        format!("{dir}/001/234/567.osc.gz")
+    );
+    assert_eq!(
+        get_diff_path(&ctx, &dir, 1234),
+        format!("{dir}/000/001/234.osc")
+    );
+}
+
+/// Tests read_state(): the case when the sequence number is missing.
+#[test]
+fn test_read_state_no_sequence() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let state = make_file(b"timestamp=2024-01-02T00\\:00\\:00Z\n");
+    let files = context::tests::TestFileSystem::make_files(&ctx, &[("state.txt", &state)]);
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+
+    let ret = read_state(&ctx, &ctx.get_abspath("state.txt"));
+
+    assert!(ret.is_err());
+}
+
+/// Tests apply_diffs(): the case when there is no previous sequence number.
+#[test]
+fn test_apply_diffs_first_run() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let state = make_file(
+        b"#Sat Jan 02 00:00:00 UTC 2024\nsequenceNumber=3\ntimestamp=2024-01-02T00\\:00\\:00Z\n",
+    );
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("workdir/replication/state.txt", &state)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+
+    let changes = apply_diffs(&ctx).unwrap();
+
+    assert!(changes.get_relations().is_empty());
+    assert_eq!(get_last_sequence(&ctx).unwrap(), Some(3));
+}
+
+/// Tests apply_diffs(): in-place updates, deletions, new objects and cache invalidation.
+#[test]
+fn test_apply_diffs() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let state = make_file(b"sequenceNumber=3\ntimestamp=2024-01-02T00\\:00\\:00Z\n");
+    let diff2 = make_file(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6" generator="osmium/1.16.0">
+  <modify>
+    <way id="100" version="2" timestamp="2024-01-01T00:00:00Z" user="mapper">
+      <nd ref="1"/>
+      <nd ref="2"/>
+      <tag k="highway" v="primary"/>
+      <tag k="name" v="Új út"/>
+    </way>
+    <way id="300" version="2" timestamp="2024-01-01T00:00:00Z" user="mapper">
+      <nd ref="1"/>
+      <nd ref="2"/>
+    </way>
+    <node id="11" version="2" timestamp="2024-01-01T00:00:00Z" user="mapper" lat="47.06" lon="19.06">
+      <tag k="addr:housenumber" v="3"/>
+      <tag k="addr:street" v="Határ út"/>
+    </node>
+  </modify>
+  <delete>
+    <node id="9" version="3" timestamp="2024-01-01T00:00:00Z" user="mapper"/>
+  </delete>
+  <create>
+    <node id="20" version="1" timestamp="2024-01-01T00:00:00Z" user="mapper" lat="47.05" lon="19.05">
+      <tag k="addr:housenumber" v="2"/>
+      <tag k="addr:street" v="Új út"/>
+    </node>
+  </create>
+</osmChange>
+"#
+        .as_bytes(),
+    );
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(
+            br#"<osmChange version="0.6">
+  <create>
+    <way id="301" version="1" timestamp="2024-01-02T00:00:00Z" user="mapper">
+      <nd ref="1"/>
+      <nd ref="2"/>
+      <tag k="highway" v="residential"/>
+      <tag k="name" v="Missing utca"/>
+    </way>
+  </create>
+</osmChange>
+"#,
+        )
+        .unwrap();
+    let diff3 = make_file(&encoder.finish().unwrap());
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("workdir/replication/state.txt", &state),
+            ("workdir/replication/000/000/002.osc", &diff2),
+            ("workdir/replication/000/000/003.osc.gz", &diff3),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_replication (sequence) values (1);
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '100', 'Határ út', 'primary', '', '', '', 'way');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('other', '300', 'Other utca', 'residential', '', '', '', 'way');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '9', 'Határ út', '1', '', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('other', '302', 'Missing utca', '5', '', '', '', '', '', '', '', '', '', 'way');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '11', 'Határ út', '3', '', '', '', '', '', '', '', '', '', 'node', '47', '19');
+             insert into whole_country (postcode, city, street, housenumber, user, osm_id, osm_type, timestamp, place, unit, name, fixme) values ('', '', 'Határ út', '1', 'mgmt', '9', 'node', '2020-01-01T00:00:00Z', '', '', '', '');
+             insert into missing_housenumbers_cache (relation, json) values ('gazdagret', '{}');
+             insert into missing_housenumbers_cache (relation, json) values ('unrelated', '{}');
+             insert into additional_housenumbers_cache (relation, json) values ('gazdagret', '{}');
+             insert into mtimes (page, last_modified) values ('missing-housenumbers-cache/gazdagret', '0');
+             insert into mtimes (page, last_modified) values ('missing-housenumbers-cache/unrelated', '0');",
+        )
+        .unwrap();
+    }
+
+    let changes = apply_diffs(&ctx).unwrap();
+
+    assert_eq!(
+        changes.streets,
+        BTreeSet::from(["gazdagret".to_string(), "other".to_string()])
+    );
+    assert_eq!(
+        changes.housenumbers,
+        BTreeSet::from(["gazdagret".to_string()])
+    );
+    // Node 20 is on a street of gazdagret, way 301 is a missing street of other.
+    assert_eq!(
+        changes.stale,
+        BTreeSet::from(["gazdagret".to_string(), "other".to_string()])
+    );
+    assert!(changes.whole_country);
+    assert_eq!(get_last_sequence(&ctx).unwrap(), Some(3));
+    assert_eq!(
+        count_rows(&ctx, "osm_streets", "osm_id = '100' and name = 'Új út'"),
+        1
+    );
+    assert_eq!(count_rows(&ctx, "osm_streets", "osm_id = '300'"), 0);
+    assert_eq!(count_rows(&ctx, "osm_housenumbers", "osm_id = '9'"), 0);
+    // Node 11 moved.
+    assert_eq!(
+        count_rows(
+            &ctx,
+            "osm_housenumbers",
+            "osm_id = '11' and lat = '47.06' and lon = '19.06'"
+        ),
+        1
+    );
+    assert_eq!(count_rows(&ctx, "whole_country", "osm_id = '9'"), 0);
+    assert_eq!(
+        count_rows(&ctx, "whole_country", "osm_id = '20' and street = 'Új út'"),
+        1
+    );
+    assert!(stats::has_sql_mtime(&ctx, "streets/gazdagret").unwrap());
+    assert!(!stats::has_sql_mtime(&ctx, "streets/unrelated").unwrap());
+    assert_eq!(
+        stats::get_sql_mtime(&ctx, "whole-country/osm-base")
+            .unwrap()
+            .unix_timestamp(),
+        1704153600
+    );
+    assert_eq!(
+        count_rows(&ctx, "missing_housenumbers_cache", "relation = 'gazdagret'"),
+        0
+    );
+    assert_eq!(
+        count_rows(
+            &ctx,
+            "additional_housenumbers_cache",
+            "relation = 'gazdagret'"
+        ),
+        0
+    );
+    assert!(!stats::has_sql_mtime(&ctx, "missing-housenumbers-cache/gazdagret").unwrap());
+    assert_eq!(
+        count_rows(&ctx, "missing_housenumbers_cache", "relation = 'unrelated'"),
+        1
+    );
+    assert!(stats::has_sql_mtime(&ctx, "missing-housenumbers-cache/unrelated").unwrap());
+}
+
+/// Tests apply_diffs(): the case when a diff is missing from the replication directory.
+#[test]
+fn test_apply_diffs_missing_diff() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let state = make_file(b"sequenceNumber=2\ntimestamp=2024-01-02T00\\:00\\:00Z\n");
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("workdir/replication/state.txt", &state)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute("insert into osm_replication (sequence) values (1)", [])
+            .unwrap();
+    }
+
+    let ret = apply_diffs(&ctx);
+
+    assert!(ret.is_err());
+    assert_eq!(get_last_sequence(&ctx).unwrap(), Some(1));
+}
+
+/// Tests apply_diffs(): a brand-new named street is located using the stored bounding boxes of the
+/// relations, a moved node using the stored nodes of the streets.
+#[test]
+fn test_apply_diffs_new_street() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let state = make_file(b"sequenceNumber=2\ntimestamp=2024-01-02T00\\:00\\:00Z\n");
+    let diff = make_file(
+        r#"<osmChange version="0.6">
+  <create>
+    <node id="30" version="1" timestamp="2024-01-01T00:00:00Z" user="mapper" lat="47.15" lon="19.15"/>
+    <node id="31" version="1" timestamp="2024-01-01T00:00:00Z" user="mapper" lat="47.16" lon="19.16"/>
+    <way id="400" version="1" timestamp="2024-01-01T00:00:00Z" user="mapper">
+      <nd ref="30"/>
+      <nd ref="31"/>
+      <tag k="highway" v="residential"/>
+      <tag k="name" v="Vadonatúj utca"/>
+    </way>
+  </create>
+  <modify>
+    <node id="40" version="2" timestamp="2024-01-01T00:00:00Z" user="mapper" lat="48.05" lon="20.05"/>
+    <node id="41" version="2" timestamp="2024-01-01T00:00:00Z" user="mapper" lat="46.05" lon="18.05"/>
+  </modify>
+</osmChange>
+"#
+        .as_bytes(),
+    );
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("workdir/replication/state.txt", &state),
+            ("workdir/replication/000/000/002.osc", &diff),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_replication (sequence) values (1);
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon, min_lat, min_lon, max_lat, max_lon) values ('gazdagret', '100', 'Határ út', 'primary', '', '', '', 'way', '47.1', '19.1', '47.1', '19.1', '47.1', '19.1');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon, min_lat, min_lon, max_lat, max_lon) values ('gazdagret', '101', 'Hamzsabégi út', 'primary', '', '', '', 'way', '47.2', '19.2', '47.2', '19.2', '47.2', '19.2');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon, min_lat, min_lon, max_lat, max_lon) values ('other', '200', 'Other utca', 'residential', '', '', '', 'way', '48', '20', '48', '20', '48.1', '20.1');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('unrelated', '300', 'Unrelated utca', 'residential', '', '', '', 'way');
+             insert into osm_relation_bounds (relation, min_lat, min_lon, max_lat, max_lon) values ('gazdagret', 47.1, 19.1, 47.2, 19.2);
+             insert into osm_relation_bounds (relation, min_lat, min_lon, max_lat, max_lon) values ('other', 48, 20, 48.1, 20.1);
+             insert into osm_relation_bounds (relation, min_lat, min_lon, max_lat, max_lon) values ('third', 46, 18, 46.1, 18.1);
+             insert into osm_street_nodes (relation, osm_id, node_id) values ('other', '200', 40);",
+        )
+        .unwrap();
+    }
+
+    let changes = apply_diffs(&ctx).unwrap();
+
+    // Way 400 is inside the bounding box of gazdagret, node 40 is on a street of other. Node 41
+    // is inside the bounding box of third, but it's not a node of a street.
+    assert_eq!(
+        changes.stale,
+        BTreeSet::from(["gazdagret".to_string(), "other".to_string()])
+    );
+    assert!(changes.streets.is_empty());
+    assert!(changes.housenumbers.is_empty());
+}
+
+/// Tests apply_diffs(): an interrupted run keeps the bookkeeping of the already applied diffs.
+#[test]
+fn test_apply_diffs_interrupted() {
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let state = make_file(b"sequenceNumber=3\ntimestamp=2024-01-02T00\\:00\\:00Z\n");
+    let diff2 = make_file(
+        r#"<osmChange version="0.6">
+  <create>
+    <node id="30" version="1" timestamp="2024-01-01T00:00:00Z" user="mapper" lat="47.15" lon="19.15"/>
+    <way id="400" version="1" timestamp="2024-01-01T00:00:00Z" user="mapper">
+      <nd ref="30"/>
+      <tag k="highway" v="residential"/>
+      <tag k="name" v="Vadonatúj utca"/>
+    </way>
+  </create>
+  <modify>
+    <way id="100" version="2" timestamp="2024-01-01T00:00:00Z" user="mapper">
+      <nd ref="1"/>
+      <tag k="highway" v="primary"/>
+      <tag k="name" v="Új út"/>
+    </way>
+  </modify>
+</osmChange>
+"#
+        .as_bytes(),
+    );
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[
+            ("workdir/replication/state.txt", &state),
+            ("workdir/replication/000/000/002.osc", &diff2),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_replication (sequence) values (1);
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '100', 'Határ út', 'primary', '', '', '', 'way');
+             insert into osm_relation_bounds (relation, min_lat, min_lon, max_lat, max_lon) values ('gazdagret', 47.1, 19.1, 47.2, 19.2);
+             insert into missing_housenumbers_cache (relation, json) values ('gazdagret', '{}');",
+        )
+        .unwrap();
+    }
+
+    // Diff 3 is missing.
+    let ret = apply_diffs(&ctx);
+
+    assert!(ret.is_err());
+    // Diff 2 is applied, together with its bookkeeping.
+    assert_eq!(get_last_sequence(&ctx).unwrap(), Some(2));
+    assert!(stats::has_sql_mtime(&ctx, "streets/gazdagret").unwrap());
+    assert_eq!(
+        count_rows(&ctx, "missing_housenumbers_cache", "relation = 'gazdagret'"),
+        0
+    );
+    assert_eq!(
+        count_rows(&ctx, "osm_street_nodes", "osm_id = '100' and node_id = 1"),
+        1
+    );
+    assert_eq!(
+        get_stale_relations(&ctx).unwrap(),
+        BTreeSet::from(["gazdagret".to_string()])
+    );
+
+    // The next run has nothing to apply, but gazdagret is still stale, till it's downloaded
+    // again.
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute("update osm_replication set sequence = 3", [])
+            .unwrap();
+    }
+    let changes = apply_diffs(&ctx).unwrap();
+    assert_eq!(changes.stale, BTreeSet::from(["gazdagret".to_string()]));
+    clear_stale_relations(&ctx, &changes.stale).unwrap();
+    assert!(get_stale_relations(&ctx).unwrap().is_empty());
+}
@@ -313,7 +313,216 @@ pub fn init(conn: &mut rusqlite::Connection) -> anyhow::Result<()> {
         )?;
     }
 
-    tx.execute("pragma user_version = 17", [])?;
+    if user_version < 18 {
+        // Tracks the sequence number of the last applied replication diff.
+        tx.execute_batch(
+            "create table osm_replication (
+                    sequence integer not null
+                );",
+        )?;
+    }
+
+    if user_version < 19 {
+        // Tracks relations where downloading OSM data failed, so the next cron run can start with
+        // them.
+        tx.execute_batch(
+            "create table cron_failures (
+                    relation text not null,
+                    kind text not null,
+                    failures integer not null,
+                    last_error text not null,
+                    last_failure text not null,
+                    unique(relation, kind)
+                );",
+        )?;
+    }
+
+    if user_version < 20 {
+        // Tracks the stages and relations of the last cron run, so it can be resumed.
+        tx.execute_batch(
+            "create table cron_journal (
+                    stage text not null,
+                    relation text not null,
+                    status text not null,
+                    started text not null,
+                    finished text not null,
+                    error text not null,
+                    unique(stage, relation)
+                );",
+        )?;
+    }
+
+    if user_version < 21 {
+        // Historical snapshots of the missing housenumbers of a relation, at most one per day.
+        tx.execute_batch(
+            "create table missing_housenumbers_snapshots (
+                    relation text not null,
+                    date text not null,
+                    json text not null,
+                    unique(relation, date)
+                );",
+        )?;
+    }
+
+    if user_version < 22 {
+        // Tracks the street and house number coverage of relations over time.
+        tx.execute_batch(
+            "create table stats_coverages (
+                    date text not null,
+                    relation text not null,
+                    streets text not null,
+                    housenumbers text not null,
+                    unique(date, relation)
+                );",
+        )?;
+    }
+
+    if user_version < 23 {
+        // Survey feedback about missing house numbers, silencing them like 'invalid' filters.
+        tx.execute_batch(
+            "create table survey_observations (
+                    id integer primary key autoincrement,
+                    relation text not null,
+                    street text not null,
+                    housenumber text not null,
+                    status text not null,
+                    note text not null,
+                    surveyor text not null,
+                    date text not null,
+                    unique(relation, street, housenumber)
+                );",
+        )?;
+    }
+
+    if user_version < 24 {
+        // Provenance of reference rows, when wsgi.ini declares labelled reference sources.
+        tx.execute_batch(
+            "alter table ref_housenumbers add column source text not null default '';
+            alter table ref_streets add column source text not null default '';",
+        )?;
+    }
+
+    if user_version < 25 {
+        // GeoJSON geometry of the boundary, streets and house numbers of a relation, for maps.
+        tx.execute_batch(
+            "create table osm_geometries (
+                    relation text not null,
+                    kind text not null,
+                    osm_type text not null,
+                    osm_id text not null,
+                    geometry text not null,
+                    unique(relation, kind, osm_type, osm_id)
+                );
+            create index idx_osm_geometries
+                on osm_geometries (relation);",
+        )?;
+    }
+
+    if user_version < 26 {
+        // Representative point and bounding box of OSM objects, empty when unknown.
+        tx.execute_batch(
+            "alter table osm_streets add column lat text not null default '';
+            alter table osm_streets add column lon text not null default '';
+            alter table osm_streets add column min_lat text not null default '';
+            alter table osm_streets add column min_lon text not null default '';
+            alter table osm_streets add column max_lat text not null default '';
+            alter table osm_streets add column max_lon text not null default '';
+            alter table osm_housenumbers add column lat text not null default '';
+            alter table osm_housenumbers add column lon text not null default '';",
+        )?;
+    }
+
+    if user_version < 27 {
+        // Changes of relations detected by cron, for feeds; and the lints these already reported.
+        tx.execute_batch(
+            "create table relation_events (
+                    id integer primary key autoincrement,
+                    relation text not null,
+                    timestamp text not null,
+                    kind text not null,
+                    details text not null
+                );
+            create index idx_relation_events
+                on relation_events (relation);
+            create table relation_event_lints (
+                    relation text not null,
+                    street_name text not null,
+                    housenumber text not null,
+                    source text not null,
+                    reason text not null,
+                    unique(relation, street_name, housenumber, source, reason)
+                );
+            insert into relation_event_lints (relation, street_name, housenumber, source, reason)
+                select distinct relation_name, street_name, housenumber, source, reason from relation_lints;",
+        )?;
+    }
+
+    if user_version < 28 {
+        // House numbers are drawn from their stored point and the street geometry is stored with
+        // the streets, drop the leftovers of the per-relation objects query.
+        tx.execute_batch(
+            "delete from osm_geometries where kind = 'housenumber';
+            delete from mtimes where page like 'geometries/%';",
+        )?;
+    }
+
+    if user_version < 29 {
+        // Relations which were already downloaded are not new: record their 'created' event with
+        // a zero timestamp, which is not shown in feeds.
+        tx.execute_batch(
+            "insert into relation_events (relation, timestamp, kind, details)
+                select distinct substr(page, length('streets/') + 1), '0', 'created', '{}' from mtimes
+                    where page like 'streets/%' and page not like 'streets/%/%'
+                union
+                select distinct relation, '0', 'created', '{}' from stats_coverages;",
+        )?;
+    }
+
+    if user_version < 30 {
+        // Number of lints of relations during the previous cron run, for notifications.
+        tx.execute_batch(
+            "create table relation_notify_lints (
+                    relation text primary key not null,
+                    count integer not null
+                );
+            insert into relation_notify_lints (relation, count)
+                select relation_name, count(*) from relation_lints group by relation_name;",
+        )?;
+    }
+
+    if user_version < 31 {
+        // Replication: relations which have to be downloaded again, the bounding box of relations
+        // and the nodes of streets, so moved nodes can be matched to streets.
+        tx.execute_batch(
+            "create table osm_stale_relations (
+                    relation text primary key not null
+                );
+            create table osm_relation_bounds (
+                    relation text primary key not null,
+                    min_lat real not null,
+                    min_lon real not null,
+                    max_lat real not null,
+                    max_lon real not null
+                );
+            create index idx_osm_relation_bounds
+                on osm_relation_bounds (min_lat, max_lat);
+            insert into osm_relation_bounds (relation, min_lat, min_lon, max_lat, max_lon)
+                select relation, min(cast(min_lat as real)), min(cast(min_lon as real)),
+                    max(cast(max_lat as real)), max(cast(max_lon as real))
+                from osm_streets where min_lat != '' group by relation;
+            create table osm_street_nodes (
+                    relation text not null,
+                    osm_id text not null,
+                    node_id integer not null
+                );
+            create index idx_osm_street_nodes_node_id
+                on osm_street_nodes (node_id);
+            create index idx_osm_street_nodes_relation
+                on osm_street_nodes (relation, osm_id);",
+        )?;
+    }
+
+    tx.execute("pragma user_version = 31", [])?;
     tx.commit()?;
     Ok(())
 }
@@ -23,6 +23,48 @@ fn test_init() {
     init(&mut conn).unwrap();
 }
 
+/// Tests init(): relations which are already downloaded get a 'created' event, so they don't show
+/// up as new areas.
+#[test]
+fn test_init_seed_created_events() {
+    let ctx = context::tests::make_test_context().unwrap();
+    let mut conn = ctx.get_database_connection().unwrap();
+    conn.execute_batch(
+        "pragma user_version = 28;
+         drop table relation_notify_lints;
+         drop table osm_stale_relations;
+         drop table osm_relation_bounds;
+         drop table osm_street_nodes;
+         insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');
+         insert into mtimes (page, last_modified) values ('streets/gazdagret/osm-base', '0');
+         insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');
+         insert into stats_coverages (date, relation, streets, housenumbers) values ('2020-05-09', 'ujbuda', '40.00', '');",
+    )
+    .unwrap();
+
+    init(&mut conn).unwrap();
+
+    let mut stmt = conn
+        .prepare("select relation, timestamp, kind from relation_events order by relation")
+        .unwrap();
+    let events: Vec<(String, String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            (
+                "gazdagret".to_string(),
+                "0".to_string(),
+                "created".to_string()
+            ),
+            ("ujbuda".to_string(), "0".to_string(), "created".to_string()),
+        ]
+    );
+}
+
 /// Tests ignore_primary_key_constraint(), when the error is a primary key constraint violation.
 #[test]
 fn test_ignore_primary_key_constraint_mapped_to_ok() {
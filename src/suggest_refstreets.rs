@@ -0,0 +1,208 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Suggests refstreets mappings for a relation, pairing reference-only and OSM-only street names
+//! which are probably the same street.
+
+use crate::areas;
+use crate::context;
+use crate::util;
+use std::io::Write;
+
+/// Pairs with a lower confidence are not suggested.
+const MIN_SCORE: f64 = 0.75;
+
+/// Abbreviations in street names, after transliteration and case folding.
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("u", "utca"),
+    ("krt", "korut"),
+    ("rkp", "rakpart"),
+    ("sgt", "sugarut"),
+    ("ltp", "lakotelep"),
+    ("str", "strasse"),
+    ("st", "street"),
+    ("ave", "avenue"),
+    ("rd", "road"),
+    ("blvd", "boulevard"),
+];
+
+/// A suggested refstreets entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefstreetSuggestion {
+    /// The OSM-only street name.
+    pub osm_name: String,
+    /// The reference-only street name.
+    pub ref_name: String,
+    /// Confidence, between 0 and 1.
+    pub score: f64,
+}
+
+/// Normalizes a street name for comparison: transliterates, folds case, drops punctuation and
+/// expands abbreviations.
+fn normalize_street_name(name: &str) -> String {
+    let name = unidecode::unidecode(name).to_lowercase();
+    let name: String = name
+        .chars()
+        .map(|i| if i.is_alphanumeric() { i } else { ' ' })
+        .collect();
+    name.split_whitespace()
+        .map(|token| {
+            match ABBREVIATIONS
+                .iter()
+                .find(|(abbreviation, _)| *abbreviation == token)
+            {
+                Some((_, expansion)) => expansion,
+                None => token,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Calculates the Levenshtein distance of two strings, in characters.
+fn get_edit_distance(first: &str, second: &str) -> usize {
+    let second: Vec<char> = second.chars().collect();
+    let mut previous: Vec<usize> = (0..=second.len()).collect();
+    for (i, first_char) in first.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, second_char) in second.iter().enumerate() {
+            let substitution = previous[j] + usize::from(first_char != *second_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[second.len()]
+}
+
+/// Calculates how similar two already normalized street names are, between 0 and 1.
+fn get_normalized_similarity(first: &str, second: &str) -> f64 {
+    let length = first.chars().count().max(second.chars().count());
+    if length == 0 {
+        return 0_f64;
+    }
+    1_f64 - get_edit_distance(first, second) as f64 / length as f64
+}
+
+/// Calculates how similar two street names are, between 0 and 1.
+pub fn get_similarity(first: &str, second: &str) -> f64 {
+    get_normalized_similarity(
+        &normalize_street_name(first),
+        &normalize_street_name(second),
+    )
+}
+
+/// Pairs reference-only and OSM-only street names, each name is used at most once, better
+/// matches first.
+pub fn suggest_pairs(ref_names: &[String], osm_names: &[String]) -> Vec<RefstreetSuggestion> {
+    // Normalize each name only once, not for each pair.
+    let normalized_osm_names: Vec<String> =
+        osm_names.iter().map(|i| normalize_street_name(i)).collect();
+    let mut candidates: Vec<RefstreetSuggestion> = Vec::new();
+    for ref_name in ref_names {
+        let normalized_ref_name = normalize_street_name(ref_name);
+        for (osm_name, normalized_osm_name) in osm_names.iter().zip(&normalized_osm_names) {
+            let score = get_normalized_similarity(&normalized_ref_name, normalized_osm_name);
+            if score >= MIN_SCORE {
+                candidates.push(RefstreetSuggestion {
+                    osm_name: osm_name.to_string(),
+                    ref_name: ref_name.to_string(),
+                    score,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.osm_name.cmp(&b.osm_name))
+            .then_with(|| a.ref_name.cmp(&b.ref_name))
+    });
+
+    let mut ret: Vec<RefstreetSuggestion> = Vec::new();
+    for candidate in candidates {
+        if ret
+            .iter()
+            .any(|i| i.osm_name == candidate.osm_name || i.ref_name == candidate.ref_name)
+        {
+            continue;
+        }
+        ret.push(candidate);
+    }
+    ret.sort_by_key(|i| util::get_sort_key(&i.osm_name));
+    ret
+}
+
+/// Suggests refstreets entries for a relation, based on its already calculated missing streets
+/// and its additional streets.
+pub fn get_suggestions(
+    relation: &areas::Relation<'_>,
+    ref_names: &[String],
+) -> anyhow::Result<Vec<RefstreetSuggestion>> {
+    let osm_names: Vec<String> = relation
+        .get_additional_streets(/*sorted_result=*/ true)?
+        .iter()
+        .map(|i| i.get_osm_name().to_string())
+        .collect();
+    Ok(suggest_pairs(ref_names, &osm_names))
+}
+
+/// Formats suggestions as YAML that can be pasted into a relation-<name>.yaml file.
+pub fn to_yaml(suggestions: &[RefstreetSuggestion]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+
+    let quote = |value: &str| format!("'{}'", value.replace('\'', "''"));
+    let mut ret = String::from("refstreets:\n");
+    for suggestion in suggestions {
+        ret += &format!(
+            "  {}: {}  # confidence: {:.2}\n",
+            quote(&suggestion.osm_name),
+            quote(&suggestion.ref_name),
+            suggestion.score
+        );
+    }
+    ret
+}
+
+/// Inner main() that is allowed to fail.
+pub fn our_main(
+    argv: &[String],
+    stream: &mut dyn Write,
+    ctx: &context::Context,
+) -> anyhow::Result<()> {
+    let relation_name = clap::Arg::new("relation").required(true);
+    let args = [relation_name];
+    let app = clap::Command::new("osm-gimmisn")
+        .override_usage("osm-gimmisn suggest-refstreets <relation>");
+    let args = app.args(&args).try_get_matches_from(argv)?;
+    let relation_name = args.get_one::<String>("relation").unwrap();
+
+    let mut relations = areas::Relations::new(ctx)?;
+    let relation = relations.get_relation(relation_name)?;
+    let (ref_names, _in_both) = relation.get_missing_streets()?;
+    let suggestions = get_suggestions(&relation, &ref_names)?;
+    stream.write_all(to_yaml(&suggestions).as_bytes())?;
+    Ok(())
+}
+
+/// Similar to plain main(), but with an interface that allows testing.
+pub fn main(argv: &[String], stream: &mut dyn Write, ctx: &context::Context) -> i32 {
+    match our_main(argv, stream, ctx) {
+        Ok(_) => 0,
+        Err(err) => {
+            stream.write_all(format!("{err:?}\n").as_bytes()).unwrap();
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
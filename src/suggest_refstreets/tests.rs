@@ -0,0 +1,135 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the suggest_refstreets module.
+
+use super::*;
+use std::io::Read;
+use std::io::Seek;
+
+/// Tests get_similarity().
+#[test]
+fn test_get_similarity() {
+    let cases = [
+        // Abbreviation.
+        ("Kossuth Lajos u.", "Kossuth Lajos utca", 1.0),
+        ("Váci krt.", "Váci körút", 1.0),
+        // Accents and case.
+        ("Petofi utca", "Petőfi Utca", 1.0),
+        // Typo: 1 of 15 characters.
+        ("Kalotaszeg utca", "Kalotaszg utca", 14.0 / 15.0),
+        ("", "", 0.0),
+    ];
+    for (first, second, expected) in cases {
+        let actual = get_similarity(first, second);
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{first} / {second}: {actual}"
+        );
+    }
+}
+
+/// Tests suggest_pairs().
+#[test]
+fn test_suggest_pairs() {
+    let ref_names: Vec<String> = vec![
+        "Kalotaszeg utca".into(),
+        "Kalotaszeg köz".into(),
+        "Hamzsabégi út".into(),
+    ];
+    let osm_names: Vec<String> = vec!["Kalotaszeg u.".into(), "Budaörsi út".into()];
+
+    let actual = suggest_pairs(&ref_names, &osm_names);
+
+    // Each name is used once, the better match wins, unrelated names are not paired.
+    assert_eq!(
+        actual,
+        vec![RefstreetSuggestion {
+            osm_name: "Kalotaszeg u.".into(),
+            ref_name: "Kalotaszeg utca".into(),
+            score: 1.0,
+        }]
+    );
+}
+
+/// Tests to_yaml().
+#[test]
+fn test_to_yaml() {
+    let suggestions = vec![RefstreetSuggestion {
+        osm_name: "Szent István tér".into(),
+        ref_name: "Szt. István' tér".into(),
+        score: 0.8,
+    }];
+
+    let actual = to_yaml(&suggestions);
+
+    assert_eq!(
+        actual,
+        "refstreets:\n  'Szent István tér': 'Szt. István'' tér'  # confidence: 0.80\n"
+    );
+    assert_eq!(to_yaml(&[]), "");
+}
+
+/// Tests main().
+#[test]
+fn test_main() {
+    let argv = vec!["".to_string(), "myrelation".to_string()];
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "myrelation": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+    {
+        let conn = ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into ref_streets (county_code, settlement_code, street) values ('0', '0', 'Kalotaszeg utca');
+             insert into ref_streets (county_code, settlement_code, street) values ('0', '0', 'Törökugrató utca');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('myrelation', '1', 'Kalotaszeg u.', 'residential', '', '', '', 'way');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('myrelation', '2', 'Törökugrató utca', 'residential', '', '', '', 'way');",
+        )
+        .unwrap();
+    }
+
+    let ret = main(&argv, &mut buf, &ctx);
+
+    assert_eq!(ret, 0);
+    buf.rewind().unwrap();
+    let mut actual: Vec<u8> = Vec::new();
+    buf.read_to_end(&mut actual).unwrap();
+    assert_eq!(
+        String::from_utf8(actual).unwrap(),
+        "refstreets:\n  'Kalotaszeg u.': 'Kalotaszeg utca'  # confidence: 1.00\n"
+    );
+}
+
+/// Tests main(), the missing relation argument case.
+#[test]
+fn test_main_no_relation() {
+    let argv = vec!["".to_string()];
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+    let ctx = context::tests::make_test_context().unwrap();
+
+    let ret = main(&argv, &mut buf, &ctx);
+
+    assert_eq!(ret, 1);
+}
@@ -11,7 +11,7 @@
 //! Synchronizes reference data between a public instance and a local dev instance.
 
 use crate::context;
-use crate::util;
+use crate::reference;
 use anyhow::Context as _;
 use std::collections::HashMap;
 use std::io::Write;
@@ -29,20 +29,21 @@ pub fn download(
         // mode is not "local", so download & write the config first.
         let config_data = ctx.get_file_system().read_to_string(config_file)?;
         let config: context::IniConfig = toml::from_str(&config_data)?;
-        let mut paths: Vec<String> = Vec::new();
-        let values = config.wsgi.reference_housenumbers;
-        paths.append(
-            &mut values
-                .split(' ')
-                .map(|value| value.strip_prefix("workdir/refs/").unwrap().to_string())
-                .collect(),
-        );
-        let value = config.wsgi.reference_street;
-        paths.push(value.strip_prefix("workdir/refs/").unwrap().to_string());
-        let value = config.wsgi.reference_citycounts;
-        paths.push(value.strip_prefix("workdir/refs/").unwrap().to_string());
-        let value = config.wsgi.reference_zipcounts;
-        paths.push(value.strip_prefix("workdir/refs/").unwrap().to_string());
+        let mut values: Vec<String> = config
+            .wsgi
+            .reference_housenumbers
+            .split(' ')
+            .map(|value| value.to_string())
+            .collect();
+        values.push(config.wsgi.reference_street);
+        values.push(config.wsgi.reference_citycounts);
+        values.push(config.wsgi.reference_zipcounts);
+        values.extend(config.wsgi.reference_sources.into_iter().map(|i| i.path));
+        let paths: Vec<String> = values
+            .iter()
+            .filter_map(|value| value.strip_prefix("workdir/refs/"))
+            .map(|value| value.to_string())
+            .collect();
 
         let mut dests: Vec<String> = Vec::new();
         for path in &paths {
@@ -79,10 +80,8 @@ pub fn download(
         conn.execute("delete from ref_housenumbers", [])?;
         conn.execute("delete from ref_streets", [])?;
     }
-    let ref_streets = ctx.get_ini().get_reference_street_path()?;
-    util::build_street_reference_index(ctx, &ref_streets)?;
-    let references = ctx.get_ini().get_reference_housenumber_paths()?;
-    util::build_reference_index(ctx, &references)?;
+    reference::build_street_reference_index(ctx)?;
+    reference::build_reference_index(ctx)?;
 
     // These caches have explicit dependencies only on OSM data, so empty them now.
     let conn = ctx.get_database_connection()?;
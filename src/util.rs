@@ -14,7 +14,6 @@ use crate::areas;
 use crate::context;
 use crate::i18n;
 use crate::i18n::translate as tr;
-use crate::overpass_query;
 use crate::ranges;
 use crate::yattag;
 use anyhow::anyhow;
@@ -35,7 +34,6 @@ lazy_static! {
         regex::Regex::new(r"^([0-9]*)([^0-9].*|)$").unwrap();
     static ref LETTER_SUFFIX: regex::Regex = regex::Regex::new(r".*([A-Za-z]+)\*?").unwrap();
     static ref NUMBER_SUFFIX: regex::Regex = regex::Regex::new(r"^.*/([0-9])\*?$").unwrap();
-    static ref NULL_END: regex::Regex = regex::Regex::new(r" null$").unwrap();
     static ref GIT_HASH: regex::Regex = regex::Regex::new(r".*-g([0-9a-f]+)(-modified)?").unwrap();
 }
 
@@ -108,7 +106,7 @@ pub trait Diff {
 
 /// A street has an OSM and a reference name. Ideally the two are the same. Sometimes the reference
 /// name differs.
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Street {
     osm_name: String,
@@ -213,7 +211,7 @@ impl Eq for Street {}
 /// A house number is a string which remembers what was its provider range.  E.g. the "1-3" string
 /// can generate 3 house numbers, all of them with the same range.
 /// The comment is similar to source, it's ignored during eq() and hash().
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
 pub struct HouseNumber {
     number: String,
     source: String,
@@ -228,7 +226,7 @@ pub struct HouseNumber {
 pub type HouseNumbers = Vec<HouseNumber>;
 
 /// A numbered street is a street with associated house numbers.
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct NumberedStreet {
     /// The common part for all housenumbers.
@@ -436,6 +434,12 @@ pub struct ZipCount {
     /// Reference count of all housenumbers.
     #[serde(rename = "CNT")]
     pub count: u64,
+    /// County code, if the zip belongs to a single settlement.
+    #[serde(rename = "COUNTY_CODE", default)]
+    pub county_code: String,
+    /// Settlement code, if the zip belongs to a single settlement.
+    #[serde(rename = "SETTLEMENT_CODE", default)]
+    pub settlement_code: String,
 }
 
 /// Creates a new typed CSV reader.
@@ -565,136 +569,6 @@ pub fn color_house_number(house_number: &HouseNumberRange) -> yattag::Doc {
     doc
 }
 
-/// One row in workdir/refs/streets_<DATE>.tsv.
-#[derive(serde::Deserialize)]
-pub struct RefStreet {
-    /// County code.
-    #[serde(rename = "COUNTY_CODE")]
-    pub county: String,
-    /// Settlement code.
-    #[serde(rename = "SETTLEMENT_CODE")]
-    pub settlement: String,
-    /// Street name.
-    #[serde(rename = "STREET")]
-    pub street: String,
-}
-
-/// One row in workdir/refs/housenumbers_<DATE>.tsv.
-#[derive(serde::Deserialize)]
-pub struct RefHouseNumber {
-    /// County code.
-    #[serde(rename = "COUNTY_CODE")]
-    pub county: String,
-    /// Settlement code.
-    #[serde(rename = "SETTLEMENT_CODE")]
-    pub settlement: String,
-    /// Street name.
-    #[serde(rename = "STREET")]
-    pub street: String,
-    /// House number.
-    #[serde(rename = "HOUSENUMBER")]
-    pub housenumber: String,
-    /// Comment.
-    #[serde(rename = "COMMENT")]
-    pub comment: Option<String>,
-}
-
-/// Builds an in-database index from the reference TSV (house number version).
-pub fn build_reference_index(ctx: &context::Context, paths: &[String]) -> anyhow::Result<()> {
-    let mut conn = ctx.get_database_connection()?;
-    {
-        // Check if the TSV is imported already.
-        let mut stmt =
-            conn.prepare("select count(*) from (select 0 from ref_housenumbers limit 1)")?;
-        let mut rows = stmt.query([])?;
-        while let Some(row) = rows.next()? {
-            let count: i64 = row.get(0).unwrap();
-            if count > 0 {
-                return Ok(());
-            }
-        }
-    }
-
-    for abspath in paths {
-        let mut tokens = abspath.split('/');
-        let path = tokens.next_back().unwrap();
-        if path.starts_with("hazszamok_kieg") {
-            let stream = ctx.get_file_system().open_read(abspath)?;
-            let mut guard = stream.borrow_mut();
-            let read = std::io::BufReader::new(guard.deref_mut());
-            let mut reader = csv::ReaderBuilder::new()
-                .delimiter(b'\t')
-                .double_quote(true)
-                .from_reader(read);
-            let tx = conn.transaction()?;
-            for result in reader.deserialize() {
-                let row: RefHouseNumber = result?;
-                tx.execute(
-                        "insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values (?1, ?2, ?3, ?4, ?5)",
-                        [row.county, row.settlement, row.street, row.housenumber, row.comment.unwrap_or(" ".into())],
-                    )?;
-            }
-            tx.commit()?;
-        } else {
-            // Assume "hazszamok_".
-            let stream = ctx.get_file_system().open_read(abspath)?;
-            let mut guard = stream.borrow_mut();
-            let read = std::io::BufReader::new(guard.deref_mut());
-            let mut reader = csv::ReaderBuilder::new()
-                .delimiter(b'\t')
-                .double_quote(true)
-                .from_reader(read);
-            let tx = conn.transaction()?;
-            for result in reader.deserialize() {
-                let row: RefHouseNumber = result?;
-                tx.execute(
-                          "insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values (?1, ?2, ?3, ?4, '')",
-                          [row.county, row.settlement, row.street, row.housenumber],
-                      )?;
-            }
-            tx.commit()?;
-        }
-    }
-
-    Ok(())
-}
-
-/// Builds an in-database index from the reference TSV (street version).
-pub fn build_street_reference_index(ctx: &context::Context, path: &str) -> anyhow::Result<()> {
-    let mut conn = ctx.get_database_connection()?;
-    {
-        // Check if the TSV is imported already.
-        let mut stmt = conn.prepare("select count(*) from (select 0 from ref_streets limit 1)")?;
-        let mut rows = stmt.query([])?;
-        while let Some(row) = rows.next()? {
-            let count: i64 = row.get(0).unwrap();
-            if count > 0 {
-                return Ok(());
-            }
-        }
-    }
-
-    let stream = ctx.get_file_system().open_read(path)?;
-    let mut guard = stream.borrow_mut();
-    let read = std::io::BufReader::new(guard.deref_mut());
-    let mut reader = csv::ReaderBuilder::new()
-        .delimiter(b'\t')
-        .double_quote(true)
-        .from_reader(read);
-    let tx = conn.transaction()?;
-    for result in reader.deserialize() {
-        let row: RefStreet = result?;
-        let street = NULL_END.replace(&row.street, "").to_string();
-        tx.execute(
-            "insert into ref_streets (county_code, settlement_code, street) values (?1, ?2, ?3)",
-            [row.county, row.settlement, street],
-        )?;
-    }
-    tx.commit()?;
-
-    Ok(())
-}
-
 /// Parses a filter description, like 'filter-for', 'refcounty', '42'.
 pub fn parse_filters(tokens: &[String]) -> HashMap<String, String> {
     let mut ret: HashMap<String, String> = HashMap::new();
@@ -729,7 +603,7 @@ pub fn handle_overpass_error(ctx: &context::Context, http_error: &str) -> yattag
     let doc = yattag::Doc::new();
     let div = doc.tag("div", &[("id", "overpass-error")]);
     div.text(&tr("Overpass error: {0}").replace("{0}", http_error));
-    let sleep = overpass_query::overpass_query_need_sleep(ctx);
+    let sleep = ctx.get_osm_source().need_sleep(ctx);
     if sleep > 0 {
         doc.stag("br");
         doc.text(&tr("Note: wait for {} seconds").replace("{}", &sleep.to_string()));
@@ -975,6 +849,8 @@ pub struct OsmHouseNumber {
     pub place: Option<String>,
     /// Object type.
     pub object_type: String,
+    /// Postcode, empty if unknown.
+    pub postcode: String,
 }
 
 impl OsmHouseNumber {
@@ -999,6 +875,7 @@ impl OsmHouseNumber {
             street,
             place,
             object_type,
+            postcode: String::new(),
         }
     }
 }
@@ -95,78 +95,6 @@ fn test_format_even_odd_html_multi_odd() {
     assert_eq!(doc.get_value(), "1, 3".to_string());
 }
 
-/// Tests build_reference_index().
-#[test]
-fn test_build_reference_index() {
-    let ctx = context::tests::make_test_context().unwrap();
-    {
-        let conn = ctx.get_database_connection().unwrap();
-        conn.execute_batch("delete from ref_housenumbers").unwrap();
-    }
-    let refpath = ctx.get_abspath("workdir/refs/hazszamok_20190511.tsv");
-    build_reference_index(&ctx, &[refpath.clone()]).unwrap();
-    {
-        let conn = ctx.get_database_connection().unwrap();
-        let mut stmt = conn
-            .prepare("select count(*) from ref_housenumbers")
-            .unwrap();
-        let mut rows = stmt.query([]).unwrap();
-        while let Some(row) = rows.next().unwrap() {
-            let count: i64 = row.get(0).unwrap();
-            // Empty table, so changes from 0 to 14.
-            assert_eq!(count, 14);
-        }
-    }
-
-    build_reference_index(&ctx, &[refpath]).unwrap();
-    {
-        let conn = ctx.get_database_connection().unwrap();
-        let mut stmt = conn
-            .prepare("select count(*) from ref_housenumbers")
-            .unwrap();
-        let mut rows = stmt.query([]).unwrap();
-        while let Some(row) = rows.next().unwrap() {
-            let count: i64 = row.get(0).unwrap();
-            // Early return, so doesn't change from 14 to 28.
-            assert_eq!(count, 14);
-        }
-    }
-}
-
-/// Tests build_street_reference_index().
-#[test]
-fn test_build_street_reference_index() {
-    let ctx = context::tests::make_test_context().unwrap();
-    {
-        let conn = ctx.get_database_connection().unwrap();
-        conn.execute_batch("delete from ref_streets").unwrap();
-    }
-    let refpath = ctx.get_abspath("workdir/refs/utcak_20190514.tsv");
-    build_street_reference_index(&ctx, &refpath).unwrap();
-    {
-        let conn = ctx.get_database_connection().unwrap();
-        let mut stmt = conn.prepare("select count(*) from ref_streets").unwrap();
-        let mut rows = stmt.query([]).unwrap();
-        while let Some(row) = rows.next().unwrap() {
-            let count: i64 = row.get(0).unwrap();
-            // Empty table, so changes from 0 to 6.
-            assert_eq!(count, 6);
-        }
-    }
-
-    build_street_reference_index(&ctx, &refpath).unwrap();
-    {
-        let conn = ctx.get_database_connection().unwrap();
-        let mut stmt = conn.prepare("select count(*) from ref_streets").unwrap();
-        let mut rows = stmt.query([]).unwrap();
-        while let Some(row) = rows.next().unwrap() {
-            let count: i64 = row.get(0).unwrap();
-            // Early return, so doesn't change from 6 to 12.
-            assert_eq!(count, 6);
-        }
-    }
-}
-
 /// Tests split_house_number(): just numbers.
 #[test]
 fn test_split_house_number_only_number() {
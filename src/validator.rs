@@ -12,6 +12,8 @@
 
 use crate::areas;
 use crate::context;
+use crate::housenumber_grammar;
+use crate::infer_ranges;
 use anyhow::Context;
 use std::collections::HashMap;
 use std::io::Write;
@@ -244,11 +246,36 @@ fn validate_relation(
             street_filters,
         )?;
     }
+    if let Some(ref grammar) = relation.housenumber_grammar {
+        if housenumber_grammar::get_grammar(grammar).is_err() {
+            errors.push(format!(
+                "expected value for '{context}housenumber-grammar' is 'hu', 'de' or 'us'"
+            ));
+        }
+    }
     if let Some(ref source) = relation.source {
         if source.parse::<i64>().is_ok() {
             errors.push(format!("expected value type for '{context}source' is str"));
         }
     }
+    if let Some(ref emails) = relation.notify_emails {
+        for (index, email) in emails.iter().enumerate() {
+            if !email.contains('@') {
+                errors.push(format!(
+                    "expected value for '{context}notify-emails[{index}]' is an email address"
+                ));
+            }
+        }
+    }
+    if let Some(ref webhooks) = relation.notify_webhooks {
+        for (index, webhook) in webhooks.iter().enumerate() {
+            if !webhook.starts_with("http://") && !webhook.starts_with("https://") {
+                errors.push(format!(
+                    "expected value for '{context}notify-webhooks[{index}]' is an http(s) URL"
+                ));
+            }
+        }
+    }
     if let Some(ref aliases) = relation.alias {
         for (index, alias) in aliases.iter().enumerate() {
             if alias.parse::<i64>().is_ok() {
@@ -268,6 +295,10 @@ fn validate_relations(
     relations: &areas::RelationsDict,
 ) -> anyhow::Result<()> {
     for (key, value) in relations {
+        if key == "filter-for" {
+            // This would be shadowed by the /api/v1/relations/filter-for/... endpoint.
+            errors.push(format!("relation name '{key}' is reserved"));
+        }
         validate_relation(errors, key, value)?;
     }
 
@@ -316,6 +347,20 @@ pub fn our_main(
             serde_yaml::from_str(&data).context(format!("failed to validate {yaml_path}"))?;
         let parent = "";
         validate_relation(&mut errors, parent, &relation_dict)?;
+
+        // Hints are not errors, they don't fail the validation.
+        let file_name = std::path::Path::new(yaml_path)
+            .file_name()
+            .and_then(|i| i.to_str())
+            .unwrap_or("");
+        if let Some(relation_name) = file_name
+            .strip_prefix("relation-")
+            .and_then(|i| i.strip_suffix(".yaml"))
+        {
+            for hint in infer_ranges::get_hints(ctx, relation_name, &relation_dict)? {
+                stream.write_all(format!("{hint}\n").as_bytes())?;
+            }
+        }
     }
     if !errors.is_empty() {
         for error in errors {
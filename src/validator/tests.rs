@@ -35,6 +35,34 @@ fn test_relations() {
     assert_eq!(ret, 0);
 }
 
+/// Tests the relations path: a reserved relation name.
+#[test]
+fn test_relations_reserved_name() {
+    let content = r#"filter-for:
+    osmrelation: 2713748
+    refcounty: "01"
+    refsettlement: "011"
+"#;
+    let path = "data/relations.yaml";
+    let mut ctx = context::tests::make_test_context().unwrap();
+    let argv: &[String] = &["".into(), ctx.get_abspath(path)];
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+    let file = context::tests::TestFileSystem::make_file();
+    file.borrow_mut().write_all(content.as_bytes()).unwrap();
+    let files = context::tests::TestFileSystem::make_files(&ctx, &[(path, &file)]);
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+
+    let ret = main(argv, &mut buf, &ctx);
+
+    assert_eq!(ret, 1);
+    let expected = format!(
+        "relation name 'filter-for' is reserved\nfailed to validate {}\n",
+        ctx.get_abspath(path)
+    );
+    assert_eq!(String::from_utf8(buf.into_inner()).unwrap(), expected);
+}
+
 /// Tests the missing-osmrelation relations path.
 #[test]
 fn test_relations_missing_osmrelation() {
@@ -251,6 +279,24 @@ fn test_relation_source_bad_type() {
     assert_failure_msg(content, expected);
 }
 
+/// Tests the relation path: unknown housenumber-grammar.
+#[test]
+fn test_relation_housenumber_grammar_bad() {
+    let content = "housenumber-grammar: fr\n";
+    let expected =
+        "expected value for 'housenumber-grammar' is 'hu', 'de' or 'us'\nfailed to validate {0}\n";
+    assert_failure_msg(content, expected);
+}
+
+/// Tests the relation path: bad notify-emails and notify-webhooks values.
+#[test]
+fn test_relation_notify_bad() {
+    let content = "notify-emails:\n  - nobody\nnotify-webhooks:\n  - ftp://localhost/\n";
+    let expected = "expected value for 'notify-emails[0]' is an email address\n\
+expected value for 'notify-webhooks[0]' is an http(s) URL\nfailed to validate {0}\n";
+    assert_failure_msg(content, expected);
+}
+
 /// Tests the relation path: bad tab indent.
 #[test]
 fn test_relation_tab() {
@@ -520,3 +566,37 @@ fn test_null_value() {
         "expected at least one sub-key for 'filters.Budaörsi út'\nfailed to validate {0}\n";
     assert_failure_msg(content, expected);
 }
+
+/// Tests the relation path: a street split across relations produces a hint, not an error.
+#[test]
+fn test_relation_infer_ranges_hint() {
+    let path = "data/relation-north.yaml";
+    let mut ctx = infer_ranges::tests::make_test_context(serde_json::json!({})).unwrap();
+    let argv: &[String] = &["".into(), ctx.get_abspath(path)];
+    let mut buf: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+    let yamls_cache_value = ctx
+        .get_file_system()
+        .read_to_string(&ctx.get_abspath("data/yamls.cache"))
+        .unwrap();
+    let yamls_cache = context::tests::TestFileSystem::make_file();
+    yamls_cache
+        .borrow_mut()
+        .write_all(yamls_cache_value.as_bytes())
+        .unwrap();
+    let file = context::tests::TestFileSystem::make_file();
+    file.borrow_mut().write_all(b"source: survey\n").unwrap();
+    let files = context::tests::TestFileSystem::make_files(
+        &ctx,
+        &[(path, &file), ("data/yamls.cache", &yamls_cache)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    ctx.set_file_system(&file_system);
+
+    let ret = main(argv, &mut buf, &ctx);
+
+    assert_eq!(ret, 0);
+    assert_eq!(
+        String::from_utf8(buf.into_inner()).unwrap(),
+        "hint: 'Hosszú utca' is split across relations, consider 'filters.Hosszú utca.ranges': [{start: '1', end: '5'}, {start: '2', end: '4'}]\n"
+    );
+}
@@ -289,6 +289,29 @@ fn fill_existing_header_items(
         a.text(&tr("Existing streets"));
     }
     items.push(doc);
+
+    let doc = yattag::Doc::new();
+    {
+        let a = doc.tag(
+            "a",
+            &[("href", &format!("{prefix}/map/{relation_name}/view-result"))],
+        );
+        a.text(&tr("Map"));
+    }
+    items.push(doc);
+
+    let doc = yattag::Doc::new();
+    {
+        let a = doc.tag(
+            "a",
+            &[(
+                "href",
+                &format!("{prefix}/feed/{relation_name}/view-result.atom"),
+            )],
+        );
+        a.text(&tr("Feed"));
+    }
+    items.push(doc);
     Ok(items)
 }
 
@@ -798,6 +821,101 @@ fn handle_invalid_addr_cities(
     Ok(doc)
 }
 
+/// Produces a UI string for a lint reason.
+pub fn get_lint_reason_string(reason: &areas::RelationLintReason) -> String {
+    match reason {
+        areas::RelationLintReason::CreatedInOsm => tr("created in OSM"),
+        areas::RelationLintReason::DeletedFromRef => tr("deleted from reference"),
+        areas::RelationLintReason::OutOfRange => tr("out of range"),
+        areas::RelationLintReason::MissingPostcode => tr("missing postcode"),
+        areas::RelationLintReason::MalformedPostcode => tr("malformed postcode"),
+        areas::RelationLintReason::UnknownPostcode => tr("unknown postcode"),
+    }
+}
+
+/// Expected request_uri: e.g. /osm/lints/whole-country/invalid-postcodes.
+fn handle_invalid_postcodes(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+) -> anyhow::Result<yattag::Doc> {
+    let doc = yattag::Doc::new();
+    doc.append_value(
+        get_toolbar(
+            ctx,
+            Some(relations),
+            /*function=*/ "",
+            /*relation_name=*/ "",
+            /*relation_osmid=*/ 0,
+        )?
+        .get_value(),
+    );
+
+    // The lints are grouped by relation, the relation lints page has the individual objects.
+    let prefix = ctx.get_ini().get_uri_prefix();
+    let mut table: Vec<Vec<yattag::Doc>> = vec![vec![
+        yattag::Doc::from_text(&tr("Area name")),
+        yattag::Doc::from_text(&tr("Missing")),
+        yattag::Doc::from_text(&tr("Malformed")),
+        yattag::Doc::from_text(&tr("Unknown")),
+        yattag::Doc::from_text(&tr("Total")),
+    ]];
+    let mut count = 0;
+    {
+        let conn = ctx.get_database_connection()?;
+        let mut stmt = conn.prepare(
+            "select relation_name, reason, count(*) from relation_lints where source = ?1 group by relation_name, reason order by relation_name",
+        )?;
+        let mut rows = stmt.query([areas::RelationLintSource::Postcode.to_string()])?;
+        // Relation name -> counts of the missing, malformed and unknown postcodes.
+        let mut relation_counts: Vec<(String, [i64; 3])> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let relation_name: String = row.get(0)?;
+            let reason = areas::RelationLintReason::try_from(row.get::<_, String>(1)?.as_str())?;
+            let reason_count: i64 = row.get(2)?;
+            if relation_counts.last().map(|i| &i.0) != Some(&relation_name) {
+                relation_counts.push((relation_name, [0; 3]));
+            }
+            let counts = &mut relation_counts.last_mut().unwrap().1;
+            match reason {
+                areas::RelationLintReason::MissingPostcode => counts[0] += reason_count,
+                areas::RelationLintReason::MalformedPostcode => counts[1] += reason_count,
+                _ => counts[2] += reason_count,
+            }
+            count += reason_count;
+        }
+        for (relation_name, counts) in relation_counts {
+            let cell = yattag::Doc::new();
+            {
+                let a = cell.tag(
+                    "a",
+                    &[(
+                        "href",
+                        &format!("{prefix}/missing-housenumbers/{relation_name}/view-lints"),
+                    )],
+                );
+                a.text(&relation_name);
+            }
+            let mut cells = vec![cell];
+            for value in counts {
+                cells.push(yattag::Doc::from_text(&value.to_string()));
+            }
+            let total: i64 = counts.iter().sum();
+            cells.push(yattag::Doc::from_text(&total.to_string()));
+            table.push(cells);
+        }
+    }
+    {
+        let p = doc.tag("p", &[]);
+        p.text(
+            &tr("The addr:postcode key of the below {0} objects is probably missing or has an invalid value.")
+                .replace("{0}", &count.to_string()),
+        );
+    }
+    doc.append_value(util::html_table_from_list(&table).get_value());
+    doc.append_value(get_footer(/*last_updated=*/ "").get_value());
+    Ok(doc)
+}
+
 fn handle_invalid_addr_cities_update(ctx: &context::Context) -> anyhow::Result<()> {
     cron::update_stats_overpass(ctx).context("update_stats_overpass failed")?;
     stats::update_invalid_addr_cities(ctx).context("update_invalid_addr_cities failed")?;
@@ -1098,6 +1216,10 @@ pub fn handle_lints(
         return handle_invalid_addr_cities(ctx, relations);
     }
 
+    if request_uri.ends_with("/invalid-postcodes") {
+        return handle_invalid_postcodes(ctx, relations);
+    }
+
     if request_uri.ends_with("/invalid-addr-cities/update-result") {
         return handle_invalid_addr_cities_update_html(ctx, relations);
     }
@@ -1119,6 +1241,11 @@ pub fn handle_lints(
     let title_ids = &[
         (tr("Invalid relation settings"), "invalid-relations"),
         (tr("Invalid addr:city values"), "invalid-addr-cities"),
+        (tr("Invalid addr:postcode values"), "invalid-postcodes"),
+        (
+            tr("Relation lints, as tasks for a cooperative tasking tool"),
+            "relation-lints.geojson",
+        ),
     ];
 
     {
@@ -1138,12 +1265,12 @@ pub fn handle_lints(
                 continue;
             }
 
-            // Assume invalid-addr-cities.
+            // Assume invalid-addr-cities, invalid-postcodes or relation-lints.geojson.
             let a = li.tag(
                 "a",
                 &[(
                     "href",
-                    &format!("{prefix}/lints/whole-country/invalid-addr-cities"),
+                    &format!("{prefix}/lints/whole-country/{identifier}"),
                 )],
             );
             a.text(title);
@@ -1154,6 +1281,130 @@ pub fn handle_lints(
     Ok(doc)
 }
 
+/// Parses a timestamp of the cron journal, in unix nanoseconds, empty if it's not known yet.
+fn parse_journal_timestamp(
+    ctx: &context::Context,
+    value: &str,
+) -> anyhow::Result<Option<time::OffsetDateTime>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let nanos: i128 = value.parse()?;
+    let timestamp = time::OffsetDateTime::from_unix_timestamp_nanos(nanos)?;
+    Ok(Some(timestamp.to_offset(ctx.get_time().now().offset())))
+}
+
+/// Expected request_uri: e.g. /osm/cron-status.
+pub fn handle_cron_status(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    _request_uri: &str,
+) -> anyhow::Result<yattag::Doc> {
+    let doc = yattag::Doc::new();
+    doc.append_value(
+        get_toolbar(
+            ctx,
+            Some(relations),
+            /*function=*/ "",
+            /*relation_name=*/ "",
+            /*relation_osmid=*/ 0,
+        )?
+        .get_value(),
+    );
+
+    let mut stages: Vec<Vec<yattag::Doc>> = vec![vec![
+        yattag::Doc::from_text(&tr("Stage")),
+        yattag::Doc::from_text(&tr("Status")),
+        yattag::Doc::from_text(&tr("Started")),
+        yattag::Doc::from_text(&tr("Finished")),
+        yattag::Doc::from_text(&tr("Duration")),
+    ]];
+    let mut failures: Vec<Vec<yattag::Doc>> = vec![vec![
+        yattag::Doc::from_text(&tr("Stage")),
+        yattag::Doc::from_text(&tr("Relation")),
+        yattag::Doc::from_text(&tr("Finished")),
+        yattag::Doc::from_text(&tr("Error")),
+    ]];
+    {
+        let conn = ctx.get_database_connection()?;
+        let mut stmt = conn.prepare(
+            "select stage, relation, status, started, finished, error from cron_journal order by started, stage, relation",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let stage: String = row.get(0)?;
+            let relation: String = row.get(1)?;
+            let status: String = row.get(2)?;
+            let started = parse_journal_timestamp(ctx, &row.get::<_, String>(3)?)?;
+            let finished = parse_journal_timestamp(ctx, &row.get::<_, String>(4)?)?;
+            let error: String = row.get(5)?;
+            let finished_text = match finished {
+                Some(value) => format_timestamp(&value)?,
+                None => "".into(),
+            };
+            if status == "failed" {
+                failures.push(vec![
+                    yattag::Doc::from_text(&stage),
+                    yattag::Doc::from_text(&relation),
+                    yattag::Doc::from_text(&finished_text),
+                    yattag::Doc::from_text(&error),
+                ]);
+            }
+            if !relation.is_empty() {
+                continue;
+            }
+
+            let started_text = match started {
+                Some(value) => format_timestamp(&value)?,
+                None => "".into(),
+            };
+            let duration = match (started, finished) {
+                (Some(started), Some(finished)) => {
+                    let duration = finished - started;
+                    format!(
+                        "{}:{:0>2}:{:0>2}",
+                        duration.whole_hours(),
+                        duration.whole_minutes() % 60,
+                        duration.whole_seconds() % 60
+                    )
+                }
+                _ => "".into(),
+            };
+            stages.push(vec![
+                yattag::Doc::from_text(&stage),
+                yattag::Doc::from_text(&status),
+                yattag::Doc::from_text(&started_text),
+                yattag::Doc::from_text(&finished_text),
+                yattag::Doc::from_text(&duration),
+            ]);
+        }
+    }
+
+    if stages.len() == 1 {
+        let p = doc.tag("p", &[]);
+        p.text(&tr("No cron run is recorded yet."));
+    } else {
+        {
+            let h2 = doc.tag("h2", &[]);
+            h2.text(&tr("Stages of the last cron run"));
+        }
+        doc.append_value(util::html_table_from_list(&stages).get_value());
+        {
+            let h2 = doc.tag("h2", &[]);
+            h2.text(&tr("Failures"));
+        }
+        if failures.len() == 1 {
+            let p = doc.tag("p", &[]);
+            p.text(&tr("No failures."));
+        } else {
+            doc.append_value(util::html_table_from_list(&failures).get_value());
+        }
+    }
+
+    doc.append_value(get_footer(/*last_updated=*/ "").get_value());
+    Ok(doc)
+}
+
 /// Finds out the request URI.
 pub fn get_request_uri(
     request: &rouille::Request,
@@ -1205,6 +1456,7 @@ pub fn check_existing_relation(
         && !request_uri.starts_with(&format!("{prefix}/missing-streets/"))
         && !request_uri.starts_with(&format!("{prefix}/street-housenumbers/"))
         && !request_uri.starts_with(&format!("{prefix}/missing-housenumbers/"))
+        && !request_uri.starts_with(&format!("{prefix}/map/"))
     {
         return Ok(doc);
     }
@@ -1302,6 +1554,73 @@ pub fn handle_github_webhook(
     Ok(yattag::Doc::from_text(""))
 }
 
+/// Compares two strings in constant time, so the time of a failed comparison doesn't leak the
+/// length of the matching prefix.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.bytes()
+        .zip(b.bytes())
+        .fold(0_u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+/// Gets the login of the surveyor who sent the request, if the request has valid credentials.
+pub fn get_surveyor(request: &rouille::Request, ctx: &context::Context) -> Option<String> {
+    let credentials = rouille::input::basic_http_auth(request)?;
+    match ctx.get_ini().get_surveyors().get(&credentials.login) {
+        Some(token) if constant_time_eq(token, &credentials.password) => Some(credentials.login),
+        _ => None,
+    }
+}
+
+/// Determines if a form submission comes from a page of this site, based on its Origin (or
+/// Referer) header. The browser sends basic authentication credentials with cross-site requests
+/// as well, so this protects against cross-site request forgery.
+pub fn is_same_origin(request: &rouille::Request, ctx: &context::Context) -> bool {
+    let origin = match request
+        .header("Origin")
+        .or_else(|| request.header("Referer"))
+    {
+        Some(value) => value,
+        None => return false,
+    };
+    let url = match url::Url::parse(origin) {
+        Ok(value) => value,
+        Err(_) => return false,
+    };
+    let origin = url.origin().ascii_serialization();
+    let site_url = ctx.get_ini().get_site_url();
+    if origin == site_url.trim_end_matches('/') {
+        return true;
+    }
+
+    // Not behind the configured public address, e.g. a local instance: compare with the Host
+    // header.
+    let host = match url.host_str() {
+        Some(value) => value,
+        None => return false,
+    };
+    let host = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    request.header("Host") == Some(host.as_str())
+}
+
+/// Creates a response which asks the client to log in as a surveyor.
+pub fn make_unauthorized_response(content_type: &str, data: Vec<u8>) -> rouille::Response {
+    let headers: Headers = vec![
+        ("Content-type".into(), content_type.to_string().into()),
+        (
+            "WWW-Authenticate".into(),
+            r#"Basic realm="osm-gimmisn surveyors""#.into(),
+        ),
+    ];
+    make_response(401_u16, headers, data)
+}
+
 /// Factory for rouille::Response.
 pub fn make_response(status_code: u16, headers: Headers, data: Vec<u8>) -> rouille::Response {
     rouille::Response {
@@ -226,6 +226,88 @@ fn test_handle_invalid_addr_cities() {
     assert_eq!(results.len(), 2);
 }
 
+/// Tests handle_invalid_postcodes().
+#[test]
+fn test_handle_invalid_postcodes() {
+    let mut test_wsgi = wsgi::tests::TestWsgi::new();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'postcode', '2', 'missing-postcode', '42', 'node');
+             insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'postcode', '3', 'unknown-postcode', '44', 'node');
+             insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('ujbuda', 'Kossuth utca', 'postcode', '1', 'malformed-postcode', '45', 'node');
+             insert into relation_lints (relation_name, street_name, source, housenumber, reason, object_id, object_type) values ('gazdagret', 'Tűzkő utca', 'invalid', '5', 'created-in-osm', '43', 'node');",
+        )
+        .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/lints/whole-country/invalid-postcodes");
+
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/table/tr");
+    // header + 1 row per relation, the invalid lint is not counted.
+    assert_eq!(results.len(), 3);
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/table/tr[2]/td[1]/a/@href");
+    assert_eq!(results, ["/osm/missing-housenumbers/gazdagret/view-lints"]);
+    // Missing, malformed, unknown and total.
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/table/tr[2]/td[5]");
+    assert_eq!(results, ["2"]);
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/table/tr[3]/td[3]");
+    assert_eq!(results, ["1"]);
+}
+
+/// Tests handle_cron_status().
+#[test]
+fn test_handle_cron_status() {
+    let mut test_wsgi = wsgi::tests::TestWsgi::new();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into cron_journal (stage, relation, status, started, finished, error) values ('osm-streets', '', 'done', '1588975200000000000', '1588978865000000000', '');
+             insert into cron_journal (stage, relation, status, started, finished, error) values ('osm-streets', 'gazdagret', 'failed', '1588975200000000000', '1588975260000000000', 'timeout');
+             insert into cron_journal (stage, relation, status, started, finished, error) values ('missing-streets', '', 'running', '1588978865000000000', '', '');",
+        )
+        .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/cron-status");
+
+    let tables = wsgi::tests::TestWsgi::find_all(&root, "body/table");
+    assert_eq!(tables.len(), 2);
+    let stages = wsgi::tests::TestWsgi::find_all(&root, "body/table[1]/tr");
+    // header + 2 stages.
+    assert_eq!(stages.len(), 3);
+    let duration = wsgi::tests::TestWsgi::find_all(&root, "body/table[1]/tr[2]/td[5]");
+    assert_eq!(duration, vec!["1:01:05".to_string()]);
+    let failures = wsgi::tests::TestWsgi::find_all(&root, "body/table[2]/tr");
+    // header + 1 failure.
+    assert_eq!(failures.len(), 2);
+}
+
+/// Tests handle_cron_status(): the case when there is no journal and when there are no failures.
+#[test]
+fn test_handle_cron_status_empty() {
+    let mut test_wsgi = wsgi::tests::TestWsgi::new();
+
+    let root = test_wsgi.get_dom_for_path("/cron-status");
+
+    let tables = wsgi::tests::TestWsgi::find_all(&root, "body/table");
+    assert_eq!(tables.len(), 0);
+
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute(
+            "insert into cron_journal (stage, relation, status, started, finished, error) values ('stats', '', 'done', '1588975200000000000', '1588975260000000000', '')",
+            [],
+        )
+        .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/cron-status");
+
+    let tables = wsgi::tests::TestWsgi::find_all(&root, "body/table");
+    assert_eq!(tables.len(), 1);
+}
+
 /// Tests handle_invalid_refstreets(), the case when a relation has no errors.
 #[test]
 fn test_handle_invalid_refstreets_no_errors() {
@@ -274,8 +356,8 @@ fn test_handle_lints() {
     let root = test_wsgi.get_dom_for_path("/lints/whole-country/");
 
     let results = wsgi::tests::TestWsgi::find_all(&root, "body/ul/li");
-    // 2 lint types.
-    assert_eq!(results.len(), 2);
+    // 4 lint types.
+    assert_eq!(results.len(), 4);
 }
 
 /// Tests handle_invalid_addr_cities_update().
@@ -397,3 +479,48 @@ fn test_handle_error_json() {
     let value: HashMap<String, String> = serde_json::from_str(&output).unwrap();
     assert_eq!(value["error"], "myerror");
 }
+
+/// Tests is_same_origin().
+#[test]
+fn test_is_same_origin() {
+    let ctx = context::tests::make_test_context().unwrap();
+    let is_same_origin_for = |headers: &[(&str, &str)]| -> bool {
+        let headers: Vec<(String, String)> = headers
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let request = rouille::Request::fake_http("POST", "/", headers, vec![]);
+        is_same_origin(&request, &ctx)
+    };
+
+    // The configured site URL.
+    assert!(is_same_origin_for(&[(
+        "Origin",
+        "https://osm-gimmisn.vmiklos.hu"
+    )]));
+    // No Origin, but a Referer.
+    assert!(is_same_origin_for(&[(
+        "Referer",
+        "https://osm-gimmisn.vmiklos.hu/osm/missing-housenumbers/gazdagret/view-survey"
+    )]));
+    // A local instance.
+    assert!(is_same_origin_for(&[
+        ("Origin", "http://localhost:8000"),
+        ("Host", "localhost:8000")
+    ]));
+    // Cross-site.
+    assert!(!is_same_origin_for(&[
+        ("Origin", "https://example.com"),
+        ("Host", "localhost:8000")
+    ]));
+    // No Origin or Referer.
+    assert!(!is_same_origin_for(&[]));
+}
+
+/// Tests constant_time_eq().
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq("secret", "secret"));
+    assert!(!constant_time_eq("secret", "secreT"));
+    assert!(!constant_time_eq("secret", "secret2"));
+}
@@ -13,17 +13,24 @@
 use crate::areas;
 use crate::cache;
 use crate::context;
+use crate::feeds;
+use crate::geometry;
 use crate::i18n::translate as tr;
-use crate::overpass_query;
+use crate::lints;
+use crate::reference;
 use crate::stats;
+use crate::suggest_refstreets;
 use crate::util;
 use crate::webframe;
 use crate::wsgi_additional;
+use crate::wsgi_api;
 use crate::wsgi_json;
+use crate::wsgi_map;
 use crate::yattag;
 use anyhow::Context;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::io::Read as _;
 use std::sync::Arc;
 
 /// Gets the update date of streets for a relation.
@@ -83,8 +90,8 @@ fn handle_streets(
         let pre = doc.tag("pre", &[]);
         pre.text(&relation.get_osm_streets_query()?);
     } else if action == "update-result" {
-        let query = relation.get_osm_streets_json_query()?;
-        match overpass_query::overpass_query(ctx, &query) {
+        let query = relation.get_osm_streets_source_query()?;
+        match ctx.get_osm_source().query(ctx, &query) {
             Ok(buf) => {
                 relation.get_files().write_osm_json_streets(ctx, &buf)?;
                 let streets = relation.get_config().should_check_missing_streets();
@@ -161,8 +168,10 @@ fn handle_street_housenumbers(
         let pre = doc.tag("pre", &[]);
         pre.text(&relation.get_osm_housenumbers_query()?);
     } else if action == "update-result" {
-        let query = relation.get_osm_housenumbers_query()?;
-        match overpass_query::overpass_query(ctx, &query) {
+        // Not get_osm_housenumbers_query(): that has CSV output, which
+        // write_osm_json_housenumbers() would silently ignore.
+        let query = relation.get_osm_housenumbers_source_query()?;
+        match ctx.get_osm_source().query(ctx, &query) {
             Ok(buf) => {
                 relation
                     .get_files()
@@ -244,6 +253,7 @@ fn missing_housenumbers_view_turbo(
 /// Expected request uri: /osm/missing-housenumbers/ormezo/view-lints.
 fn missing_housenumbers_view_lints(
     ctx: &context::Context,
+    relations: &areas::Relations<'_>,
     relation: &mut areas::Relation<'_>,
 ) -> anyhow::Result<yattag::Doc> {
     let doc = yattag::Doc::new();
@@ -278,17 +288,15 @@ fn missing_housenumbers_view_lints(
             let source_string = match source {
                 areas::RelationLintSource::Range => tr("street ranges"),
                 areas::RelationLintSource::Invalid => tr("invalid housenumbers"),
+                areas::RelationLintSource::Survey => tr("survey observations"),
+                areas::RelationLintSource::Postcode => tr("postcodes"),
             };
             let housenumber: String = lint.get(2).unwrap();
             let reason =
                 areas::RelationLintReason::try_from(lint.get::<_, String>(3).unwrap().as_str())?;
             let id: String = lint.get(4).unwrap();
             let object_type: String = lint.get(5).unwrap();
-            let reason_string = match reason {
-                areas::RelationLintReason::CreatedInOsm => tr("created in OSM"),
-                areas::RelationLintReason::DeletedFromRef => tr("deleted from reference"),
-                areas::RelationLintReason::OutOfRange => tr("out of range"),
-            };
+            let reason_string = webframe::get_lint_reason_string(&reason);
             cells.push(yattag::Doc::from_text(&street));
             cells.push(yattag::Doc::from_text(&source_string));
             cells.push(yattag::Doc::from_text(&housenumber));
@@ -322,6 +330,504 @@ fn missing_housenumbers_view_lints(
         );
     }
     doc.append_value(util::html_table_from_list(&table).get_value());
+    {
+        let p = doc.tag("p", &[]);
+        p.text(&tr("Tasks for a cooperative tasking tool: "));
+        let prefix = ctx.get_ini().get_uri_prefix();
+        let a = p.tag(
+            "a",
+            &[(
+                "href",
+                &format!(
+                    "{prefix}/missing-housenumbers/{}/view-lints.geojson",
+                    relation.get_name()
+                ),
+            )],
+        );
+        a.text(&tr("GeoJSON format"));
+    }
+
+    let invalids = relations.get_invalid_addr_cities(relation)?;
+    if !invalids.is_empty() {
+        let mut table: Vec<Vec<yattag::Doc>> = vec![vec![
+            yattag::Doc::from_text(&tr("Identifier")),
+            yattag::Doc::from_text(&tr("Street")),
+            yattag::Doc::from_text(&tr("Housenumber")),
+            yattag::Doc::from_text(&tr("City")),
+            yattag::Doc::from_text(&tr("Suggested city")),
+        ]];
+        for invalid in &invalids {
+            let cell = yattag::Doc::new();
+            {
+                let href = format!(
+                    "https://www.openstreetmap.org/{}/{}",
+                    invalid.osm_type, invalid.osm_id
+                );
+                let a = cell.tag("a", &[("href", &href), ("target", "_blank")]);
+                a.text(&invalid.osm_id);
+            }
+            table.push(vec![
+                cell,
+                yattag::Doc::from_text(&invalid.street),
+                yattag::Doc::from_text(&invalid.housenumber),
+                yattag::Doc::from_text(&invalid.city),
+                yattag::Doc::from_text(&invalid.suggested_city),
+            ]);
+        }
+        {
+            let p = doc.tag("p", &[("id", "invalid-addr-cities")]);
+            p.text(
+                &tr("The addr:city key of the below {0} objects probably has an invalid value.")
+                    .replace("{0}", &invalids.len().to_string()),
+            );
+        }
+        doc.append_value(util::html_table_from_list(&table).get_value());
+    }
+
+    Ok(doc)
+}
+
+/// Gets the localized description of a survey status.
+fn get_survey_status_string(status: &areas::SurveyStatus) -> String {
+    match status {
+        areas::SurveyStatus::ConfirmedAbsent => tr("confirmed absent"),
+        areas::SurveyStatus::DifferentNumber => tr("different number"),
+        areas::SurveyStatus::NeedsResurvey => tr("needs resurvey"),
+    }
+}
+
+/// Expected request uri: /osm/missing-housenumbers/ormezo/view-duplicates.
+fn missing_housenumbers_view_duplicates(
+    relation: &areas::Relation<'_>,
+) -> anyhow::Result<yattag::Doc> {
+    let doc = yattag::Doc::new();
+    let duplicates = relation.get_duplicate_addresses()?;
+
+    let mut table: Vec<Vec<yattag::Doc>> = vec![vec![
+        yattag::Doc::from_text(&tr("Street")),
+        yattag::Doc::from_text(&tr("Housenumber")),
+        yattag::Doc::from_text(&tr("Objects")),
+        yattag::Doc::from_text(&tr("Status")),
+        yattag::Doc::from_text(&tr("Edit")),
+    ]];
+    let mut errors = 0;
+    for duplicate in &duplicates {
+        let objects = yattag::Doc::new();
+        for (index, object) in duplicate.objects.iter().enumerate() {
+            if index > 0 {
+                objects.text(", ");
+            }
+            let href = format!(
+                "https://www.openstreetmap.org/{}/{}",
+                object.osm_type, object.osm_id
+            );
+            {
+                let a = objects.tag("a", &[("href", &href), ("target", "_blank")]);
+                a.text(&format!("{} {}", object.osm_type, object.osm_id));
+            }
+            let unit_door: Vec<&str> = [object.unit.as_str(), object.door.as_str()]
+                .into_iter()
+                .filter(|i| !i.is_empty())
+                .collect();
+            if !unit_door.is_empty() {
+                objects.text(&format!(" ({})", unit_door.join(", ")));
+            }
+        }
+
+        let status = yattag::Doc::new();
+        {
+            let (value, text) = if duplicate.legitimate {
+                ("legitimate", tr("different units or doors"))
+            } else {
+                errors += 1;
+                ("error", tr("probably an error"))
+            };
+            let div = status.tag("div", &[("data-value", value)]);
+            div.text(&text);
+        }
+
+        let edit = yattag::Doc::new();
+        {
+            let objects: Vec<String> = duplicate
+                .objects
+                .iter()
+                .map(|i| format!("{}{}", i.osm_type.chars().next().unwrap_or('n'), i.osm_id))
+                .collect();
+            let href = format!(
+                "http://127.0.0.1:8111/load_object?objects={}",
+                objects.join(",")
+            );
+            let a = edit.tag("a", &[("href", &href), ("target", "_blank")]);
+            a.text("JOSM");
+        }
+
+        table.push(vec![
+            yattag::Doc::from_text(&duplicate.street),
+            yattag::Doc::from_text(&duplicate.housenumber),
+            objects,
+            status,
+            edit,
+        ]);
+    }
+
+    {
+        let p = doc.tag("p", &[]);
+        p.text(
+            &tr("The below {0} addresses are present on multiple OSM objects, {1} of them are probably errors.")
+                .replace("{0}", &duplicates.len().to_string())
+                .replace("{1}", &errors.to_string()),
+        );
+    }
+    doc.append_value(util::html_table_from_list(&table).get_value());
+    Ok(doc)
+}
+
+/// Expected request uri: /osm/missing-housenumbers/ormezo/view-survey.
+fn missing_housenumbers_view_survey(
+    ctx: &context::Context,
+    relation: &mut areas::Relation<'_>,
+) -> anyhow::Result<yattag::Doc> {
+    let doc = yattag::Doc::new();
+    let json = cache::get_missing_housenumbers_json(relation)
+        .context("get_missing_housenumbers_json() failed")?;
+    let missing_housenumbers: areas::MissingHousenumbers = serde_json::from_str(&json)?;
+
+    {
+        let p = doc.tag("p", &[]);
+        p.text(&tr("Record what you found on the ground for a missing house number. 'Confirmed absent' and 'different number' hide the house number from the missing list, like an invalid filter would do."));
+    }
+    let format = time::format_description::parse("[year]-[month]-[day]")?;
+    let today = ctx.get_time().now().format(&format)?;
+    {
+        let form = doc.tag("form", &[("method", "post")]);
+        form.stag(
+            "input",
+            &[("type", "hidden"), ("name", "action"), ("value", "set")],
+        );
+        {
+            let label = form.tag("label", &[]);
+            label.text(&tr("Street"));
+            label.stag(
+                "input",
+                &[("name", "street"), ("list", "streets"), ("required", "")],
+            );
+        }
+        {
+            let datalist = form.tag("datalist", &[("id", "streets")]);
+            for street in &missing_housenumbers.ongoing_streets {
+                datalist.stag("option", &[("value", street.street.get_osm_name())]);
+            }
+        }
+        {
+            let label = form.tag("label", &[]);
+            label.text(&tr("House number"));
+            label.stag("input", &[("name", "housenumber"), ("required", "")]);
+        }
+        {
+            let label = form.tag("label", &[]);
+            label.text(&tr("Status"));
+            let select = label.tag("select", &[("name", "status")]);
+            for status in [
+                areas::SurveyStatus::ConfirmedAbsent,
+                areas::SurveyStatus::DifferentNumber,
+                areas::SurveyStatus::NeedsResurvey,
+            ] {
+                let option = select.tag("option", &[("value", &status.to_string())]);
+                option.text(&get_survey_status_string(&status));
+            }
+        }
+        {
+            let label = form.tag("label", &[]);
+            label.text(&tr("Note"));
+            label.stag("input", &[("name", "note")]);
+        }
+        {
+            let label = form.tag("label", &[]);
+            label.text(&tr("Date"));
+            label.stag(
+                "input",
+                &[("type", "date"), ("name", "date"), ("value", &today)],
+            );
+        }
+        form.stag("input", &[("type", "submit"), ("value", &tr("Record"))]);
+    }
+
+    let mut table: Vec<Vec<yattag::Doc>> = vec![vec![
+        yattag::Doc::from_text(&tr("Street")),
+        yattag::Doc::from_text(&tr("House number")),
+        yattag::Doc::from_text(&tr("Status")),
+        yattag::Doc::from_text(&tr("Note")),
+        yattag::Doc::from_text(&tr("Surveyor")),
+        yattag::Doc::from_text(&tr("Date")),
+        yattag::Doc::new(),
+    ]];
+    for observation in relation.get_survey_observations()? {
+        let remove = yattag::Doc::new();
+        {
+            let form = remove.tag("form", &[("method", "post")]);
+            form.stag(
+                "input",
+                &[("type", "hidden"), ("name", "action"), ("value", "remove")],
+            );
+            form.stag(
+                "input",
+                &[
+                    ("type", "hidden"),
+                    ("name", "street"),
+                    ("value", &observation.street),
+                ],
+            );
+            form.stag(
+                "input",
+                &[
+                    ("type", "hidden"),
+                    ("name", "housenumber"),
+                    ("value", &observation.housenumber),
+                ],
+            );
+            form.stag("input", &[("type", "submit"), ("value", &tr("Remove"))]);
+        }
+        let status = yattag::Doc::new();
+        {
+            let div = status.tag("div", &[("data-value", &observation.status.to_string())]);
+            div.text(&get_survey_status_string(&observation.status));
+        }
+        table.push(vec![
+            yattag::Doc::from_text(&observation.street),
+            yattag::Doc::from_text(&observation.housenumber),
+            status,
+            yattag::Doc::from_text(&observation.note),
+            yattag::Doc::from_text(&observation.surveyor),
+            yattag::Doc::from_text(&observation.date),
+            remove,
+        ]);
+    }
+    if table.len() > 1 {
+        doc.append_value(util::html_table_from_list(&table).get_value());
+    }
+
+    Ok(doc)
+}
+
+/// Handles a form submission of /osm/missing-housenumbers/ormezo/view-survey: records or removes
+/// a survey observation. Returns an error message if the submitted form is invalid.
+fn handle_survey_post(
+    request: &rouille::Request,
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+    surveyor: &str,
+) -> anyhow::Result<Option<String>> {
+    let mut tokens = request_uri.split('/');
+    tokens.next_back();
+    let relation_name = tokens.next_back().context("no relation_name")?;
+    let relation = relations.get_relation(relation_name)?;
+
+    let mut request_data = Vec::new();
+    let mut reader = request.data().context("data() gave None")?;
+    reader.read_to_end(&mut request_data)?;
+    let fields: HashMap<String, String> = url::form_urlencoded::parse(&request_data)
+        .into_owned()
+        .collect();
+    let get_field = |key: &str| -> String { fields.get(key).cloned().unwrap_or_default() };
+    let street = get_field("street");
+    let housenumber = get_field("housenumber").trim().to_string();
+    if street.is_empty() || housenumber.is_empty() {
+        return Ok(Some("street and housenumber must not be empty".into()));
+    }
+
+    if get_field("action") == "remove" {
+        relation.remove_survey_observation(&street, &housenumber)?;
+        return Ok(None);
+    }
+
+    let format = time::format_description::parse("[year]-[month]-[day]")?;
+    let mut date = get_field("date");
+    if date.is_empty() {
+        date = ctx.get_time().now().format(&format)?;
+    }
+    if time::Date::parse(&date, &format).is_err() {
+        return Ok(Some(format!("invalid date: {date}")));
+    }
+    let status = match areas::SurveyStatus::try_from(get_field("status").as_str()) {
+        Ok(value) => value,
+        Err(err) => return Ok(Some(err.to_string())),
+    };
+    let observation = areas::SurveyObservation {
+        street,
+        housenumber,
+        status,
+        note: get_field("note"),
+        surveyor: surveyor.to_string(),
+        date,
+    };
+    relation.set_survey_observation(&observation)?;
+    Ok(None)
+}
+
+/// Expected request uri: /osm/missing-housenumbers/ormezo/view-changes[-2020-05-03].
+fn missing_housenumbers_view_changes(
+    ctx: &context::Context,
+    relation: &mut areas::Relation<'_>,
+    suffix: &str,
+) -> anyhow::Result<yattag::Doc> {
+    let doc = yattag::Doc::new();
+    let prefix = ctx.get_ini().get_uri_prefix();
+    let relation_name = relation.get_name();
+    if !stats::has_sql_mtime(ctx, &format!("streets/{relation_name}"))? {
+        return Ok(webframe::handle_no_osm_streets(&prefix, &relation_name));
+    }
+    if !stats::has_sql_mtime(ctx, &format!("housenumbers/{relation_name}"))? {
+        return Ok(webframe::handle_no_osm_housenumbers(
+            &prefix,
+            &relation_name,
+        ));
+    }
+
+    let format = time::format_description::parse("[year]-[month]-[day]")?;
+    let now = ctx.get_time().now();
+    let since = match suffix.strip_prefix('-') {
+        Some(value) => value.to_string(),
+        None => (now - time::Duration::days(7)).format(&format)?,
+    };
+    time::Date::parse(&since, &format).context(format!("invalid date: {since}"))?;
+    let changes = relation
+        .get_missing_housenumbers_changes(&since)
+        .context("get_missing_housenumbers_changes() failed")?;
+
+    {
+        let p = doc.tag("p", &[]);
+        p.text(
+            &tr("Changes of the missing house numbers since {0}.").replace("{0}", &changes.since),
+        );
+    }
+    {
+        let p = doc.tag("p", &[]);
+        p.text(&tr("Since: "));
+        let links = [
+            (7, tr("a week ago")),
+            (30, tr("a month ago")),
+            (365, tr("a year ago")),
+        ];
+        for (index, (days, label)) in links.iter().enumerate() {
+            if index > 0 {
+                p.text(", ");
+            }
+            let date = (now - time::Duration::days(*days)).format(&format)?;
+            let a = p.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{prefix}/missing-housenumbers/{relation_name}/view-changes-{date}"),
+                )],
+            );
+            a.text(label);
+        }
+    }
+
+    let sections = [
+        (tr("Newly mapped"), &changes.newly_mapped),
+        (tr("Newly missing"), &changes.newly_missing),
+        (tr("No longer mapped"), &changes.no_longer_mapped),
+        (
+            tr("Removed from reference"),
+            &changes.removed_from_reference,
+        ),
+    ];
+    for (title, streets) in sections {
+        let count: usize = streets.values().map(|i| i.len()).sum();
+        {
+            let h2 = doc.tag("h2", &[]);
+            h2.text(&format!("{title} ({count})"));
+        }
+        if streets.is_empty() {
+            continue;
+        }
+        let mut table: Vec<Vec<yattag::Doc>> = vec![vec![
+            yattag::Doc::from_text(&tr("Street name")),
+            yattag::Doc::from_text(&tr("House numbers")),
+        ]];
+        for (street, numbers) in streets {
+            let mut numbers: Vec<&String> = numbers.iter().collect();
+            numbers.sort_by_key(|i| util::split_house_number(i));
+            let numbers: Vec<&str> = numbers.iter().map(|i| i.as_str()).collect();
+            table.push(vec![
+                yattag::Doc::from_text(street),
+                yattag::Doc::from_text(&numbers.join(", ")),
+            ]);
+        }
+        doc.append_value(util::html_table_from_list(&table).get_value());
+    }
+
+    Ok(doc)
+}
+
+/// Shows the coverage of a relation over time: a chart and the last value of each month.
+fn get_coverage_history_html(
+    ctx: &context::Context,
+    relation: &areas::Relation<'_>,
+) -> anyhow::Result<yattag::Doc> {
+    let doc = yattag::Doc::new();
+    let history = relation.get_coverage_history()?;
+    if history.is_empty() {
+        return Ok(doc);
+    }
+
+    {
+        let h2 = doc.tag("h2", &[]);
+        h2.text(&tr("Coverage history"));
+    }
+    let string_pairs = &[
+        ("str-coverage-history-streets", tr("Streets")),
+        ("str-coverage-history-housenumbers", tr("House numbers")),
+        ("str-coverage-history-y-axis", tr("Coverage (%)")),
+    ];
+    webframe::emit_l10n_strings_for_js(&doc, string_pairs);
+    {
+        let prefix = ctx.get_ini().get_uri_prefix();
+        let relation_name = relation.get_name();
+        let div = doc.tag("div", &[("class", "canvasblock js")]);
+        let canvas = div.tag(
+            "canvas",
+            &[
+                ("id", "coverage-history"),
+                (
+                    "data-url",
+                    &format!("{prefix}/api/v1/relations/{relation_name}/coverage-history"),
+                ),
+            ],
+        );
+        drop(canvas);
+    }
+
+    // Last entry of each month, the history is ordered by date.
+    let mut monthly: Vec<&areas::CoverageHistoryEntry> = Vec::new();
+    for entry in &history {
+        match monthly.last() {
+            Some(last) if last.date.get(..7) == entry.date.get(..7) => {
+                monthly.pop();
+            }
+            _ => (),
+        }
+        monthly.push(entry);
+    }
+    let mut table = vec![vec![
+        yattag::Doc::from_text(&tr("Month")),
+        yattag::Doc::from_text(&tr("Street coverage")),
+        yattag::Doc::from_text(&tr("House number coverage")),
+    ]];
+    for entry in monthly {
+        let format = |coverage: &Option<String>| match coverage {
+            Some(value) => format!("{value}%"),
+            None => "".into(),
+        };
+        table.push(vec![
+            yattag::Doc::from_text(entry.date.get(..7).unwrap_or(&entry.date)),
+            yattag::Doc::from_text(&format(&entry.streets)),
+            yattag::Doc::from_text(&format(&entry.housenumbers)),
+        ]);
+    }
+    doc.append_value(util::html_table_from_list(&table).get_value());
 
     Ok(doc)
 }
@@ -397,6 +903,39 @@ fn missing_housenumbers_view_res_html(
             a.text(&tr("Checklist format"));
         }
         doc.stag("br");
+        {
+            let a = doc.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{prefix}/missing-housenumbers/{relation_name}/view-result.geojson"),
+                )],
+            );
+            a.text(&tr("GeoJSON format"));
+        }
+        doc.stag("br");
+        {
+            let a = doc.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{prefix}/missing-housenumbers/{relation_name}/view-result.gpx"),
+                )],
+            );
+            a.text(&tr("GPX format"));
+        }
+        doc.stag("br");
+        {
+            let a = doc.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{prefix}/missing-housenumbers/{relation_name}/view-result.osm"),
+                )],
+            );
+            a.text(&tr("JOSM format, with proposed address nodes"));
+        }
+        doc.stag("br");
         {
             let a = doc.tag(
                 "a",
@@ -407,6 +946,39 @@ fn missing_housenumbers_view_res_html(
             );
             a.text(&tr("View lints"));
         }
+        doc.stag("br");
+        {
+            let a = doc.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{prefix}/missing-housenumbers/{relation_name}/view-duplicates"),
+                )],
+            );
+            a.text(&tr("View duplicate addresses"));
+        }
+        doc.stag("br");
+        {
+            let a = doc.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{prefix}/missing-housenumbers/{relation_name}/view-changes"),
+                )],
+            );
+            a.text(&tr("View changes"));
+        }
+        doc.stag("br");
+        {
+            let a = doc.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{prefix}/missing-housenumbers/{relation_name}/view-survey"),
+                )],
+            );
+            a.text(&tr("Survey feedback"));
+        }
     }
 
     doc.append_value(util::html_table_from_list(&table).get_value());
@@ -418,6 +990,7 @@ fn missing_housenumbers_view_res_html(
     doc.append_value(
         util::invalid_filter_keys_to_html(&relation.get_invalid_filter_keys()?).get_value(),
     );
+    doc.append_value(get_coverage_history_html(ctx, relation)?.get_value());
 
     Ok(doc)
 }
@@ -467,8 +1040,8 @@ fn missing_streets_view_result(
     let (todo_count, done_count, percent, mut streets) = relation.write_missing_streets()?;
     streets.sort_by_key(|i| util::get_sort_key(i));
     let mut table = vec![vec![yattag::Doc::from_text(&tr("Street name"))]];
-    for street in streets {
-        table.push(vec![yattag::Doc::from_text(&street)]);
+    for street in &streets {
+        table.push(vec![yattag::Doc::from_text(street)]);
     }
 
     {
@@ -520,6 +1093,16 @@ fn missing_streets_view_result(
     }
 
     doc.append_value(util::html_table_from_list(&table).get_value());
+    let suggestions = suggest_refstreets::get_suggestions(&relation, &streets)?;
+    if !suggestions.is_empty() {
+        doc.stag("br");
+        let div = doc.tag("div", &[("id", "refstreets-suggestions-container")]);
+        div.text(&tr(
+            "The following reference and OSM names are similar, consider adding them to refstreets:",
+        ));
+        let pre = div.tag("pre", &[]);
+        pre.text(&suggest_refstreets::to_yaml(&suggestions));
+    }
     let (osm_invalids, ref_invalids) = relation.get_invalid_refstreets()?;
     doc.append_value(util::invalid_refstreets_to_html(&osm_invalids, &ref_invalids).get_value());
     doc.append_value(
@@ -641,6 +1224,175 @@ fn missing_housenumbers_view_chkl(
     Ok((output, relation_name.into()))
 }
 
+/// Places the missing housenumbers of a relation, nothing if there is no OSM data yet.
+fn get_located_missing_housenumbers(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<(Vec<geometry::LocatedHousenumber>, String)> {
+    let mut tokens = request_uri.split('/');
+    tokens.next_back();
+    let relation_name = tokens.next_back().context("no relation_name")?;
+    let mut relation = relations.get_relation(relation_name)?;
+
+    if !stats::has_sql_mtime(ctx, &format!("streets/{}", relation_name))?
+        || !stats::has_sql_mtime(ctx, &format!("housenumbers/{}", relation_name))?
+    {
+        return Ok((Vec::new(), relation_name.into()));
+    }
+
+    let located = geometry::locate_missing_housenumbers(&mut relation)
+        .context("locate_missing_housenumbers() failed")?;
+    Ok((located, relation_name.into()))
+}
+
+/// Expected request_uri: e.g. /osm/missing-housenumbers/ormezo/view-result.geojson.
+fn missing_housenumbers_view_geojson(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<(String, String)> {
+    let (located, relation_name) = get_located_missing_housenumbers(ctx, relations, request_uri)?;
+    let features: Vec<serde_json::Value> = located
+        .iter()
+        .map(|i| {
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [i.point.lon, i.point.lat],
+                },
+                "properties": {
+                    "name": format!("{} {}", i.street, i.housenumber),
+                    "street": i.street,
+                    "housenumber": i.housenumber,
+                    "placement": i.placement.to_string(),
+                },
+            })
+        })
+        .collect();
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    Ok((serde_json::to_string(&collection)?, relation_name))
+}
+
+/// Expected request_uri: e.g. /osm/missing-housenumbers/ormezo/view-result.osm.
+fn missing_housenumbers_view_osm(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<(String, String)> {
+    let (located, relation_name) = get_located_missing_housenumbers(ctx, relations, request_uri)?;
+    let doc = yattag::Doc::new();
+    doc.append_value("<?xml version='1.0' encoding='UTF-8'?>".into());
+    {
+        // Proposed nodes only, to be verified and merged by a mapper, never uploaded as-is.
+        let osm = doc.tag(
+            "osm",
+            &[
+                ("version", "0.6"),
+                ("generator", "osm-gimmisn"),
+                ("upload", "never"),
+            ],
+        );
+        for (index, i) in located.iter().enumerate() {
+            let id = -(index as i64 + 1);
+            let node = osm.tag(
+                "node",
+                &[
+                    ("id", &id.to_string()),
+                    ("lat", &i.point.lat.to_string()),
+                    ("lon", &i.point.lon.to_string()),
+                ],
+            );
+            node.stag("tag", &[("k", "addr:street"), ("v", &i.street)]);
+            node.stag("tag", &[("k", "addr:housenumber"), ("v", &i.housenumber)]);
+            node.stag("tag", &[("k", "fixme"), ("v", "survey")]);
+        }
+    }
+    Ok((doc.get_value(), relation_name))
+}
+
+/// Expected request_uri: e.g. /osm/missing-housenumbers/ormezo/view-lints.geojson.
+fn missing_housenumbers_view_lints_geojson(
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<(String, String)> {
+    let mut tokens = request_uri.split('/');
+    tokens.next_back();
+    let relation_name = tokens.next_back().context("short tokens")?;
+    let mut relation = relations.get_relation(relation_name)?;
+
+    // Update lints if they are outdated.
+    cache::get_missing_housenumbers_json(&mut relation)
+        .context("get_missing_housenumbers_json() failed")?;
+
+    let (output, skipped) = lints::get_lints_challenge(relation.get_ctx(), Some(relation_name))
+        .context("get_lints_challenge() failed")?;
+    if skipped > 0 {
+        log::info!("missing_housenumbers_view_lints_geojson: {relation_name}: skipped {skipped} lints without a known location");
+    }
+    Ok((output, format!("{relation_name}-lints")))
+}
+
+/// Expected request_uri: e.g. /osm/missing-housenumbers/ormezo/view-result.gpx.
+fn missing_housenumbers_view_gpx(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<(String, String)> {
+    let (located, relation_name) = get_located_missing_housenumbers(ctx, relations, request_uri)?;
+    let doc = yattag::Doc::new();
+    doc.append_value("<?xml version='1.0' encoding='UTF-8'?>".into());
+    {
+        let gpx = doc.tag(
+            "gpx",
+            &[
+                ("version", "1.1"),
+                ("creator", "osm-gimmisn"),
+                ("xmlns", "http://www.topografix.com/GPX/1/1"),
+                ("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"),
+                (
+                    "xsi:schemaLocation",
+                    "http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd",
+                ),
+            ],
+        );
+        {
+            let metadata = gpx.tag("metadata", &[]);
+            {
+                let desc = metadata.tag("desc", &[]);
+                desc.text(&relation_name);
+            }
+            {
+                let time = metadata.tag("time", &[]);
+                let now = ctx.get_time().now();
+                time.text(&now.format(&time::format_description::well_known::Rfc3339)?);
+            }
+        }
+        for i in located {
+            let wpt = gpx.tag(
+                "wpt",
+                &[
+                    ("lat", &i.point.lat.to_string()),
+                    ("lon", &i.point.lon.to_string()),
+                ],
+            );
+            {
+                let name = wpt.tag("name", &[]);
+                name.text(&format!("{} {}", i.street, i.housenumber));
+            }
+            {
+                let desc = wpt.tag("desc", &[]);
+                desc.text(&i.placement.to_string());
+            }
+        }
+    }
+    Ok((doc.get_value(), relation_name))
+}
+
 /// Expected request_uri: e.g. /osm/missing-streets/ujbuda/view-result.txt.
 fn missing_streets_view_txt(
     ctx: &context::Context,
@@ -709,7 +1461,7 @@ fn handle_missing_housenumbers(
             let conn = ctx.get_database_connection()?;
             let mut lst: Vec<String> = Vec::new();
             let mut stmt = conn.prepare(
-            "select distinct housenumber, comment from ref_housenumbers where county_code = ?1 and settlement_code = ?2 and street = ?3 order by housenumber")?;
+            "select distinct housenumber, comment, source from ref_housenumbers where county_code = ?1 and settlement_code = ?2 and street = ?3 order by housenumber")?;
             for street in streets {
                 let street = relation
                     .get_config()
@@ -728,7 +1480,14 @@ fn handle_missing_housenumbers(
                         if comment == " " {
                             comment = "".into();
                         }
-                        lst.push(street.clone() + "\t" + &housenumber + suffix + "\t" + &comment);
+                        let mut line =
+                            street.clone() + "\t" + &housenumber + suffix + "\t" + &comment;
+                        // Provenance, if the reference source has a label.
+                        let source: String = row.get(2).unwrap();
+                        if !source.is_empty() {
+                            line += &format!("\t{source}");
+                        }
+                        lst.push(line);
                     }
                 }
             }
@@ -736,10 +1495,28 @@ fn handle_missing_housenumbers(
         }
     } else if action == "view-lints" {
         doc.append_value(
-            missing_housenumbers_view_lints(ctx, &mut relation)
+            missing_housenumbers_view_lints(ctx, relations, &mut relation)
                 .context("missing_housenumbers_view_lints() failed")?
                 .get_value(),
         )
+    } else if action == "view-duplicates" {
+        doc.append_value(
+            missing_housenumbers_view_duplicates(&relation)
+                .context("missing_housenumbers_view_duplicates() failed")?
+                .get_value(),
+        )
+    } else if action == "view-survey" {
+        doc.append_value(
+            missing_housenumbers_view_survey(ctx, &mut relation)
+                .context("missing_housenumbers_view_survey() failed")?
+                .get_value(),
+        )
+    } else if let Some(suffix) = action.strip_prefix("view-changes") {
+        doc.append_value(
+            missing_housenumbers_view_changes(ctx, &mut relation, suffix)
+                .context("missing_housenumbers_view_changes() failed")?
+                .get_value(),
+        )
     } else {
         // assume view-result
         let ret = missing_housenumbers_view_res(ctx, relations, request_uri);
@@ -808,20 +1585,24 @@ fn handle_missing_streets(
         doc.append_value(missing_streets_view_turbo(relations, request_uri)?.get_value());
     } else if action == "view-query" {
         let pre = doc.tag("pre", &[]);
-        let reference = ctx.get_ini().get_reference_street_path()?;
-        util::build_street_reference_index(ctx, &reference)?;
+        reference::build_street_reference_index(ctx)?;
         let conn = ctx.get_database_connection()?;
         let mut lst: Vec<String> = Vec::new();
         let mut stmt = conn.prepare(
-            "select distinct street from ref_streets where county_code = ?1 and settlement_code = ?2 order by street",
+            "select street, group_concat(distinct nullif(source, '')) from ref_streets where county_code = ?1 and settlement_code = ?2 group by street order by street",
         )?;
         let mut rows = stmt.query([
             &relation.get_config().get_refcounty(),
             &relation.get_config().get_refsettlement(),
         ])?;
         while let Some(row) = rows.next()? {
-            let street: String = row.get(0).unwrap();
-            lst.push(street);
+            let mut line: String = row.get(0).unwrap();
+            // Provenance, if the reference sources have labels.
+            let source: Option<String> = row.get(1).unwrap();
+            if let Some(source) = source {
+                line += &format!("\t{source}");
+            }
+            lst.push(line);
         }
         pre.text(&lst.join("\n"));
     } else {
@@ -1078,7 +1859,7 @@ pub fn handle_main_housenr_additional_count(
 }
 
 /// Does not filter out anything.
-fn filter_for_everything(_complete: bool, _relation: &areas::Relation<'_>) -> bool {
+pub fn filter_for_everything(_complete: bool, _relation: &areas::Relation<'_>) -> bool {
     true
 }
 
@@ -1087,7 +1868,8 @@ fn filter_for_incomplete(complete: bool, _relation: &areas::Relation<'_>) -> boo
     !complete
 }
 
-type RelationFilter = dyn Fn(bool, &areas::Relation<'_>) -> bool;
+/// Decides if a relation should be shown, based on if it's complete.
+pub type RelationFilter = dyn Fn(bool, &areas::Relation<'_>) -> bool;
 
 /// Creates a function that filters for a single refcounty.
 fn create_filter_for_refcounty(refcounty_filter: &str) -> Box<RelationFilter> {
@@ -1133,8 +1915,16 @@ fn create_filter_for_refcounty_refsettlement(
 
 /// Sets up a filter-for function from request uri: only certain areas are shown then.
 fn setup_main_filter_for(request_uri: &str) -> anyhow::Result<(Box<RelationFilter>, String)> {
+    setup_filter_for(request_uri, Box::new(filter_for_incomplete))
+}
+
+/// Sets up a filter-for function from request uri, using `default` if the uri has no filter.
+pub fn setup_filter_for(
+    request_uri: &str,
+    default: Box<RelationFilter>,
+) -> anyhow::Result<(Box<RelationFilter>, String)> {
     let tokens: Vec<String> = request_uri.split('/').map(|i| i.to_string()).collect();
-    let mut filter_for: Box<RelationFilter> = Box::new(filter_for_incomplete);
+    let mut filter_for: Box<RelationFilter> = default;
     let filters = util::parse_filters(&tokens);
     let mut refcounty = "";
     if filters.contains_key("incomplete") {
@@ -1242,6 +2032,34 @@ fn handle_main_filters(
     }
     items.push(doc);
 
+    doc = yattag::Doc::new();
+    {
+        let mut href = format!("{prefix}/coverage-map");
+        if !refcounty_id.is_empty() {
+            href += &format!("/filter-for/refcounty/{refcounty_id}/whole-county");
+        }
+        let a = doc.tag("a", &[("href", &href)]);
+        a.text(&tr("Coverage map"));
+    }
+    items.push(doc);
+
+    if !refcounty_id.is_empty() {
+        doc = yattag::Doc::new();
+        {
+            let a = doc.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!(
+                        "{prefix}/feed/filter-for/refcounty/{refcounty_id}/whole-county/view-result.atom"
+                    ),
+                )],
+            );
+            a.text(&tr("Feed"));
+        }
+        items.push(doc);
+    }
+
     // Sorted set of refcounty values of all relations.
     let mut refcounties: Vec<_> = relations
         .get_relations()?
@@ -1426,6 +2244,7 @@ fn get_html_title(request_uri: &str) -> String {
         "missing-streets" => format!(" - {} {}", relation_name, tr("missing streets")),
         "street-housenumbers" => format!(" - {} {}", relation_name, tr("existing house numbers")),
         "streets" => format!(" - {} {}", relation_name, tr("existing streets")),
+        "map" => format!(" - {} {}", relation_name, tr("map")),
         _ => "".into(),
     }
 }
@@ -1502,10 +2321,16 @@ fn our_application_gpx(
 ) -> anyhow::Result<rouille::Response> {
     let content_type = "text/gpx+xml; charset=utf-8";
     let mut headers: webframe::Headers = Vec::new();
-    // assume prefix + "/additional-streets/"
+    let prefix = ctx.get_ini().get_uri_prefix();
     let (output, relation_name) =
-        wsgi_additional::additional_streets_view_gpx(ctx, relations, request_uri)
-            .context("additional_streets_view_gpx() failed")?;
+        if request_uri.starts_with(&format!("{prefix}/missing-housenumbers/")) {
+            missing_housenumbers_view_gpx(ctx, relations, request_uri)
+                .context("missing_housenumbers_view_gpx() failed")?
+        } else {
+            // assume prefix + "/additional-streets/"
+            wsgi_additional::additional_streets_view_gpx(ctx, relations, request_uri)
+                .context("additional_streets_view_gpx() failed")?
+        };
     headers.push((
         "Content-Disposition".into(),
         format!(r#"attachment;filename="{relation_name}.gpx""#).into(),
@@ -1515,6 +2340,88 @@ fn our_application_gpx(
     Ok(webframe::make_response(200_u16, headers, data))
 }
 
+/// Dispatches Atom requests based on their URIs.
+fn our_application_atom(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<rouille::Response> {
+    let content_type = "application/atom+xml; charset=utf-8";
+    let headers: webframe::Headers = vec![("Content-type".into(), content_type.into())];
+    // assume prefix + "/feed/"
+    let output =
+        feeds::feed_view_atom(ctx, relations, request_uri).context("feed_view_atom() failed")?;
+    Ok(webframe::make_response(
+        200_u16,
+        headers,
+        output.as_bytes().to_vec(),
+    ))
+}
+
+/// Dispatches OSM XML requests based on their URIs.
+fn our_application_osm(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<rouille::Response> {
+    let content_type = "application/x-osm+xml; charset=utf-8";
+    let mut headers: webframe::Headers = Vec::new();
+    // assume prefix + "/missing-housenumbers/"
+    let (output, relation_name) = missing_housenumbers_view_osm(ctx, relations, request_uri)
+        .context("missing_housenumbers_view_osm() failed")?;
+    headers.push((
+        "Content-Disposition".into(),
+        format!(r#"attachment;filename="{relation_name}.osm""#).into(),
+    ));
+    let data = output.as_bytes().to_vec();
+    headers.push(("Content-type".into(), content_type.into()));
+    Ok(webframe::make_response(200_u16, headers, data))
+}
+
+/// Dispatches GeoJSON requests based on their URIs.
+fn our_application_geojson(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<rouille::Response> {
+    let mut content_type = "application/geo+json; charset=utf-8";
+    let mut headers: webframe::Headers = Vec::new();
+    let prefix = ctx.get_ini().get_uri_prefix();
+    let (output, relation_name) =
+        if request_uri == format!("{prefix}/lints/whole-country/relation-lints.geojson") {
+            content_type = "application/geo+json-seq; charset=utf-8";
+            // Written by cron.
+            let path = lints::get_lints_challenge_path(ctx);
+            let output = if ctx.get_file_system().path_exists(&path) {
+                ctx.get_file_system().read_to_string(&path)?
+            } else {
+                String::new()
+            };
+            (output, "relation-lints".to_string())
+        } else if request_uri.starts_with(&format!("{prefix}/map/")) {
+            wsgi_map::map_view_geojson(ctx, relations, request_uri)
+                .context("map_view_geojson() failed")?
+        } else if request_uri.starts_with(&format!("{prefix}/coverage-map")) {
+            wsgi_map::coverage_map_view_geojson(ctx, relations, request_uri)
+                .context("coverage_map_view_geojson() failed")?
+        } else if request_uri.ends_with("/view-lints.geojson") {
+            content_type = "application/geo+json-seq; charset=utf-8";
+            missing_housenumbers_view_lints_geojson(relations, request_uri)
+                .context("missing_housenumbers_view_lints_geojson() failed")?
+        } else {
+            // assume prefix + "/missing-housenumbers/"
+            missing_housenumbers_view_geojson(ctx, relations, request_uri)
+                .context("missing_housenumbers_view_geojson() failed")?
+        };
+    headers.push((
+        "Content-Disposition".into(),
+        format!(r#"attachment;filename="{relation_name}.geojson""#).into(),
+    ));
+    let data = output.as_bytes().to_vec();
+    headers.push(("Content-type".into(), content_type.into()));
+    Ok(webframe::make_response(200_u16, headers, data))
+}
+
 /// Dispatches plain text requests based on their URIs.
 fn our_application_txt(
     ctx: &context::Context,
@@ -1595,6 +2502,9 @@ lazy_static! {
         ret.insert("/missing-housenumbers/".into(), handle_missing_housenumbers);
         ret.insert("/housenumber-stats/".into(), webframe::handle_stats);
         ret.insert("/lints/".into(), webframe::handle_lints);
+        ret.insert("/cron-status".into(), webframe::handle_cron_status);
+        ret.insert("/map/".into(), wsgi_map::handle_map);
+        ret.insert("/coverage-map".into(), wsgi_map::handle_coverage_map);
         ret
     };
 }
@@ -1621,6 +2531,16 @@ fn our_application(
 
     let request_uri = webframe::get_request_uri(request, ctx, &mut relations)
         .context("get_request_uri() failed")?;
+    let prefix = ctx.get_ini().get_uri_prefix();
+    if request_uri.starts_with(&format!("{prefix}/api/")) {
+        return Ok(wsgi_api::our_application_api(
+            request,
+            ctx,
+            &mut relations,
+            &request_uri,
+        ));
+    }
+
     let mut ext: String = "".into();
     let tokens: Vec<_> = request_uri.split('.').collect();
     if let Some((last, _elements)) = tokens.split_last() {
@@ -1636,7 +2556,21 @@ fn our_application(
             .context("our_application_gpx() failed");
     }
 
-    let prefix = ctx.get_ini().get_uri_prefix();
+    if ext == "atom" {
+        return our_application_atom(ctx, &mut relations, &request_uri)
+            .context("our_application_atom() failed");
+    }
+
+    if ext == "osm" {
+        return our_application_osm(ctx, &mut relations, &request_uri)
+            .context("our_application_osm() failed");
+    }
+
+    if ext == "geojson" {
+        return our_application_geojson(ctx, &mut relations, &request_uri)
+            .context("our_application_geojson() failed");
+    }
+
     if !(request_uri == "/" || request_uri.starts_with(&prefix)) {
         let doc = webframe::handle_404();
         return Ok(webframe::make_response(
@@ -1646,6 +2580,49 @@ fn our_application(
         ));
     }
 
+    if request_uri.starts_with(&format!("{prefix}/missing-housenumbers/"))
+        && request_uri.ends_with("/view-survey")
+    {
+        // Only surveyors may see and submit the survey feedback form.
+        let surveyor = match webframe::get_surveyor(request, ctx) {
+            Some(value) => value,
+            None => {
+                let doc = yattag::Doc::from_text(&tr("Valid surveyor credentials are required."));
+                return Ok(webframe::make_unauthorized_response(
+                    "text/html; charset=utf-8",
+                    doc.get_value().as_bytes().to_vec(),
+                ));
+            }
+        };
+        if request.method() == "POST" {
+            if !webframe::is_same_origin(request, ctx) {
+                let doc = yattag::Doc::from_text(&tr("The form was not submitted from this site."));
+                return Ok(webframe::make_response(
+                    403_u16,
+                    vec![("Content-type".into(), "text/html; charset=utf-8".into())],
+                    doc.get_value().as_bytes().to_vec(),
+                ));
+            }
+            if let Some(error) =
+                handle_survey_post(request, ctx, &mut relations, &request_uri, &surveyor)
+                    .context("handle_survey_post() failed")?
+            {
+                let doc = yattag::Doc::from_text(&error);
+                return Ok(webframe::make_response(
+                    400_u16,
+                    vec![("Content-type".into(), "text/html; charset=utf-8".into())],
+                    doc.get_value().as_bytes().to_vec(),
+                ));
+            }
+            // Redirect, so reloading the page doesn't submit the form again.
+            return Ok(webframe::make_response(
+                303_u16,
+                vec![("Location".into(), request_uri.clone().into())],
+                Vec::new(),
+            ));
+        }
+    }
+
     if request_uri.starts_with(&format!("{prefix}/static/"))
         || request_uri.ends_with("favicon.ico")
         || request_uri.ends_with("favicon.svg")
@@ -11,6 +11,7 @@
 //! Tests for the wsgi module.
 
 use super::*;
+use crate::reference;
 use std::cell::RefCell;
 use std::io::Read;
 use std::io::Write;
@@ -485,6 +486,97 @@ fn test_per_relation_lints() {
     );
 }
 
+/// Tests the per-relation lints page, the invalid addr:city case.
+#[test]
+fn test_per_relation_lints_invalid_addr_cities() {
+    let mut test_wsgi = TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "01",
+                "refsettlement": "011",
+                "osmrelation": 42,
+            },
+        },
+        "refsettlement-names.yaml": {
+            "01": {
+                "011": "Budaörs",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &test_wsgi.ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.ctx.set_file_system(&file_system);
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Tűzkő utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into stats_invalid_addr_cities (osm_id, osm_type, postcode, city, street, housenumber, user) values ('1', 'node', '1111', 'Budaros', 'Tűzkő utca', '1', 'myuser');
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');",
+        )
+        .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/missing-housenumbers/gazdagret/view-lints");
+
+    assert_eq!(
+        TestWsgi::find_all(&root, "body/p[@id='invalid-addr-cities']").len(),
+        1
+    );
+    let cells = TestWsgi::find_all(&root, "body/table/tr/td");
+    assert!(cells.contains(&"Budaros".to_string()));
+    assert!(cells.contains(&"Budaörs".to_string()));
+}
+
+/// Tests the per-relation duplicate addresses page.
+#[test]
+fn test_per_relation_duplicates() {
+    let mut test_wsgi = TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &test_wsgi.ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.ctx.set_file_system(&file_system);
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Tűzkő utca', '10', '', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '2', 'Tűzkő utca', '10', '', '', '', '', '', '', '', '', '', 'way');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '3', 'Tűzkő utca', '2', '', '', '', '', '', '', '1', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '4', 'Tűzkő utca', '2', '', '', '', '', '', '', '2', '', '', 'node');",
+        )
+        .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/missing-housenumbers/gazdagret/view-duplicates");
+
+    assert_eq!(
+        TestWsgi::find_all(&root, "body/table/tr/td/div[@data-value='error']").len(),
+        1
+    );
+    assert_eq!(
+        TestWsgi::find_all(&root, "body/table/tr/td/div[@data-value='legitimate']").len(),
+        1
+    );
+    let links = TestWsgi::find_all(&root, "body/table/tr/td/a/@href");
+    assert!(links.contains(&"http://127.0.0.1:8111/load_object?objects=n1,w2".to_string()));
+}
+
 /// Tests the per-relation lints page, the out-of-range case.
 #[test]
 fn test_per_relation_lints_out_of_range() {
@@ -545,6 +637,177 @@ fn test_per_relation_lints_out_of_range() {
     );
 }
 
+/// Tests the per-relation lints page: the GeoJSON output.
+#[test]
+fn test_per_relation_lints_geojson() {
+    let mut test_wsgi = TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "osmrelation": 42,
+            },
+        },
+        "relation-gazdagret.yaml": {
+            "filters": {
+                "Tűzkő utca": {
+                    "invalid": [ "5", "7" ],
+                }
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &test_wsgi.ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.ctx.set_file_system(&file_system);
+    {
+        let conn = test_wsgi.ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '1', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '5', '');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '10', 'Tűzkő utca', '', '', '', '', 'way', '47', '19.005');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '11', 'Tűzkő utca', '1', '1111', '', '', '', '', '', '', '', '', 'node', '47', '19');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '12', 'Tűzkő utca', '5', '1111', '', '', '', '', '', '', '', '', 'node', '47', '19.004');
+             insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');",
+        )
+        .unwrap();
+    }
+    let prefix = test_wsgi.ctx.get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}/missing-housenumbers/gazdagret/view-lints.geojson");
+    let request = rouille::Request::fake_http("GET", abspath, vec![], vec![]);
+
+    let response = application(&request, &test_wsgi.ctx);
+
+    assert_eq!(response.status_code, 200);
+    let headers_map: HashMap<_, _> = response.headers.into_iter().collect();
+    assert_eq!(
+        headers_map["Content-type"],
+        "application/geo+json-seq; charset=utf-8"
+    );
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    let output = String::from_utf8(data).unwrap();
+    let features: Vec<serde_json::Value> = output
+        .lines()
+        .map(|i| serde_json::from_str(i.trim_start_matches('\u{1e}')).unwrap())
+        .collect();
+    assert_eq!(features.len(), 2);
+    // 5 is created-in-osm, located at the house number.
+    assert_eq!(features[0]["properties"]["@id"], "node/12");
+    assert_eq!(features[0]["properties"]["reason"], "created-in-osm");
+    assert_eq!(
+        features[0]["geometry"]["coordinates"],
+        serde_json::json!([19.004, 47.0])
+    );
+    // 7 is deleted-from-ref, located at the street.
+    assert_eq!(features[1]["properties"]["housenumber"], "7");
+    assert_eq!(features[1]["properties"]["reason"], "deleted-from-ref");
+}
+
+/// Tests the whole country lints: the GeoJSON output is the file written by cron.
+#[test]
+fn test_whole_country_lints_geojson() {
+    let mut test_wsgi = TestWsgi::new();
+    let lints = context::tests::TestFileSystem::make_file();
+    lints.borrow_mut().write_all(b"\x1e{}\n").unwrap();
+    let files = context::tests::TestFileSystem::make_files(
+        &test_wsgi.ctx,
+        &[("workdir/relation-lints.geojson", &lints)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.ctx.set_file_system(&file_system);
+    let prefix = test_wsgi.ctx.get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}/lints/whole-country/relation-lints.geojson");
+    let request = rouille::Request::fake_http("GET", abspath, vec![], vec![]);
+
+    let response = application(&request, &test_wsgi.ctx);
+
+    assert_eq!(response.status_code, 200);
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    assert_eq!(String::from_utf8(data).unwrap(), "\u{1e}{}\n");
+}
+
+/// Tests the survey feedback page.
+#[test]
+fn test_missing_housenumbers_view_survey() {
+    let mut test_wsgi = make_test_wsgi_geo();
+    let prefix = test_wsgi.ctx.get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}/missing-housenumbers/gazdagret/view-survey");
+
+    // No credentials.
+    let request = rouille::Request::fake_http("GET", abspath.clone(), vec![], vec![]);
+    let response = application(&request, &test_wsgi.ctx);
+    assert_eq!(response.status_code, 401);
+
+    // alice:secret, see tests/workdir/wsgi.ini.
+    let auth = (
+        "Authorization".to_string(),
+        "Basic YWxpY2U6c2VjcmV0".to_string(),
+    );
+    let body = "action=set&street=T%C5%B1zk%C5%91+utca&housenumber=3&status=different-number&note=3%2Fa&date=";
+
+    // Submitted from an other site.
+    let origin = ("Origin".to_string(), "https://example.com".to_string());
+    let request = rouille::Request::fake_http(
+        "POST",
+        abspath.clone(),
+        vec![auth.clone(), origin],
+        body.as_bytes().to_vec(),
+    );
+    let response = application(&request, &test_wsgi.ctx);
+    assert_eq!(response.status_code, 403);
+
+    // Invalid status.
+    let origin = (
+        "Origin".to_string(),
+        "https://osm-gimmisn.vmiklos.hu".to_string(),
+    );
+    let request = rouille::Request::fake_http(
+        "POST",
+        abspath.clone(),
+        vec![auth.clone(), origin.clone()],
+        body.replace("different-number", "foo").as_bytes().to_vec(),
+    );
+    let response = application(&request, &test_wsgi.ctx);
+    assert_eq!(response.status_code, 400);
+
+    let request = rouille::Request::fake_http(
+        "POST",
+        abspath.clone(),
+        vec![auth.clone(), origin],
+        body.as_bytes().to_vec(),
+    );
+    let response = application(&request, &test_wsgi.ctx);
+    assert_eq!(response.status_code, 303);
+
+    test_wsgi.headers.push(auth);
+    let root = test_wsgi.get_dom_for_path("/missing-housenumbers/gazdagret/view-survey");
+
+    let mut streets = TestWsgi::find_all(&root, "body/form/datalist/option/@value");
+    streets.sort();
+    assert_eq!(streets, ["Tűzkő utca"]);
+    let results = TestWsgi::find_all(&root, "body/table/tr/td");
+    assert!(results.contains(&"3/a".to_string()));
+    assert!(results.contains(&"alice".to_string()));
+    assert!(results.contains(&"2020-05-10".to_string()));
+    assert_eq!(
+        TestWsgi::find_all(
+            &root,
+            "body/table/tr/td/div[@data-value='different-number']"
+        )
+        .len(),
+        1
+    );
+}
+
 /// Tests the missing house numbers page: if the output is well-formed.
 #[test]
 fn test_missing_housenumbers_well_formed() {
@@ -611,6 +874,226 @@ fn test_missing_housenumbers_well_formed() {
     assert_eq!(results.len(), 1);
 }
 
+/// Tests the missing house numbers changes page.
+#[test]
+fn test_missing_housenumbers_view_changes() {
+    let mut test_wsgi = TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &test_wsgi.ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.ctx.set_file_system(&file_system);
+    {
+        let conn = test_wsgi.ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            r#"insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '1', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '2', '');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '1', 'Tűzkő utca', '', '', '', '', '');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Tűzkő utca', '1', '', '', '', '', '', '', '', '', '', 'node');
+             insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');
+             insert into missing_housenumbers_snapshots (relation, date, json) values ('gazdagret', '2020-04-01', '{"missing":{"Tűzkő utca":["1","3"]},"done":{}}');
+             insert into missing_housenumbers_snapshots (relation, date, json) values ('gazdagret', '2020-05-01', '{"missing":{"Tűzkő utca":["1"]},"done":{}}');"#,
+        )
+        .unwrap();
+    }
+
+    // Default: since a week ago, i.e. 2020-05-01.
+    let root = test_wsgi.get_dom_for_path("/missing-housenumbers/gazdagret/view-changes");
+
+    let mut results = TestWsgi::find_all(&root, "body/h2");
+    results.sort();
+    assert_eq!(
+        results,
+        [
+            "Newly mapped (1)",
+            "Newly missing (1)",
+            "No longer mapped (0)",
+            "Removed from reference (0)"
+        ]
+    );
+    let results = TestWsgi::find_all(&root, "body/table/tr/td");
+    assert_eq!(results.len(), 4);
+
+    // Explicit date: 3 is removed from the reference since then.
+    let root =
+        test_wsgi.get_dom_for_path("/missing-housenumbers/gazdagret/view-changes-2020-04-15");
+
+    let results = TestWsgi::find_all(&root, "body/h2");
+    assert!(results.contains(&"Removed from reference (1)".to_string()));
+}
+
+/// Tests the missing house numbers page: the coverage history.
+#[test]
+fn test_missing_housenumbers_coverage_history() {
+    let mut test_wsgi = TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &test_wsgi.ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.ctx.set_file_system(&file_system);
+    {
+        let conn = test_wsgi.ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');
+             insert into stats_coverages (date, relation, streets, housenumbers) values ('2020-04-01', 'gazdagret', '10.00', '5.00');
+             insert into stats_coverages (date, relation, streets, housenumbers) values ('2020-04-30', 'gazdagret', '20.00', '10.00');
+             insert into stats_coverages (date, relation, streets, housenumbers) values ('2020-05-10', 'gazdagret', '30.00', '');",
+        )
+        .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/missing-housenumbers/gazdagret/view-result");
+
+    let results = TestWsgi::find_all(&root, "body/div/canvas[@id='coverage-history']");
+    assert_eq!(results.len(), 1);
+    // One row for each month, the last value wins.
+    let mut results = TestWsgi::find_all(&root, "body/table/tr/td");
+    results.sort();
+    assert_eq!(
+        results,
+        ["", "10.00%", "20.00%", "2020-04", "2020-05", "30.00%"]
+    );
+}
+
+/// Sets up a relation with missing house numbers and the stored geometry of the street.
+fn make_test_wsgi_geo() -> TestWsgi {
+    let mut test_wsgi = TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &test_wsgi.ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.ctx.set_file_system(&file_system);
+    {
+        let conn = test_wsgi.ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            r#"insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '1', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '2', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '3', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '5', '');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '10', 'Tűzkő utca', '', '', '', '', 'way', '47', '19.005');
+             insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('gazdagret', 'street', 'way', '10', '{"type":"MultiLineString","coordinates":[[[19.0,47.0],[19.01,47.0]]]}');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '11', 'Tűzkő utca', '1', '', '', '', '', '', '', '', '', '', 'node', '47', '19');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '12', 'Tűzkő utca', '5', '', '', '', '', '', '', '', '', '', 'node', '47', '19.004');
+             insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');"#,
+        )
+        .unwrap();
+    }
+    test_wsgi
+}
+
+/// Tests the missing house numbers page: the geojson output.
+#[test]
+fn test_missing_housenumbers_view_result_geojson() {
+    let test_wsgi = make_test_wsgi_geo();
+    let prefix = test_wsgi.ctx.get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}/missing-housenumbers/gazdagret/view-result.geojson");
+    let request = rouille::Request::fake_http("GET", abspath, vec![], vec![]);
+
+    let response = application(&request, &test_wsgi.ctx);
+
+    assert_eq!(response.status_code, 200);
+    let headers_map: HashMap<_, _> = response.headers.into_iter().collect();
+    assert_eq!(
+        headers_map["Content-type"],
+        "application/geo+json; charset=utf-8"
+    );
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    let root: serde_json::Value = serde_json::from_slice(&data).unwrap();
+    assert_eq!(root["type"], "FeatureCollection");
+    let features = root["features"].as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0]["properties"]["name"], "Tűzkő utca 2");
+    assert_eq!(features[0]["properties"]["placement"], "interpolated");
+    let coordinates = features[1]["geometry"]["coordinates"].as_array().unwrap();
+    assert!((coordinates[0].as_f64().unwrap() - 19.002).abs() < 1e-9);
+    assert!((coordinates[1].as_f64().unwrap() - 47.0).abs() < 1e-9);
+}
+
+/// Tests the missing house numbers page: the gpx output.
+#[test]
+fn test_missing_housenumbers_view_result_gpx() {
+    let test_wsgi = make_test_wsgi_geo();
+    let prefix = test_wsgi.ctx.get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}/missing-housenumbers/gazdagret/view-result.gpx");
+    let request = rouille::Request::fake_http("GET", abspath, vec![], vec![]);
+
+    let response = application(&request, &test_wsgi.ctx);
+
+    assert_eq!(response.status_code, 200);
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    let output = String::from_utf8(data).unwrap();
+    assert!(output.contains(
+        r#"<wpt lat="47" lon="19.002"><name>Tűzkő utca 3</name><desc>interpolated</desc></wpt>"#
+    ));
+}
+
+/// Tests the missing house numbers page: the JOSM output.
+#[test]
+fn test_missing_housenumbers_view_result_osm() {
+    let test_wsgi = make_test_wsgi_geo();
+    let prefix = test_wsgi.ctx.get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}/missing-housenumbers/gazdagret/view-result.osm");
+    let request = rouille::Request::fake_http("GET", abspath, vec![], vec![]);
+
+    let response = application(&request, &test_wsgi.ctx);
+
+    assert_eq!(response.status_code, 200);
+    let headers_map: HashMap<_, _> = response.headers.into_iter().collect();
+    assert_eq!(
+        headers_map["Content-type"],
+        "application/x-osm+xml; charset=utf-8"
+    );
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    let output = String::from_utf8(data).unwrap();
+    assert!(output.contains(r#"<osm version="0.6" generator="osm-gimmisn" upload="never">"#));
+    // 3 is between 1 and 5.
+    assert!(output.contains(
+        r#"<node id="-2" lat="47" lon="19.002"><tag k="addr:street" v="Tűzkő utca"/><tag k="addr:housenumber" v="3"/><tag k="fixme" v="survey"/></node>"#
+    ));
+}
+
 /// Tests the missing house numbers page: the output for a non-existing relation.
 #[test]
 fn test_missing_housenumbers_no_such_relation() {
@@ -1225,7 +1708,7 @@ fn test_missing_housenumbers_view_query_well_formed() {
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '1', '');
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '10', '');
              insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '2', '');
-             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '9', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment, source) values ('0', '0', 'Tűzkő utca', '9', '', 'register');
              insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '1', 'Tűzkő utca', '', '', '', '', '');
              insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '2', 'Törökugrató utca', '', '', '', '', '');
              insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '3', 'OSM Name 1', '', '', '', '', '');
@@ -1238,6 +1721,8 @@ fn test_missing_housenumbers_view_query_well_formed() {
 
     let results = TestWsgi::find_all(&root, "body/pre");
     assert_eq!(results.len(), 1);
+    // The provenance label of the reference source is shown.
+    assert!(results[0].contains("Tűzkő utca\t9\t\tregister"));
 }
 
 /// Tests handle_street_housenumbers(): view result: the update-result link.
@@ -1319,7 +1804,7 @@ fn test_housenumbers_update_result_well_formed() {
     let mut test_wsgi = TestWsgi::new();
     let routes = vec![context::tests::URLRoute::new(
         /*url=*/ "https://overpass-api.de/api/interpreter",
-        /*data_path=*/ "",
+        /*data_path=*/ "src/fixtures/network/overpass-housenumbers-gazdagret.overpassql",
         /*result_path=*/ "src/fixtures/network/overpass-housenumbers-gazdagret.json",
     )];
     let network = context::tests::TestNetwork::new(&routes);
@@ -1336,7 +1821,7 @@ fn test_housenumbers_update_result_well_formed() {
     let overpass_template = context::tests::TestFileSystem::make_file();
     overpass_template
         .borrow_mut()
-        .write_all(b"housenr aaa @RELATION@ bbb @AREA@ ccc\n")
+        .write_all(b"[out:csv(::id)];\nfoo @AREA@ bar\n")
         .unwrap();
     let files = context::tests::TestFileSystem::make_files(
         &test_wsgi.ctx,
@@ -1377,6 +1862,17 @@ fn test_housenumbers_update_result_well_formed() {
             .len(),
         4
     );
+    // The house numbers are updated, too.
+    assert!(stats::has_sql_mtime(&test_wsgi.ctx, "housenumbers/gazdagret").unwrap());
+    let conn = test_wsgi.ctx.get_database_connection().unwrap();
+    let count: i64 = conn
+        .query_row(
+            "select count(*) from osm_housenumbers where relation = 'gazdagret'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(count > 0);
 }
 
 /// Tests handle_street_housenumbers(): if the update-result output on error is well-formed.
@@ -1505,13 +2001,52 @@ fn test_missing_streets_well_formed() {
     assert_eq!(results.len(), 1);
 }
 
+/// Tests the missing streets page: the refstreets suggestions.
+#[test]
+fn test_missing_streets_refstreets_suggestions() {
+    let mut test_wsgi = TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "01",
+                "refsettlement": "011",
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        &test_wsgi.ctx,
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.ctx.set_file_system(&file_system);
+    {
+        let conn = test_wsgi.ctx.get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into ref_streets (county_code, settlement_code, street) values ('01', '011', 'Tűzkő utca');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '1', 'Tüzkő u.', '', '', '', '', '');
+             insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');",
+        )
+        .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/missing-streets/gazdagret/view-result");
+
+    let results = TestWsgi::find_all(
+        &root,
+        "body/div[@id='refstreets-suggestions-container']/pre",
+    );
+    assert_eq!(results.len(), 1);
+    assert!(results[0].contains("'Tüzkő u.': 'Tűzkő utca'"));
+}
+
 /// Tests the missing streets page: if the output is well-formed (URL rewrite).
 #[test]
 fn test_missing_streets_well_formed_compat() {
     let mut test_wsgi = TestWsgi::new();
     {
-        let ref_streets = test_wsgi.ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&test_wsgi.ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&test_wsgi.ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -1580,8 +2115,7 @@ fn test_missing_streets_no_osm_streets_well_formed() {
 fn test_missing_streets_view_result_txt() {
     let mut test_wsgi = TestWsgi::new();
     {
-        let ref_streets = test_wsgi.ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&test_wsgi.ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&test_wsgi.ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -1626,8 +2160,7 @@ fn test_missing_streets_view_result_txt() {
 fn test_missing_streets_view_result_chkl() {
     let mut test_wsgi = TestWsgi::new();
     {
-        let ref_streets = test_wsgi.ctx.get_ini().get_reference_street_path().unwrap();
-        util::build_street_reference_index(&test_wsgi.ctx, &ref_streets).unwrap();
+        reference::build_street_reference_index(&test_wsgi.ctx).unwrap();
     }
     let yamls_cache = serde_json::json!({
         "relations.yaml": {
@@ -1698,12 +2231,23 @@ fn test_missing_streets_view_query_well_formed() {
     );
     let file_system = context::tests::TestFileSystem::from_files(&files);
     test_wsgi.ctx.set_file_system(&file_system);
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into ref_streets (county_code, settlement_code, street, source) values ('01', '011', 'Only In Ref utca', 'register');
+             insert into ref_streets (county_code, settlement_code, street, source) values ('01', '011', 'Only In Ref utca', '');
+             insert into ref_streets (county_code, settlement_code, street, source) values ('01', '011', 'Törökugrató utca', '');",
+        )
+        .unwrap();
+    }
 
     let root = test_wsgi.get_dom_for_path("/missing-streets/gazdagret/view-query");
 
     let results = TestWsgi::find_all(&root, "body/pre");
 
     assert_eq!(results.len(), 1);
+    // The provenance label of the reference source is shown.
+    assert_eq!(results[0], "Only In Ref utca\tregister\nTörökugrató utca");
 }
 
 /// Tests the missing streets page: the view-turbo output.
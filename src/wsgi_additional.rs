@@ -13,59 +13,33 @@
 use crate::areas;
 use crate::context;
 use crate::i18n::translate as tr;
-use crate::overpass_query;
 use crate::stats;
 use crate::util;
 use crate::webframe;
 use crate::yattag;
 use anyhow::Context;
+use std::collections::HashMap;
 
-/// OverpassMember represents one member of a relation's members.
-#[derive(serde::Deserialize)]
-struct OverpassMember {
-    #[serde(rename = "ref")]
-    ref_id: u64,
-}
-
-/// OverpassElement represents one result from Overpass.
-#[derive(serde::Deserialize)]
-struct OverpassElement {
-    id: u64,
-    nodes: Option<Vec<u64>>,
-    members: Option<Vec<OverpassMember>>,
-    lat: Option<f64>,
-    lon: Option<f64>,
-}
-
-/// OverpassResult is the result from Overpass.
-#[derive(serde::Deserialize)]
-struct OverpassResult {
-    elements: Vec<OverpassElement>,
-}
-
-fn get_gpx_street_lat_lon(
-    overpass: &OverpassResult,
-    element: &OverpassElement,
-) -> anyhow::Result<(String, String)> {
-    if let Some(ref members) = element.members {
-        let member = &members[0];
-        let way = overpass
-            .elements
-            .iter()
-            .find(|i| i.id == member.ref_id)
-            .unwrap();
-        get_gpx_street_lat_lon(overpass, way)
-    } else if let Some(ref nodes) = element.nodes {
-        let node_id = nodes.clone()[0];
-        let node = overpass.elements.iter().find(|i| i.id == node_id).unwrap();
-        let lat = node.lat.unwrap().to_string();
-        let lon = node.lon.unwrap().to_string();
-        Ok((lat, lon))
-    } else {
-        let lat = element.lat.context("missing lat")?.to_string();
-        let lon = element.lon.context("missing lon")?.to_string();
-        Ok((lat, lon))
+/// Gets the representative point of the OSM streets of a relation, as stored by cron.
+fn get_osm_street_points(
+    ctx: &context::Context,
+    relation_name: &str,
+) -> anyhow::Result<HashMap<(String, u64), (String, String)>> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(
+        "select osm_type, osm_id, lat, lon from osm_streets where relation = ?1 and lat != '' and lon != ''",
+    )?;
+    let mut rows = stmt.query([relation_name])?;
+    let mut ret = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let osm_type: String = row.get(0)?;
+        let osm_id: String = row.get(1)?;
+        ret.insert(
+            (osm_type, osm_id.parse::<u64>()?),
+            (row.get(2)?, row.get(3)?),
+        );
     }
+    Ok(ret)
 }
 
 /// Expected request_uri: e.g. /osm/additional-streets/ujbuda/view-result.gpx.
@@ -81,10 +55,7 @@ pub fn additional_streets_view_gpx(
         .get_relation(relation_name)
         .context("get_relation() failed")?;
     let mut streets = relation.get_additional_streets(/*sorted_result=*/ true)?;
-    let query = areas::make_turbo_query_for_street_objs(&relation, &streets);
-    let buf = overpass_query::overpass_query(ctx, &query)?;
-    let overpass: OverpassResult =
-        serde_json::from_str(&buf).context(format!("failed to parse '{buf}' as json"))?;
+    let points = get_osm_street_points(ctx, relation_name)?;
 
     let doc = yattag::Doc::new();
     doc.append_value("<?xml version='1.0' encoding='UTF-8'?>".into());
@@ -116,14 +87,13 @@ pub fn additional_streets_view_gpx(
         }
         streets.sort_by_key(|street| util::get_sort_key(street.get_osm_name()));
         for street in streets {
-            let overpass_element = overpass
-                .elements
-                .iter()
-                .find(|i| i.id == street.get_osm_id())
-                .unwrap();
-            let (lat, lon) = get_gpx_street_lat_lon(&overpass, overpass_element)
-                .context("get_gpx_street_lat_lon() failed")?;
-            let wpt = gpx.tag("wpt", &[("lat", &lat), ("lon", &lon)]);
+            // Streets from before the coordinates were stored have no point till the next refresh.
+            let (lat, lon) =
+                match points.get(&(street.get_osm_type().to_string(), street.get_osm_id())) {
+                    Some(point) => point,
+                    None => continue,
+                };
+            let wpt = gpx.tag("wpt", &[("lat", lat), ("lon", lon)]);
             let name = wpt.tag("name", &[]);
             name.text(street.get_osm_name());
         }
@@ -16,8 +16,6 @@ use crate::areas;
 use crate::context;
 use crate::wsgi;
 
-use super::*;
-
 /// Tests additional streets: the txt output.
 #[test]
 fn test_streets_view_result_txt() {
@@ -100,14 +98,6 @@ fn test_streets_view_result_gpx() {
     );
     let file_system = context::tests::TestFileSystem::from_files(&files);
     test_wsgi.get_ctx().set_file_system(&file_system);
-    let routes = vec![context::tests::URLRoute::new(
-        /*url=*/ "https://overpass-api.de/api/interpreter",
-        /*data_path=*/ "src/fixtures/network/overpass-additional-streets.overpassql",
-        /*result_path=*/ "src/fixtures/network/overpass-additional-streets.json",
-    )];
-    let network = context::tests::TestNetwork::new(&routes);
-    let network_rc: Rc<dyn context::Network> = Rc::new(network);
-    test_wsgi.get_ctx().set_network(network_rc);
     test_wsgi.set_content_type("text/gpx+xml; charset=utf-8");
     {
         let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
@@ -126,14 +116,42 @@ fn test_streets_view_result_gpx() {
              insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '6', 'OSM Name 1', '2', '', '', '', '', '', '', '', '', '', 'node');
              insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '7', 'Only In OSM utca', '1', '', '', '', '', '', '', '', '', '', 'node');
              insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '8', 'Second Only In OSM utca', '1', '', '', '', '', '', '', '', '', '', 'node');
-             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');",
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '1', 'Tűzkő utca', '', '', '', '', 'way', '47.4', '19.0');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '2', 'Only In OSM utca', '', '', '', '', 'way', '47.4638', '19.0112');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '3', 'Second Only In OSM utca', '', '', '', '', 'relation', '47.4647', '19.0093');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type, lat, lon) values ('gazdagret', '4', 'No Point utca', '', '', '', '', 'way', '', '');
+             insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');",
         )
         .unwrap();
     }
 
-    let _root = test_wsgi.get_dom_for_path("/additional-streets/gazdagret/view-result.gpx");
-
-    // TODO assert that there are two results here
+    let root = test_wsgi.get_dom_for_path("/additional-streets/gazdagret/view-result.gpx");
+
+    // No Point utca has no coordinates yet, so it's not in the output.
+    let document = root.as_document();
+    let find_all = |path: &str| -> Vec<String> {
+        match sxd_xpath::evaluate_xpath(&document, path).unwrap() {
+            sxd_xpath::Value::Nodeset(nodeset) => nodeset
+                .document_order()
+                .iter()
+                .map(|i| i.string_value())
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+    assert_eq!(
+        find_all("//*[local-name()='wpt']/*[local-name()='name']"),
+        ["Only In OSM utca", "Second Only In OSM utca"]
+    );
+    assert_eq!(
+        find_all("//*[local-name()='wpt']/@lat"),
+        ["47.4638", "47.4647"]
+    );
+    assert_eq!(
+        find_all("//*[local-name()='wpt']/@lon"),
+        ["19.0112", "19.0093"]
+    );
 }
 
 /// Tests additional streets: the chkl output.
@@ -541,65 +559,3 @@ fn test_streets_no_osm_streets_well_formed() {
     let results = wsgi::tests::TestWsgi::find_all(&root, "body/div[@id='no-osm-streets']");
     assert_eq!(results.len(), 1);
 }
-
-/// Tests get_gpx_street_lat_lon(), the case when a "street" is a node.
-#[test]
-fn test_get_gpx_street_lat_lon_node() {
-    let json = serde_json::json!({
-        "elements": [
-            {
-                "type": "node",
-                "id": 42,
-                "lat": 47,
-                "lon": 18,
-                "tags": {
-                    "addr:city": "mycity",
-                    "addr:housenumber": "43",
-                    "addr:postcode": "1234",
-                    "addr:street": "mystreet",
-                },
-            },
-        ],
-    });
-    let overpass: OverpassResult = serde_json::from_value(json).unwrap();
-    let element = &overpass.elements[0];
-    let (lat, lon) = get_gpx_street_lat_lon(&overpass, &element).unwrap();
-    assert_eq!(lat, "47");
-    assert_eq!(lon, "18");
-}
-
-/// Tests get_gpx_street_lat_lon(), the case when a "street" is a relation.
-#[test]
-fn test_get_gpx_street_lat_lon_relation() {
-    let json = serde_json::json!({
-        "elements": [
-            {
-                "type": "relation",
-                "id": 2262333,
-                "members": [
-                    {
-                        "ref": 366696002,
-                    },
-                ],
-            },
-            {
-                "type": "way",
-                "id": 366696002,
-                "nodes": [
-                    370687421,
-                ]
-            },
-            {
-                "type": "node",
-                "id": 370687421,
-                "lat": 47.0273397,
-                "lon": 18.0187039
-            },
-        ]
-    });
-    let overpass: OverpassResult = serde_json::from_value(json).unwrap();
-    let element = &overpass.elements[0];
-    let (lat, lon) = get_gpx_street_lat_lon(&overpass, &element).unwrap();
-    assert_eq!(lat, "47.0273397");
-    assert_eq!(lon, "18.0187039");
-}
@@ -0,0 +1,580 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Contains functionality specific to the versioned /api/v1/ part of the web interface.
+
+use crate::areas;
+use crate::cache;
+use crate::context;
+use crate::openapi;
+use crate::stats;
+use crate::util;
+use crate::webframe;
+use anyhow::Context as _;
+use std::collections::HashMap;
+use std::io::Read as _;
+
+/// An error that is reported to the client as a JSON body with a matching status code.
+#[derive(Debug)]
+enum ApiError {
+    /// The request is malformed, status 400.
+    BadRequest(String),
+    /// The request has no valid surveyor credentials, status 401.
+    Unauthorized(String),
+    /// The request doesn't come from this site, status 403.
+    Forbidden(String),
+    /// The requested resource doesn't exist, status 404.
+    NotFound(String),
+    /// The request body is not JSON, status 415.
+    UnsupportedMediaType(String),
+    /// Unexpected failure, status 500.
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        ApiError::Internal(err)
+    }
+}
+
+/// Settings of a relation, one entry of the /api/v1/relations list.
+#[derive(schemars::JsonSchema, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(rename = "Relation")]
+pub struct ApiRelation {
+    name: String,
+    osmrelation: u64,
+    refcounty: String,
+    refsettlement: String,
+    active: bool,
+}
+
+impl ApiRelation {
+    fn new(relation: &areas::Relation<'_>) -> Self {
+        let config = relation.get_config();
+        ApiRelation {
+            name: relation.get_name(),
+            osmrelation: config.get_osmrelation(),
+            refcounty: config.get_refcounty(),
+            refsettlement: config.get_refsettlement(),
+            active: config.is_active(),
+        }
+    }
+}
+
+/// A filter of a relation that is probably no longer necessary, or an OSM address with a
+/// problematic postcode: one row of the /api/v1/relations/<relation>/lints list.
+#[derive(schemars::JsonSchema, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(rename = "Lint")]
+pub struct ApiLint {
+    street_name: String,
+    #[schemars(with = "areas::RelationLintSource")]
+    source: String,
+    housenumber: String,
+    #[schemars(with = "areas::RelationLintReason")]
+    reason: String,
+    object_id: String,
+    object_type: String,
+}
+
+/// An OSM address of a relation with an addr:city value which is not a valid settlement: one row
+/// of the /api/v1/relations/<relation>/invalid-addr-cities list.
+#[derive(schemars::JsonSchema, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(rename = "InvalidAddrCity")]
+pub struct ApiInvalidAddrCity {
+    street_name: String,
+    housenumber: String,
+    object_id: String,
+    object_type: String,
+    /// The invalid addr:city value.
+    city: String,
+    /// The name of the refsettlement of the street, empty if unknown.
+    suggested_city: String,
+}
+
+/// Result of the missing streets analysis, output of /api/v1/relations/<relation>/missing-streets.
+#[derive(schemars::JsonSchema, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(rename = "MissingStreets")]
+pub struct ApiMissingStreets {
+    /// Streets which are only in the reference.
+    ongoing_streets: Vec<String>,
+    /// Streets which are both in OSM and in the reference.
+    done_streets: Vec<String>,
+}
+
+/// A single coverage value, with its last modification time as a unix timestamp.
+#[derive(schemars::JsonSchema, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(rename = "Coverage")]
+pub struct ApiCoverage {
+    coverage: String,
+    last_modified: i64,
+}
+
+/// Street and house number coverage of a relation, null if not yet calculated: output of
+/// /api/v1/relations/<relation>/coverage.
+#[derive(schemars::JsonSchema, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(rename = "Coverages")]
+pub struct ApiCoverages {
+    streets: Option<ApiCoverage>,
+    housenumbers: Option<ApiCoverage>,
+}
+
+/// Identifies a survey observation to be removed.
+#[derive(schemars::JsonSchema, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(rename = "SurveyObservationKey")]
+pub struct ApiSurveyObservationKey {
+    street: String,
+    housenumber: String,
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/openapi.json.
+fn openapi_json(ctx: &context::Context) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(&openapi::get_openapi(ctx))?)
+}
+
+/// Decides if a relation matches the filters parsed by util::parse_filters().
+fn relation_matches_filters(
+    relation: &areas::Relation<'_>,
+    filters: &HashMap<String, String>,
+) -> bool {
+    let config = relation.get_config();
+    if let Some(refcounty) = filters.get("refcounty") {
+        if &config.get_refcounty() != refcounty {
+            return false;
+        }
+    }
+    if let Some(refsettlement) = filters.get("refsettlement") {
+        if &config.get_refsettlement() != refsettlement {
+            return false;
+        }
+    }
+    if let Some(osmrelations) = filters.get("relations") {
+        let osmrelation = config.get_osmrelation().to_string();
+        if !osmrelations.split(',').any(|i| i == osmrelation) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations or
+/// /osm/api/v1/relations/filter-for/refcounty/01/refsettlement/011.
+fn relations_json(relations: &mut areas::Relations<'_>, tokens: &[&str]) -> anyhow::Result<String> {
+    let tokens: Vec<String> = tokens.iter().map(|i| i.to_string()).collect();
+    let filters = util::parse_filters(&tokens);
+    let ret: Vec<ApiRelation> = relations
+        .get_relations()?
+        .iter()
+        .filter(|relation| relation_matches_filters(relation, &filters))
+        .map(ApiRelation::new)
+        .collect();
+    Ok(serde_json::to_string(&ret)?)
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations/ormezo.
+fn relation_json(relation: &areas::Relation<'_>) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(&ApiRelation::new(relation))?)
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations/ormezo/missing-streets.
+fn missing_streets_json(relation: &areas::Relation<'_>) -> anyhow::Result<String> {
+    let (ongoing_streets, done_streets) = relation.get_missing_streets()?;
+    let ret = ApiMissingStreets {
+        ongoing_streets,
+        done_streets,
+    };
+    Ok(serde_json::to_string(&ret)?)
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations/ormezo/additional-streets.
+fn additional_streets_json(relation: &areas::Relation<'_>) -> anyhow::Result<String> {
+    let streets = relation.get_additional_streets(/*sorted_result=*/ true)?;
+    Ok(serde_json::to_string(&streets)?)
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations/ormezo/lints.
+fn lints_json(
+    ctx: &context::Context,
+    relation: &mut areas::Relation<'_>,
+) -> anyhow::Result<String> {
+    // Update lints if they are outdated.
+    cache::get_missing_housenumbers_json(relation)
+        .context("get_missing_housenumbers_json() failed")?;
+
+    let mut ret: Vec<ApiLint> = Vec::new();
+    {
+        let conn = ctx.get_database_connection()?;
+        let mut stmt = conn.prepare(
+            "select street_name, source, housenumber, reason, object_id, object_type from relation_lints where relation_name = ?1 order by id",
+        )?;
+        let mut lints = stmt.query([relation.get_name()])?;
+        while let Some(lint) = lints.next()? {
+            ret.push(ApiLint {
+                street_name: lint.get(0)?,
+                source: lint.get(1)?,
+                housenumber: lint.get(2)?,
+                reason: lint.get(3)?,
+                object_id: lint.get(4)?,
+                object_type: lint.get(5)?,
+            });
+        }
+    }
+    Ok(serde_json::to_string(&ret)?)
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations/ormezo/invalid-addr-cities.
+fn invalid_addr_cities_json(
+    relations: &areas::Relations<'_>,
+    relation: &areas::Relation<'_>,
+) -> anyhow::Result<String> {
+    let ret: Vec<ApiInvalidAddrCity> = relations
+        .get_invalid_addr_cities(relation)?
+        .into_iter()
+        .map(|invalid| ApiInvalidAddrCity {
+            street_name: invalid.street,
+            housenumber: invalid.housenumber,
+            object_id: invalid.osm_id,
+            object_type: invalid.osm_type,
+            city: invalid.city,
+            suggested_city: invalid.suggested_city,
+        })
+        .collect();
+    Ok(serde_json::to_string(&ret)?)
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations/ormezo/coverage.
+fn coverage_json(relation: &areas::Relation<'_>) -> anyhow::Result<String> {
+    let mut ret = ApiCoverages {
+        streets: None,
+        housenumbers: None,
+    };
+    if relation.has_osm_street_coverage()? {
+        ret.streets = Some(ApiCoverage {
+            coverage: relation.get_osm_street_coverage()?,
+            last_modified: relation.get_osm_street_coverage_mtime()?.unix_timestamp(),
+        });
+    }
+    if relation.has_osm_housenumber_coverage()? {
+        ret.housenumbers = Some(ApiCoverage {
+            coverage: relation.get_osm_housenumber_coverage()?,
+            last_modified: relation
+                .get_osm_housenumber_coverage_mtime()?
+                .unix_timestamp(),
+        });
+    }
+    Ok(serde_json::to_string(&ret)?)
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations/ormezo/coverage-history.
+fn coverage_history_json(relation: &areas::Relation<'_>) -> anyhow::Result<String> {
+    let history = relation.get_coverage_history()?;
+    Ok(serde_json::to_string(&history)?)
+}
+
+/// Expected request_uri: e.g. /osm/api/v1/relations/ormezo/survey-observations.
+fn survey_observations_json(relation: &areas::Relation<'_>) -> anyhow::Result<String> {
+    let observations = relation.get_survey_observations()?;
+    Ok(serde_json::to_string(&observations)?)
+}
+
+/// Makes sure that a date is in the YYYY-MM-DD format.
+fn check_date(date: &str) -> Result<(), ApiError> {
+    let format = time::format_description::parse("[year]-[month]-[day]")
+        .context("format_description::parse() failed")?;
+    if time::Date::parse(date, &format).is_err() {
+        return Err(ApiError::BadRequest(format!(
+            "invalid date, expected YYYY-MM-DD: {date}"
+        )));
+    }
+    Ok(())
+}
+
+/// Makes sure that OSM data is available for a relation before running an analysis on it.
+fn require_osm_data(
+    ctx: &context::Context,
+    relation_name: &str,
+    streets: bool,
+    housenumbers: bool,
+) -> Result<(), ApiError> {
+    if streets && !stats::has_sql_mtime(ctx, &format!("streets/{relation_name}"))? {
+        return Err(ApiError::NotFound(format!(
+            "no OSM streets for relation: {relation_name}"
+        )));
+    }
+    if housenumbers && !stats::has_sql_mtime(ctx, &format!("housenumbers/{relation_name}"))? {
+        return Err(ApiError::NotFound(format!(
+            "no OSM housenumbers for relation: {relation_name}"
+        )));
+    }
+    Ok(())
+}
+
+/// Resolves relation aliases and makes sure that the relation exists.
+fn get_relation_name(
+    relations: &mut areas::Relations<'_>,
+    relation_name: &str,
+) -> Result<String, ApiError> {
+    let aliases = relations.get_aliases()?;
+    let relation_name = match aliases.get(relation_name) {
+        Some(value) => value.to_string(),
+        None => relation_name.to_string(),
+    };
+    if !relations.get_names().contains(&relation_name) {
+        return Err(ApiError::NotFound(format!(
+            "no such relation: {relation_name}"
+        )));
+    }
+    Ok(relation_name)
+}
+
+/// Handles /api/v1/relations/<relation>[/<analysis>].
+fn relation_api(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    relation_name: &str,
+    analysis: Option<&str>,
+) -> Result<String, ApiError> {
+    let relation_name = get_relation_name(relations, relation_name)?;
+    let mut relation = relations.get_relation(&relation_name)?;
+
+    let output = match analysis {
+        None => relation_json(&relation)?,
+        Some("missing-housenumbers") => {
+            require_osm_data(ctx, &relation_name, true, true)?;
+            cache::get_missing_housenumbers_json(&mut relation)?
+        }
+        Some("additional-housenumbers") => {
+            require_osm_data(ctx, &relation_name, true, true)?;
+            cache::get_additional_housenumbers_json(&mut relation)?
+        }
+        Some("missing-streets") => {
+            require_osm_data(ctx, &relation_name, true, false)?;
+            missing_streets_json(&relation)?
+        }
+        Some("additional-streets") => {
+            require_osm_data(ctx, &relation_name, true, false)?;
+            additional_streets_json(&relation)?
+        }
+        Some("lints") => {
+            require_osm_data(ctx, &relation_name, true, true)?;
+            lints_json(ctx, &mut relation)?
+        }
+        Some("invalid-addr-cities") => {
+            require_osm_data(ctx, &relation_name, false, true)?;
+            invalid_addr_cities_json(relations, &relation)?
+        }
+        Some("coverage") => coverage_json(&relation)?,
+        Some("coverage-history") => coverage_history_json(&relation)?,
+        Some("survey-observations") => survey_observations_json(&relation)?,
+        Some(analysis) => {
+            return Err(ApiError::NotFound(format!("no such analysis: {analysis}")));
+        }
+    };
+    Ok(output)
+}
+
+/// Handles /api/v1/relations/<relation>/missing-housenumbers-changes/<since>.
+fn missing_housenumbers_changes_api(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    relation_name: &str,
+    since: &str,
+) -> Result<String, ApiError> {
+    let relation_name = get_relation_name(relations, relation_name)?;
+    check_date(since)?;
+    require_osm_data(ctx, &relation_name, true, true)?;
+    let mut relation = relations.get_relation(&relation_name)?;
+    let changes = relation.get_missing_housenumbers_changes(since)?;
+    Ok(serde_json::to_string(&changes).context("to_string() failed")?)
+}
+
+/// Handles POST and DELETE requests of /api/v1/relations/<relation>/survey-observations, which
+/// record or remove survey feedback.
+fn survey_observations_api(
+    request: &rouille::Request,
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    relation_name: &str,
+) -> Result<String, ApiError> {
+    let surveyor = match webframe::get_surveyor(request, ctx) {
+        Some(value) => value,
+        None => {
+            return Err(ApiError::Unauthorized(
+                "valid surveyor credentials are required".into(),
+            ));
+        }
+    };
+    if !webframe::is_same_origin(request, ctx) {
+        return Err(ApiError::Forbidden(
+            "the request was not sent from this site".into(),
+        ));
+    }
+    let content_type = request.header("Content-Type").unwrap_or_default();
+    let mime_type = content_type.split(';').next().unwrap_or_default().trim();
+    if !mime_type.eq_ignore_ascii_case("application/json") {
+        return Err(ApiError::UnsupportedMediaType(format!(
+            "unsupported content type: '{content_type}', expected application/json"
+        )));
+    }
+    let relation_name = get_relation_name(relations, relation_name)?;
+    let relation = relations.get_relation(&relation_name)?;
+    let mut body = Vec::new();
+    if let Some(mut reader) = request.data() {
+        reader
+            .read_to_end(&mut body)
+            .context("read_to_end() failed")?;
+    }
+    if request.method() == "DELETE" {
+        let key: ApiSurveyObservationKey = serde_json::from_slice(&body)
+            .map_err(|err| ApiError::BadRequest(format!("invalid request body: {err}")))?;
+        if !relation.remove_survey_observation(&key.street, &key.housenumber)? {
+            return Err(ApiError::NotFound(format!(
+                "no survey observation for: {} {}",
+                key.street, key.housenumber
+            )));
+        }
+        return Ok(survey_observations_json(&relation)?);
+    }
+
+    let mut observation: areas::SurveyObservation = serde_json::from_slice(&body)
+        .map_err(|err| ApiError::BadRequest(format!("invalid request body: {err}")))?;
+    if observation.street.is_empty() || observation.housenumber.is_empty() {
+        return Err(ApiError::BadRequest(
+            "street and housenumber must not be empty".into(),
+        ));
+    }
+    if observation.date.is_empty() {
+        let format = time::format_description::parse("[year]-[month]-[day]")
+            .context("format_description::parse() failed")?;
+        observation.date = ctx
+            .get_time()
+            .now()
+            .format(&format)
+            .context("format() failed")?;
+    }
+    check_date(&observation.date)?;
+    observation.surveyor = surveyor;
+    relation.set_survey_observation(&observation)?;
+    Ok(survey_observations_json(&relation)?)
+}
+
+/// Dispatches API requests based on their URIs, without turning errors into responses.
+fn dispatch_api(
+    request: &rouille::Request,
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> Result<String, ApiError> {
+    ctx.get_unit().make_error()?;
+    let prefix = ctx.get_ini().get_uri_prefix();
+    let path = match request_uri.strip_prefix(&format!("{prefix}/api/v1/")) {
+        Some(value) => value,
+        None => {
+            return Err(ApiError::NotFound(format!(
+                "unsupported API version: {request_uri}"
+            )));
+        }
+    };
+    let tokens: Vec<&str> = path.split('/').collect();
+    if tokens.iter().any(|token| token.is_empty()) {
+        return Err(ApiError::BadRequest(format!("empty path segment: {path}")));
+    }
+    if request.method() == "POST" || request.method() == "DELETE" {
+        return match tokens[..] {
+            ["relations", relation_name, "survey-observations"] => {
+                survey_observations_api(request, ctx, relations, relation_name)
+            }
+            _ => Err(ApiError::BadRequest(format!(
+                "unsupported method for {path}: {}",
+                request.method()
+            ))),
+        };
+    }
+    match tokens[..] {
+        ["openapi.json"] => Ok(openapi_json(ctx)?),
+        ["relations"] => Ok(relations_json(relations, &tokens)?),
+        ["relations", "filter-for", ..] => Ok(relations_json(relations, &tokens)?),
+        ["relations", relation_name] => relation_api(ctx, relations, relation_name, None),
+        ["relations", relation_name, analysis] => {
+            relation_api(ctx, relations, relation_name, Some(analysis))
+        }
+        ["relations", relation_name, "missing-housenumbers-changes", since] => {
+            missing_housenumbers_changes_api(ctx, relations, relation_name, since)
+        }
+        ["relations", ..] => Err(ApiError::BadRequest(format!(
+            "too many path segments: {path}"
+        ))),
+        _ => Err(ApiError::NotFound(format!("no such endpoint: {path}"))),
+    }
+}
+
+/// Creates a JSON response with the given status code.
+fn make_json_response(status_code: u16, output: &str) -> rouille::Response {
+    let headers: webframe::Headers = vec![(
+        "Content-type".into(),
+        "application/json; charset=utf-8".into(),
+    )];
+    webframe::make_response(status_code, headers, output.as_bytes().to_vec())
+}
+
+/// Error message, empty on success for update-result endpoints.
+#[derive(schemars::JsonSchema, serde::Serialize)]
+#[schemars(rename = "Error")]
+pub struct ApiErrorJson {
+    error: String,
+}
+
+/// Creates the JSON body of an error response.
+fn make_error_json(error: &str) -> String {
+    let ret = ApiErrorJson {
+        error: error.into(),
+    };
+    // Serializing a string can't fail.
+    serde_json::to_string(&ret).unwrap()
+}
+
+/// Creates a JSON error response with the given status code.
+fn make_error_response(status_code: u16, error: &str) -> rouille::Response {
+    make_json_response(status_code, &make_error_json(error))
+}
+
+/// Dispatches API requests based on their URIs. Errors are reported as JSON, never as HTML.
+pub fn our_application_api(
+    request: &rouille::Request,
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> rouille::Response {
+    match dispatch_api(request, ctx, relations, request_uri) {
+        Ok(output) => make_json_response(200_u16, &output),
+        Err(ApiError::BadRequest(error)) => make_error_response(400_u16, &error),
+        Err(ApiError::Unauthorized(error)) => webframe::make_unauthorized_response(
+            "application/json; charset=utf-8",
+            make_error_json(&error).as_bytes().to_vec(),
+        ),
+        Err(ApiError::Forbidden(error)) => make_error_response(403_u16, &error),
+        Err(ApiError::NotFound(error)) => make_error_response(404_u16, &error),
+        Err(ApiError::UnsupportedMediaType(error)) => make_error_response(415_u16, &error),
+        Err(ApiError::Internal(err)) => {
+            // The details may contain paths and a backtrace, only log them.
+            log::error!("our_application_api: {request_uri}: {err:?}");
+            make_error_response(500_u16, "internal error")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,717 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the wsgi_api module.
+
+use std::collections::HashMap;
+use std::io::Read as _;
+use std::rc::Rc;
+
+use crate::areas;
+use crate::context;
+use crate::openapi;
+use crate::wsgi;
+
+/// Performs an API request and returns the status code and the parsed JSON body.
+fn get_api_json(test_wsgi: &mut wsgi::tests::TestWsgi, path: &str) -> (u16, serde_json::Value) {
+    send_api_json(test_wsgi, "GET", path, vec![], "")
+}
+
+/// Performs an API request with the given method, headers and body and returns the status code
+/// and the parsed JSON body.
+fn send_api_json(
+    test_wsgi: &mut wsgi::tests::TestWsgi,
+    method: &str,
+    path: &str,
+    headers: Vec<(String, String)>,
+    body: &str,
+) -> (u16, serde_json::Value) {
+    let prefix = test_wsgi.get_ctx().get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}{path}");
+    let request = rouille::Request::fake_http(method, abspath, headers, body.as_bytes().to_vec());
+    let response = wsgi::application(&request, test_wsgi.get_ctx());
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    let headers_map: HashMap<_, _> = response.headers.into_iter().collect();
+    assert_eq!(
+        headers_map["Content-type"],
+        "application/json; charset=utf-8"
+    );
+    let output = String::from_utf8(data).unwrap();
+    (response.status_code, serde_json::from_str(&output).unwrap())
+}
+
+/// Sets up a test relation with OSM and reference data.
+fn make_test_wsgi() -> wsgi::tests::TestWsgi {
+    let mut test_wsgi = wsgi::tests::TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "osmrelation": 42,
+                "refcounty": "0",
+                "refsettlement": "0",
+                "alias": ["budaors"],
+            },
+            "empty": {
+                "osmrelation": 43,
+                "refcounty": "0",
+                "refsettlement": "0",
+                "inactive": true,
+            },
+        },
+        "relation-gazdagret.yaml": {
+            "filters": {
+                "Törökugrató utca": {
+                    "invalid": [ "1", "11" ],
+                }
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        test_wsgi.get_ctx(),
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.get_ctx().set_file_system(&file_system);
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into ref_streets (county_code, settlement_code, street) values ('0', '0', 'Törökugrató utca');
+             insert into ref_streets (county_code, settlement_code, street) values ('0', '0', 'Only In Ref utca');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Törökugrató utca', '1', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Törökugrató utca', '2', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Törökugrató utca', '3', '');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '1', 'Törökugrató utca', '', '', '', '', 'way');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '2', 'Only In OSM utca', '', '', '', '', 'way');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '1', 'Törökugrató utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '2', 'Törökugrató utca', '2', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type) values ('gazdagret', '3', 'Only In OSM utca', '1', '1111', '', '', '', '', '', '', '', '', 'node');
+             insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');
+             insert into osm_street_coverages (relation_name, coverage, last_modified) values ('gazdagret', '50.00', '1000000000');",
+        )
+        .unwrap();
+    }
+    test_wsgi
+}
+
+/// Tests relations_json().
+#[test]
+fn test_relations() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations");
+
+    assert_eq!(status, 200);
+    let relations = root.as_array().unwrap();
+    assert_eq!(relations.len(), 2);
+    assert_eq!(relations[0]["name"], "empty");
+    assert_eq!(relations[0]["active"], false);
+    assert_eq!(relations[1]["name"], "gazdagret");
+    assert_eq!(relations[1]["osmrelation"], 42);
+    assert_eq!(relations[1]["refcounty"], "0");
+    assert_eq!(relations[1]["refsettlement"], "0");
+    assert_eq!(relations[1]["active"], true);
+}
+
+/// Tests relation_json(), including alias resolution.
+#[test]
+fn test_relation() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/budaors");
+
+    assert_eq!(status, 200);
+    assert_eq!(root["name"], "gazdagret");
+    assert_eq!(root["osmrelation"], 42);
+}
+
+/// Tests the missing-housenumbers endpoint.
+#[test]
+fn test_missing_housenumbers() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/missing-housenumbers",
+    );
+
+    assert_eq!(status, 200);
+    let missing_housenumbers: areas::MissingHousenumbers = serde_json::from_value(root).unwrap();
+    assert_eq!(missing_housenumbers.ongoing_streets.len(), 1);
+    let ongoing_street = &missing_housenumbers.ongoing_streets[0];
+    assert_eq!(ongoing_street.street.get_osm_name(), "Törökugrató utca");
+    // 3, 1 is filtered out.
+    assert_eq!(ongoing_street.house_numbers.len(), 1);
+}
+
+/// Tests the coverage-history endpoint.
+#[test]
+fn test_coverage_history() {
+    let mut test_wsgi = make_test_wsgi();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into stats_coverages (date, relation, streets, housenumbers) values ('2020-05-09', 'gazdagret', '40.00', '');
+             insert into stats_coverages (date, relation, streets, housenumbers) values ('2020-05-10', 'gazdagret', '50.00', '25.00');",
+        )
+        .unwrap();
+    }
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/coverage-history",
+    );
+
+    assert_eq!(status, 200);
+    let history = root.as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["date"], "2020-05-09");
+    assert_eq!(history[0]["streets"], "40.00");
+    assert!(history[0]["housenumbers"].is_null());
+    assert_eq!(history[1]["housenumbers"], "25.00");
+    openapi::tests::assert_schema_keys("CoverageHistoryEntry", &history[0]);
+}
+
+/// Tests the missing-housenumbers-changes endpoint.
+#[test]
+fn test_missing_housenumbers_changes() {
+    let mut test_wsgi = make_test_wsgi();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            r#"insert into missing_housenumbers_snapshots (relation, date, json) values ('gazdagret', '2020-05-01', '{"missing":{"Törökugrató utca":["2","3"]},"done":{}}');"#,
+        )
+        .unwrap();
+    }
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/budaors/missing-housenumbers-changes/2020-05-03",
+    );
+
+    assert_eq!(status, 200);
+    let changes: areas::MissingHousenumbersChanges = serde_json::from_value(root).unwrap();
+    assert_eq!(changes.since, "2020-05-01");
+    let street = "Törökugrató utca".to_string();
+    let expected: areas::StreetHousenumbers = [(street, ["2".to_string()].into())].into();
+    assert_eq!(changes.newly_mapped, expected);
+    assert!(changes.newly_missing.is_empty());
+}
+
+/// Tests the missing-housenumbers-changes endpoint, the invalid date case.
+#[test]
+fn test_missing_housenumbers_changes_bad_date() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/missing-housenumbers-changes/yesterday",
+    );
+
+    assert_eq!(status, 400);
+    assert_eq!(
+        root["error"],
+        "invalid date, expected YYYY-MM-DD: yesterday"
+    );
+}
+
+/// Tests the additional-housenumbers endpoint.
+#[test]
+fn test_additional_housenumbers() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/additional-housenumbers",
+    );
+
+    assert_eq!(status, 200);
+    let streets = root.as_array().unwrap();
+    assert_eq!(streets.len(), 2);
+    assert_eq!(streets[0]["street"]["osm-name"], "Only In OSM utca");
+    // 1 is filtered out from the reference.
+    assert_eq!(streets[1]["street"]["osm-name"], "Törökugrató utca");
+}
+
+/// Tests missing_streets_json().
+#[test]
+fn test_missing_streets() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/missing-streets",
+    );
+
+    assert_eq!(status, 200);
+    assert_eq!(
+        root["ongoing-streets"],
+        serde_json::json!(["Only In Ref utca"])
+    );
+    assert_eq!(
+        root["done-streets"],
+        serde_json::json!(["Törökugrató utca"])
+    );
+}
+
+/// Tests additional_streets_json().
+#[test]
+fn test_additional_streets() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/additional-streets",
+    );
+
+    assert_eq!(status, 200);
+    let streets = root.as_array().unwrap();
+    assert_eq!(streets.len(), 1);
+    assert_eq!(streets[0]["osm-name"], "Only In OSM utca");
+    assert_eq!(streets[0]["osm-id"], 2);
+}
+
+/// Tests lints_json().
+#[test]
+fn test_lints() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret/lints");
+
+    assert_eq!(status, 200);
+    let lints = root.as_array().unwrap();
+    assert_eq!(lints.len(), 2);
+    assert_eq!(lints[0]["street-name"], "Törökugrató utca");
+    assert_eq!(lints[0]["source"], "invalid");
+    assert_eq!(lints[0]["housenumber"], "1");
+    assert_eq!(lints[0]["reason"], "created-in-osm");
+    assert_eq!(lints[0]["object-id"], "1");
+    assert_eq!(lints[0]["object-type"], "node");
+    assert_eq!(lints[1]["housenumber"], "11");
+    assert_eq!(lints[1]["reason"], "deleted-from-ref");
+}
+
+/// Tests invalid_addr_cities_json().
+#[test]
+fn test_invalid_addr_cities() {
+    let mut test_wsgi = make_test_wsgi();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into stats_invalid_addr_cities (osm_id, osm_type, postcode, city, street, housenumber, user) values ('2', 'node', '1111', 'Budaros', 'Törökugrató utca', '2', 'myuser');",
+        )
+        .unwrap();
+    }
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/invalid-addr-cities",
+    );
+
+    assert_eq!(status, 200);
+    let invalids = root.as_array().unwrap();
+    assert_eq!(invalids.len(), 1);
+    assert_eq!(invalids[0]["street-name"], "Törökugrató utca");
+    assert_eq!(invalids[0]["housenumber"], "2");
+    assert_eq!(invalids[0]["object-id"], "2");
+    assert_eq!(invalids[0]["object-type"], "node");
+    assert_eq!(invalids[0]["city"], "Budaros");
+    // No refsettlement-names.yaml in the test data.
+    assert_eq!(invalids[0]["suggested-city"], "");
+
+    // The invalid addr:city is not a lint of the relation.
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret/lints");
+    assert_eq!(status, 200);
+    assert_eq!(root.as_array().unwrap().len(), 2);
+}
+
+/// Tests coverage_json().
+#[test]
+fn test_coverage() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret/coverage");
+
+    assert_eq!(status, 200);
+    assert_eq!(root["streets"]["coverage"], "50.00");
+    assert_eq!(root["streets"]["last-modified"], 1);
+    assert_eq!(root["housenumbers"], serde_json::Value::Null);
+
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumber_coverages (relation_name, coverage, last_modified) values ('gazdagret', '25.00', '2000000000');",
+        )
+        .unwrap();
+    }
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret/coverage");
+
+    assert_eq!(status, 200);
+    assert_eq!(root["housenumbers"]["coverage"], "25.00");
+    assert_eq!(root["housenumbers"]["last-modified"], 2);
+}
+
+/// Tests the 404 error for an unknown relation.
+#[test]
+fn test_no_such_relation() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/nosuchrelation/lints");
+
+    assert_eq!(status, 404);
+    assert_eq!(root["error"], "no such relation: nosuchrelation");
+}
+
+/// Tests the 404 error when OSM data is not yet available.
+#[test]
+fn test_no_osm_data() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/empty/missing-streets");
+    assert_eq!(status, 404);
+    assert_eq!(root["error"], "no OSM streets for relation: empty");
+
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into mtimes (page, last_modified) values ('streets/empty', '0');",
+        )
+        .unwrap();
+    }
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/empty/missing-housenumbers",
+    );
+    assert_eq!(status, 404);
+    assert_eq!(root["error"], "no OSM housenumbers for relation: empty");
+}
+
+/// Tests the 404 errors for unknown endpoints.
+#[test]
+fn test_not_found() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret/nosuchanalysis");
+    assert_eq!(status, 404);
+    assert_eq!(root["error"], "no such analysis: nosuchanalysis");
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/nosuchendpoint");
+    assert_eq!(status, 404);
+    assert_eq!(root["error"], "no such endpoint: nosuchendpoint");
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v2/relations");
+    assert_eq!(status, 404);
+    assert_eq!(
+        root["error"],
+        "unsupported API version: /osm/api/v2/relations"
+    );
+}
+
+/// Tests the 400 errors for malformed paths.
+#[test]
+fn test_bad_request() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations//lints");
+    assert_eq!(status, 400);
+    assert_eq!(root["error"], "empty path segment: relations//lints");
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret/lints/extra");
+    assert_eq!(status, 400);
+    assert_eq!(
+        root["error"],
+        "too many path segments: relations/gazdagret/lints/extra"
+    );
+}
+
+/// Tests the 500 error for an internal failure: it's JSON, not HTML.
+#[test]
+fn test_internal_error() {
+    let mut test_wsgi = make_test_wsgi();
+    let unit = context::tests::TestUnit::new();
+    let unit_rc: Rc<dyn context::Unit> = Rc::new(unit);
+    test_wsgi.get_ctx().set_unit(&unit_rc);
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations");
+
+    assert_eq!(status, 500);
+    // The details are only logged, not exposed to clients.
+    assert_eq!(root["error"], "internal error");
+}
+
+/// Tests the survey-observations endpoint.
+#[test]
+fn test_survey_observations() {
+    let mut test_wsgi = make_test_wsgi();
+    let path = "/api/v1/relations/gazdagret/survey-observations";
+    let body = r#"{"street": "Törökugrató utca", "housenumber": "3", "status": "confirmed-absent", "note": "garage"}"#;
+    // alice:secret, see tests/workdir/wsgi.ini.
+    let auth = vec![
+        (
+            "Authorization".to_string(),
+            "Basic YWxpY2U6c2VjcmV0".to_string(),
+        ),
+        (
+            "Origin".to_string(),
+            "https://osm-gimmisn.vmiklos.hu".to_string(),
+        ),
+        ("Content-Type".to_string(), "application/json".to_string()),
+    ];
+
+    // No credentials.
+    let (status, root) = send_api_json(&mut test_wsgi, "POST", path, vec![], body);
+    assert_eq!(status, 401);
+    assert_eq!(root["error"], "valid surveyor credentials are required");
+
+    let (status, root) = send_api_json(&mut test_wsgi, "POST", path, auth.clone(), body);
+    assert_eq!(status, 200);
+    let observations = root.as_array().unwrap();
+    assert_eq!(observations.len(), 1);
+    assert_eq!(observations[0]["surveyor"], "alice");
+    assert_eq!(observations[0]["date"], "2020-05-10");
+    openapi::tests::assert_schema_keys("SurveyObservation", &observations[0]);
+
+    // 3 is no longer missing.
+    let (_status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/missing-housenumbers",
+    );
+    let missing_housenumbers: areas::MissingHousenumbers = serde_json::from_value(root).unwrap();
+    assert!(missing_housenumbers.ongoing_streets.is_empty());
+
+    let (status, root) = get_api_json(&mut test_wsgi, path);
+    assert_eq!(status, 200);
+    assert_eq!(root[0]["note"], "garage");
+
+    let key = r#"{"street": "Törökugrató utca", "housenumber": "3"}"#;
+    let (status, root) = send_api_json(&mut test_wsgi, "DELETE", path, auth.clone(), key);
+    assert_eq!(status, 200);
+    assert!(root.as_array().unwrap().is_empty());
+    let (status, _root) = send_api_json(&mut test_wsgi, "DELETE", path, auth.clone(), key);
+    assert_eq!(status, 404);
+
+    // Bad status.
+    let body = r#"{"street": "Törökugrató utca", "housenumber": "3", "status": "unknown"}"#;
+    let (status, _root) = send_api_json(&mut test_wsgi, "POST", path, auth, body);
+    assert_eq!(status, 400);
+}
+
+/// Tests the survey-observations endpoint, the foreign origin case.
+#[test]
+fn test_survey_observations_foreign_origin() {
+    let mut test_wsgi = make_test_wsgi();
+    let path = "/api/v1/relations/gazdagret/survey-observations";
+    let body =
+        r#"{"street": "Törökugrató utca", "housenumber": "3", "status": "confirmed-absent"}"#;
+    let headers = vec![
+        (
+            "Authorization".to_string(),
+            "Basic YWxpY2U6c2VjcmV0".to_string(),
+        ),
+        ("Origin".to_string(), "https://example.com".to_string()),
+        ("Content-Type".to_string(), "application/json".to_string()),
+    ];
+
+    let (status, root) = send_api_json(&mut test_wsgi, "POST", path, headers, body);
+
+    assert_eq!(status, 403);
+    assert_eq!(root["error"], "the request was not sent from this site");
+    // Nothing is recorded.
+    let (_status, root) = get_api_json(&mut test_wsgi, path);
+    assert!(root.as_array().unwrap().is_empty());
+}
+
+/// Tests the survey-observations endpoint, the non-JSON content type case.
+#[test]
+fn test_survey_observations_bad_content_type() {
+    let mut test_wsgi = make_test_wsgi();
+    let path = "/api/v1/relations/gazdagret/survey-observations";
+    let body =
+        r#"{"street": "Törökugrató utca", "housenumber": "3", "status": "confirmed-absent"}"#;
+    let headers = vec![
+        (
+            "Authorization".to_string(),
+            "Basic YWxpY2U6c2VjcmV0".to_string(),
+        ),
+        (
+            "Origin".to_string(),
+            "https://osm-gimmisn.vmiklos.hu".to_string(),
+        ),
+        ("Content-Type".to_string(), "text/plain".to_string()),
+    ];
+
+    let (status, root) = send_api_json(&mut test_wsgi, "POST", path, headers, body);
+
+    assert_eq!(status, 415);
+    assert_eq!(
+        root["error"],
+        "unsupported content type: 'text/plain', expected application/json"
+    );
+    let (_status, root) = get_api_json(&mut test_wsgi, path);
+    assert!(root.as_array().unwrap().is_empty());
+}
+
+/// Tests the openapi.json endpoint.
+#[test]
+fn test_openapi() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/openapi.json");
+
+    assert_eq!(status, 200);
+    assert_eq!(root["openapi"], "3.0.3");
+    assert!(root["paths"]
+        .as_object()
+        .unwrap()
+        .contains_key("/api/v1/relations/{relation}/coverage"));
+}
+
+/// Tests that the API responses are in sync with their OpenAPI schema.
+#[test]
+fn test_openapi_schemas() {
+    let mut test_wsgi = make_test_wsgi();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumber_coverages (relation_name, coverage, last_modified) values ('gazdagret', '25.00', '2000000000');",
+        )
+        .unwrap();
+    }
+
+    let (_status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret");
+    openapi::tests::assert_schema_keys("Relation", &root);
+    let (_status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret/lints");
+    openapi::tests::assert_schema_keys("Lint", &root[0]);
+    let (_status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/missing-streets",
+    );
+    openapi::tests::assert_schema_keys("MissingStreets", &root);
+    let (_status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/missing-housenumbers-changes/2020-05-10",
+    );
+    openapi::tests::assert_schema_keys("MissingHousenumbersChanges", &root);
+    let (_status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/gazdagret/coverage");
+    openapi::tests::assert_schema_keys("Coverages", &root);
+    openapi::tests::assert_schema_keys("Coverage", &root["streets"]);
+    let (_status, root) = get_api_json(&mut test_wsgi, "/api/v1/nosuchendpoint");
+    openapi::tests::assert_schema_keys("Error", &root);
+    let (_status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/additional-streets",
+    );
+    openapi::tests::assert_schema_keys("Street", &root[0]);
+    let (_status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/gazdagret/missing-housenumbers",
+    );
+    openapi::tests::assert_schema_keys("MissingHousenumbers", &root);
+    openapi::tests::assert_schema_keys("NumberedStreet", &root["ongoing-streets"][0]);
+    openapi::tests::assert_schema_keys(
+        "HouseNumber",
+        &root["ongoing-streets"][0]["house-numbers"][0],
+    );
+}
+
+/// Tests that the types of the API are in sync with their OpenAPI schema, including the optional
+/// values.
+#[test]
+fn test_openapi_serde_types() {
+    let relation = super::ApiRelation {
+        name: "gazdagret".into(),
+        osmrelation: 42,
+        refcounty: "01".into(),
+        refsettlement: "011".into(),
+        active: true,
+    };
+    openapi::tests::assert_schema_keys("Relation", &serde_json::to_value(&relation).unwrap());
+    let lint = super::ApiLint {
+        street_name: "Tűzkő utca".into(),
+        source: "invalid".into(),
+        housenumber: "1".into(),
+        reason: "created-in-osm".into(),
+        object_id: "1".into(),
+        object_type: "node".into(),
+    };
+    openapi::tests::assert_schema_keys("Lint", &serde_json::to_value(&lint).unwrap());
+    let invalid_addr_city = super::ApiInvalidAddrCity {
+        street_name: "Tűzkő utca".into(),
+        housenumber: "1".into(),
+        object_id: "1".into(),
+        object_type: "node".into(),
+        city: "Budaros".into(),
+        suggested_city: "Budapest".into(),
+    };
+    openapi::tests::assert_schema_keys(
+        "InvalidAddrCity",
+        &serde_json::to_value(&invalid_addr_city).unwrap(),
+    );
+    let missing_streets = super::ApiMissingStreets {
+        ongoing_streets: Vec::new(),
+        done_streets: Vec::new(),
+    };
+    openapi::tests::assert_schema_keys(
+        "MissingStreets",
+        &serde_json::to_value(&missing_streets).unwrap(),
+    );
+    let coverages = super::ApiCoverages {
+        streets: None,
+        housenumbers: Some(super::ApiCoverage {
+            coverage: "25.00".into(),
+            last_modified: 0,
+        }),
+    };
+    let value = serde_json::to_value(&coverages).unwrap();
+    openapi::tests::assert_schema_keys("Coverages", &value);
+    openapi::tests::assert_schema_keys("Coverage", &value["housenumbers"]);
+    // Deserialize-only: all properties of the schema are accepted.
+    let key: super::ApiSurveyObservationKey =
+        serde_json::from_value(serde_json::json!({"street": "Tűzkő utca", "housenumber": "1"}))
+            .unwrap();
+    let value = serde_json::json!({"street": key.street, "housenumber": key.housenumber});
+    openapi::tests::assert_schema_keys("SurveyObservationKey", &value);
+}
+
+/// Tests relations_json(), with filters.
+#[test]
+fn test_relations_filter_for() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/filter-for/refcounty/0/refsettlement/0",
+    );
+    assert_eq!(status, 200);
+    assert_eq!(root.as_array().unwrap().len(), 2);
+
+    let (status, root) = get_api_json(&mut test_wsgi, "/api/v1/relations/filter-for/refcounty/1");
+    assert_eq!(status, 200);
+    assert_eq!(root.as_array().unwrap().len(), 0);
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/filter-for/refcounty/0/refsettlement/1",
+    );
+    assert_eq!(status, 200);
+    assert_eq!(root.as_array().unwrap().len(), 0);
+
+    let (status, root) = get_api_json(
+        &mut test_wsgi,
+        "/api/v1/relations/filter-for/relations/1,43",
+    );
+    assert_eq!(status, 200);
+    let relations = root.as_array().unwrap();
+    assert_eq!(relations.len(), 1);
+    assert_eq!(relations[0]["name"], "empty");
+}
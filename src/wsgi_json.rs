@@ -13,7 +13,6 @@
 use crate::areas;
 use crate::cache;
 use crate::context;
-use crate::overpass_query;
 use crate::webframe;
 use anyhow::Context;
 use std::collections::HashMap;
@@ -31,8 +30,8 @@ fn streets_update_result_json(
         .get_relation(relation_name)
         .context("get_relation() failed")?;
     let mut ret: HashMap<String, String> = HashMap::new();
-    let query = relation.get_osm_streets_json_query()?;
-    match overpass_query::overpass_query(ctx, &query) {
+    let query = relation.get_osm_streets_source_query()?;
+    match ctx.get_osm_source().query(ctx, &query) {
         Ok(buf) => {
             relation.get_files().write_osm_json_streets(ctx, &buf)?;
             ret.insert("error".into(), "".into())
@@ -53,8 +52,8 @@ fn street_housenumbers_update_result_json(
     let relation_name = tokens.next_back().context("short tokens")?;
     let relation = relations.get_relation(relation_name)?;
     let mut ret: HashMap<String, String> = HashMap::new();
-    let query = relation.get_osm_housenumbers_json_query()?;
-    match overpass_query::overpass_query(ctx, &query) {
+    let query = relation.get_osm_housenumbers_source_query()?;
+    match ctx.get_osm_source().query(ctx, &query) {
         Ok(buf) => {
             relation
                 .get_files()
@@ -0,0 +1,430 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! The wsgi_map module contains functionality for the map of a relation and for the coverage map
+//! of all relations.
+
+use crate::areas;
+use crate::cache;
+use crate::context;
+use crate::geometry;
+use crate::i18n::translate as tr;
+use crate::stats;
+use crate::util;
+use crate::webframe;
+use crate::wsgi;
+use crate::yattag;
+use anyhow::Context;
+use std::collections::HashSet;
+
+/// Gets the OSM type and id of the objects which have a lint in a relation.
+fn get_lint_objects(
+    ctx: &context::Context,
+    relation_name: &str,
+) -> anyhow::Result<HashSet<(String, String)>> {
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(
+        "select object_type, object_id from relation_lints where relation_name = ?1 and object_id != ''",
+    )?;
+    let mut rows = stmt.query([relation_name])?;
+    let mut ret = HashSet::new();
+    while let Some(row) = rows.next()? {
+        ret.insert((row.get(0)?, row.get(1)?));
+    }
+    Ok(ret)
+}
+
+/// Expected request_uri: e.g. /osm/map/ormezo/view-result.geojson.
+///
+/// The boundary, the streets and the house numbers of a relation, based on the geometry and the
+/// locations stored by cron. Streets have an 'incomplete' status if they have missing house numbers. House numbers
+/// have a 'lint', 'additional' or 'matched' status.
+pub fn map_view_geojson(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<(String, String)> {
+    let mut tokens = request_uri.split('/');
+    tokens.next_back();
+    let relation_name = tokens.next_back().context("no relation_name")?;
+    let mut relation = relations.get_relation(relation_name)?;
+
+    let mut incomplete_streets: HashSet<String> = HashSet::new();
+    let mut additional_objects: HashSet<(String, String)> = HashSet::new();
+    if stats::has_sql_mtime(ctx, &format!("streets/{}", relation_name))?
+        && stats::has_sql_mtime(ctx, &format!("housenumbers/{}", relation_name))?
+    {
+        // This also updates the lints if they are outdated.
+        let json = cache::get_missing_housenumbers_json(&mut relation)
+            .context("get_missing_housenumbers_json() failed")?;
+        let missing_housenumbers: areas::MissingHousenumbers = serde_json::from_str(&json)?;
+        incomplete_streets = missing_housenumbers
+            .ongoing_streets
+            .iter()
+            .map(|i| i.street.get_osm_name().to_string())
+            .collect();
+        let json = cache::get_additional_housenumbers_json(&mut relation)
+            .context("get_additional_housenumbers_json() failed")?;
+        let additional_housenumbers: util::NumberedStreets = serde_json::from_str(&json)?;
+        for street in additional_housenumbers {
+            for house_number in street.house_numbers {
+                if let (Some(object_type), Some(id)) =
+                    (house_number.get_object_type(), house_number.get_id())
+                {
+                    additional_objects.insert((object_type, id.to_string()));
+                }
+            }
+        }
+    }
+    let lint_objects = get_lint_objects(ctx, relation_name)?;
+
+    let mut features: Vec<serde_json::Value> = Vec::new();
+    let conn = ctx.get_database_connection()?;
+    let mut stmt = conn.prepare(
+        "select * from (
+            select kind, osm_geometries.osm_type as osm_type, osm_geometries.osm_id as osm_id, geometry, coalesce(osm_streets.name, ''), '', '', '', '', ''
+            from osm_geometries
+            left join osm_streets on kind = 'street' and osm_streets.relation = osm_geometries.relation and osm_streets.osm_type = osm_geometries.osm_type and osm_streets.osm_id = osm_geometries.osm_id
+            where osm_geometries.relation = ?1
+            union all
+            select 'housenumber', osm_type, osm_id, '', '', street, place, housenumber, lat, lon
+            from osm_housenumbers
+            where relation = ?1
+        )
+        order by kind, cast(osm_id as integer)",
+    )?;
+    let mut rows = stmt.query([relation_name])?;
+    while let Some(row) = rows.next()? {
+        let kind: String = row.get(0)?;
+        let osm_type: String = row.get(1)?;
+        let osm_id: String = row.get(2)?;
+        let geometry: serde_json::Value = if kind == "housenumber" {
+            let lat: String = row.get(8)?;
+            let lon: String = row.get(9)?;
+            match geometry::parse_point(&lat, &lon) {
+                Some(point) => serde_json::json!({
+                    "type": "Point",
+                    "coordinates": [point.lon, point.lat],
+                }),
+                None => continue,
+            }
+        } else {
+            let geometry: String = row.get(3)?;
+            serde_json::from_str(&geometry)?
+        };
+        let mut properties = serde_json::json!({
+            "kind": kind,
+            "osm_type": osm_type,
+            "osm_id": osm_id,
+        });
+        if kind == "street" {
+            let name: String = row.get(4)?;
+            let status = if incomplete_streets.contains(&name) {
+                "incomplete"
+            } else {
+                "complete"
+            };
+            properties["name"] = name.into();
+            properties["status"] = status.into();
+        } else if kind == "housenumber" {
+            let mut street: String = row.get(5)?;
+            if street.is_empty() {
+                street = row.get(6)?;
+            }
+            let housenumber: String = row.get(7)?;
+            let object = (osm_type.to_string(), osm_id.to_string());
+            let status = if lint_objects.contains(&object) {
+                "lint"
+            } else if additional_objects.contains(&object) {
+                "additional"
+            } else {
+                "matched"
+            };
+            properties["name"] = format!("{street} {housenumber}").into();
+            properties["status"] = status.into();
+        }
+        features.push(serde_json::json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": properties,
+        }));
+    }
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    Ok((serde_json::to_string(&collection)?, relation_name.into()))
+}
+
+/// Expected request_uri: e.g. /osm/map/ormezo/view-result.
+pub fn handle_map(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<yattag::Doc> {
+    let mut tokens = request_uri.split('/');
+    tokens.next_back();
+    let relation_name = tokens.next_back().context("no relation_name")?;
+    let relation = relations.get_relation(relation_name)?;
+    let osmrelation = relation.get_config().get_osmrelation();
+
+    let doc = yattag::Doc::new();
+    doc.append_value(
+        webframe::get_toolbar(ctx, Some(relations), "map", relation_name, osmrelation)?.get_value(),
+    );
+
+    let prefix = ctx.get_ini().get_uri_prefix();
+    if !stats::has_sql_mtime(ctx, &format!("streets/{}", relation_name))? {
+        let div = doc.tag("div", &[("id", "no-map")]);
+        div.text(&tr(
+            "No map yet: the geometry of this area is updated during the next nightly run.",
+        ));
+    } else {
+        let string_pairs = &[
+            ("str-map-matched", tr("Matched house number")),
+            ("str-map-additional", tr("Additional house number")),
+            ("str-map-lint", tr("House number with a lint")),
+            (
+                "str-map-incomplete",
+                tr("Street with missing house numbers"),
+            ),
+            (
+                "str-map-complete",
+                tr("Street without missing house numbers"),
+            ),
+        ];
+        webframe::emit_l10n_strings_for_js(&doc, string_pairs);
+        {
+            let div = doc.tag(
+                "div",
+                &[
+                    ("id", "map"),
+                    ("class", "mapblock js"),
+                    (
+                        "data-url",
+                        &format!("{prefix}/map/{relation_name}/view-result.geojson"),
+                    ),
+                ],
+            );
+            drop(div);
+        }
+        {
+            let p = doc.tag("p", &[]);
+            let a = p.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{prefix}/map/{relation_name}/view-result.geojson"),
+                )],
+            );
+            a.text(&tr("GeoJSON format"));
+        }
+    }
+
+    // Missing streets have no geometry, list them next to the map.
+    if relation.get_config().should_check_missing_streets() != "no"
+        && stats::has_sql_mtime(ctx, &format!("streets/{}", relation_name))?
+    {
+        let (mut streets, _) = relation.get_missing_streets()?;
+        if !streets.is_empty() {
+            streets.sort_by_key(|i| util::get_sort_key(i));
+            {
+                let h2 = doc.tag("h2", &[]);
+                h2.text(&tr("Missing streets"));
+            }
+            let ul = doc.tag("ul", &[("id", "map-missing-streets")]);
+            for street in streets {
+                let li = ul.tag("li", &[]);
+                li.text(&street);
+            }
+        }
+    }
+
+    let date = webframe::format_timestamp(&stats::get_sql_mtime(
+        ctx,
+        &format!("streets/{}", relation_name),
+    )?)?;
+    doc.append_value(webframe::get_footer(&date).get_value());
+    Ok(doc)
+}
+
+/// Gets the house number and street coverage of a relation, None if it's not known or not checked.
+fn get_coverage(relation: &areas::Relation<'_>) -> anyhow::Result<(Option<f64>, Option<f64>)> {
+    let streets = relation.get_config().should_check_missing_streets();
+    let mut housenumber_coverage: Option<f64> = None;
+    if streets != "only" && relation.has_osm_housenumber_coverage()? {
+        let string = relation.get_osm_housenumber_coverage()?;
+        housenumber_coverage = Some(string.parse::<f64>().context("parse to f64 failed")?);
+    }
+    let mut street_coverage: Option<f64> = None;
+    if streets != "no" && relation.has_osm_street_coverage()? {
+        let string = relation.get_osm_street_coverage()?;
+        street_coverage = Some(string.parse::<f64>().context("parse to f64 failed")?);
+    }
+    Ok((housenumber_coverage, street_coverage))
+}
+
+/// Expected request_uri: e.g. /osm/coverage-map/filter-for/refcounty/01/whole-county/view-result.geojson.
+///
+/// The boundary of the relations, as cached by cron, with their house number and street coverage.
+/// The filters are the same as the ones of the main page, but complete relations are shown by
+/// default.
+pub fn coverage_map_view_geojson(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<(String, String)> {
+    let (filter_for, _) =
+        wsgi::setup_filter_for(request_uri, Box::new(wsgi::filter_for_everything))?;
+    let path = geometry::get_boundaries_path(ctx);
+    let mut features: Vec<serde_json::Value> = Vec::new();
+    if ctx.get_file_system().path_exists(&path) {
+        let boundaries: serde_json::Value =
+            serde_json::from_str(&ctx.get_file_system().read_to_string(&path)?)?;
+        let names = relations.get_names();
+        let prefix = ctx.get_ini().get_uri_prefix();
+        for feature in boundaries["features"].as_array().context("no features")? {
+            let relation_name = feature["properties"]["name"].as_str().context("no name")?;
+            if !names.contains(&relation_name.to_string()) {
+                continue;
+            }
+            let relation = relations.get_relation(relation_name)?;
+            let (housenumber_coverage, street_coverage) = get_coverage(&relation)?;
+            // Same as on the main page: unknown coverage is not complete.
+            let mut complete = true;
+            if relation.get_config().should_check_missing_streets() != "only" {
+                complete &= housenumber_coverage.unwrap_or(0_f64) >= 100_f64;
+            }
+            if relation.get_config().should_check_missing_streets() != "no" {
+                complete &= street_coverage.unwrap_or(0_f64) >= 100_f64;
+            }
+            if !filter_for(complete, &relation) {
+                continue;
+            }
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": feature["geometry"],
+                "properties": {
+                    "name": relation_name,
+                    "housenumber_coverage": housenumber_coverage,
+                    "street_coverage": street_coverage,
+                    "missing_housenumbers_url": format!("{prefix}/missing-housenumbers/{relation_name}/view-result"),
+                    "missing_streets_url": format!("{prefix}/missing-streets/{relation_name}/view-result"),
+                },
+            }));
+        }
+    }
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    Ok((serde_json::to_string(&collection)?, "coverage-map".into()))
+}
+
+/// Expected request_uri: e.g. /osm/coverage-map or
+/// /osm/coverage-map/filter-for/refcounty/01/whole-county.
+pub fn handle_coverage_map(
+    ctx: &context::Context,
+    relations: &mut areas::Relations<'_>,
+    request_uri: &str,
+) -> anyhow::Result<yattag::Doc> {
+    let doc = yattag::Doc::new();
+    doc.append_value(
+        webframe::get_toolbar(
+            ctx,
+            Some(relations),
+            /*function=*/ "",
+            /*relation_name=*/ "",
+            /*relation_osmid=*/ 0,
+        )?
+        .get_value(),
+    );
+
+    let prefix = ctx.get_ini().get_uri_prefix();
+    let base = format!("{prefix}/coverage-map");
+    // The filter part of the uri, e.g. /filter-for/refcounty/01/whole-county.
+    let filter = request_uri
+        .strip_prefix(&base)
+        .unwrap_or("")
+        .trim_end_matches('/');
+    {
+        let h1 = doc.tag("h1", &[]);
+        h1.text(&tr("Coverage map"));
+    }
+    {
+        let p = doc.tag("p", &[]);
+        p.text(&format!("{} ", tr("Filters:")));
+        let a = p.tag("a", &[("href", &base)]);
+        a.text(&tr("All areas"));
+        drop(a);
+        let mut refcounties: Vec<_> = relations
+            .get_relations()?
+            .iter()
+            .map(|i| i.get_config().get_refcounty())
+            .collect();
+        refcounties.sort();
+        refcounties.dedup();
+        for refcounty in refcounties {
+            let name = relations.refcounty_get_name(&refcounty);
+            if name.is_empty() {
+                continue;
+            }
+            p.text(" ¦ ");
+            let a = p.tag(
+                "a",
+                &[(
+                    "href",
+                    &format!("{base}/filter-for/refcounty/{refcounty}/whole-county"),
+                )],
+            );
+            a.text(&name);
+        }
+    }
+
+    if !ctx
+        .get_file_system()
+        .path_exists(&geometry::get_boundaries_path(ctx))
+    {
+        let div = doc.tag("div", &[("id", "no-coverage-map")]);
+        div.text(&tr(
+            "No map yet: the boundaries are updated during the next nightly run.",
+        ));
+    } else {
+        let string_pairs = &[
+            ("str-coverage-housenumbers", tr("House number coverage")),
+            ("str-coverage-streets", tr("Street coverage")),
+            ("str-coverage-unknown", tr("Unknown")),
+        ];
+        webframe::emit_l10n_strings_for_js(&doc, string_pairs);
+        let url = format!("{base}{filter}/view-result.geojson");
+        {
+            let div = doc.tag(
+                "div",
+                &[
+                    ("id", "coverage-map"),
+                    ("class", "mapblock js"),
+                    ("data-url", &url),
+                ],
+            );
+            drop(div);
+        }
+        {
+            let p = doc.tag("p", &[]);
+            let a = p.tag("a", &[("href", &url)]);
+            a.text(&tr("GeoJSON format"));
+        }
+    }
+
+    doc.append_value(webframe::get_footer(/*last_updated=*/ "").get_value());
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests;
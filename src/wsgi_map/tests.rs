@@ -0,0 +1,363 @@
+/*
+ * Copyright 2024 Miklos Vajna
+ *
+ * SPDX-License-Identifier: MIT
+ */
+
+#![deny(warnings)]
+#![warn(clippy::all)]
+#![warn(missing_docs)]
+
+//! Tests for the wsgi_map module.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::context;
+use crate::wsgi;
+
+/// Sets up a relation with streets, house numbers and their geometry.
+fn make_test_wsgi() -> wsgi::tests::TestWsgi {
+    let mut test_wsgi = wsgi::tests::TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "0",
+                "refsettlement": "0",
+                "osmrelation": 42,
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    let files = context::tests::TestFileSystem::make_files(
+        test_wsgi.get_ctx(),
+        &[("data/yamls.cache", &yamls_cache_value)],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.get_ctx().set_file_system(&file_system);
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into ref_streets (county_code, settlement_code, street) values ('0', '0', 'Tűzkő utca');
+             insert into ref_streets (county_code, settlement_code, street) values ('0', '0', 'Only In Ref utca');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '1', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '2', '');
+             insert into ref_housenumbers (county_code, settlement_code, street, housenumber, comment) values ('0', '0', 'Tűzkő utca', '5', '');
+             insert into osm_streets (relation, osm_id, name, highway, service, surface, leisure, osm_type) values ('gazdagret', '10', 'Tűzkő utca', '', '', '', '', 'way');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '11', 'Tűzkő utca', '1', '1111', '', '', '', '', '', '', '', '', 'node', '47', '19.0');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '12', 'Tűzkő utca', '3', '1111', '', '', '', '', '', '', '', '', 'node', '47', '19.004');
+             insert into osm_housenumbers (relation, osm_id, street, housenumber, postcode, place, housename, conscriptionnumber, flats, floor, door, unit, name, osm_type, lat, lon) values ('gazdagret', '13', 'Tűzkő utca', '2', '', '', '', '', '', '', '', '', '', 'node', '47', '19.002');
+             insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('gazdagret', 'boundary', 'relation', '42', '{\"type\":\"MultiLineString\",\"coordinates\":[[[19.0,47.0],[19.01,47.0]]]}');
+             insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('gazdagret', 'street', 'way', '10', '{\"type\":\"MultiLineString\",\"coordinates\":[[[19.0,47.0],[19.01,47.0]]]}');
+             insert into mtimes (page, last_modified) values ('streets/gazdagret', '0');
+             insert into mtimes (page, last_modified) values ('housenumbers/gazdagret', '0');",
+        )
+        .unwrap();
+    }
+    test_wsgi
+}
+
+/// Tests map_view_geojson().
+#[test]
+fn test_map_view_geojson() {
+    let mut test_wsgi = make_test_wsgi();
+    let prefix = test_wsgi.get_ctx().get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}/map/gazdagret/view-result.geojson");
+    let request = rouille::Request::fake_http("GET", abspath, vec![], vec![]);
+
+    let response = wsgi::application(&request, test_wsgi.get_ctx());
+
+    assert_eq!(response.status_code, 200);
+    let headers_map: HashMap<_, _> = response.headers.into_iter().collect();
+    assert_eq!(
+        headers_map["Content-type"],
+        "application/geo+json; charset=utf-8"
+    );
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    let root: serde_json::Value = serde_json::from_slice(&data).unwrap();
+    assert_eq!(root["type"], "FeatureCollection");
+    let features = root["features"].as_array().unwrap();
+    assert_eq!(features.len(), 5);
+    assert_eq!(features[0]["properties"]["kind"], "boundary");
+    assert_eq!(features[0]["geometry"]["type"], "MultiLineString");
+    let housenumbers: Vec<(String, String)> = features
+        .iter()
+        .filter(|i| i["properties"]["kind"] == "housenumber")
+        .map(|i| {
+            (
+                i["properties"]["name"].as_str().unwrap().to_string(),
+                i["properties"]["status"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        housenumbers,
+        vec![
+            ("Tűzkő utca 1".to_string(), "matched".to_string()),
+            ("Tűzkő utca 3".to_string(), "additional".to_string()),
+            ("Tűzkő utca 2".to_string(), "lint".to_string()),
+        ]
+    );
+    let street = &features[4];
+    assert_eq!(street["properties"]["kind"], "street");
+    assert_eq!(street["properties"]["name"], "Tűzkő utca");
+    // 5 is missing.
+    assert_eq!(street["properties"]["status"], "incomplete");
+}
+
+/// Tests map_view_geojson(): a way and a relation with the same id.
+#[test]
+fn test_map_view_geojson_same_id() {
+    let mut test_wsgi = make_test_wsgi();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_geometries (relation, kind, osm_type, osm_id, geometry) values ('gazdagret', 'street', 'relation', '10', '{\"type\":\"MultiLineString\",\"coordinates\":[[[19.0,47.0],[19.01,47.0]]]}');",
+        )
+        .unwrap();
+    }
+    let prefix = test_wsgi.get_ctx().get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}/map/gazdagret/view-result.geojson");
+    let request = rouille::Request::fake_http("GET", abspath, vec![], vec![]);
+
+    let response = wsgi::application(&request, test_wsgi.get_ctx());
+
+    assert_eq!(response.status_code, 200);
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    let root: serde_json::Value = serde_json::from_slice(&data).unwrap();
+    let mut streets: Vec<(String, String)> = root["features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|i| i["properties"]["osm_id"] == "10")
+        .map(|i| {
+            (
+                i["properties"]["osm_type"].as_str().unwrap().to_string(),
+                i["properties"]["name"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    streets.sort();
+    // Each object is listed once, the name of the way is not used for the relation.
+    assert_eq!(
+        streets,
+        vec![
+            ("relation".to_string(), "".to_string()),
+            ("way".to_string(), "Tűzkő utca".to_string()),
+        ]
+    );
+}
+
+/// Tests handle_map().
+#[test]
+fn test_handle_map() {
+    let mut test_wsgi = make_test_wsgi();
+
+    let root = test_wsgi.get_dom_for_path("/map/gazdagret/view-result");
+
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/div[@id='map']/@data-url");
+    assert_eq!(results, vec!["/osm/map/gazdagret/view-result.geojson"]);
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/ul[@id='map-missing-streets']/li");
+    assert_eq!(results, vec!["Only In Ref utca"]);
+}
+
+/// Tests handle_map(): the case when cron didn't store the streets yet.
+#[test]
+fn test_handle_map_no_geometry() {
+    let mut test_wsgi = make_test_wsgi();
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch("delete from mtimes where page = 'streets/gazdagret'")
+            .unwrap();
+    }
+
+    let root = test_wsgi.get_dom_for_path("/map/gazdagret/view-result");
+
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/div[@id='no-map']");
+    assert_eq!(results.len(), 1);
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/div[@id='map']");
+    assert!(results.is_empty());
+}
+
+/// Sets up 3 relations in 2 counties, with their boundary cached by cron.
+fn make_test_wsgi_coverage() -> wsgi::tests::TestWsgi {
+    let mut test_wsgi = wsgi::tests::TestWsgi::new();
+    let yamls_cache = serde_json::json!({
+        "refcounty-names.yaml": {
+            "01": "Budapest",
+            "13": "Pest megye",
+        },
+        "relations.yaml": {
+            "gazdagret": {
+                "refcounty": "01",
+                "refsettlement": "011",
+                "osmrelation": 42,
+            },
+            "ujbuda": {
+                "refcounty": "01",
+                "refsettlement": "011",
+                "osmrelation": 43,
+            },
+            "budaors": {
+                "refcounty": "13",
+                "refsettlement": "013",
+                "osmrelation": 44,
+                "missing-streets": "no",
+            },
+        },
+    });
+    let yamls_cache_value = context::tests::TestFileSystem::write_json_to_file(&yamls_cache);
+    // The 'deleted' relation is no longer in relations.yaml.
+    let features: Vec<serde_json::Value> = ["budaors", "deleted", "gazdagret", "ujbuda"]
+        .iter()
+        .map(|name| {
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": [[[[19.0, 47.0], [19.1, 47.0], [19.1, 47.1], [19.0, 47.0]]]],
+                },
+                "properties": {"name": name},
+            })
+        })
+        .collect();
+    let boundaries = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    let boundaries_value = context::tests::TestFileSystem::write_json_to_file(&boundaries);
+    let files = context::tests::TestFileSystem::make_files(
+        test_wsgi.get_ctx(),
+        &[
+            ("data/yamls.cache", &yamls_cache_value),
+            ("workdir/boundaries.geojson", &boundaries_value),
+        ],
+    );
+    let file_system = context::tests::TestFileSystem::from_files(&files);
+    test_wsgi.get_ctx().set_file_system(&file_system);
+    {
+        let conn = test_wsgi.get_ctx().get_database_connection().unwrap();
+        conn.execute_batch(
+            "insert into osm_housenumber_coverages (relation_name, coverage, last_modified) values ('gazdagret', '50.00', '0');
+             insert into osm_street_coverages (relation_name, coverage, last_modified) values ('gazdagret', '100.00', '0');
+             insert into osm_housenumber_coverages (relation_name, coverage, last_modified) values ('ujbuda', '100.00', '0');
+             insert into osm_street_coverages (relation_name, coverage, last_modified) values ('ujbuda', '100.00', '0');
+             insert into osm_housenumber_coverages (relation_name, coverage, last_modified) values ('budaors', '100.00', '0');
+             insert into osm_street_coverages (relation_name, coverage, last_modified) values ('budaors', '10.00', '0');",
+        )
+        .unwrap();
+    }
+    test_wsgi
+}
+
+/// Gets the features of a coverage map GeoJSON as name -> properties.
+fn get_coverage_features(
+    test_wsgi: &mut wsgi::tests::TestWsgi,
+    path: &str,
+) -> Vec<(String, serde_json::Value)> {
+    let prefix = test_wsgi.get_ctx().get_ini().get_uri_prefix();
+    let abspath = format!("{prefix}{path}");
+    let request = rouille::Request::fake_http("GET", abspath, vec![], vec![]);
+    let response = wsgi::application(&request, test_wsgi.get_ctx());
+    assert_eq!(response.status_code, 200);
+    let mut data = Vec::new();
+    let (mut reader, _size) = response.data.into_reader_and_size();
+    reader.read_to_end(&mut data).unwrap();
+    let root: serde_json::Value = serde_json::from_slice(&data).unwrap();
+    root["features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| {
+            (
+                i["properties"]["name"].as_str().unwrap().to_string(),
+                i["properties"].clone(),
+            )
+        })
+        .collect()
+}
+
+/// Tests coverage_map_view_geojson().
+#[test]
+fn test_coverage_map_view_geojson() {
+    let mut test_wsgi = make_test_wsgi_coverage();
+
+    let features = get_coverage_features(&mut test_wsgi, "/coverage-map/view-result.geojson");
+
+    // Complete relations are shown by default, relations not in relations.yaml are not.
+    let names: Vec<&str> = features.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["budaors", "gazdagret", "ujbuda"]);
+    let (_, budaors) = &features[0];
+    assert_eq!(budaors["housenumber_coverage"], 100.0);
+    // Streets are not checked for budaors.
+    assert_eq!(budaors["street_coverage"], serde_json::Value::Null);
+    let (_, gazdagret) = &features[1];
+    assert_eq!(gazdagret["housenumber_coverage"], 50.0);
+    assert_eq!(gazdagret["street_coverage"], 100.0);
+    assert_eq!(
+        gazdagret["missing_housenumbers_url"],
+        "/osm/missing-housenumbers/gazdagret/view-result"
+    );
+}
+
+/// Tests coverage_map_view_geojson(): the filters of the main page.
+#[test]
+fn test_coverage_map_view_geojson_filters() {
+    let mut test_wsgi = make_test_wsgi_coverage();
+
+    let features = get_coverage_features(
+        &mut test_wsgi,
+        "/coverage-map/filter-for/refcounty/01/whole-county/view-result.geojson",
+    );
+    let names: Vec<&str> = features.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["gazdagret", "ujbuda"]);
+
+    let features = get_coverage_features(
+        &mut test_wsgi,
+        "/coverage-map/filter-for/incomplete/view-result.geojson",
+    );
+    let names: Vec<&str> = features.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["gazdagret"]);
+
+    let features = get_coverage_features(
+        &mut test_wsgi,
+        "/coverage-map/filter-for/relations/43,44/view-result.geojson",
+    );
+    let names: Vec<&str> = features.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["budaors", "ujbuda"]);
+}
+
+/// Tests handle_coverage_map().
+#[test]
+fn test_handle_coverage_map() {
+    let mut test_wsgi = make_test_wsgi_coverage();
+
+    let root = test_wsgi.get_dom_for_path("/coverage-map/filter-for/refcounty/13/whole-county");
+
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/div[@id='coverage-map']/@data-url");
+    assert_eq!(
+        results,
+        vec!["/osm/coverage-map/filter-for/refcounty/13/whole-county/view-result.geojson"]
+    );
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/p/a");
+    assert!(results.contains(&"Budapest".to_string()));
+    assert!(results.contains(&"Pest megye".to_string()));
+}
+
+/// Tests handle_coverage_map(): the case when cron didn't cache the boundaries yet.
+#[test]
+fn test_handle_coverage_map_no_boundaries() {
+    let mut test_wsgi = wsgi::tests::TestWsgi::new();
+
+    let root = test_wsgi.get_dom_for_path("/coverage-map");
+
+    let results = wsgi::tests::TestWsgi::find_all(&root, "body/div[@id='no-coverage-map']");
+    assert_eq!(results.len(), 1);
+
+    let features = get_coverage_features(&mut test_wsgi, "/coverage-map/view-result.geojson");
+    assert!(features.is_empty());
+}